@@ -0,0 +1,126 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+#[cfg(feature = "curses")]
+use pancurses;
+#[cfg(feature = "curses")]
+use pancurses::chtype;
+
+use config::LineDrawing;
+use renderer::Glyph;
+
+// `translate` returns the glyph that should be drawn in place of `c` if `c` is
+// one of the characters of the DEC special graphics character set, which
+// programs such as `dialog` use to draw lines and boxes. `alacritty::Term`
+// translates the DEC special graphics character set into Unicode, which can't
+// be displayed by hosts that don't use UTF-8, so we draw these characters
+// using the host's line-drawing characters or, if `line_drawing` is
+// `LineDrawing::Ascii`, using ASCII approximations.
+pub fn translate(line_drawing: LineDrawing, c: char) -> Option<Glyph<'static>> {
+    // `to_ascii` approximates every character of the set, so it's also used to
+    // check whether `c` belongs to it.
+    match line_drawing {
+        LineDrawing::Acs => to_ascii(c).map(|_| Glyph::LineDrawing(c)),
+        LineDrawing::Ascii => to_ascii(c).map(Glyph::Char),
+        LineDrawing::Unicode => None,
+    }
+}
+
+// `to_acs` returns curses' ACS character for `c`, which is used to draw
+// `Glyph::LineDrawing`.
+#[cfg(feature = "curses")]
+pub fn to_acs(c: char) -> Option<chtype> {
+    let acs =
+        match c {
+            '◆' => pancurses::ACS_DIAMOND(),
+            '▒' => pancurses::ACS_CKBOARD(),
+            '°' => pancurses::ACS_DEGREE(),
+            '±' => pancurses::ACS_PLMINUS(),
+            '┘' => pancurses::ACS_LRCORNER(),
+            '┐' => pancurses::ACS_URCORNER(),
+            '┌' => pancurses::ACS_ULCORNER(),
+            '└' => pancurses::ACS_LLCORNER(),
+            '┼' => pancurses::ACS_PLUS(),
+            '⎺' => pancurses::ACS_S1(),
+            '⎻' => pancurses::ACS_S3(),
+            '─' => pancurses::ACS_HLINE(),
+            '⎼' => pancurses::ACS_S7(),
+            '⎽' => pancurses::ACS_S9(),
+            '├' => pancurses::ACS_LTEE(),
+            '┤' => pancurses::ACS_RTEE(),
+            '┴' => pancurses::ACS_BTEE(),
+            '┬' => pancurses::ACS_TTEE(),
+            '│' => pancurses::ACS_VLINE(),
+            '≤' => pancurses::ACS_LEQUAL(),
+            '≥' => pancurses::ACS_GEQUAL(),
+            'π' => pancurses::ACS_PI(),
+            '≠' => pancurses::ACS_NEQUAL(),
+            '£' => pancurses::ACS_STERLING(),
+            '·' => pancurses::ACS_BULLET(),
+            _ => return None,
+        };
+
+    Some(acs)
+}
+
+// `to_dec` returns the character that selects `c` from the DEC special
+// graphics character set, which terminals draw `c` with while the set is
+// designated as G0 using `ESC ( 0`. This is used to draw `Glyph::LineDrawing`
+// by writing escape sequences to the host.
+pub fn to_dec(c: char) -> Option<char> {
+    let dec =
+        match c {
+            '◆' => '`',
+            '▒' => 'a',
+            '°' => 'f',
+            '±' => 'g',
+            '┘' => 'j',
+            '┐' => 'k',
+            '┌' => 'l',
+            '└' => 'm',
+            '┼' => 'n',
+            '⎺' => 'o',
+            '⎻' => 'p',
+            '─' => 'q',
+            '⎼' => 'r',
+            '⎽' => 's',
+            '├' => 't',
+            '┤' => 'u',
+            '┴' => 'v',
+            '┬' => 'w',
+            '│' => 'x',
+            '≤' => 'y',
+            '≥' => 'z',
+            'π' => '{',
+            '≠' => '|',
+            '£' => '}',
+            '·' => '~',
+            _ => return None,
+        };
+
+    Some(dec)
+}
+
+fn to_ascii(c: char) -> Option<char> {
+    let ascii =
+        match c {
+            '◆' => '*',
+            '▒' => '#',
+            '°' => 'o',
+            '±' => '#',
+            '┘' | '┐' | '┌' | '└' | '┼' | '├' | '┤' | '┴' | '┬' => '+',
+            '⎺' | '⎻' | '─' | '⎼' => '-',
+            '⎽' => '_',
+            '│' => '|',
+            '≤' => '<',
+            '≥' => '>',
+            'π' => '*',
+            '≠' => '!',
+            '£' => 'f',
+            '·' => '.',
+            _ => return None,
+        };
+
+    Some(ascii)
+}
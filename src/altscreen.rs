@@ -0,0 +1,113 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::mem;
+
+use alacritty::ansi::Processor;
+use alacritty::config::Config as TermConfig;
+use alacritty::Term;
+use alacritty::term::SizeInfo;
+use alacritty::term::cell::Cell;
+
+use extras::Extras;
+use reflow;
+
+// `AltScreen` tracks whether the child is using the alternate screen, and keeps
+// a copy of the primary screen while it is, so that the primary screen can be
+// viewed ("peeked at") without leaving the alternate screen. `alacritty::Term`
+// keeps the primary screen while the alternate screen is active, but doesn't
+// expose it.
+pub struct AltScreen {
+    // `primary` holds a copy of the primary screen, along with its `Extras`,
+    // while the alternate screen is active.
+    primary: Option<(Term, Extras)>,
+    peeking: bool,
+}
+
+impl AltScreen {
+    pub fn new() -> AltScreen {
+        AltScreen{
+            primary: None,
+            peeking: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.primary.is_some()
+    }
+
+    // `enter` should be called before `term` switches to the alternate
+    // screen. `extras` is saved with the copy of the primary screen and
+    // replaced with an empty `Extras` for the alternate screen.
+    pub fn enter(
+        &mut self,
+        term: &Term,
+        extras: &mut Extras,
+        term_conf: &TermConfig,
+        size: SizeInfo,
+    ) {
+        if self.is_active() {
+            return;
+        }
+
+        let mut primary = Term::new(term_conf, size);
+        // The copy is the same size as `term`, so no lines scroll off it, and
+        // `extras` already applies to it.
+        if let Some(snapshot) = reflow::capture(term, extras) {
+            reflow::replay(&snapshot, &mut primary, &mut Processor::new(), &mut Extras::new());
+        }
+        self.primary = Some((primary, mem::replace(extras, Extras::new())));
+    }
+
+    // `exit` should be called after `term` switches back to the primary
+    // screen, and restores the `Extras` that were saved by `enter`.
+    pub fn exit(&mut self, extras: &mut Extras) {
+        if let Some((_, saved)) = self.primary.take() {
+            *extras = saved;
+        }
+        self.peeking = false;
+    }
+
+    // `toggle_peek` toggles whether the primary screen is being peeked at,
+    // and returns `false` if the alternate screen isn't active.
+    pub fn toggle_peek(&mut self) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        self.peeking = !self.peeking;
+
+        true
+    }
+
+    // `peeked` returns the copy of the primary screen if it's being peeked at.
+    pub fn peeked(&self) -> Option<(&Term, &Extras)> {
+        if !self.peeking {
+            return None;
+        }
+
+        self.primary.as_ref().map(|&(ref term, ref extras)| (term, extras))
+    }
+
+    // `primary` returns the copy of the primary screen while the alternate
+    // screen is active.
+    pub fn primary(&self) -> Option<&Term> {
+        self.primary.as_ref().map(|&(ref term, _)| term)
+    }
+
+    // `resize` resizes the copy of the primary screen, and returns the rows
+    // that were scrolled off its top by being rewrapped (see
+    // `reflow::replay`).
+    pub fn resize(&mut self, size: &SizeInfo) -> Vec<Vec<Cell>> {
+        if let Some((ref mut term, ref mut extras)) = self.primary {
+            let snapshot = reflow::capture(term, extras);
+            term.resize(size);
+            extras.clear();
+            if let Some(ref snapshot) = snapshot {
+                return reflow::replay(snapshot, term, &mut Processor::new(), extras);
+            }
+        }
+
+        vec![]
+    }
+}
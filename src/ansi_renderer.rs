@@ -0,0 +1,156 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+use std::env;
+use std::io::Write;
+
+use acs;
+use colour;
+use colour::Spec;
+use layout::Rect;
+use palette::Rgb;
+use quantise::Depth;
+use renderer::{Attrs, Glyph, Renderer};
+
+// `AnsiRenderer` is a `Renderer` that draws in `area` of the host terminal by
+// writing ANSI escape sequences to `out` (usually standard output) itself,
+// rather than through a library. Unlike the other renderers, it draws
+// undercurls, strikethrough and coloured underlines as the program gave them,
+// instead of approximating them, and draws 24-bit colours if `truecolour` is
+// `true`; the host ignores the sequences that it doesn't support. Cells outside
+// of `area` aren't drawn. Write errors are ignored, as they are by curses.
+pub struct AnsiRenderer<W: Write> {
+    out: W,
+    area: Rect,
+    truecolour: bool,
+}
+
+impl<W: Write> AnsiRenderer<W> {
+    pub fn new(out: W, area: Rect, truecolour: bool) -> AnsiRenderer<W> {
+        AnsiRenderer{out: out, area: area, truecolour: truecolour}
+    }
+
+    // `set_area` moves the area that's drawn in, such as after the host
+    // terminal is resized.
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+    }
+
+    // `sgr` returns the SGR sequence that sets `attrs`, starting from the
+    // default attributes.
+    fn sgr(&self, attrs: Attrs) -> String {
+        let mut params = vec!["0".to_string()];
+        let mappings = [
+            (attrs.bold, "1"),
+            (attrs.dim, "2"),
+            (attrs.italic, "3"),
+            (attrs.reverse, "7"),
+            (attrs.hidden, "8"),
+            (attrs.strikethrough, "9"),
+        ];
+        for &(set, param) in mappings.iter() {
+            if set {
+                params.push(param.to_string());
+            }
+        }
+        if attrs.undercurl {
+            params.push("4:3".to_string());
+        } else if attrs.underline {
+            params.push("4".to_string());
+        }
+        if let Some((fg, bg)) = attrs.colours {
+            params.push(self.colour(38, fg, attrs.rgb.0));
+            params.push(self.colour(48, bg, attrs.rgb.1));
+        }
+        if let Some(spec) = attrs.underline_colour {
+            params.push(self.underline_colour(spec));
+        }
+
+        format!("\x1b[{}m", params.join(";"))
+    }
+
+    // `colour` returns the SGR parameters that set the foreground colour, if
+    // `base` is 38, or the background colour, if `base` is 48, to `rgb` if
+    // it's given and the host supports 24-bit colour, and to `c` otherwise.
+    // The first 16 colours use their own parameters, which more hosts support.
+    fn colour(&self, base: i16, c: i16, rgb: Option<Rgb>) -> String {
+        match rgb {
+            Some((r, g, b)) if self.truecolour => format!("{};2;{};{};{}", base, r, g, b),
+            _ if c == colour::DEFAULT => format!("{}", base + 1),
+            _ if c < 8 => format!("{}", base - 8 + c),
+            _ if c < 16 => format!("{}", base + 52 + c - 8),
+            _ => format!("{};5;{}", base, c),
+        }
+    }
+
+    fn underline_colour(&self, spec: Spec) -> String {
+        match spec {
+            Spec::Indexed(i) => format!("58;5;{}", i),
+            Spec::Rgb((r, g, b)) if self.truecolour => format!("58;2;{};{};{}", r, g, b),
+            Spec::Rgb((r, g, b)) => format!("58;5;{}", colour::rgb_to_curses(Depth::Colours256, r, g, b)),
+        }
+    }
+
+    fn move_to(&mut self, y: i32, x: i32) {
+        let _ = write!(self.out, "\x1b[{};{}H", self.area.y + y + 1, self.area.x + x + 1);
+    }
+}
+
+impl<W: Write> Renderer for AnsiRenderer<W> {
+    fn size(&self) -> (i32, i32) {
+        (self.area.lines, self.area.cols)
+    }
+
+    // Only the area is cleared, rather than the whole screen, so that the
+    // rest of the host terminal is left as it is.
+    fn clear(&mut self) {
+        let _ = write!(self.out, "\x1b[0m");
+        let blank = " ".repeat(cmp::max(self.area.cols, 0) as usize);
+        for y in 0..self.area.lines {
+            self.move_to(y, 0);
+            let _ = write!(self.out, "{}", blank);
+        }
+    }
+
+    fn draw_cell(&mut self, y: i32, x: i32, glyph: Glyph, attrs: Attrs) {
+        if y < 0 || y >= self.area.lines || x < 0 || x >= self.area.cols {
+            return;
+        }
+
+        self.move_to(y, x);
+        let sgr = self.sgr(attrs);
+        let _ =
+            match glyph {
+                Glyph::Text(text) => write!(self.out, "{}{}", sgr, text),
+                Glyph::Char(c) => write!(self.out, "{}{}", sgr, c),
+                Glyph::LineDrawing(c) => {
+                    match acs::to_dec(c) {
+                        Some(dec) => write!(self.out, "{}\x1b(0{}\x1b(B", sgr, dec),
+                        None => write!(self.out, "{}{}", sgr, c),
+                    }
+                },
+            };
+        let _ = write!(self.out, "\x1b[0m");
+    }
+
+    fn move_cursor(&mut self, y: i32, x: i32, visible: bool) {
+        self.move_to(y, x);
+        let _ = write!(self.out, "\x1b[?25{}", if visible { 'h' } else { 'l' });
+    }
+
+    fn present(&mut self) {
+        let _ = self.out.flush();
+    }
+
+    fn passthrough(&self) -> bool {
+        true
+    }
+}
+
+// `host_truecolour` returns `true` if the host terminal supports 24-bit colour,
+// according to `COLORTERM`.
+pub fn host_truecolour() -> bool {
+    env::var("COLORTERM").map_or(false, |v| v == "truecolor" || v == "24bit")
+}
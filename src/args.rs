@@ -0,0 +1,485 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{App, AppSettings, Arg, ArgMatches, ErrorKind, SubCommand};
+
+use backend::Backend;
+use outbox::Enter;
+use pane::Program;
+use serial::{self, Flow, Parity};
+use tmux;
+
+// `Command` is what `tep` was asked to do, which is given as a subcommand, or
+// is `New` if there's no subcommand.
+pub enum Command {
+    // `New` starts a new session, and attaches to it.
+    New,
+    // `Dash` starts a new session in the same way as `New`, which runs each of
+    // the given commands in its own pane.
+    Dash(Vec<String>),
+    // `Attach` attaches to a session that's already running, as a read-only
+    // client if its second value is `true`.
+    Attach(Option<String>, bool),
+    // `List` lists the sessions that are running.
+    List,
+    // `KillSession` ends a session.
+    KillSession(String),
+    // `Play(path, speed, max_wait)` plays the output that was recorded to
+    // `path` by `tep record`, `speed` times faster than it was recorded,
+    // waiting at most `max_wait` between parts of the output.
+    Play(PathBuf, f64, Option<Duration>),
+    // `Tmux` runs a `tmux` command, which is given along with its arguments,
+    // in a session (see `tmux::COMMANDS`).
+    Tmux(Vec<String>),
+}
+
+pub struct Args {
+    pub command: Command,
+    // `session` is the name of the session that's started.
+    pub session: Option<String>,
+    pub no_colour: bool,
+    pub config: Option<PathBuf>,
+    // `paste` is a file whose contents are pasted into the child when it
+    // starts.
+    pub paste: Option<PathBuf>,
+    // `restore` is a file that holds the state of a terminal, written by the
+    // `save` command, which is restored when the emulator starts.
+    pub restore: Option<PathBuf>,
+    // `program` is the program that's run instead of the user's shell, which
+    // is given using `--command` or after `--`.
+    pub program: Option<Program>,
+    // `log_file` is a file that the server's errors are written to, which
+    // would otherwise be drawn over by the screen.
+    pub log_file: Option<PathBuf>,
+    // `cwd` is the directory that children are started in, and `env` holds
+    // the environment variables that are set for them, in the order that they
+    // were given.
+    pub cwd: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+    pub size: FixedSize,
+    // `hold` is `true` if the screen is kept after the last child exits,
+    // until a key is pressed.
+    pub hold: bool,
+    // `restart` is `true` if children are restarted when they exit, and
+    // `restart_delay` is the number of milliseconds that's waited before the
+    // first restart, if it was given.
+    pub restart: bool,
+    pub restart_delay: Option<u64>,
+    // `pipe` is `true` if the programs that are given on the command line are
+    // connected using pipes, instead of being run in PTYs.
+    pub pipe: bool,
+    // `login` is `true` if the user's shell is started as a login shell.
+    pub login: bool,
+    // `backend` is the renderer that panes are drawn with.
+    pub backend: Backend,
+    // `record` is a file that the output of the first pane is recorded to,
+    // which is given to `tep record`.
+    pub record: Option<PathBuf>,
+}
+
+// `FixedSize` is the size that the terminal of a single pane is fixed to,
+// regardless of the size of the host terminal, where a dimension that's `None`
+// follows the host terminal.
+#[derive(Clone, Copy)]
+pub struct FixedSize {
+    pub lines: Option<i32>,
+    pub cols: Option<i32>,
+}
+
+pub fn parse() -> Result<Args, String> {
+    let argv: Vec<String> = env::args().collect();
+    let mut args = Args{
+        command: Command::New,
+        session: None,
+        no_colour: false,
+        config: None,
+        paste: None,
+        restore: None,
+        program: None,
+        log_file: None,
+        cwd: None,
+        env: vec![],
+        size: FixedSize{lines: None, cols: None},
+        hold: false,
+        restart: false,
+        restart_delay: None,
+        pipe: false,
+        login: false,
+        backend: Backend::Curses,
+        record: None,
+    };
+
+    // `tmux` commands are passed to the session as they were given, because
+    // their arguments are parsed by `tmux::parse`, other than `send`, which
+    // is also a subcommand of `tep`.
+    if let Some(name) = argv.get(1) {
+        if tmux::is_command(name) && name != "send" {
+            args.command = Command::Tmux(argv[1..].to_vec());
+
+            return Ok(args);
+        }
+    }
+
+    let matches =
+        match app().get_matches_from_safe(argv) {
+            Ok(matches) => matches,
+            // Help and the version are printed to stdout by `exit`.
+            Err(ref e) if e.kind == ErrorKind::HelpDisplayed || e.kind == ErrorKind::VersionDisplayed => e.exit(),
+            Err(e) => return Err(e.message),
+        };
+
+    match matches.subcommand() {
+        ("run", Some(m)) => session_options(m, &mut args)?,
+        ("record", Some(m)) => {
+            session_options(m, &mut args)?;
+            args.record = m.value_of_os("file").map(PathBuf::from);
+        },
+        ("dash", Some(m)) => {
+            session_options(m, &mut args)?;
+            args.command = Command::Dash(values(m, "commands"));
+        },
+        ("ssh", Some(m)) => {
+            session_options(m, &mut args)?;
+            args.program = Some(Program::Ssh(values(m, "destination")));
+        },
+        ("serial", Some(m)) => {
+            session_options(m, &mut args)?;
+            args.program = Some(Program::Serial(serial_options(m)?));
+        },
+        ("play", Some(m)) => {
+            let speed =
+                match m.value_of("speed").map(str::parse::<f64>) {
+                    None => 1.0,
+                    Some(Ok(speed)) if speed > 0.0 && speed.is_finite() => speed,
+                    Some(_) => return Err("--speed expects a positive number".to_string()),
+                };
+            let max_wait =
+                match m.value_of("max-wait").map(str::parse::<f64>) {
+                    None => None,
+                    Some(Ok(secs)) if secs >= 0.0 && secs.is_finite() => Some(Duration::from_secs_f64(secs)),
+                    Some(_) => return Err("--max-wait expects a number of seconds".to_string()),
+                };
+            let path = m.value_of_os("file").map(PathBuf::from).unwrap_or_default();
+            args.command = Command::Play(path, speed, max_wait);
+        },
+        ("attach", Some(m)) => {
+            args.command = Command::Attach(m.value_of("name").map(str::to_string), m.is_present("read-only"));
+        },
+        ("ls", _) => {
+            args.command = Command::List;
+        },
+        ("kill-session", Some(m)) => {
+            args.command = Command::KillSession(m.value_of("name").unwrap_or_default().to_string());
+        },
+        // `capture` prints the screen of the active pane, which is short for
+        // `capture-pane -p`.
+        ("capture", _) => {
+            args.command = Command::Tmux(vec!["capture-pane".to_string(), "-p".to_string()]);
+        },
+        ("send", Some(m)) => {
+            let mut words = vec!["send-keys".to_string()];
+            if m.is_present("literal") {
+                words.push("-l".to_string());
+            }
+            words.extend(values(m, "keys"));
+            args.command = Command::Tmux(words);
+        },
+        _ => session_options(&matches, &mut args)?,
+    }
+
+    Ok(args)
+}
+
+// `app` describes the arguments of `tep`. The options that start a session are
+// given before any operands, and can't be given to the subcommands that manage
+// sessions.
+fn app() -> App<'static, 'static> {
+    App::new("tep")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Runs programs in a terminal emulator that can be detached from")
+        .settings(&[AppSettings::ArgsNegateSubcommands, AppSettings::VersionlessSubcommands])
+        .args(&session_args())
+        .args(&program_args())
+        .after_help(concat!(
+            "Other subcommands run the tmux command of the same name, such as\n",
+            "`tep split-window -h`, in the session that they're run from.",
+        ))
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Starts a new session and attaches to it, which is the default")
+                .args(&session_args())
+                .args(&program_args())
+        )
+        .subcommand(
+            SubCommand::with_name("record")
+                .about("Starts a new session, and records the output of its first pane to <file>")
+                .args(&session_args())
+                .arg(Arg::with_name("file").required(true).help("The file that the output is recorded to"))
+                .args(&program_args())
+        )
+        .subcommand(
+            SubCommand::with_name("play")
+                .about("Plays the output that was recorded to <file>")
+                .arg(Arg::with_name("file").required(true).help("The file that the output was recorded to"))
+                .arg(
+                    Arg::with_name("speed")
+                        .long("speed")
+                        .value_name("n")
+                        .help("Plays the output <n> times faster")
+                )
+                .arg(
+                    Arg::with_name("max-wait")
+                        .long("max-wait")
+                        .value_name("seconds")
+                        .help("Waits at most <seconds> between parts of the output")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("dash")
+                .about("Starts a new session that runs each of <commands> in its own pane")
+                .args(&session_args())
+                .arg(Arg::with_name("commands").multiple(true))
+        )
+        .subcommand(
+            SubCommand::with_name("ssh")
+                .about("Starts a new session whose panes connect to <destination>")
+                .setting(AppSettings::TrailingVarArg)
+                .args(&session_args())
+                .arg(Arg::with_name("destination").required(true).multiple(true).value_name("destination"))
+        )
+        .subcommand(
+            SubCommand::with_name("serial")
+                .about("Starts a new session whose first pane is connected to a serial port")
+                .args(&session_args())
+                .args(&serial_args())
+        )
+        .subcommand(
+            SubCommand::with_name("attach")
+                .about("Attaches to a session")
+                .arg(Arg::with_name("read-only").long("read-only").help("Attaches without sending input"))
+                .arg(Arg::with_name("name"))
+        )
+        .subcommand(SubCommand::with_name("ls").about("Lists the sessions that are running"))
+        .subcommand(
+            SubCommand::with_name("kill-session")
+                .about("Ends a session")
+                .arg(Arg::with_name("name").required(true))
+        )
+        .subcommand(
+            SubCommand::with_name("capture")
+                .about("Prints the screen of the active pane of the current session")
+        )
+        .subcommand(
+            SubCommand::with_name("send")
+                .about("Sends keys to the active pane of the current session, like `send-keys`")
+                .setting(AppSettings::TrailingVarArg)
+                .arg(Arg::with_name("literal").short("l").help("Sends <keys> as text"))
+                .arg(Arg::with_name("keys").required(true).multiple(true).allow_hyphen_values(true))
+        )
+}
+
+// `session_args` are the options that are used to start a session.
+fn session_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("session").long("session").value_name("name").help("Names the session"),
+        Arg::with_name("no-color").long("no-color").help("Draws without colours"),
+        Arg::with_name("config").long("config").value_name("path").help("Reads the configuration from <path>"),
+        Arg::with_name("paste")
+            .long("paste")
+            .value_name("path")
+            .help("Pastes the contents of <path> into the first pane"),
+        Arg::with_name("restore")
+            .long("restore")
+            .value_name("path")
+            .help("Restores the state of a terminal that was saved to <path>"),
+        Arg::with_name("log-file")
+            .long("log-file")
+            .value_name("path")
+            .help("Writes the errors of the session to <path>"),
+        Arg::with_name("hold").long("hold").help("Keeps the screen after the last program exits"),
+        Arg::with_name("restart")
+            .long("restart")
+            .value_name("ms")
+            .min_values(0)
+            .require_equals(true)
+            .help("Restarts programs when they exit, after <ms> milliseconds"),
+        Arg::with_name("pipe").long("pipe").help("Connects programs using pipes instead of PTYs"),
+        Arg::with_name("login").long("login").help("Starts the shell as a login shell"),
+        Arg::with_name("backend")
+            .long("backend")
+            .value_name("name")
+            .help("Draws panes using curses, ansi, crossterm or termion"),
+        Arg::with_name("cwd").long("cwd").value_name("dir").help("Starts programs in <dir>"),
+        Arg::with_name("env")
+            .long("env")
+            .value_name("key=value")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Sets an environment variable for programs"),
+        Arg::with_name("cols").long("cols").value_name("n").help("Fixes the width of the terminal"),
+        Arg::with_name("rows").long("rows").value_name("n").help("Fixes the height of the terminal"),
+    ]
+}
+
+// `program_args` give the program that the first pane runs instead of the
+// user's shell.
+fn program_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("command")
+            .long("command")
+            .value_name("command")
+            .conflicts_with("program")
+            .help("Runs <command> using `sh -c`"),
+        Arg::with_name("program").multiple(true).last(true).help("Runs <program> with its arguments"),
+    ]
+}
+
+fn serial_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("baud").long("baud").value_name("n"),
+        Arg::with_name("data-bits").long("data-bits").value_name("n"),
+        Arg::with_name("parity").long("parity").value_name("none|odd|even"),
+        Arg::with_name("stop-bits").long("stop-bits").value_name("1|2"),
+        Arg::with_name("flow").long("flow").value_name("none|hardware|software"),
+        Arg::with_name("enter").long("enter").value_name("cr|lf|crlf").help("Sets what Enter sends"),
+        Arg::with_name("lf-crlf").long("lf-crlf").help("Returns to the first column at each newline"),
+        Arg::with_name("device").required(true),
+    ]
+}
+
+// `session_options` sets the options of `args` that are used to start a
+// session, using `m`, which holds the arguments of `run` or of one of the
+// other subcommands that start a session.
+fn session_options(m: &ArgMatches, args: &mut Args) -> Result<(), String> {
+    args.session = m.value_of("session").map(str::to_string);
+    args.no_colour = m.is_present("no-color");
+    args.config = m.value_of_os("config").map(PathBuf::from);
+    args.paste = m.value_of_os("paste").map(PathBuf::from);
+    args.restore = m.value_of_os("restore").map(PathBuf::from);
+    args.log_file = m.value_of_os("log-file").map(PathBuf::from);
+    args.cwd = m.value_of_os("cwd").map(PathBuf::from);
+    args.hold = m.is_present("hold");
+    args.pipe = m.is_present("pipe");
+    args.login = m.is_present("login");
+    if let Some(name) = m.value_of("backend") {
+        args.backend = Backend::parse(name)?;
+    }
+    if m.is_present("restart") {
+        args.restart = true;
+        if let Some(delay) = m.value_of("restart") {
+            match delay.parse() {
+                Ok(delay) => args.restart_delay = Some(delay),
+                Err(_) => return Err(format!("--restart expects a number of milliseconds: {}", delay)),
+            }
+        }
+    }
+    for var in values(m, "env") {
+        match var.find('=') {
+            Some(i) if i > 0 => args.env.push((var[..i].to_string(), var[i+1..].to_string())),
+            _ => return Err(format!("--env expects <key>=<value>: {}", var)),
+        }
+    }
+    if let Some(value) = m.value_of("cols") {
+        args.size.cols = Some(parse_dimension("--cols", value)?);
+    }
+    if let Some(value) = m.value_of("rows") {
+        args.size.lines = Some(parse_dimension("--rows", value)?);
+    }
+    if let Some(line) = m.value_of("command") {
+        args.program = Some(Program::Shell(line.to_string()));
+    }
+    if m.is_present("program") {
+        args.program = Some(Program::Exec(values(m, "program")));
+    }
+
+    Ok(())
+}
+
+// `serial_options` returns the options of the serial port that `tep serial`
+// connects to, which are given by `m`.
+fn serial_options(m: &ArgMatches) -> Result<serial::Options, String> {
+    let device = m.value_of_os("device").map(PathBuf::from).unwrap_or_default();
+    let mut opts = serial::Options::new(device);
+    for &arg in &["baud", "data-bits", "parity", "stop-bits", "flow", "enter"] {
+        if let Some(value) = m.value_of(arg) {
+            parse_serial_option(&mut opts, arg, value)?;
+        }
+    }
+    opts.lf_crlf = m.is_present("lf-crlf");
+
+    Ok(opts)
+}
+
+// `values` returns the values of the argument `name`, or an empty `Vec` if it
+// wasn't given.
+fn values(m: &ArgMatches, name: &str) -> Vec<String> {
+    m.values_of(name).map_or(vec![], |values| values.map(str::to_string).collect())
+}
+
+// `parse_serial_option` sets the option of `opts` that `arg` names to `value`.
+fn parse_serial_option(opts: &mut serial::Options, arg: &str, value: &str) -> Result<(), String> {
+    let invalid = || format!("invalid value for --{}: {}", arg, value);
+    match arg {
+        "baud" => {
+            opts.baud = value.parse().map_err(|_| invalid())?;
+        },
+        "data-bits" => {
+            opts.data_bits =
+                match value.parse() {
+                    Ok(n) if n >= 5 && n <= 8 => n,
+                    _ => return Err(invalid()),
+                };
+        },
+        "parity" => {
+            opts.parity =
+                match value {
+                    "none" => Parity::None,
+                    "odd" => Parity::Odd,
+                    "even" => Parity::Even,
+                    _ => return Err(invalid()),
+                };
+        },
+        "stop-bits" => {
+            opts.stop_bits =
+                match value {
+                    "1" => 1,
+                    "2" => 2,
+                    _ => return Err(invalid()),
+                };
+        },
+        "flow" => {
+            opts.flow =
+                match value {
+                    "none" => Flow::None,
+                    "hardware" => Flow::Hardware,
+                    "software" => Flow::Software,
+                    _ => return Err(invalid()),
+                };
+        },
+        _ => {
+            opts.enter =
+                match value {
+                    "cr" => Enter::Cr,
+                    "lf" => Enter::Lf,
+                    "crlf" => Enter::CrLf,
+                    _ => return Err(invalid()),
+                };
+        },
+    }
+
+    Ok(())
+}
+
+// `parse_dimension` parses `value`, which was given for the option `arg`, as a
+// number of lines or columns.
+fn parse_dimension(arg: &str, value: &str) -> Result<i32, String> {
+    match value.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("{} expects a positive number: {}", arg, value)),
+    }
+}
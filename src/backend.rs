@@ -0,0 +1,236 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::io::{self, Stdout, Write};
+
+use pancurses;
+use pancurses::Window;
+
+use ansi_renderer::{self, AnsiRenderer};
+#[cfg(feature = "crossterm")]
+use crossterm_renderer::CrosstermRenderer;
+use curses_renderer::CursesRenderer;
+use layout::Rect;
+use pairs::Pairs;
+use renderer::{Attrs, Glyph, Renderer};
+#[cfg(feature = "termion")]
+use termion_renderer::TermionRenderer;
+
+// `SAVE_CURSOR` and `RESTORE_CURSOR` save and restore the position of the
+// host's cursor, along with its attributes and character sets (DECSC and
+// DECRC).
+const SAVE_CURSOR: &str = "\x1b7";
+const RESTORE_CURSOR: &str = "\x1b8";
+
+// `Backend` is the renderer that the panes of `tep`, and the pickers that are
+// drawn over them, are drawn with, which is chosen using `--backend`. The
+// status line and prompts are always drawn using curses, which also reads the
+// input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Curses,
+    // `Ansi` draws the attributes that curses can't, and draws 24-bit colours
+    // if `truecolour` is `true`.
+    Ansi{truecolour: bool},
+    #[cfg(feature = "crossterm")]
+    Crossterm,
+    #[cfg(feature = "termion")]
+    Termion,
+}
+
+impl Backend {
+    pub fn parse(name: &str) -> Result<Backend, String> {
+        match name {
+            "curses" => Ok(Backend::Curses),
+            "ansi" => Ok(Backend::Ansi{truecolour: ansi_renderer::host_truecolour()}),
+            #[cfg(feature = "crossterm")]
+            "crossterm" => Ok(Backend::Crossterm),
+            #[cfg(not(feature = "crossterm"))]
+            "crossterm" => Err("tep was built without the 'crossterm' feature".to_string()),
+            #[cfg(feature = "termion")]
+            "termion" => Ok(Backend::Termion),
+            #[cfg(not(feature = "termion"))]
+            "termion" => Err("tep was built without the 'termion' feature".to_string()),
+            _ => Err(format!("unknown backend: {}", name)),
+        }
+    }
+}
+
+// `PaneRenderer` is the `Renderer` of a `Backend`, which draws in a window.
+pub enum PaneRenderer<'a> {
+    Curses(CursesRenderer<'a>),
+    Ansi(BehindCurses<'a, AnsiRenderer<Stdout>>),
+    #[cfg(feature = "crossterm")]
+    Crossterm(BehindCurses<'a, CrosstermRenderer<Stdout>>),
+    #[cfg(feature = "termion")]
+    Termion(BehindCurses<'a, TermionRenderer<Stdout>>),
+}
+
+impl<'a> PaneRenderer<'a> {
+    // `new` returns a renderer that draws in `win`. `pairs` is only used by
+    // `Backend::Curses`. Other backends draw in the part of the host terminal
+    // that `win` covers, because `win` belongs to the standard screen of
+    // curses, which covers the whole host terminal.
+    pub fn new(backend: Backend, win: &'a Window, pairs: &'a mut Pairs) -> PaneRenderer<'a> {
+        match backend {
+            Backend::Curses => PaneRenderer::Curses(CursesRenderer::new(win, pairs)),
+            Backend::Ansi{truecolour} => {
+                PaneRenderer::Ansi(BehindCurses::new(AnsiRenderer::new(io::stdout(), area(win), truecolour), win))
+            },
+            #[cfg(feature = "crossterm")]
+            Backend::Crossterm => {
+                PaneRenderer::Crossterm(BehindCurses::new(CrosstermRenderer::new(io::stdout(), area(win)), win))
+            },
+            #[cfg(feature = "termion")]
+            Backend::Termion => PaneRenderer::Termion(BehindCurses::new(TermionRenderer::new(io::stdout(), area(win)), win)),
+        }
+    }
+}
+
+impl<'a> Renderer for PaneRenderer<'a> {
+    fn size(&self) -> (i32, i32) {
+        match *self {
+            PaneRenderer::Curses(ref r) => r.size(),
+            PaneRenderer::Ansi(ref r) => r.size(),
+            #[cfg(feature = "crossterm")]
+            PaneRenderer::Crossterm(ref r) => r.size(),
+            #[cfg(feature = "termion")]
+            PaneRenderer::Termion(ref r) => r.size(),
+        }
+    }
+
+    fn clear(&mut self) {
+        match *self {
+            PaneRenderer::Curses(ref mut r) => r.clear(),
+            PaneRenderer::Ansi(ref mut r) => r.clear(),
+            #[cfg(feature = "crossterm")]
+            PaneRenderer::Crossterm(ref mut r) => r.clear(),
+            #[cfg(feature = "termion")]
+            PaneRenderer::Termion(ref mut r) => r.clear(),
+        }
+    }
+
+    fn draw_cell(&mut self, y: i32, x: i32, glyph: Glyph, attrs: Attrs) {
+        match *self {
+            PaneRenderer::Curses(ref mut r) => r.draw_cell(y, x, glyph, attrs),
+            PaneRenderer::Ansi(ref mut r) => r.draw_cell(y, x, glyph, attrs),
+            #[cfg(feature = "crossterm")]
+            PaneRenderer::Crossterm(ref mut r) => r.draw_cell(y, x, glyph, attrs),
+            #[cfg(feature = "termion")]
+            PaneRenderer::Termion(ref mut r) => r.draw_cell(y, x, glyph, attrs),
+        }
+    }
+
+    fn move_cursor(&mut self, y: i32, x: i32, visible: bool) {
+        match *self {
+            PaneRenderer::Curses(ref mut r) => r.move_cursor(y, x, visible),
+            PaneRenderer::Ansi(ref mut r) => r.move_cursor(y, x, visible),
+            #[cfg(feature = "crossterm")]
+            PaneRenderer::Crossterm(ref mut r) => r.move_cursor(y, x, visible),
+            #[cfg(feature = "termion")]
+            PaneRenderer::Termion(ref mut r) => r.move_cursor(y, x, visible),
+        }
+    }
+
+    fn present(&mut self) {
+        match *self {
+            PaneRenderer::Curses(ref mut r) => r.present(),
+            PaneRenderer::Ansi(ref mut r) => r.present(),
+            #[cfg(feature = "crossterm")]
+            PaneRenderer::Crossterm(ref mut r) => r.present(),
+            #[cfg(feature = "termion")]
+            PaneRenderer::Termion(ref mut r) => r.present(),
+        }
+    }
+
+    fn passthrough(&self) -> bool {
+        match *self {
+            PaneRenderer::Curses(ref r) => r.passthrough(),
+            PaneRenderer::Ansi(ref r) => r.passthrough(),
+            #[cfg(feature = "crossterm")]
+            PaneRenderer::Crossterm(ref r) => r.passthrough(),
+            #[cfg(feature = "termion")]
+            PaneRenderer::Termion(ref r) => r.passthrough(),
+        }
+    }
+}
+
+// `BehindCurses` is a `Renderer` that draws using `r`, which writes to the host
+// terminal directly, while curses is also drawing on it. curses only writes
+// what it thinks has changed on the host, and moves the cursor relative to
+// where it thinks the cursor is, so the cursor, along with its attributes and
+// character sets, is restored once `r` has drawn, and the cursor is then moved
+// using curses, so that what curses knows about the host stays correct. The
+// cells that `r` draws aren't known to curses, so curses has to be made to
+// redraw the screen before it draws over them (see `event_loop::render`).
+pub struct BehindCurses<'a, R: Renderer> {
+    r: R,
+    win: &'a Window,
+    // `saved` is `true` if the cursor was saved before `r` drew.
+    saved: bool,
+}
+
+impl<'a, R: Renderer> BehindCurses<'a, R> {
+    pub fn new(r: R, win: &'a Window) -> BehindCurses<'a, R> {
+        BehindCurses{r: r, win: win, saved: false}
+    }
+
+    fn save_cursor(&mut self) {
+        if !self.saved {
+            write_stdout(SAVE_CURSOR);
+            self.saved = true;
+        }
+    }
+}
+
+impl<'a, R: Renderer> Renderer for BehindCurses<'a, R> {
+    fn size(&self) -> (i32, i32) {
+        self.r.size()
+    }
+
+    fn clear(&mut self) {
+        self.save_cursor();
+        self.r.clear();
+    }
+
+    fn draw_cell(&mut self, y: i32, x: i32, glyph: Glyph, attrs: Attrs) {
+        self.save_cursor();
+        self.r.draw_cell(y, x, glyph, attrs);
+    }
+
+    // The cursor is moved when `present` is called, as it is by
+    // `CursesRenderer`.
+    fn move_cursor(&mut self, y: i32, x: i32, visible: bool) {
+        pancurses::curs_set(if visible { 1 } else { 0 });
+        self.win.mv(y, x);
+    }
+
+    fn present(&mut self) {
+        if self.saved {
+            write_stdout(RESTORE_CURSOR);
+            self.saved = false;
+        }
+        self.r.present();
+        self.win.refresh();
+    }
+
+    fn passthrough(&self) -> bool {
+        self.r.passthrough()
+    }
+}
+
+// `write_stdout` writes `seq` to the host terminal, without flushing it, so
+// that it's written along with the output of the renderer that's drawing.
+// Write errors are ignored, as they are by the renderers.
+fn write_stdout(seq: &str) {
+    let _ = io::stdout().write_all(seq.as_bytes());
+}
+
+// `area` returns the part of the host terminal that `win` covers.
+fn area(win: &Window) -> Rect {
+    let (y, x) = win.get_beg_yx();
+    let (lines, cols) = win.get_max_yx();
+
+    Rect{y: y, x: x, lines: lines, cols: cols}
+}
@@ -0,0 +1,99 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use libc;
+use libc::pid_t;
+
+// `SIGNAL_NAMES` are the names of the signals that children are most commonly
+// ended by.
+const SIGNAL_NAMES: &[(i32, &str)] = &[
+    (libc::SIGHUP, "SIGHUP"),
+    (libc::SIGINT, "SIGINT"),
+    (libc::SIGQUIT, "SIGQUIT"),
+    (libc::SIGILL, "SIGILL"),
+    (libc::SIGABRT, "SIGABRT"),
+    (libc::SIGBUS, "SIGBUS"),
+    (libc::SIGFPE, "SIGFPE"),
+    (libc::SIGKILL, "SIGKILL"),
+    (libc::SIGUSR1, "SIGUSR1"),
+    (libc::SIGSEGV, "SIGSEGV"),
+    (libc::SIGUSR2, "SIGUSR2"),
+    (libc::SIGPIPE, "SIGPIPE"),
+    (libc::SIGALRM, "SIGALRM"),
+    (libc::SIGTERM, "SIGTERM"),
+];
+
+// `Exit` is how a child exited.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exit {
+    // `Status` is the status that the child exited with.
+    Status(i32),
+    // `Signal(signal, core_dumped)` is the signal that ended the child.
+    Signal(i32, bool),
+}
+
+impl Exit {
+    // `from_wait_status` returns `None` if `status`, which was returned by
+    // `waitpid`, doesn't describe an exit.
+    fn from_wait_status(status: i32) -> Option<Exit> {
+        unsafe {
+            if libc::WIFEXITED(status) {
+                Some(Exit::Status(libc::WEXITSTATUS(status)))
+            } else if libc::WIFSIGNALED(status) {
+                Some(Exit::Signal(libc::WTERMSIG(status), libc::WCOREDUMP(status)))
+            } else {
+                None
+            }
+        }
+    }
+
+    // `describe` returns a description of how the child exited, or `None` if
+    // it exited successfully.
+    pub fn describe(self) -> Option<String> {
+        match self {
+            Exit::Status(0) => None,
+            Exit::Status(status) => Some(format!("exited with status {}", status)),
+            Exit::Signal(signal, core_dumped) => {
+                let core = if core_dumped { " (core dumped)" } else { "" };
+
+                Some(format!("killed by {}{}", signal_name(signal), core))
+            },
+        }
+    }
+}
+
+// `signal_name` returns the name of `signal`, such as `SIGTERM`.
+pub fn signal_name(signal: i32) -> String {
+    match SIGNAL_NAMES.iter().find(|&&(s, _)| s == signal) {
+        Some(&(_, name)) => name.to_string(),
+        None => format!("signal {}", signal),
+    }
+}
+
+// `reap` reaps the children that have exited, and returns their IDs along with
+// how they exited.
+pub fn reap() -> Vec<(pid_t, Exit)> {
+    let mut exits = vec![];
+    loop {
+        let mut status = 0;
+        let pid = unsafe { libc::waitpid(-1, &mut status, libc::WNOHANG) };
+        if pid <= 0 {
+            return exits;
+        }
+        if let Some(exit) = Exit::from_wait_status(status) {
+            exits.push((pid, exit));
+        }
+    }
+}
+
+// `wait` reaps the child `pid` if it has exited, and returns how it exited.
+pub fn wait(pid: pid_t) -> Option<Exit> {
+    let mut status = 0;
+    let reaped = unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG) };
+    if reaped == pid {
+        Exit::from_wait_status(status)
+    } else {
+        None
+    }
+}
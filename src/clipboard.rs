@@ -0,0 +1,179 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use base64;
+
+// `Target` is one of the host's selections.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    // `Clipboard` is the selection that's used by explicit copy and paste
+    // actions.
+    Clipboard,
+    // `Primary` is the selection that's set by selecting text, and pasted
+    // using the middle button, on X11 and Wayland.
+    Primary,
+}
+
+// `READERS` are the commands that can read each selection, in order of
+// preference, along with the environment variable that must be set for each to
+// be used, where an empty name means that the command can always be tried.
+const READERS: &[(Target, &str, &str, &[&str])] = &[
+    (Target::Primary, "WAYLAND_DISPLAY", "wl-paste", &["--primary", "--no-newline"]),
+    (Target::Clipboard, "WAYLAND_DISPLAY", "wl-paste", &["--no-newline"]),
+    (Target::Primary, "DISPLAY", "xclip", &["-o", "-selection", "primary"]),
+    (Target::Clipboard, "DISPLAY", "xclip", &["-o", "-selection", "clipboard"]),
+    (Target::Primary, "DISPLAY", "xsel", &["--primary", "--output"]),
+    (Target::Clipboard, "DISPLAY", "xsel", &["--clipboard", "--output"]),
+    (Target::Clipboard, "", "pbpaste", &[]),
+];
+
+// `READ_TIMEOUT` is how long a command that reads a selection is given to
+// finish before it's killed, such as when the owner of the selection doesn't
+// respond.
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+// `WRITERS` are the commands that can write each selection, in the same format
+// as `READERS`.
+const WRITERS: &[(Target, &str, &str, &[&str])] = &[
+    (Target::Primary, "WAYLAND_DISPLAY", "wl-copy", &["--primary"]),
+    (Target::Clipboard, "WAYLAND_DISPLAY", "wl-copy", &[]),
+    (Target::Primary, "DISPLAY", "xclip", &["-i", "-selection", "primary"]),
+    (Target::Clipboard, "DISPLAY", "xclip", &["-i", "-selection", "clipboard"]),
+    (Target::Primary, "DISPLAY", "xsel", &["--primary", "--input"]),
+    (Target::Clipboard, "DISPLAY", "xsel", &["--clipboard", "--input"]),
+    (Target::Clipboard, "", "pbcopy", &[]),
+];
+
+// `read` returns the contents of `target`, if it can be read.
+pub fn read(target: Target) -> Option<Vec<u8>> {
+    for &(t, var, cmd, args) in READERS {
+        if t != target || !available(var) {
+            continue;
+        }
+        // The next command is tried if this one isn't installed, fails, such
+        // as when there's no selection, or times out.
+        if let Some(content) = output(cmd, args) {
+            return Some(content);
+        }
+    }
+
+    None
+}
+
+// `read_async` reads `target` on another thread, so that the caller isn't
+// blocked while the commands that read it run. The contents of `target`, if it
+// can be read, are sent on the returned channel.
+pub fn read_async(target: Target) -> Receiver<Option<Vec<u8>>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(read(target));
+    });
+
+    rx
+}
+
+// `output` returns the output of `cmd`, if it succeeds within `READ_TIMEOUT`.
+fn output(cmd: &str, args: &[&str]) -> Option<Vec<u8>> {
+    let mut child = Command::new(cmd)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    // The output is read on another thread so that the read can be abandoned
+    // if it times out.
+    let mut stdout = child.stdout.take()?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut content = vec![];
+        let _ = tx.send(stdout.read_to_end(&mut content).map(|_| content));
+    });
+
+    match rx.recv_timeout(READ_TIMEOUT) {
+        Ok(Ok(content)) => {
+            let succeeded = child.wait().map_or(false, |status| status.success());
+            if succeeded {
+                Some(content)
+            } else {
+                None
+            }
+        },
+        _ => {
+            let _ = child.kill();
+            let _ = child.wait();
+            None
+        },
+    }
+}
+
+// `write_async` writes `target` on another thread, in the same way as
+// `read_async`, so that the caller isn't blocked by a command that hangs.
+// Whether `target` was written is sent on the returned channel.
+pub fn write_async(target: Target, content: Vec<u8>) -> Receiver<bool> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(write(target, &content));
+    });
+
+    rx
+}
+
+// `write` sets the contents of `target` to `content`, and returns `false` if
+// none of the commands that can write `target` succeeded.
+fn write(target: Target, content: &[u8]) -> bool {
+    for &(t, var, cmd, args) in WRITERS {
+        if t != target || !available(var) {
+            continue;
+        }
+        // The output of the command isn't captured, because commands such as
+        // `xclip` leave a process running in the background to serve the
+        // selection, which would hold the output open.
+        let child = Command::new(cmd)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child =
+            match child {
+                Ok(child) => child,
+                Err(_) => continue,
+            };
+        let written = match child.stdin.take() {
+            // `stdin` is dropped after it's written to, so that the command
+            // sees the end of its input.
+            Some(mut stdin) => stdin.write_all(content).is_ok(),
+            None => false,
+        };
+        let succeeded = child.wait().map_or(false, |status| status.success());
+        if written && succeeded {
+            return true;
+        }
+    }
+
+    false
+}
+
+// `osc52` returns the OSC 52 sequence that sets `target` to `content`, which
+// is also the format of replies to OSC 52 queries.
+pub fn osc52(target: Target, content: &[u8]) -> String {
+    let selection = if target == Target::Primary { "p" } else { "c" };
+
+    format!("\x1b]52;{};{}\x1b\\", selection, base64::encode(content))
+}
+
+// `available` returns `true` if the commands that require the environment
+// variable `var` can be used.
+fn available(var: &str) -> bool {
+    var.is_empty() || env::var_os(var).map_or(false, |v| !v.is_empty())
+}
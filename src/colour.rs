@@ -0,0 +1,238 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use alacritty::ansi::{Color, NamedColor};
+
+#[cfg(feature = "curses")]
+use pancurses;
+
+use palette::{Palette, Rgb};
+use quantise;
+use quantise::Depth;
+
+// `DEFAULT` is the curses colour that refers to the host terminal's default
+// foreground or background colour, when `Support::default_colours` is `true`.
+pub const DEFAULT: i16 = -1;
+
+// `Support` describes the colour support of the host curses session.
+#[derive(Clone, Copy, Debug)]
+pub struct Support {
+    pub depth: Depth,
+    // `default_colours` is `true` if `pancurses::use_default_colors()` was
+    // called successfully, in which case `DEFAULT` can be used to draw cells
+    // using the host's default colours. This is preferable to drawing such
+    // cells using white and black, which can look wrong on hosts with light or
+    // transparent backgrounds.
+    pub default_colours: bool,
+}
+
+impl Support {
+    // `detect` should only be called after `pancurses::start_color()`.
+    #[cfg(feature = "curses")]
+    pub fn detect() -> Support {
+        Support{
+            depth: Depth::detect(),
+            default_colours: pancurses::use_default_colors() == pancurses::OK,
+        }
+    }
+}
+
+// `Spec` is a colour that's tracked outside of `alacritty::Term`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Spec {
+    Indexed(u8),
+    Rgb(Rgb),
+}
+
+pub fn spec_to_curses(support: Support, palette: &Palette, spec: Spec) -> (i16, bool) {
+    match spec {
+        Spec::Indexed(i) => {
+            match palette.get(i) {
+                Some((r, g, b)) => (rgb_to_curses(support.depth, r, g, b), false),
+                None => indexed_to_curses(support.depth, i),
+            }
+        },
+        Spec::Rgb((r, g, b)) => (rgb_to_curses(support.depth, r, g, b), false),
+    }
+}
+
+// `to_curses` returns the curses colour that should be used to draw `c`, and
+// whether the colour should be drawn in bold. The latter is used to distinguish
+// bright colours on hosts that don't support 16 colours, as many terminals draw
+// bold text using the bright variant of the text colour. Base colours are
+// translated through `palette` before being approximated.
+pub fn to_curses(support: Support, palette: &Palette, c: Color) -> (i16, bool) {
+    let index =
+        match c {
+            Color::Named(NamedColor::Foreground) => {
+                match palette.foreground() {
+                    Some((r, g, b)) => {
+                        return (rgb_to_curses(support.depth, r, g, b), false);
+                    },
+                    None if support.default_colours => {
+                        return (DEFAULT, false);
+                    },
+                    None => named_to_index(NamedColor::Foreground),
+                }
+            },
+            Color::Named(NamedColor::Background) => {
+                match palette.background() {
+                    Some((r, g, b)) => {
+                        return (rgb_to_curses(support.depth, r, g, b), false);
+                    },
+                    None if support.default_colours => {
+                        return (DEFAULT, false);
+                    },
+                    None => named_to_index(NamedColor::Background),
+                }
+            },
+            Color::Named(name) => named_to_index(name),
+            Color::Indexed(i) => i,
+            Color::Spec(rgb) => {
+                return (rgb_to_curses(support.depth, rgb.r, rgb.g, rgb.b), false);
+            },
+        };
+
+    match palette.get(index) {
+        Some((r, g, b)) => (rgb_to_curses(support.depth, r, g, b), false),
+        None => indexed_to_curses(support.depth, index),
+    }
+}
+
+// `to_rgb` returns the 24-bit colour that `c` is drawn in, if `c` was given as
+// a 24-bit colour or `palette` overrides it, or `None` if `c` is drawn in an
+// entry of the host's palette, or in its default colour.
+pub fn to_rgb(palette: &Palette, c: Color) -> Option<Rgb> {
+    match c {
+        Color::Named(NamedColor::Foreground) => palette.foreground(),
+        Color::Named(NamedColor::Background) => palette.background(),
+        Color::Named(name) => palette.get(named_to_index(name)),
+        Color::Indexed(i) => palette.get(i),
+        Color::Spec(rgb) => Some((rgb.r, rgb.g, rgb.b)),
+    }
+}
+
+// `resolve_spec` returns `spec`, or the 24-bit colour that `palette` overrides
+// it with.
+pub fn resolve_spec(palette: &Palette, spec: Spec) -> Spec {
+    match spec {
+        Spec::Indexed(i) => palette.get(i).map_or(spec, Spec::Rgb),
+        Spec::Rgb(_) => spec,
+    }
+}
+
+// `named_to_index` returns the entry of the xterm 256-colour palette that
+// corresponds to `name`.
+pub fn named_to_index(name: NamedColor) -> u8 {
+    match name {
+        NamedColor::Background => 0,
+        NamedColor::Black => 0,
+        NamedColor::Blue => 4,
+        NamedColor::BrightBlack => 8,
+        NamedColor::BrightBlue => 12,
+        NamedColor::BrightCyan => 14,
+        NamedColor::BrightGreen => 10,
+        NamedColor::BrightMagenta => 13,
+        NamedColor::BrightRed => 9,
+        NamedColor::BrightWhite => 15,
+        NamedColor::BrightYellow => 11,
+        NamedColor::Cursor => 0,
+        NamedColor::CursorText => 7,
+        NamedColor::Cyan => 6,
+        NamedColor::DimBlack => 0,
+        NamedColor::DimBlue => 4,
+        NamedColor::DimCyan => 6,
+        NamedColor::DimGreen => 2,
+        NamedColor::DimMagenta => 5,
+        NamedColor::DimRed => 1,
+        NamedColor::DimWhite => 7,
+        NamedColor::DimYellow => 3,
+        NamedColor::Foreground => 7,
+        NamedColor::Green => 2,
+        NamedColor::Magenta => 5,
+        NamedColor::Red => 1,
+        NamedColor::White => 7,
+        NamedColor::Yellow => 3,
+    }
+}
+
+// `indexed_to_curses` approximates an entry of the xterm 256-colour palette
+// using the colours available in `depth`. Bright colours are approximated using
+// their normal variants, drawn in bold, if `depth` doesn't include them.
+pub fn indexed_to_curses(depth: Depth, index: u8) -> (i16, bool) {
+    if depth == Depth::Colours8 && index >= 8 && index < 16 {
+        return ((index - 8) as i16, true);
+    }
+
+    (quantise::reduce(depth, index) as i16, false)
+}
+
+pub fn rgb_to_curses(depth: Depth, r: u8, g: u8, b: u8) -> i16 {
+    quantise::quantise(depth, r, g, b) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUPPORT_256: Support = Support{depth: Depth::Colours256, default_colours: true};
+    const SUPPORT_8: Support = Support{depth: Depth::Colours8, default_colours: false};
+
+    #[test]
+    fn indexed_to_curses_draws_bright_colours_in_bold_in_8_colours() {
+        assert_eq!(indexed_to_curses(Depth::Colours8, 9), (1, true));
+        assert_eq!(indexed_to_curses(Depth::Colours8, 1), (1, false));
+        assert_eq!(indexed_to_curses(Depth::Colours16, 9), (9, false));
+    }
+
+    #[test]
+    fn indexed_to_curses_reduces_colours_outside_depth() {
+        assert_eq!(indexed_to_curses(Depth::Colours256, 196), (196, false));
+        assert_eq!(indexed_to_curses(Depth::Colours16, 196), (9, false));
+    }
+
+    #[test]
+    fn to_curses_uses_default_colours_if_supported() {
+        let palette = Palette::default();
+
+        assert_eq!(
+            to_curses(SUPPORT_256, &palette, Color::Named(NamedColor::Foreground)),
+            (DEFAULT, false),
+        );
+        assert_eq!(
+            to_curses(SUPPORT_8, &palette, Color::Named(NamedColor::Background)),
+            (0, false),
+        );
+    }
+
+    #[test]
+    fn to_curses_translates_base_colours_through_palette() {
+        let mut palette = Palette::default();
+        palette.set(1, (0x00, 0xff, 0x00));
+
+        assert_eq!(to_curses(SUPPORT_256, &palette, Color::Named(NamedColor::Red)), (46, false));
+        assert_eq!(to_curses(SUPPORT_256, &palette, Color::Indexed(1)), (46, false));
+        assert_eq!(to_curses(SUPPORT_256, &palette, Color::Indexed(2)), (2, false));
+    }
+
+    #[test]
+    fn to_rgb_returns_overrides_only() {
+        let mut palette = Palette::default();
+        palette.set(4, (0x12, 0x34, 0x56));
+
+        assert_eq!(to_rgb(&palette, Color::Named(NamedColor::Blue)), Some((0x12, 0x34, 0x56)));
+        assert_eq!(to_rgb(&palette, Color::Indexed(5)), None);
+        assert_eq!(to_rgb(&palette, Color::Named(NamedColor::Foreground)), None);
+    }
+
+    #[test]
+    fn resolve_spec_replaces_overridden_indices() {
+        let mut palette = Palette::default();
+        palette.set(3, (0xab, 0xcd, 0xef));
+
+        assert_eq!(resolve_spec(&palette, Spec::Indexed(3)), Spec::Rgb((0xab, 0xcd, 0xef)));
+        assert_eq!(resolve_spec(&palette, Spec::Indexed(4)), Spec::Indexed(4));
+        assert_eq!(resolve_spec(&palette, Spec::Rgb((1, 2, 3))), Spec::Rgb((1, 2, 3)));
+    }
+}
@@ -0,0 +1,632 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml;
+
+use palette;
+use palette::Rgb;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub colours: Colours,
+    pub fallbacks: Fallbacks,
+    pub selection: Highlight,
+    pub search_match: Highlight,
+    // `active_border` is used to draw the border of the active pane while the
+    // current tab has more than one pane.
+    pub active_border: Highlight,
+    pub cursor: Cursor,
+    pub text: Text,
+    pub keyboard: Keyboard,
+    pub mouse: Mouse,
+    // `keybindings` maps keys to the emulator actions that they trigger (see
+    // `Keybindings`).
+    pub keybindings: HashMap<String, Action>,
+    pub prefix: Prefix,
+    pub quit: Quit,
+    pub scrollback: Scrollback,
+    pub clipboard: Clipboard,
+    pub panes: Panes,
+    pub dash: Dash,
+    // `log_file` is a file that the server's errors are written to, unless
+    // another file is given using `--log-file`.
+    pub log_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Clipboard {
+    // `copy_on_select` is `true` if text that's selected using the mouse should
+    // be copied to the clipboard, as well as to the primary selection.
+    pub copy_on_select: bool,
+    // `osc52` is the access to the host's selections that the child is given
+    // through OSC 52 sequences.
+    pub osc52: Osc52,
+    // `osc52_limit` is the maximum number of bytes that the child can copy
+    // using a single OSC 52 sequence.
+    pub osc52_limit: usize,
+    // `history` is the number of entries that are kept in the paste history.
+    pub history: usize,
+}
+
+impl Default for Clipboard {
+    fn default() -> Clipboard {
+        Clipboard{
+            copy_on_select: false,
+            osc52: Osc52::Write,
+            osc52_limit: 1_000_000,
+            history: 20,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Osc52 {
+    Deny,
+    Write,
+    Read,
+    ReadWrite,
+}
+
+impl Osc52 {
+    pub fn can_write(self) -> bool {
+        self == Osc52::Write || self == Osc52::ReadWrite
+    }
+
+    pub fn can_read(self) -> bool {
+        self == Osc52::Read || self == Osc52::ReadWrite
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Panes {
+    // `remain_on_exit` is `true` if panes should be kept after their children
+    // exit, so that their output can still be read, and so that new children
+    // can be started in them.
+    pub remain_on_exit: bool,
+    // `command` is a command line that new panes run using `sh -c`, instead
+    // of the user's shell.
+    pub command: Option<String>,
+    // `shell` is the shell that new panes run, instead of the user's shell,
+    // and `login` is `true` if the shell is started as a login shell.
+    pub shell: Option<String>,
+    pub login: bool,
+    // `border` is the character that the borders of panes are drawn with,
+    // until `Action::ToggleBorder` is used.
+    pub border: char,
+    // `restart` is `true` if the children of new panes are restarted when
+    // they exit (see `Restart`), and `restart_delay` is the number of
+    // milliseconds that's waited before the first restart.
+    pub restart: bool,
+    pub restart_delay: u64,
+    // `env` holds the environment variables that `--env` gives, which
+    // children are started with, and which can't be set in the file.
+    #[serde(skip)]
+    pub env: Vec<(String, String)>,
+}
+
+impl Default for Panes {
+    fn default() -> Panes {
+        Panes{
+            remain_on_exit: false,
+            command: None,
+            shell: None,
+            login: false,
+            border: '*',
+            restart: false,
+            restart_delay: 1000,
+            env: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Dash {
+    // `commands` are the commands that `tep dash` runs if none are given on
+    // the command line.
+    pub commands: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scrollback {
+    // `lines` is the maximum number of lines that are kept in the scrollback,
+    // where `0` disables the scrollback.
+    pub lines: usize,
+}
+
+impl Default for Scrollback {
+    fn default() -> Scrollback {
+        Scrollback{lines: 10_000}
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quit {
+    // `confirm` is `true` if quitting should be confirmed by pressing `y`.
+    pub confirm: bool,
+    // `signal` is the signal that's sent to the child when quitting.
+    pub signal: Signal,
+}
+
+impl Default for Quit {
+    fn default() -> Quit {
+        Quit{
+            confirm: true,
+            signal: Signal::Hup,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Signal {
+    Hup,
+    Int,
+    Term,
+    Kill,
+    // `None` leaves the child running, though it will still receive `SIGHUP`
+    // from the kernel if it's still attached to the terminal when the
+    // emulator exits.
+    None,
+}
+
+// `Prefix` configures the prefix key, which is pressed before the keys in
+// `bindings` to trigger their actions, so that they don't need to be taken
+// from the child.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Prefix {
+    // `key` is the prefix key, in the same format as the keys of
+    // `Config::keybindings`. The prefix key is disabled if `key` is empty.
+    pub key: String,
+    pub bindings: HashMap<String, Action>,
+}
+
+impl Default for Prefix {
+    fn default() -> Prefix {
+        Prefix{
+            key: "^A".to_string(),
+            bindings: HashMap::new(),
+        }
+    }
+}
+
+// `Action` is an emulator action that can be bound to a key.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    ToggleBorder,
+    PeekPrimary,
+    // `Prompt` opens a prompt for entering emulator commands.
+    Prompt,
+    // `PasteFile` opens the prompt with the `paste` command.
+    PasteFile,
+    // `StartMacro` starts recording the keys that are sent to the child, until
+    // `StopMacro`, so that they can be replayed using `PlayMacro`.
+    StartMacro,
+    StopMacro,
+    PlayMacro,
+    // `TogglePassthrough` toggles passing all keys, other than the prefix key
+    // followed by the key that's bound to `TogglePassthrough`, to the child.
+    TogglePassthrough,
+    // `ScrollPageUp` and `ScrollPageDown` scroll the scrollback by a page.
+    ScrollPageUp,
+    ScrollPageDown,
+    // `ScrollMode` enters copy mode, in which the scrollback can be navigated,
+    // and text can be selected, using the keyboard.
+    ScrollMode,
+    // `PasteClipboard` pastes the contents of the host's clipboard, or the text
+    // that was last selected if the clipboard can't be read.
+    PasteClipboard,
+    // `PasteHistory` opens a list of the text that was recently copied or
+    // pasted, to choose an entry to paste.
+    PasteHistory,
+    // `Search` opens a prompt for a regular expression to search the
+    // scrollback for.
+    Search,
+    // `ClearScrollback` discards the lines that have scrolled off the top of
+    // the screen, and `ClearScreen` also clears the screen, other than the
+    // line that the cursor is on.
+    ClearScrollback,
+    ClearScreen,
+    // `Detach` detaches the client from the session, which keeps running in
+    // the background.
+    Detach,
+    // `SplitRight` and `SplitDown` split the active pane in two, and start a
+    // new child in the new pane, which is placed to the right of or below the
+    // active pane.
+    SplitRight,
+    SplitDown,
+    // `SelectPaneLeft`, `SelectPaneDown`, `SelectPaneUp` and `SelectPaneRight`
+    // make the pane next to the active pane in that direction active, and
+    // `NextPane` makes the next pane in the layout active.
+    SelectPaneLeft,
+    SelectPaneDown,
+    SelectPaneUp,
+    SelectPaneRight,
+    NextPane,
+    // `ClosePane` sends `SIGHUP` to the child of the active pane, and the pane
+    // is closed when the child exits.
+    ClosePane,
+    // `ResizePaneLeft`, `ResizePaneDown`, `ResizePaneUp` and `ResizePaneRight`
+    // move a border of the active pane in that direction.
+    ResizePaneLeft,
+    ResizePaneDown,
+    ResizePaneUp,
+    ResizePaneRight,
+    // `SwapPaneNext` and `SwapPanePrevious` exchange the position of the
+    // active pane with that of the next or previous pane in the layout, and
+    // `RotatePanes` moves each pane of the current tab to the position of the
+    // next pane.
+    SwapPaneNext,
+    SwapPanePrevious,
+    RotatePanes,
+    // `TogglePause` stops and restarts reading the output of the child of the
+    // active pane.
+    TogglePause,
+    // `ToggleSync` toggles sending input to every pane of the current tab.
+    ToggleSync,
+    // `NextLayout` arranges the panes of the current tab using the next
+    // layout preset.
+    NextLayout,
+    // `NewTab` opens a new tab with a single pane, and `NextTab` and
+    // `PreviousTab` switch between tabs.
+    NewTab,
+    NextTab,
+    PreviousTab,
+    // `RenameTab` opens the prompt with the `rename` command.
+    RenameTab,
+    // `ChooseTab` opens a list of the tabs to choose a tab to switch to.
+    ChooseTab,
+    Quit,
+    // `None` removes the default binding of a key, so that it's passed to the
+    // child.
+    None,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Mouse {
+    // `wheel` is `true` if the mouse wheel should be used to scroll when the
+    // child hasn't enabled mouse reporting. Otherwise, wheel events are left
+    // to the host. It's `false` by default, because capturing any mouse event
+    // makes the host report every mouse event, so the host can no longer be
+    // used to select text without holding Shift.
+    pub wheel: bool,
+    // `wheel_lines` is the number of lines that are scrolled for each step of
+    // the mouse wheel.
+    pub wheel_lines: usize,
+    // `alternate_scroll` is `true` if the mouse wheel should send the Up and
+    // Down keys while the alternate screen is active.
+    pub alternate_scroll: bool,
+    // `middle_click_paste` is `true` if the middle button should paste the
+    // primary selection when the child hasn't enabled mouse reporting. Like
+    // `wheel`, it's `false` by default, so that the host can paste instead.
+    pub middle_click_paste: bool,
+    // `double_click_select` is `true` if double-clicking a word should select
+    // it when the child hasn't enabled mouse reporting.
+    pub double_click_select: bool,
+    // `drag_select` is `true` if dragging with the left button should select
+    // text when the child hasn't enabled mouse reporting. Like `wheel`, these
+    // are `false` by default, so that the host can select text.
+    pub drag_select: bool,
+}
+
+impl Default for Mouse {
+    fn default() -> Mouse {
+        Mouse{
+            wheel: false,
+            wheel_lines: 3,
+            alternate_scroll: true,
+            middle_click_paste: false,
+            double_click_select: false,
+            drag_select: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keyboard {
+    // `meta_8bit` should be `true` if the host sends Alt-modified characters
+    // by setting their eighth bit, rather than by prefixing them with `ESC`.
+    pub meta_8bit: bool,
+    // `meta_prefix` is a key, in caret notation (such as `^_`), that sends the
+    // next key to the child prefixed with `ESC`, as if it had been pressed
+    // with Alt held down.
+    pub meta_prefix: Option<String>,
+    pub backspace: Backspace,
+    pub delete: Delete,
+}
+
+// `Backspace` is the byte that's sent when Backspace is pressed.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum Backspace {
+    #[serde(rename = "^?")]
+    Del,
+    #[serde(rename = "^H")]
+    Bs,
+}
+
+impl Default for Backspace {
+    fn default() -> Backspace {
+        Backspace::Del
+    }
+}
+
+// `Delete` is the sequence that's sent when Delete is pressed.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum Delete {
+    // `Csi` sends `CSI 3 ~`.
+    #[serde(rename = "csi")]
+    Csi,
+    #[serde(rename = "^?")]
+    Del,
+}
+
+impl Default for Delete {
+    fn default() -> Delete {
+        Delete::Csi
+    }
+}
+
+impl Keyboard {
+    pub fn meta_prefix_key(&self) -> Result<Option<char>, String> {
+        match self.meta_prefix {
+            Some(ref s) => match parse_caret(s) {
+                Some(c) => Ok(Some(c)),
+                None => Err(format!(
+                    "invalid key for 'keyboard.meta_prefix' (expected caret notation, such as '^_'): {}",
+                    s,
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+// `parse_caret` parses a control character in caret notation, such as `^A`
+// or `^?`.
+pub fn parse_caret(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('^'), Some('?'), None) => Some('\x7f'),
+        (Some('^'), Some(c), None) => {
+            let c = c.to_ascii_uppercase();
+            if '@' <= c && c <= '_' {
+                Some(((c as u8) ^ 0x40) as char)
+            } else {
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Text {
+    // `ambiguous_width` is the width of East Asian ambiguous-width characters,
+    // and should match the width that the host terminal uses for them.
+    pub ambiguous_width: Width,
+    pub line_drawing: LineDrawing,
+    // `word_separators` are the characters that separate words when selecting
+    // words.
+    pub word_separators: String,
+}
+
+impl Default for Text {
+    fn default() -> Text {
+        Text{
+            ambiguous_width: Width::default(),
+            line_drawing: LineDrawing::default(),
+            word_separators: ",│`|:\"' ()[]{}<>\t".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Width {
+    Narrow,
+    Wide,
+}
+
+impl Default for Width {
+    fn default() -> Width {
+        Width::Narrow
+    }
+}
+
+// `LineDrawing` defines how the line-drawing characters of the DEC special
+// graphics character set are drawn.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineDrawing {
+    Acs,
+    Ascii,
+    Unicode,
+}
+
+impl Default for LineDrawing {
+    fn default() -> LineDrawing {
+        LineDrawing::Acs
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Cursor {
+    pub blink: bool,
+    // `blink_interval` is the time, in milliseconds, between each change in
+    // the visibility of a blinking cursor.
+    pub blink_interval: u64,
+}
+
+impl Default for Cursor {
+    fn default() -> Cursor {
+        Cursor{
+            blink: false,
+            blink_interval: 500,
+        }
+    }
+}
+
+// `Fallbacks` defines how attributes that curses can't render are
+// approximated.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fallbacks {
+    pub undercurl: Fallback,
+    pub strikethrough: Fallback,
+}
+
+impl Default for Fallbacks {
+    fn default() -> Fallbacks {
+        Fallbacks{
+            undercurl: Fallback::Underline,
+            strikethrough: Fallback::Dim,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Fallback {
+    Underline,
+    Bold,
+    Dim,
+    Italic,
+    Reverse,
+    None,
+}
+
+// `Colours` remaps the 16 base colours. Each colour is given as a string of the
+// form `#rrggbb`, and colours that aren't given keep their default values.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colours {
+    pub black: Option<String>,
+    pub red: Option<String>,
+    pub green: Option<String>,
+    pub yellow: Option<String>,
+    pub blue: Option<String>,
+    pub magenta: Option<String>,
+    pub cyan: Option<String>,
+    pub white: Option<String>,
+    pub bright_black: Option<String>,
+    pub bright_red: Option<String>,
+    pub bright_green: Option<String>,
+    pub bright_yellow: Option<String>,
+    pub bright_blue: Option<String>,
+    pub bright_magenta: Option<String>,
+    pub bright_cyan: Option<String>,
+    pub bright_white: Option<String>,
+}
+
+impl Colours {
+    // `entries` returns the colours in the order of the SGR colour numbering,
+    // along with their names.
+    pub fn entries(&self) -> [(&'static str, &Option<String>); 16] {
+        [
+            ("black", &self.black),
+            ("red", &self.red),
+            ("green", &self.green),
+            ("yellow", &self.yellow),
+            ("blue", &self.blue),
+            ("magenta", &self.magenta),
+            ("cyan", &self.cyan),
+            ("white", &self.white),
+            ("bright_black", &self.bright_black),
+            ("bright_red", &self.bright_red),
+            ("bright_green", &self.bright_green),
+            ("bright_yellow", &self.bright_yellow),
+            ("bright_blue", &self.bright_blue),
+            ("bright_magenta", &self.bright_magenta),
+            ("bright_cyan", &self.bright_cyan),
+            ("bright_white", &self.bright_white),
+        ]
+    }
+}
+
+// `Highlight` defines the colours used to draw highlighted cells, such as
+// selected cells, in the form `#rrggbb`. Highlighted cells are drawn using
+// attributes if these aren't given.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Highlight {
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+}
+
+impl Highlight {
+    // `colours` returns the parsed colours of the highlight. `name` is the name
+    // of the highlight's table, and is used in error messages.
+    pub fn colours(&self, name: &str) -> Result<Option<(Rgb, Rgb)>, String> {
+        match (&self.foreground, &self.background) {
+            (Some(fg), Some(bg)) => {
+                let fg = parse_colour(&format!("{}.foreground", name), fg)?;
+                let bg = parse_colour(&format!("{}.background", name), bg)?;
+
+                Ok(Some((fg, bg)))
+            },
+            (None, None) => Ok(None),
+            _ => Err(format!(
+                "'{0}.foreground' and '{0}.background' must be given together",
+                name,
+            )),
+        }
+    }
+}
+
+fn parse_colour(name: &str, s: &str) -> Result<Rgb, String> {
+    match palette::parse_rgb(s) {
+        Some(rgb) => Ok(rgb),
+        None => Err(format!("invalid colour for '{}' (expected '#rrggbb'): {}", name, s)),
+    }
+}
+
+// `dir` returns the directory that the files of `tep`, such as its
+// configuration file, are kept in, which is `$XDG_CONFIG_HOME/tep`, or
+// `~/.config/tep` if `XDG_CONFIG_HOME` isn't set.
+pub fn dir() -> Option<PathBuf> {
+    let config_dir =
+        match env::var_os("XDG_CONFIG_HOME") {
+            Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+
+    Some(config_dir.join("tep"))
+}
+
+// `default_path` returns the path of the configuration file that's loaded if
+// `--config` isn't given, which is `config.toml` in `dir`.
+pub fn default_path() -> Option<PathBuf> {
+    Some(dir()?.join("config.toml"))
+}
+
+pub fn load(path: &Path) -> Result<Config, String> {
+    let src = match fs::read_to_string(path) {
+        Ok(src) => src,
+        Err(e) => {
+            return Err(format!("couldn't read '{}': {}", path.display(), e));
+        },
+    };
+
+    match toml::from_str(&src) {
+        Ok(conf) => Ok(conf),
+        Err(e) => Err(format!("couldn't parse '{}': {}", path.display(), e)),
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+
+use alacritty::Term;
+use alacritty::term::cell::Flags;
+
+use pancurses::Input;
+
+use scrollback;
+use scrollback::Scrollback;
+use selection::Selection;
+
+// `Step` is the result of passing a key to `CopyMode`.
+pub enum Step {
+    Pending,
+    Exit,
+    // `Yank` exits copy mode, returning the selected text.
+    Yank(String),
+}
+
+// `CopyMode` navigates the scrollback and the screen using a cursor that's
+// moved using `vi`-style keys, so that text can be selected without a mouse.
+// Positions are `(line, column)` pairs, where lines count from the oldest line
+// of the scrollback, followed by the lines of the screen, so that they don't
+// change when the viewport is scrolled.
+pub struct CopyMode {
+    cursor: (usize, usize),
+    // `anchor` is the position where the selection was started, if any.
+    anchor: Option<(usize, usize)>,
+    // `block` is `true` if the selection is a block selection.
+    block: bool,
+    separators: String,
+    // `evicted` is the number of lines that had been evicted from the
+    // scrollback when the positions were last synced (see `sync`).
+    evicted: usize,
+}
+
+impl CopyMode {
+    // `new` starts copy mode with the cursor at the cursor of `term`, or at the
+    // bottom of the viewport if it's scrolled back. `separators` are the
+    // characters that separate words.
+    pub fn new(term: &Term, scrollback: &Scrollback, separators: &str) -> CopyMode {
+        let num_lines = term.grid().num_lines().0;
+        let cursor =
+            if scrollback.offset() == 0 {
+                let point = term.cursor().point;
+                (scrollback.len() + point.line.0, point.col.0)
+            } else {
+                (scrollback.len() - scrollback.offset() + num_lines - 1, 0)
+            };
+
+        CopyMode{
+            cursor: cursor,
+            anchor: None,
+            block: false,
+            separators: separators.to_string(),
+            evicted: scrollback.evicted(),
+        }
+    }
+
+    // `sync` moves the positions of copy mode up by the number of lines that
+    // have been evicted from `scrollback` since they were last synced, so that
+    // they stay on the same lines, and should be called after lines are added
+    // to `scrollback`. Positions on lines that were evicted move to the oldest
+    // line.
+    pub fn sync(&mut self, scrollback: &Scrollback) {
+        // The scrollback is replaced when the pane is respawned, which resets
+        // its count.
+        let n = scrollback.evicted().saturating_sub(self.evicted);
+        self.evicted = scrollback.evicted();
+        self.cursor.0 = self.cursor.0.saturating_sub(n);
+        if let Some(ref mut anchor) = self.anchor {
+            anchor.0 = anchor.0.saturating_sub(n);
+        }
+    }
+
+    // `handle` moves the cursor, or changes the selection, according to
+    // `input`. The viewport of `scrollback` follows the cursor.
+    pub fn handle(&mut self, input: &Input, term: &Term, scrollback: &mut Scrollback) -> Step {
+        self.sync(scrollback);
+        let num_lines = term.grid().num_lines().0;
+        let last_line = scrollback.len() + num_lines - 1;
+        let last_col = term.grid().num_cols().0 - 1;
+        let (line, col) = self.cursor;
+        match *input {
+            Input::KeyLeft | Input::Character('h') => self.cursor.1 = col.saturating_sub(1),
+            Input::KeyRight | Input::Character('l') => self.cursor.1 = cmp::min(col + 1, last_col),
+            Input::KeyUp | Input::Character('k') => self.cursor.0 = line.saturating_sub(1),
+            Input::KeyDown | Input::Character('j') => self.cursor.0 = cmp::min(line + 1, last_line),
+            Input::Character('w') => self.cursor = self.next_word(term, scrollback),
+            Input::Character('b') => self.cursor = self.prev_word(term, scrollback),
+            Input::KeyHome | Input::Character('0') => self.cursor.1 = 0,
+            Input::KeyEnd | Input::Character('$') => {
+                let cells = scrollback::line_cells(term, scrollback, line);
+                let end = cells.iter().rposition(|c| c.c != ' ').unwrap_or(0);
+                self.cursor.1 = cmp::min(end, last_col);
+            },
+            // Paging moves the viewport by a page, and the cursor along with
+            // it.
+            Input::KeyPPage | Input::KeySPrevious | Input::Character('\x02') => {
+                scrollback.scroll(num_lines as isize);
+                self.cursor.0 = line.saturating_sub(num_lines);
+            },
+            Input::KeyNPage | Input::KeySNext | Input::Character('\x06') | Input::Character(' ') => {
+                scrollback.scroll(-(num_lines as isize));
+                self.cursor.0 = cmp::min(line + num_lines, last_line);
+            },
+            Input::Character('g') => self.cursor = (0, 0),
+            Input::Character('G') => self.cursor = (last_line, 0),
+            // `v` and `^V` start character and block selections, as in `vi`.
+            // Pressing the key for the current kind of selection clears it,
+            // and pressing the key for the other kind switches to it.
+            Input::Character('v') | Input::Character('\x16') => {
+                let block = *input == Input::Character('\x16');
+                if self.anchor.is_some() && self.block == block {
+                    self.anchor = None;
+                } else {
+                    self.anchor = self.anchor.or(Some(self.cursor));
+                    self.block = block;
+                }
+            },
+            Input::Character('y') | Input::Character('\n') | Input::Character('\r') | Input::KeyEnter => {
+                if let Some(selection) = self.selected() {
+                    return Step::Yank(selection.text(|line| scrollback::line_cells(term, scrollback, line)));
+                }
+            },
+            Input::Character('q') | Input::Character('\x1b') => return Step::Exit,
+            _ => {},
+        }
+        scrollback.reveal(self.cursor.0, num_lines);
+
+        Step::Pending
+    }
+
+    // `move_to` moves the cursor to `pos`, scrolling the viewport to show it.
+    pub fn move_to(&mut self, pos: (usize, usize), term: &Term, scrollback: &mut Scrollback) {
+        self.sync(scrollback);
+        self.cursor = pos;
+        scrollback.reveal(pos.0, term.grid().num_lines().0);
+    }
+
+    // `cursor` returns the position of the cursor relative to the top of the
+    // viewport, if it's visible on a screen of `num_lines` lines.
+    pub fn cursor(&self, scrollback: &Scrollback, num_lines: usize) -> Option<(usize, usize)> {
+        let top = scrollback.len() - scrollback.offset();
+        let (line, col) = self.cursor;
+        if line < top || line >= top + num_lines {
+            return None;
+        }
+
+        Some((line - top, col))
+    }
+
+    // `selection` returns the part of the selection that's visible in the
+    // viewport, relative to the top of the viewport, on a screen of
+    // `num_lines` lines and `num_cols` columns.
+    pub fn selection(&self, scrollback: &Scrollback, num_lines: usize, num_cols: usize) -> Option<Selection> {
+        let selection = self.selected()?;
+        let (first, last) = selection.bounds();
+        let top = scrollback.len() - scrollback.offset();
+        if last.0 < top || first.0 >= top + num_lines {
+            return None;
+        }
+
+        // The ends of the selection that are outside the viewport are moved
+        // to its edges. Block selections keep their columns.
+        let clamp = |line: usize| cmp::min(cmp::max(line, top), top + num_lines - 1) - top;
+        if selection.block {
+            let start = (clamp(selection.start.0), selection.start.1);
+            let end = (clamp(selection.end.0), selection.end.1);
+            return Some(Selection::block(start, end));
+        }
+        let start = if first.0 < top { (0, 0) } else { (first.0 - top, first.1) };
+        let end = if last.0 >= top + num_lines { (num_lines - 1, num_cols - 1) } else { (last.0 - top, last.1) };
+
+        Some(Selection::new(start, end))
+    }
+
+    // `selected` returns the selection, using the positions of copy mode.
+    fn selected(&self) -> Option<Selection> {
+        let anchor = self.anchor?;
+        if self.block {
+            Some(Selection::block(anchor, self.cursor))
+        } else {
+            Some(Selection::new(anchor, self.cursor))
+        }
+    }
+
+    // `next_word` returns the position of the start of the word after the
+    // cursor, as with `w` in `vi`.
+    fn next_word(&self, term: &Term, scrollback: &Scrollback) -> (usize, usize) {
+        let lines = Lines::new(term, scrollback);
+        let mut pos = self.cursor;
+        let class = lines.class(pos, &self.separators);
+        if class != Class::Blank {
+            while let Some(next) = lines.next(pos) {
+                if next.0 != pos.0 || lines.class(next, &self.separators) != class {
+                    break;
+                }
+                pos = next;
+            }
+        }
+        while let Some(next) = lines.next(pos) {
+            pos = next;
+            if lines.class(pos, &self.separators) != Class::Blank {
+                break;
+            }
+        }
+
+        pos
+    }
+
+    // `prev_word` returns the position of the start of the word before the
+    // cursor, as with `b` in `vi`.
+    fn prev_word(&self, term: &Term, scrollback: &Scrollback) -> (usize, usize) {
+        let lines = Lines::new(term, scrollback);
+        let mut pos = self.cursor;
+        while let Some(prev) = lines.prev(pos) {
+            pos = prev;
+            if lines.class(pos, &self.separators) != Class::Blank {
+                break;
+            }
+        }
+        let class = lines.class(pos, &self.separators);
+        while let Some(prev) = lines.prev(pos) {
+            if prev.0 != pos.0 || lines.class(prev, &self.separators) != class {
+                break;
+            }
+            pos = prev;
+        }
+
+        pos
+    }
+}
+
+// `Class` is the class of a character for the purpose of word motions. Words
+// are runs of characters of the same class, other than `Blank`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Class {
+    Blank,
+    Separator,
+    Word,
+}
+
+// `Lines` reads the cells of the scrollback and the screen for word motions.
+struct Lines<'a> {
+    term: &'a Term,
+    scrollback: &'a Scrollback,
+    num_lines: usize,
+    num_cols: usize,
+}
+
+impl<'a> Lines<'a> {
+    fn new(term: &'a Term, scrollback: &'a Scrollback) -> Lines<'a> {
+        Lines{
+            term: term,
+            scrollback: scrollback,
+            num_lines: scrollback.len() + term.grid().num_lines().0,
+            num_cols: term.grid().num_cols().0,
+        }
+    }
+
+    fn class(&self, (line, col): (usize, usize), separators: &str) -> Class {
+        let cells = scrollback::line_cells(self.term, self.scrollback, line);
+        // The spacers that follow wide characters have the class of the wide
+        // characters.
+        let mut col = col;
+        while col > 0 && col < cells.len() && cells[col].flags.contains(Flags::WIDE_CHAR_SPACER) {
+            col -= 1;
+        }
+        match cells.get(col).map_or(' ', |c| c.c) {
+            ' ' | '\t' => Class::Blank,
+            c if separators.contains(c) => Class::Separator,
+            _ => Class::Word,
+        }
+    }
+
+    fn next(&self, (line, col): (usize, usize)) -> Option<(usize, usize)> {
+        if col + 1 < self.num_cols {
+            Some((line, col + 1))
+        } else if line + 1 < self.num_lines {
+            Some((line + 1, 0))
+        } else {
+            None
+        }
+    }
+
+    fn prev(&self, (line, col): (usize, usize)) -> Option<(usize, usize)> {
+        if col > 0 {
+            Some((line, col - 1))
+        } else if line > 0 {
+            Some((line - 1, self.num_cols - 1))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+use std::io::Write;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor};
+use crossterm::QueueableCommand;
+
+use acs;
+use colour;
+use layout::Rect;
+use renderer::{Attrs, Glyph, Renderer};
+
+// `CrosstermRenderer` is a `Renderer` that draws in `area` of the host terminal
+// using crossterm, which writes escape sequences to `out` (usually standard
+// output) rather than drawing in a curses window. Cells outside of `area`
+// aren't drawn. Write errors are ignored, as they are by curses.
+pub struct CrosstermRenderer<W: Write> {
+    out: W,
+    area: Rect,
+}
+
+impl<W: Write> CrosstermRenderer<W> {
+    pub fn new(out: W, area: Rect) -> CrosstermRenderer<W> {
+        CrosstermRenderer{out: out, area: area}
+    }
+
+    // `set_area` moves the area that's drawn in, such as after the host
+    // terminal is resized.
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+    }
+
+    fn set_attrs(&mut self, attrs: Attrs) {
+        let _ = self.out.queue(SetAttribute(Attribute::Reset));
+        let mappings = [
+            (attrs.bold, Attribute::Bold),
+            (attrs.dim, Attribute::Dim),
+            (attrs.italic, Attribute::Italic),
+            (attrs.underline, Attribute::Underlined),
+            (attrs.reverse, Attribute::Reverse),
+            (attrs.hidden, Attribute::Hidden),
+        ];
+        for &(set, attr) in mappings.iter() {
+            if set {
+                let _ = self.out.queue(SetAttribute(attr));
+            }
+        }
+        if let Some((fg, bg)) = attrs.colours {
+            let _ = self.out.queue(SetForegroundColor(to_color(fg)));
+            let _ = self.out.queue(SetBackgroundColor(to_color(bg)));
+        }
+    }
+
+    fn move_to(&mut self, y: i32, x: i32) {
+        let _ = self.out.queue(MoveTo((self.area.x + x) as u16, (self.area.y + y) as u16));
+    }
+}
+
+impl<W: Write> Renderer for CrosstermRenderer<W> {
+    fn size(&self) -> (i32, i32) {
+        (self.area.lines, self.area.cols)
+    }
+
+    // Only the area is cleared, rather than the whole screen, so that the
+    // rest of the host terminal is left as it is.
+    fn clear(&mut self) {
+        self.set_attrs(Attrs::default());
+        let blank = " ".repeat(cmp::max(self.area.cols, 0) as usize);
+        for y in 0..self.area.lines {
+            self.move_to(y, 0);
+            let _ = self.out.queue(Print(&blank));
+        }
+    }
+
+    fn draw_cell(&mut self, y: i32, x: i32, glyph: Glyph, attrs: Attrs) {
+        if y < 0 || y >= self.area.lines || x < 0 || x >= self.area.cols {
+            return;
+        }
+
+        self.move_to(y, x);
+        self.set_attrs(attrs);
+        match glyph {
+            Glyph::Text(text) => {
+                let _ = self.out.queue(Print(text));
+            },
+            Glyph::Char(c) => {
+                let _ = self.out.queue(Print(c));
+            },
+            Glyph::LineDrawing(c) => {
+                match acs::to_dec(c) {
+                    Some(dec) => {
+                        let _ = self.out.queue(Print(format!("\x1b(0{}\x1b(B", dec)));
+                    },
+                    None => {
+                        let _ = self.out.queue(Print(c));
+                    },
+                }
+            },
+        }
+        let _ = self.out.queue(SetAttribute(Attribute::Reset));
+    }
+
+    fn move_cursor(&mut self, y: i32, x: i32, visible: bool) {
+        self.move_to(y, x);
+        if visible {
+            let _ = self.out.queue(Show);
+        } else {
+            let _ = self.out.queue(Hide);
+        }
+    }
+
+    fn present(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+// `to_color` returns the crossterm colour for `c`, which is an entry of the
+// xterm 256-colour palette, or `colour::DEFAULT`.
+fn to_color(c: i16) -> Color {
+    if c == colour::DEFAULT {
+        Color::Reset
+    } else {
+        Color::AnsiValue(c as u8)
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use pancurses;
+use pancurses::chtype;
+use pancurses::ToChtype;
+use pancurses::Window;
+
+use acs;
+use pairs::Pairs;
+use renderer::{Attrs, Glyph, Renderer};
+
+// `CursesRenderer` is a `Renderer` that draws in a curses window, using colour
+// pairs that are allocated from `pairs`, which should be used for every
+// window of the curses session.
+pub struct CursesRenderer<'a> {
+    win: &'a Window,
+    pairs: &'a mut Pairs,
+}
+
+impl<'a> CursesRenderer<'a> {
+    pub fn new(win: &'a Window, pairs: &'a mut Pairs) -> CursesRenderer<'a> {
+        CursesRenderer{win: win, pairs: pairs}
+    }
+
+    fn to_chtype(&mut self, attrs: Attrs) -> chtype {
+        let mut ch = pancurses::A_NORMAL;
+        let mappings = [
+            (attrs.bold, pancurses::A_BOLD),
+            (attrs.dim, pancurses::A_DIM),
+            (attrs.italic, pancurses::A_ITALIC),
+            (attrs.underline, pancurses::A_UNDERLINE),
+            (attrs.reverse, pancurses::A_REVERSE),
+            (attrs.hidden, pancurses::A_INVIS),
+        ];
+        for &(set, attr) in mappings.iter() {
+            if set {
+                ch |= attr;
+            }
+        }
+        if let Some((fg, bg)) = attrs.colours {
+            ch |= pancurses::COLOR_PAIR(self.pairs.get(fg, bg) as chtype);
+        }
+
+        ch
+    }
+}
+
+impl<'a> Renderer for CursesRenderer<'a> {
+    fn size(&self) -> (i32, i32) {
+        self.win.get_max_yx()
+    }
+
+    // Colour pairs that were used to draw the last frame can be redefined
+    // once the window is cleared.
+    fn clear(&mut self) {
+        self.win.clear();
+        self.pairs.begin_frame();
+    }
+
+    fn draw_cell(&mut self, y: i32, x: i32, glyph: Glyph, attrs: Attrs) {
+        let attrs = self.to_chtype(attrs);
+        self.win.attrset(attrs);
+        match glyph {
+            Glyph::Text(text) => {
+                self.win.mvaddstr(y, x, text);
+            },
+            Glyph::Char(c) if c.is_ascii() => {
+                self.win.mvaddch(y, x, c.to_chtype());
+            },
+            Glyph::Char(c) => {
+                self.win.mvaddstr(y, x, &c.to_string());
+            },
+            Glyph::LineDrawing(c) => {
+                match acs::to_acs(c) {
+                    Some(ch) => self.win.mvaddch(y, x, ch),
+                    None => self.win.mvaddstr(y, x, &c.to_string()),
+                };
+            },
+        }
+        self.win.attrset(pancurses::A_NORMAL);
+    }
+
+    fn move_cursor(&mut self, y: i32, x: i32, visible: bool) {
+        pancurses::curs_set(if visible { 1 } else { 0 });
+        self.win.mv(y, x);
+    }
+
+    fn present(&mut self) {
+        self.win.refresh();
+    }
+}
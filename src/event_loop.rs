@@ -0,0 +1,2510 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use alacritty::ansi::Processor;
+use alacritty::config::Config as TermConfig;
+use alacritty::index::Line;
+use alacritty::Term;
+use alacritty::term::mode::TermMode;
+
+use altscreen::AltScreen;
+use args;
+use args::FixedSize;
+use backend::{Backend, PaneRenderer};
+use child;
+use child::Exit;
+use clipboard;
+use clipboard::Target;
+use colour;
+use config;
+use config::Action;
+use copymode::{CopyMode, Step};
+use extras::Extras;
+use focus;
+use focus::Focus;
+use grapheme;
+use history::History;
+use input;
+use input::{Encoder, KeyboardModes};
+use keybindings::Keybindings;
+use layout;
+use layout::{Direction, Layout, PaneId, Preset, Rect, Side};
+use macros::Macros;
+use mouse;
+use pairs::Pairs;
+use palette::Palette;
+use pane;
+use pane::{HostEvent, Output, Pane, Program};
+use paste;
+use paste::Paste;
+use picker::Picker;
+use prompt;
+use prompt::{Command, Outcome, Prompt};
+use record;
+use record::Recorder;
+use render;
+use renderer::Renderer;
+use restart::Restart;
+use scrollback;
+use scrollback::Scrollback;
+use search::Search;
+use selection;
+use selection::Selection;
+use session;
+use session::{End, Role};
+use state;
+use tab;
+use tab::Tab;
+
+use libc;
+use pancurses;
+use pancurses::{Input, Window};
+
+// `DOUBLE_CLICK_INTERVAL` is the maximum time between two presses of the left
+// button for them to be treated as a double-click. We detect double-clicks
+// ourselves because curses doesn't report them when `mouseinterval(0)` is
+// used.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+// `REPEAT_INTERVAL` is the time after an action that's bound to a key after the
+// prefix key, and that can be repeated, during which the key can be pressed
+// again without pressing the prefix key to repeat the action.
+const REPEAT_INTERVAL: Duration = Duration::from_millis(500);
+
+// `RESIZE_STEP` is the number of cells that the actions that resize panes move
+// a border by.
+const RESIZE_STEP: i32 = 1;
+
+// `SESSION_VAR` is the environment variable that holds the name of the session
+// that the children run in, so that `tmux` commands that are run by them are
+// sent to that session, rather than to the default session.
+const SESSION_VAR: &str = "TEP_SESSION";
+
+// `RELOAD_REQUESTED` is set when the server receives `SIGUSR1`, which requests
+// that the configuration is reloaded.
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// `RESIZED` is set when the server receives `SIGWINCH`, and `CURSES_WINCH` is
+// the handler that curses installed for `SIGWINCH`, which is called by
+// `on_winch` so that curses still reports the resize.
+static RESIZED: AtomicBool = AtomicBool::new(false);
+static CURSES_WINCH: AtomicUsize = AtomicUsize::new(0);
+
+// `SHUTDOWN_SIGNAL` is set to the signal that the server receives when it's
+// asked to end, such as `SIGTERM`, and is `0` until then.
+static SHUTDOWN_SIGNAL: AtomicUsize = AtomicUsize::new(0);
+
+// `SHUTDOWN_WAIT` is how long the server waits for the children to exit after
+// they've been hung up, before the server exits.
+const SHUTDOWN_WAIT: Duration = Duration::from_millis(500);
+
+// `RESIZE_DELAY` is how long the window must keep the same size before the
+// panes are laid out for it, so that the resizes that are reported while the
+// host window is being dragged are coalesced, and the children are only told
+// about the final size, rather than receiving a `SIGWINCH` for each step.
+const RESIZE_DELAY: Duration = Duration::from_millis(100);
+
+// `BORDER_CHARS` are the characters that `Action::ToggleBorder` cycles through.
+const BORDER_CHARS: [char; 3] = ['*', '+', '-'];
+
+// `MIN_LINES` and `MIN_COLS` are the smallest size of the window that panes are
+// drawn in, which leaves room for the status line, and for a pane's border
+// around at least one cell. `TOO_SMALL` is drawn instead while the window is
+// smaller.
+const MIN_LINES: i32 = 4;
+const MIN_COLS: i32 = 3;
+const TOO_SMALL: &str = "window too small";
+
+// `Chrome` holds the state of the parts of the window that are drawn around the
+// terminal.
+struct Chrome {
+    border_char: char,
+    peek_label: String,
+    // `prompt` is drawn over the status line while a command is being
+    // entered.
+    prompt: Option<Prompt>,
+    // `message` is drawn over the status line after a command fails, until
+    // the next key is pressed.
+    message: Option<String>,
+    // `recording` is `true` while a macro is being recorded.
+    recording: bool,
+    // `passthrough` is `true` if keys that are bound to actions are passed to
+    // the child instead.
+    passthrough: bool,
+    // `selection` is the region of the screen that's selected, if any.
+    selection: Option<Selection>,
+    // `copy_mode` is used to navigate the scrollback while it's active.
+    copy_mode: Option<CopyMode>,
+    // `picker` is drawn over the screen while an item is being chosen from
+    // it, along with what the chosen item is used for.
+    picker: Option<(Pick, Picker)>,
+    // `search` holds the last search, whose matches are highlighted while
+    // copy mode is active.
+    search: Option<Search>,
+}
+
+// `Pick` is what the item that's chosen from `Chrome::picker` is used for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Pick {
+    // `Paste` pastes the item.
+    Paste,
+    // `Tab` switches to the tab at the position of the item.
+    Tab,
+}
+
+// `run` runs `tep` using the arguments that the process was started with. Some
+// commands, such as `tep ls`, exit the process instead of returning.
+pub fn run() {
+    let args = match args::parse() {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(1);
+        },
+    };
+
+    match args.command {
+        args::Command::New | args::Command::Dash(_) => {},
+        args::Command::Attach(ref name, read_only) => attach(&socket_path(name.as_ref()), read_only),
+        args::Command::List => list_sessions(),
+        args::Command::KillSession(ref name) => kill_session(&socket_path(Some(name))),
+        args::Command::Play(ref path, speed, max_wait) => play(path, speed, max_wait),
+        args::Command::Tmux(ref words) => run_command(&socket_path(env::var(SESSION_VAR).ok().as_ref()), words),
+    }
+    // A session that isn't named is given the first name that isn't in use,
+    // so that starting `tep` doesn't fail while the default session is
+    // running.
+    let session_name =
+        match args.session.clone().map_or_else(session::unused_name, Ok) {
+            Ok(name) => name,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                process::exit(1);
+            },
+        };
+    let session_path = socket_path(Some(&session_name));
+
+    // The configuration file at the default path is optional, but a file
+    // that's given using `--config` must exist.
+    let conf_path = args.config.clone().or_else(|| config::default_path().filter(|path| path.exists()));
+    let mut conf =
+        match conf_path {
+            Some(ref path) => {
+                match config::load(path) {
+                    Ok(conf) => conf,
+                    Err(msg) => {
+                        eprintln!("{}", msg);
+                        process::exit(1);
+                    },
+                }
+            },
+            None => config::Config::default(),
+        };
+
+    // `dash_commands` are the commands that are run in their own panes by
+    // `tep dash`, which is empty if the emulator was started normally.
+    let dash_commands =
+        match args.command {
+            args::Command::Dash(ref commands) if !commands.is_empty() => commands.clone(),
+            args::Command::Dash(_) if !conf.dash.commands.is_empty() => conf.dash.commands.clone(),
+            args::Command::Dash(_) => {
+                eprintln!("no commands given, and 'dash.commands' isn't set");
+                process::exit(1);
+            },
+            _ => vec![],
+        };
+    // The panes of a dashboard are kept after their commands exit, so that
+    // their output can still be read.
+    if !dash_commands.is_empty() {
+        conf.panes.remain_on_exit = true;
+    }
+    if args.restart {
+        conf.panes.restart = true;
+    }
+    if args.login {
+        conf.panes.login = true;
+    }
+    if let Some(delay) = args.restart_delay {
+        conf.panes.restart_delay = delay;
+    }
+    conf.panes.env = args.env.clone();
+    // `new_program` is the program that the panes that are opened later run,
+    // which connect to the same host as the first pane if it was started using
+    // `tep ssh`, and is otherwise `None`, so that they run the user's shell.
+    let new_program =
+        match args.program {
+            Some(Program::Ssh(ref words)) => Some(Program::Ssh(vec![words[0].clone()])),
+            _ => None,
+        };
+
+    let startup_paste =
+        match args.paste {
+            Some(ref path) => {
+                match fs::read(path) {
+                    Ok(content) => Some(content),
+                    Err(e) => {
+                        eprintln!("couldn't read '{}': {}", path.display(), e);
+                        process::exit(1);
+                    },
+                }
+            },
+            None => None,
+        };
+
+    let restored_state =
+        match args.restore {
+            Some(ref path) => {
+                match fs::read(path) {
+                    Ok(content) => content,
+                    Err(e) => {
+                        eprintln!("couldn't read '{}': {}", path.display(), e);
+                        process::exit(1);
+                    },
+                }
+            },
+            None => vec![],
+        };
+
+    // The palette is checked before the first pane is started, although each
+    // pane loads its own palette (see `Pane::palette`).
+    if let Err(msg) = Palette::from_config(&conf.colours) {
+        eprintln!("{}", msg);
+        process::exit(1);
+    }
+
+    let selection_colours =
+        match conf.selection.colours("selection") {
+            Ok(colours) => colours,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                process::exit(1);
+            },
+        };
+
+    let match_colours =
+        match conf.search_match.colours("search_match") {
+            Ok(colours) => colours,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                process::exit(1);
+            },
+        };
+
+    let active_border_colours =
+        match conf.active_border.colours("active_border") {
+            Ok(colours) => colours,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                process::exit(1);
+            },
+        };
+
+    let meta_prefix =
+        match conf.keyboard.meta_prefix_key() {
+            Ok(key) => key,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                process::exit(1);
+            },
+        };
+
+    let mut keybindings =
+        match Keybindings::new(&conf.keybindings, &conf.prefix) {
+            Ok(keybindings) => keybindings,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                process::exit(1);
+            },
+        };
+    let peek_label = peek_label(&keybindings);
+
+    if conf.scrollback.lines > scrollback::MAX_LINES {
+        eprintln!(
+            "'scrollback.lines' can't be more than {}: {}",
+            scrollback::MAX_LINES,
+            conf.scrollback.lines,
+        );
+        process::exit(1);
+    }
+
+    let ambiguous_wide = conf.text.ambiguous_width == config::Width::Wide;
+
+    // `SIGUSR1` is handled before the server starts, so that a client isn't
+    // ended by a signal that was meant for the server.
+    unsafe {
+        libc::signal(libc::SIGUSR1, request_reload as libc::sighandler_t);
+    }
+
+    // The log file is opened before the server starts, so that a failure to
+    // open it can be reported on the host terminal.
+    let log_file =
+        match args.log_file.as_ref().or_else(|| conf.log_file.as_ref()) {
+            Some(ref path) => {
+                match OpenOptions::new().create(true).append(true).open(path) {
+                    Ok(file) => Some(file),
+                    Err(e) => {
+                        eprintln!("couldn't open '{}': {}", path.display(), e);
+                        process::exit(1);
+                    },
+                }
+            },
+            None => None,
+        };
+
+    // The recording is created before the server starts, for the same reason
+    // as the log file.
+    let recorder =
+        match args.record {
+            Some(ref path) => {
+                match Recorder::create(path) {
+                    Ok(recorder) => Some(recorder),
+                    Err(msg) => {
+                        eprintln!("{}", msg);
+                        process::exit(1);
+                    },
+                }
+            },
+            None => None,
+        };
+
+    // Children inherit the working directory of the emulator, so `--cwd` is
+    // applied to the emulator itself, after the paths of the other options
+    // have been resolved, and it also applies to panes that are opened later.
+    // `--env` is passed to children when they're started (see
+    // `config::Panes::env`).
+    if let Some(ref dir) = args.cwd {
+        if let Err(e) = env::set_current_dir(dir) {
+            eprintln!("couldn't change directory to '{}': {}", dir.display(), e);
+            process::exit(1);
+        }
+    }
+
+    // The emulator runs in a server in the background, which owns the child,
+    // and this process becomes a client that relays the host terminal to it,
+    // so that the client can be detached without ending the session.
+    let server =
+        match session::start(&session_path) {
+            Ok(Role::Server(server)) => server,
+            Ok(Role::Client) => attach(&session_path, false),
+            Err(msg) => {
+                eprintln!("{}", msg);
+                process::exit(1);
+            },
+        };
+    env::set_var(SESSION_VAR, &session_name);
+    // Signals that end the server are handled, so that the children are hung
+    // up and the screen is restored before the server exits. They're only
+    // handled by the server, as the client must still be ended by them.
+    unsafe {
+        for &signal in &[libc::SIGTERM, libc::SIGHUP, libc::SIGINT] {
+            libc::signal(signal, request_shutdown as libc::sighandler_t);
+        }
+    }
+    // The server's standard error is the PTY that's relayed to clients, where
+    // its errors are drawn over by the screen, or are lost if no client is
+    // attached, so it's redirected to the log file, which then receives the
+    // server's errors and panics.
+    if let Some(ref file) = log_file {
+        unsafe {
+            libc::dup2(file.as_raw_fd(), libc::STDERR_FILENO);
+        }
+    }
+
+    // curses decodes input, and encodes output, using the locale's character
+    // encoding, which must be loaded from the environment before `initscr` is
+    // called. Otherwise, the bytes of multibyte characters, such as those
+    // composed using dead keys or an input method, are read as separate
+    // characters, each of which would be re-encoded as UTF-8 for the child.
+    pancurses::setlocale(pancurses::LcCategory::all, "");
+
+    let win = pancurses::initscr();
+    // Some curses setups report resizes using `Input::KeyResize` unreliably,
+    // such as when `SIGWINCH` arrives while input isn't being read, so the
+    // signal is also handled directly, after curses has installed its own
+    // handler.
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = on_winch as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        action.sa_flags = libc::SA_RESTART;
+        let mut old: libc::sigaction = mem::zeroed();
+        if libc::sigaction(libc::SIGWINCH, &action, &mut old) == 0 && old.sa_flags & libc::SA_SIGINFO == 0 {
+            CURSES_WINCH.store(old.sa_sigaction, Ordering::SeqCst);
+        }
+    }
+
+    // Characters are not rendered when they're typed, instead they're sent to
+    // the underlying terminal, which decides whether to echo them or not (by
+    // writing new characters from `ptyf`, below). An example scenario of when
+    // this comes in handy is in the case of typing backspace. Using `noecho`
+    // prevents `^?` being briefly echoed before the cursor in between the time
+    // that the backspace key was pressed and the time when the new rendering of
+    // the terminal state is received and output.
+    pancurses::noecho();
+
+    // All control characters are passed to the child unchanged, rather than
+    // being interpreted by the host's terminal driver. `raw` stops `^C`, `^\`
+    // and `^Z` from sending signals to this process, `^S` and `^Q` from being
+    // used for flow control, `^V` and `^O` from being handled specially, and
+    // input from being buffered until a newline is typed. `nonl` stops `^M`
+    // (which Enter sends) from being translated into `^J`.
+    pancurses::raw();
+    pancurses::nonl();
+
+    // `colour_support` is `None` if we're rendering without colour, in which
+    // case only cell attributes are rendered. See <https://no-color.org> for
+    // details on `NO_COLOR`.
+    let no_colour_env = env::var_os("NO_COLOR").map_or(false, |v| !v.is_empty());
+    let colour_support =
+        if args.no_colour || no_colour_env || !pancurses::has_colors() {
+            None
+        } else {
+            pancurses::start_color();
+            Some(colour::Support::detect())
+        };
+    // `pairs` allocates the colour pairs that are used to draw every pane.
+    let mut pairs = Pairs::new(colour_support.map_or(false, |s| s.default_colours));
+    let backend = args.backend;
+    let mut render_ctx = render::Context{
+        colour_support: colour_support,
+        fallbacks: conf.fallbacks,
+        selection_colours: selection_colours,
+        match_colours: match_colours,
+        active_border_colours: active_border_colours,
+        cursor_shape: None,
+        cursor_blink_on: true,
+        utf8: grapheme::host_is_utf8(),
+        ambiguous_wide: ambiguous_wide,
+        line_drawing: conf.text.line_drawing,
+    };
+
+    // We put the window input into non-blocking mode so that `win.getch()`
+    // returns `None` immediately if there is no input. This allows us to read
+    // from the PTY and the the window in the same thread. Note that this
+    // results in a busy loop, which should ideally be replaced by blocking
+    // reads on separate threads for efficiency.
+    win.nodelay(true);
+
+    // `keypad` makes curses decode special keys, such as the arrow keys, into
+    // `Input`s, using the host's terminfo entry, which are then encoded for the
+    // child using `Encoder`. This means that the child receives the encodings
+    // that it expects for these keys, regardless of how the host encodes them.
+    win.keypad(true);
+
+    // We report mouse presses and releases to the child as they happen,
+    // rather than letting curses combine them into clicks.
+    pancurses::mouseinterval(0);
+
+    // We enable bracketed paste mode on the host so that pastes can be
+    // distinguished from typed input (see `Paste`).
+    write_host(paste::ENABLE);
+
+    // We enable focus reporting on the host so that changes in focus can be
+    // reported to the child (see `Focus`).
+    write_host(focus::ENABLE);
+
+    let term_conf = TermConfig::default();
+
+    // `panes` holds the children, each of which is drawn in an area of the
+    // window that's given by the layout of its tab. Only the panes of the
+    // `current` tab are drawn, and input is sent to the `active` pane, which
+    // is one of them.
+    let mut panes: BTreeMap<PaneId, Pane> = BTreeMap::new();
+    let mut tabs = vec![Tab::new(0)];
+    let spawned =
+        if dash_commands.is_empty() {
+            // Only the first pane runs the program that was given on the
+            // command line, if any, and other panes run the user's shell.
+            let area = window_area(&win, args.size);
+            Pane::spawn(0, &term_conf, &conf, Some(&win), area, args.program.clone(), args.pipe)
+                .map(|pane| { panes.insert(0, pane); })
+        } else {
+            // A dashboard arranges its commands in a grid.
+            let ids: Vec<PaneId> = (0..dash_commands.len()).collect();
+            tabs[0].layout = Layout::with_preset(&ids, Preset::Tiled);
+            tabs[0].layout.rects(window_area(&win, args.size))
+                .into_iter()
+                .map(|(id, rect)| {
+                    let program = Some(Program::Shell(dash_commands[id].clone()));
+                    Pane::spawn(id, &term_conf, &conf, Some(&win), rect, program, args.pipe)
+                        .map(|pane| { panes.insert(id, pane); })
+                })
+                .collect()
+        };
+    if let Err(msg) = spawned {
+        pancurses::endwin();
+        eprintln!("{}", msg);
+        process::exit(1);
+    }
+    // Only the output of the first pane is recorded.
+    if let Some(pane) = panes.get_mut(&0) {
+        pane.recorder = recorder;
+    }
+    let mut current = 0;
+    let mut active: PaneId = 0;
+    let mut next_pane_id: PaneId = panes.len();
+    pancurses::mousemask(panes[&active].mouse.mask(), ptr::null_mut());
+
+    let mut paste = Paste::new();
+    let mut focus = Focus::new();
+    let mut macros = Macros::new();
+    let mut encoder = Encoder::new(
+        conf.keyboard.meta_8bit,
+        meta_prefix,
+        conf.keyboard.backspace,
+        conf.keyboard.delete,
+    );
+
+    let mut chrome = Chrome{
+        border_char: conf.panes.border,
+        peek_label: peek_label,
+        prompt: None,
+        message: None,
+        recording: false,
+        passthrough: false,
+        selection: None,
+        copy_mode: None,
+        picker: None,
+        search: None,
+    };
+    // `last_click` is the time and position of the last press of the left
+    // button, which is used to detect double-clicks.
+    let mut last_click: Option<(Instant, (usize, usize))> = None;
+    // `drag_start` is the position where the left button is being held down,
+    // if it is, which is used to select text by dragging.
+    let mut drag_start: Option<(usize, usize)> = None;
+    // `border_drag` is the position where the left button is being held
+    // down, if it was pressed on a border between panes, which is used to
+    // resize panes by dragging the border.
+    let mut border_drag: Option<(i32, i32)> = None;
+    // `history` holds the text that was most recently copied or pasted.
+    let mut history = History::new(cmp::max(conf.clipboard.history, 1));
+    // `prefixed` is `true` if the last key that was pressed was the prefix
+    // key.
+    let mut prefixed = false;
+    // `repeatable` is the time of the last action that can be repeated
+    // without pressing the prefix key again (see `REPEAT_INTERVAL`).
+    let mut repeatable: Option<Instant> = None;
+    // `confirming_quit` is `true` while waiting for the user to confirm that
+    // the emulator should quit.
+    let mut confirming_quit = false;
+    // `holding` is `true` while the screen is kept after the last child
+    // exited, until a key is pressed (see `--hold`).
+    let mut holding = false;
+    // `quit` is `true` if the emulator is quitting at the user's request,
+    // rather than because the child exited.
+    let mut quit = false;
+
+    // `blink_interval` is `None` if the cursor doesn't blink.
+    let blink_interval =
+        if conf.cursor.blink {
+            Some(Duration::from_millis(conf.cursor.blink_interval))
+        } else {
+            None
+        };
+    let mut last_blink = Instant::now();
+
+    let mut exit_reason: Option<String> = None;
+
+    // `host_size` is the last size of the window that was reported, which is
+    // used to handle each resize once, whether it's reported by `SIGWINCH` or
+    // by `Input::KeyResize`, and `relayout_due` is when the panes are laid
+    // out for it (see `RESIZE_DELAY`).
+    let mut host_size = win.get_max_yx();
+    let mut relayout_due: Option<Instant> = None;
+
+    // `clipboard_reads` are the OSC 52 queries that are waiting for a selection
+    // to be read, along with the panes that made them.
+    let mut clipboard_reads: Vec<(PaneId, Target, Receiver<Option<Vec<u8>>>)> = vec![];
+    // `clipboard_writes` are the selections that children are setting using
+    // OSC 52, along with their contents, which are passed to the host if the
+    // selection can't be set.
+    let mut clipboard_writes: Vec<(Target, Vec<u8>, Receiver<bool>)> = vec![];
+
+    // The startup paste isn't bracketed, because the child won't have enabled
+    // bracketed paste mode yet.
+    if let Some(content) = startup_paste {
+        panes.get_mut(&0).unwrap().outbox.push(&content);
+    }
+
+    // `restored` holds the state that's being restored, which is processed as
+    // output from the first child, before the output of the child.
+    let mut restored = io::Cursor::new(restored_state);
+
+    let mut buf = [0u8; 0x1000];
+    // We would ideally avoid using labels for loop termination but we use one
+    // here for simplicity.
+    'evt_loop: loop {
+        // `rerender` is set when the window needs to be redrawn, which is
+        // done once all of the output and input that's available has been
+        // handled.
+        let mut rerender = false;
+
+        let signal = SHUTDOWN_SIGNAL.swap(0, Ordering::SeqCst) as i32;
+        if signal != 0 {
+            hang_up(&panes);
+            exit_reason = Some(format!("received {}", child::signal_name(signal)));
+            break 'evt_loop;
+        }
+
+        if RESIZED.swap(false, Ordering::SeqCst) {
+            if let Some((lines, cols)) = terminal_size() {
+                if (lines, cols) != win.get_max_yx() {
+                    pancurses::resize_term(lines, cols);
+                }
+            }
+            if host_resized(&win, &mut host_size) {
+                relayout_due = Some(Instant::now() + RESIZE_DELAY);
+            }
+        }
+        if relayout_due.map_or(false, |due| Instant::now() >= due) {
+            relayout_due = None;
+            for tab in &tabs {
+                relayout(&mut panes, &tab.layout, &win, args.size);
+            }
+            // The lines are reflowed, so the positions of the selection, copy
+            // mode and the search no longer point at the lines that they were
+            // on.
+            chrome.selection = None;
+            chrome.copy_mode = None;
+            chrome.search = None;
+            rerender = true;
+        }
+
+        if RELOAD_REQUESTED.swap(false, Ordering::SeqCst) {
+            let result = reload_config(
+                conf_path.as_ref(),
+                !dash_commands.is_empty(),
+                &mut conf,
+                &mut keybindings,
+                &mut encoder,
+                &mut render_ctx,
+                &mut chrome,
+                &mut panes,
+            );
+            chrome.message = Some(result.err().unwrap_or_else(|| "reloaded the configuration".to_string()));
+            rerender = true;
+        }
+
+        // A client that attaches has a new host terminal, which doesn't have
+        // the contents of the window, or the modes that were enabled on the
+        // other hosts. The clients that are already attached are sent the
+        // same output, which redraws their windows. Refreshing the window
+        // after `endwin` makes curses enable its modes again and redraw the
+        // whole window.
+        if server.attached() {
+            pancurses::endwin();
+            win.refresh();
+            write_host(paste::ENABLE);
+            write_host(focus::ENABLE);
+            let pane = &panes[&active];
+            pancurses::mousemask(pane.mouse.mask(), ptr::null_mut());
+            write_host(pane.mouse.host_motion());
+            rerender = true;
+        }
+
+        if server.killed() {
+            quit = true;
+            break 'evt_loop;
+        }
+
+        // Output is read from each pane in turn. `closed` holds the panes
+        // whose children have exited, along with the reason if they didn't
+        // exit normally, which are removed once all of the input that's
+        // available has been handled.
+        let mut closed: Vec<(PaneId, Option<String>)> = vec![];
+        // Panes whose children are due to be restarted are respawned, keeping
+        // their scrollback.
+        for (&id, pane) in panes.iter_mut() {
+            if pane.restart.as_ref().map_or(false, Restart::is_due) {
+                // The pane is kept, showing why, if its child can't be
+                // restarted.
+                match respawn(pane, id == active, true, &term_conf, &conf, &mut focus, &mut chrome) {
+                    Ok(()) => {
+                        if let Some(ref mut restart) = pane.restart {
+                            restart.restarted();
+                        }
+                    },
+                    Err(msg) => {
+                        pane.exited = Some(msg);
+                        pane.restart = None;
+                    },
+                }
+                rerender = true;
+            }
+        }
+        let exits = child::reap();
+        for (&id, pane) in panes.iter_mut() {
+            if pane.exited.is_some() {
+                continue;
+            }
+            if let Some(&(_, exit)) = exits.iter().find(|&&(pid, _)| Some(pid) == pane.pid) {
+                pane.status = Some(exit);
+            }
+            let is_active = id == active;
+            // The state that's being restored isn't translated using
+            // `pty::onlcr`, as it wasn't output by the child.
+            let output =
+                if id == 0 && !pane.paused && (restored.position() as usize) < restored.get_ref().len() {
+                    let n = restored.read(&mut buf[..]).unwrap_or(0);
+                    Output::Read(pane.process_bytes(&buf[..n], &term_conf, ambiguous_wide))
+                } else {
+                    pane.process_output(&mut buf[..], &term_conf, ambiguous_wide)
+                };
+            let evts =
+                match output {
+                    Output::Pending => continue,
+                    Output::Closed(reason) => {
+                        closed.push((id, reason));
+                        continue;
+                    },
+                    Output::Read(evts) => evts,
+                };
+            for evt in evts {
+                match evt {
+                    // The scrollback isn't shown while the alternate screen is
+                    // active.
+                    HostEvent::EnterAltScreen if is_active => {
+                        chrome.copy_mode = None;
+                        chrome.search = None;
+                    },
+                    // The host's modes are only changed for the active pane,
+                    // and are changed for the other panes when they become
+                    // active.
+                    HostEvent::SetFocusReporting(set) if is_active => focus.set_reporting(set),
+                    HostEvent::MouseChanged if is_active => {
+                        pancurses::mousemask(pane.mouse.mask(), ptr::null_mut());
+                        write_host(pane.mouse.host_motion());
+                    },
+                    HostEvent::SetClipboard(target, content) => {
+                        if conf.clipboard.osc52.can_write() {
+                            // The selection is set in the background, and the
+                            // sequence is passed to the host if it can't be
+                            // set locally, such as when running over `ssh`, as
+                            // the host may be able to set it.
+                            history.push(String::from_utf8_lossy(&content).into_owned());
+                            let written = clipboard::write_async(target, content.clone());
+                            clipboard_writes.push((target, content, written));
+                        }
+                    },
+                    // The selection is read in the background, and the query
+                    // is answered once it has been read, so that slow commands
+                    // don't block the emulator.
+                    HostEvent::QueryClipboard(target) if conf.clipboard.osc52.can_read() => {
+                        clipboard_reads.push((id, target, clipboard::read_async(target)));
+                    },
+                    HostEvent::ClearScrollback if is_active => {
+                        chrome.selection = None;
+                        chrome.search = None;
+                    },
+                    HostEvent::RecordingFailed(msg) => {
+                        chrome.message = Some(msg);
+                    },
+                    _ => {},
+                }
+            }
+            // We restart the blink cycle whenever the active screen changes,
+            // so that the cursor doesn't disappear while output is arriving.
+            // The positions of copy mode and the search are kept on the same
+            // lines if the output evicted lines from the scrollback.
+            if is_active {
+                if let Some(ref mut copy_mode) = chrome.copy_mode {
+                    copy_mode.sync(&pane.scrollback);
+                }
+                if let Some(ref mut search) = chrome.search {
+                    search.sync(&pane.scrollback);
+                }
+                render_ctx.cursor_blink_on = true;
+                last_blink = Instant::now();
+            }
+            rerender = true;
+        }
+
+        if let Some(interval) = blink_interval {
+            // The cursor stops blinking while the host doesn't have focus, as
+            // in most terminals.
+            if !focus.focused() && !render_ctx.cursor_blink_on {
+                render_ctx.cursor_blink_on = true;
+                render_cursor(&panes[&active], &mut render_ctx, backend, &mut pairs, &chrome);
+            } else if focus.focused() && last_blink.elapsed() >= interval {
+                render_ctx.cursor_blink_on = !render_ctx.cursor_blink_on;
+                last_blink = Instant::now();
+                render_cursor(&panes[&active], &mut render_ctx, backend, &mut pairs, &chrome);
+            }
+        }
+
+        // All of the input that's available is handled before the next read
+        // from the PTY, and the bytes for the child are queued together, so
+        // that input doesn't lag behind the output of the child when typing
+        // quickly or pasting.
+        let mut to_pty = vec![];
+        // Commands that were sent using `tep <tmux-command>` are run before the
+        // input that's available, in the same way as commands that are entered
+        // at the prompt.
+        let mut remote_commands = server.take_commands().into_iter();
+        loop {
+            let mut remote = remote_commands.next();
+            let input =
+                match remote {
+                    // `Input::Unknown` has no encoding, so it's never passed
+                    // to the child.
+                    Some(_) => Input::Unknown(0),
+                    None => {
+                        match win.getch() {
+                            Some(input) => input,
+                            None => break,
+                        }
+                    },
+                };
+            let pane = panes.get_mut(&active).unwrap();
+
+            // Keybindings aren't handled during pastes, so that pasted text is
+            // passed to the child unchanged, rather than being encoded as keys
+            // for the child's keyboard modes.
+            if remote.is_none() && paste.active() {
+                if let Some(bytes) = encoder.encode(&input, *pane.term.mode(), &KeyboardModes::new()) {
+                    let bytes = paste.filter(&bytes, pane.term.mode().contains(TermMode::BRACKETED_PASTE));
+                    to_pty.extend_from_slice(&bytes);
+                }
+                continue;
+            }
+
+            if remote.is_none() && chrome.message.take().is_some() {
+                rerender = true;
+            }
+
+            if holding && remote.is_none() && input != Input::KeyResize && input != Input::KeyMouse {
+                break 'evt_loop;
+            }
+
+            if confirming_quit && remote.is_none() {
+                confirming_quit = false;
+                if input == Input::Character('y') || input == Input::Character('Y') {
+                    quit = true;
+                    break 'evt_loop;
+                }
+                continue;
+            }
+
+            let outcome =
+                if remote.is_some() {
+                    None
+                } else {
+                    chrome.picker.as_mut().map(|&mut (_, ref mut picker)| picker.handle(&input))
+                };
+            match outcome {
+                Some(Outcome::Pending) => {
+                    rerender = true;
+                    continue;
+                },
+                Some(Outcome::Cancel) => {
+                    chrome.picker = None;
+                    rerender = true;
+                    continue;
+                },
+                Some(Outcome::Submit(text)) => {
+                    match chrome.picker.take() {
+                        Some((Pick::Tab, picker)) => {
+                            pane.outbox.push(&to_pty);
+                            to_pty.clear();
+                            switch_tab(&panes, &mut tabs, &mut current, &mut active, picker.selected(), &mut focus, &mut chrome);
+                        },
+                        _ => {
+                            let bracketed = pane.term.mode().contains(TermMode::BRACKETED_PASTE);
+                            to_pty.extend(paste::bracket(text.as_bytes(), bracketed));
+                            history.push(text);
+                        },
+                    }
+                    rerender = true;
+                    continue;
+                },
+                None => {},
+            }
+
+            let outcome =
+                match remote {
+                    Some(ref remote) => Some(Outcome::Submit(remote.line.clone())),
+                    None => chrome.prompt.as_mut().map(|prompt| prompt.handle(&input)),
+                };
+            // `command_action` is the action of the command that was run, if
+            // any, which is handled as if its key had been pressed.
+            let mut command_action = None;
+            match outcome {
+                Some(Outcome::Pending) => {
+                    rerender = true;
+                    continue;
+                },
+                Some(Outcome::Cancel) => {
+                    chrome.prompt = None;
+                    rerender = true;
+                    continue;
+                },
+                Some(Outcome::Submit(ref line)) if remote.is_none() && chrome.prompt.as_ref().map_or(false, |p| p.is_search()) => {
+                    chrome.prompt = None;
+                    match Search::new(line) {
+                        Ok(mut search) => {
+                            if search.step(&pane.term, &pane.scrollback, true) {
+                                // Copy mode is started at the first match, so
+                                // that the other matches can be navigated to.
+                                let separators = &conf.text.word_separators;
+                                let copy_mode = chrome.copy_mode
+                                    .get_or_insert_with(|| CopyMode::new(&pane.term, &pane.scrollback, separators));
+                                if let Some(pos) = search.position() {
+                                    copy_mode.move_to(pos, &pane.term, &mut pane.scrollback);
+                                }
+                                chrome.search = Some(search);
+                            } else {
+                                chrome.message = Some(format!("no matches: {}", line));
+                            }
+                        },
+                        Err(msg) => chrome.message = Some(msg),
+                    }
+                    rerender = true;
+                    continue;
+                },
+                Some(Outcome::Submit(line)) => {
+                    if remote.is_none() {
+                        chrome.prompt = None;
+                    }
+                    // `output` is the output of the command, which is only
+                    // shown if the command was sent from the command line.
+                    let mut output = String::new();
+                    let mut select = None;
+                    let result = match prompt::parse(&line) {
+                        Ok(Command::Resize(cols, lines)) => {
+                            // The terminal can't be made larger than the pane.
+                            let cols = cmp::min(cols as i32, pane.rect.cols - 2);
+                            let lines = cmp::min(lines as i32, pane.rect.lines - 2);
+                            pane.resize(pane::new_size_info(cols, lines));
+                            Ok(())
+                        },
+                        Ok(Command::Dump(path)) => {
+                            fs::write(&path, screen_text(&pane.term, &pane.extras))
+                                .map_err(|e| format!("couldn't write '{}': {}", path, e))
+                        },
+                        Ok(Command::Save(path)) => {
+                            let primary = pane.alt_screen.primary().unwrap_or(&pane.term);
+                            state::save(&path, primary, &pane.scrollback)
+                        },
+                        Ok(Command::Paste(path)) => {
+                            match fs::read(&path) {
+                                Ok(content) => {
+                                    let bracketed = pane.term.mode().contains(TermMode::BRACKETED_PASTE);
+                                    to_pty.extend_from_slice(&paste::bracket(&content, bracketed));
+                                    Ok(())
+                                },
+                                Err(e) => Err(format!("couldn't read '{}': {}", path, e)),
+                            }
+                        },
+                        Ok(Command::SaveMacro(name)) => macros.save(&name),
+                        Ok(Command::PlayMacro(name)) => {
+                            match macros.load(&name) {
+                                Ok(bytes) => {
+                                    to_pty.extend_from_slice(bytes);
+                                    Ok(())
+                                },
+                                Err(msg) => Err(msg),
+                            }
+                        },
+                        Ok(Command::SetBorder(c)) => {
+                            chrome.border_char = c.unwrap_or(' ');
+                            Ok(())
+                        },
+                        Ok(Command::SetRestart(on)) => {
+                            pane.restart =
+                                if on {
+                                    Some(Restart::new(Duration::from_millis(conf.panes.restart_delay)))
+                                } else {
+                                    None
+                                };
+                            Ok(())
+                        },
+                        Ok(Command::Layout(preset)) => {
+                            tabs[current].layout.apply(preset);
+                            relayout(&mut panes, &tabs[current].layout, &win, args.size);
+                            Ok(())
+                        },
+                        Ok(Command::Kill(_)) if pane.exited.is_some() => {
+                            Err("the pane's program has exited".to_string())
+                        },
+                        Ok(Command::Kill(signal)) => {
+                            signal_child(pane, signal);
+                            Ok(())
+                        },
+                        Ok(Command::Respawn(_)) if pane.exited.is_none() => {
+                            Err("the pane's program is still running".to_string())
+                        },
+                        Ok(Command::Respawn(keep)) => {
+                            respawn(pane, true, keep, &term_conf, &conf, &mut focus, &mut chrome)
+                        },
+                        Ok(Command::Rename(name)) => {
+                            tabs[current].name = name;
+                            Ok(())
+                        },
+                        Ok(Command::Title(name)) => {
+                            pane.name = name;
+                            Ok(())
+                        },
+                        Ok(Command::Reload) => {
+                            reload_config(
+                                conf_path.as_ref(),
+                                !dash_commands.is_empty(),
+                                &mut conf,
+                                &mut keybindings,
+                                &mut encoder,
+                                &mut render_ctx,
+                                &mut chrome,
+                                &mut panes,
+                            )
+                        },
+                        Ok(Command::Clear(screen)) => {
+                            let screen = screen && !pane.alt_screen.is_active();
+                            clear(&mut pane.term, &mut pane.parser, &mut pane.extras, &mut pane.scrollback, screen);
+                            chrome.selection = None;
+                            chrome.search = None;
+                            Ok(())
+                        },
+                        Ok(Command::Action(action)) => {
+                            command_action = Some(action);
+                            Ok(())
+                        },
+                        Ok(Command::SelectPane(i)) => {
+                            match tabs[current].layout.panes().get(i) {
+                                Some(&id) => {
+                                    select = Some(id);
+                                    Ok(())
+                                },
+                                None => Err(format!("no such pane: {}", i)),
+                            }
+                        },
+                        Ok(Command::SendKeys(keys)) => {
+                            for key in keys {
+                                if let Some(bytes) = encoder.encode(&key, *pane.term.mode(), &pane.keyboard_modes) {
+                                    to_pty.extend(bytes);
+                                }
+                            }
+                            Ok(())
+                        },
+                        Ok(Command::CapturePane(print)) => {
+                            let text = screen_text(&pane.term, &pane.extras);
+                            if print {
+                                output = text;
+                            } else {
+                                history.push(text);
+                            }
+                            Ok(())
+                        },
+                        Err(msg) => Err(msg),
+                    };
+                    match remote.take() {
+                        Some(remote) => remote.reply(result.map(|_| output)),
+                        None => chrome.message = result.err(),
+                    }
+                    rerender = true;
+                    if let Some(to) = select {
+                        if to != active {
+                            // `pane` isn't used here, as the command may have
+                            // rearranged the panes.
+                            panes.get_mut(&active).unwrap().outbox.push(&to_pty);
+                            to_pty.clear();
+                            switch_pane(&panes, to, &mut focus, &mut chrome);
+                            active = to;
+                        }
+                        continue;
+                    }
+                    if command_action.is_none() {
+                        continue;
+                    }
+                },
+                None => {},
+            }
+            // `pane` is borrowed again, because the command that was run may
+            // have rearranged the panes.
+            let pane = panes.get_mut(&active).unwrap();
+
+            if let (None, Some(copy_mode)) = (command_action, chrome.copy_mode.as_mut()) {
+                let step =
+                    match input {
+                        // Copy mode is left when the window is resized, as
+                        // resizing reflows the screen, which moves the lines
+                        // that its positions refer to.
+                        Input::KeyResize => Step::Exit,
+                        Input::Character('/') => {
+                            chrome.prompt = Some(Prompt::search());
+                            Step::Pending
+                        },
+                        // `n` moves to the next match up the scrollback, in
+                        // the direction of the search, and `N` moves back
+                        // down.
+                        Input::Character('n') | Input::Character('N') => {
+                            let older = input == Input::Character('n');
+                            if let Some(ref mut search) = chrome.search {
+                                if search.step(&pane.term, &pane.scrollback, older) {
+                                    if let Some(pos) = search.position() {
+                                        copy_mode.move_to(pos, &pane.term, &mut pane.scrollback);
+                                    }
+                                } else {
+                                    chrome.message = Some("no more matches".to_string());
+                                }
+                            }
+                            Step::Pending
+                        },
+                        _ => copy_mode.handle(&input, &pane.term, &mut pane.scrollback),
+                    };
+                let exit =
+                    match step {
+                        Step::Pending => false,
+                        Step::Exit => true,
+                        Step::Yank(text) => {
+                            clipboard::write_async(Target::Clipboard, text.as_bytes().to_vec());
+                            clipboard::write_async(Target::Primary, text.as_bytes().to_vec());
+                            history.push(text);
+                            true
+                        },
+                    };
+                if exit {
+                    pane.scrollback.scroll_to_bottom();
+                    chrome.copy_mode = None;
+                    chrome.search = None;
+                }
+                if input != Input::KeyResize {
+                    rerender = true;
+                    continue;
+                }
+            }
+
+            let repeating = repeatable.take().map_or(false, |at| at.elapsed() < REPEAT_INTERVAL);
+            let action =
+                if command_action.is_some() {
+                    command_action
+                } else if repeating && keybindings.prefix_action(&input).map_or(false, is_repeatable) {
+                    keybindings.prefix_action(&input)
+                } else if prefixed {
+                    prefixed = false;
+                    match keybindings.prefix_action(&input) {
+                        Some(Action::TogglePassthrough) => Some(Action::TogglePassthrough),
+                        // In passthrough mode, only the prefix key followed by
+                        // the key that's bound to `Action::TogglePassthrough`
+                        // is handled, and the prefix key is otherwise passed
+                        // to the child along with the key that follows it.
+                        _ if chrome.passthrough => {
+                            if let Some(bytes) = keybindings.prefix().and_then(|p| encoder.encode(&p, *pane.term.mode(), &pane.keyboard_modes)) {
+                                to_pty.extend(bytes);
+                            }
+                            None
+                        },
+                        Some(action) => Some(action),
+                        // Pressing the prefix key twice sends it to the child.
+                        None if keybindings.is_prefix(&input) => None,
+                        // Other keys that aren't bound are discarded.
+                        None => continue,
+                    }
+                } else if keybindings.is_prefix(&input) {
+                    prefixed = true;
+                    continue;
+                } else if chrome.passthrough {
+                    None
+                } else {
+                    keybindings.action(&input)
+                };
+
+            match action {
+                // The scrollback belongs to the primary screen, so keys that
+                // are bound to scrolling actions are passed to the child
+                // while the alternate screen is active.
+                Some(Action::ScrollPageUp) | Some(Action::ScrollPageDown) | Some(Action::ScrollMode) |
+                Some(Action::Search)
+                    if pane.alt_screen.is_active() => {},
+                Some(Action::ScrollPageUp) | Some(Action::ScrollPageDown) => {
+                    let page = pane.term.grid().num_lines().0 as isize;
+                    pane.scrollback.scroll(if action == Some(Action::ScrollPageUp) { page } else { -page });
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::ScrollMode) => {
+                    chrome.copy_mode = Some(CopyMode::new(&pane.term, &pane.scrollback, &conf.text.word_separators));
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::Search) => {
+                    chrome.prompt = Some(Prompt::search());
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::ClearScrollback) | Some(Action::ClearScreen) => {
+                    // The alternate screen belongs to the child, so only the
+                    // scrollback is cleared while it's active.
+                    let screen = action == Some(Action::ClearScreen) && !pane.alt_screen.is_active();
+                    clear(&mut pane.term, &mut pane.parser, &mut pane.extras, &mut pane.scrollback, screen);
+                    chrome.selection = None;
+                    chrome.search = None;
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::TogglePassthrough) => {
+                    chrome.passthrough = !chrome.passthrough;
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::ToggleBorder) => {
+                    let i = BORDER_CHARS.iter().position(|&c| c == chrome.border_char);
+                    chrome.border_char = BORDER_CHARS[i.map_or(0, |i| (i + 1) % BORDER_CHARS.len())];
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::SplitRight) | Some(Action::SplitDown) => {
+                    let direction =
+                        if action == Some(Action::SplitRight) {
+                            Direction::Horizontal
+                        } else {
+                            Direction::Vertical
+                        };
+                    let area = window_area(&win, args.size);
+                    let layout = &mut tabs[current].layout;
+                    if !layout.split(active, next_pane_id, direction, area) {
+                        chrome.message = Some("the pane is too small to split".to_string());
+                        rerender = true;
+                        continue;
+                    }
+                    // The input for the pane that was active is queued before
+                    // the new pane becomes active.
+                    pane.outbox.push(&to_pty);
+                    to_pty.clear();
+                    let rect = layout.rects(area).into_iter().find(|&(id, _)| id == next_pane_id).map(|(_, r)| r);
+                    match Pane::spawn(next_pane_id, &term_conf, &conf, Some(&win), rect.unwrap_or(area), new_program.clone(), false) {
+                        Ok(pane) => {
+                            panes.insert(next_pane_id, pane);
+                        },
+                        Err(msg) => {
+                            layout.remove(next_pane_id);
+                            chrome.message = Some(msg);
+                            rerender = true;
+                            continue;
+                        },
+                    }
+                    relayout(&mut panes, layout, &win, args.size);
+                    switch_pane(&panes, next_pane_id, &mut focus, &mut chrome);
+                    active = next_pane_id;
+                    next_pane_id += 1;
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::SelectPaneLeft) | Some(Action::SelectPaneRight) |
+                Some(Action::SelectPaneUp) | Some(Action::SelectPaneDown) | Some(Action::NextPane) => {
+                    let layout = &tabs[current].layout;
+                    let to =
+                        match action {
+                            Some(Action::SelectPaneLeft) => layout.neighbour(active, Side::Left, window_area(&win, args.size)),
+                            Some(Action::SelectPaneRight) => layout.neighbour(active, Side::Right, window_area(&win, args.size)),
+                            Some(Action::SelectPaneUp) => layout.neighbour(active, Side::Up, window_area(&win, args.size)),
+                            Some(Action::SelectPaneDown) => layout.neighbour(active, Side::Down, window_area(&win, args.size)),
+                            _ => {
+                                let ids = layout.panes();
+                                let i = ids.iter().position(|&id| id == active).unwrap_or(0);
+                                Some(ids[(i + 1) % ids.len()])
+                            },
+                        };
+                    if let Some(to) = to {
+                        if to != active {
+                            pane.outbox.push(&to_pty);
+                            to_pty.clear();
+                            switch_pane(&panes, to, &mut focus, &mut chrome);
+                            active = to;
+                            rerender = true;
+                        }
+                    }
+                    continue;
+                },
+                Some(Action::ResizePaneLeft) | Some(Action::ResizePaneDown) |
+                Some(Action::ResizePaneUp) | Some(Action::ResizePaneRight) => {
+                    let side =
+                        match action {
+                            Some(Action::ResizePaneLeft) => Side::Left,
+                            Some(Action::ResizePaneDown) => Side::Down,
+                            Some(Action::ResizePaneUp) => Side::Up,
+                            _ => Side::Right,
+                        };
+                    if tabs[current].layout.resize(active, side, RESIZE_STEP, window_area(&win, args.size)) {
+                        relayout(&mut panes, &tabs[current].layout, &win, args.size);
+                        rerender = true;
+                    }
+                    repeatable = Some(Instant::now());
+                    continue;
+                },
+                Some(Action::SwapPaneNext) | Some(Action::SwapPanePrevious) | Some(Action::RotatePanes) => {
+                    // The active pane moves with its program, so it stays
+                    // active.
+                    let layout = &mut tabs[current].layout;
+                    let ids = layout.panes();
+                    let i = ids.iter().position(|&id| id == active).unwrap_or(0);
+                    match action {
+                        Some(Action::SwapPaneNext) => {
+                            layout.swap(active, ids[(i + 1) % ids.len()]);
+                        },
+                        Some(Action::SwapPanePrevious) => {
+                            layout.swap(active, ids[(i + ids.len() - 1) % ids.len()]);
+                        },
+                        _ => layout.rotate(false),
+                    }
+                    relayout(&mut panes, &tabs[current].layout, &win, args.size);
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::TogglePause) => {
+                    let pane = panes.get_mut(&active).unwrap();
+                    pane.paused = !pane.paused;
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::ToggleSync) => {
+                    tabs[current].sync = !tabs[current].sync;
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::NextLayout) => {
+                    let preset = tabs[current].layout.cycle();
+                    relayout(&mut panes, &tabs[current].layout, &win, args.size);
+                    let name = layout::PRESETS.iter().find(|&&(_, p)| p == preset).map(|&(name, _)| name);
+                    chrome.message = name.map(|name| name.to_string());
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::NewTab) => {
+                    pane.outbox.push(&to_pty);
+                    to_pty.clear();
+                    let area = window_area(&win, args.size);
+                    match Pane::spawn(next_pane_id, &term_conf, &conf, Some(&win), area, new_program.clone(), false) {
+                        Ok(pane) => {
+                            panes.insert(next_pane_id, pane);
+                        },
+                        Err(msg) => {
+                            chrome.message = Some(msg);
+                            rerender = true;
+                            continue;
+                        },
+                    }
+                    tabs.push(Tab::new(next_pane_id));
+                    next_pane_id += 1;
+                    let to = tabs.len() - 1;
+                    switch_tab(&panes, &mut tabs, &mut current, &mut active, to, &mut focus, &mut chrome);
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::NextTab) | Some(Action::PreviousTab) => {
+                    let n = tabs.len();
+                    let to = if action == Some(Action::NextTab) { (current + 1) % n } else { (current + n - 1) % n };
+                    if to != current {
+                        pane.outbox.push(&to_pty);
+                        to_pty.clear();
+                        switch_tab(&panes, &mut tabs, &mut current, &mut active, to, &mut focus, &mut chrome);
+                        rerender = true;
+                    }
+                    continue;
+                },
+                Some(Action::RenameTab) => {
+                    chrome.prompt = Some(Prompt::with_text("rename "));
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::ChooseTab) => {
+                    let names = tabs.iter().map(|tab| tab.name.clone()).collect();
+                    let mut picker = Picker::new("tabs", names);
+                    picker.select(current);
+                    chrome.picker = Some((Pick::Tab, picker));
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::ClosePane) => {
+                    // The pane is removed once its child exits, in the same
+                    // way as when the child exits by itself, or straight away
+                    // if its child has already exited.
+                    // A child that's closed isn't restarted.
+                    pane.restart = None;
+                    if pane.exited.is_some() {
+                        closed.push((active, None));
+                    } else {
+                        signal_child(pane, config::Signal::Hup);
+                    }
+                    continue;
+                },
+                Some(Action::Detach) => {
+                    server.detach();
+                    continue;
+                },
+                Some(Action::Quit) => {
+                    if !conf.quit.confirm {
+                        quit = true;
+                        break 'evt_loop;
+                    }
+                    confirming_quit = true;
+                    chrome.message = Some("quit? (y/n)".to_string());
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::PasteFile) => {
+                    chrome.prompt = Some(Prompt::with_text("paste "));
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::StartMacro) | Some(Action::StopMacro) => {
+                    if action == Some(Action::StartMacro) {
+                        macros.start();
+                    } else {
+                        macros.stop();
+                    }
+                    chrome.recording = macros.is_recording();
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::PasteClipboard) => {
+                    let content = clipboard::read(Target::Clipboard)
+                        .or_else(|| history.latest().map(|s| s.as_bytes().to_vec()));
+                    if let Some(content) = content {
+                        let bracketed = pane.term.mode().contains(TermMode::BRACKETED_PASTE);
+                        to_pty.extend(paste::bracket(&content, bracketed));
+                        history.push(String::from_utf8_lossy(&content).into_owned());
+                    }
+                    continue;
+                },
+                Some(Action::PasteHistory) => {
+                    let entries = history.entries();
+                    if entries.is_empty() {
+                        chrome.message = Some("the paste history is empty".to_string());
+                    } else {
+                        chrome.picker = Some((Pick::Paste, Picker::new("paste history", entries)));
+                    }
+                    rerender = true;
+                    continue;
+                },
+                Some(Action::PlayMacro) => {
+                    to_pty.extend_from_slice(macros.last());
+                    continue;
+                },
+                Some(Action::Prompt) => {
+                    chrome.prompt = Some(Prompt::new());
+                    rerender = true;
+                    continue;
+                },
+                // The key that's bound to `Action::PeekPrimary` is passed to
+                // the child if the alternate screen isn't active.
+                Some(Action::PeekPrimary) if pane.alt_screen.toggle_peek() => {
+                    rerender = true;
+                    continue;
+                },
+                _ => {},
+            }
+
+            match input {
+                Input::KeyMouse => {
+                    let evt = match pancurses::getmouse() {
+                        Ok(evt) => evt,
+                        Err(_) => continue,
+                    };
+
+                    // Releasing the left button after pressing it on a border
+                    // between panes moves the border to where it was
+                    // released.
+                    if mouse::is_left_release(evt.bstate) {
+                        if let Some(from) = border_drag.take() {
+                            if tabs[current].layout.drag(from, (evt.y, evt.x), window_area(&win, args.size)) {
+                                relayout(&mut panes, &tabs[current].layout, &win, args.size);
+                                rerender = true;
+                            }
+                            continue;
+                        }
+                    }
+                    // Pressing the left button in another pane makes it the
+                    // active pane, and isn't otherwise handled.
+                    if mouse::is_left_press(evt.bstate) && !panes[&active].rect.contains(evt.y, evt.x) {
+                        let to = tabs[current].layout.panes()
+                            .into_iter()
+                            .find(|id| panes[id].rect.contains(evt.y, evt.x));
+                        match to {
+                            Some(to) => {
+                                panes.get_mut(&active).unwrap().outbox.push(&to_pty);
+                                to_pty.clear();
+                                switch_pane(&panes, to, &mut focus, &mut chrome);
+                                active = to;
+                                rerender = true;
+                            },
+                            None => border_drag = Some((evt.y, evt.x)),
+                        }
+                        continue;
+                    }
+                    let pane = panes.get_mut(&active).unwrap();
+                    // `(y, x)` is the position of the top-left cell of the
+                    // pane's terminal in the window.
+                    let (y, x) = (pane.rect.y + 1, pane.rect.x + 1);
+
+                    // Only wheel events, left-button presses and releases, and
+                    // middle-button presses are captured when the child hasn't
+                    // enabled mouse tracking (see `Mouse::mask`).
+                    if pane.mouse.tracking() == mouse::Tracking::Off && mouse::is_middle_press(evt.bstate) {
+                        let content = clipboard::read(Target::Primary)
+                            .or_else(|| history.latest().map(|s| s.as_bytes().to_vec()));
+                        if let Some(content) = content {
+                            let bracketed = pane.term.mode().contains(TermMode::BRACKETED_PASTE);
+                            to_pty.extend(paste::bracket(&content, bracketed));
+                            history.push(String::from_utf8_lossy(&content).into_owned());
+                        }
+                        continue;
+                    }
+                    if pane.mouse.tracking() == mouse::Tracking::Off && mouse::is_left_press(evt.bstate) {
+                        let grid = pane.term.grid();
+                        let (line, col) = (evt.y - y, evt.x - x);
+                        if line < 0 || col < 0 || line as usize >= grid.num_lines().0 || col as usize >= grid.num_cols().0 {
+                            continue;
+                        }
+                        let point = (line as usize, col as usize);
+                        let double_click =
+                            match last_click {
+                                Some((at, p)) => p == point && at.elapsed() < DOUBLE_CLICK_INTERVAL,
+                                None => false,
+                            };
+                        last_click = Some((Instant::now(), point));
+                        drag_start = Some(point);
+
+                        chrome.selection =
+                            if double_click && conf.mouse.double_click_select {
+                                let rows = render::screen_rows(&pane.term, visible_scrollback(&pane.alt_screen, &pane.scrollback));
+                                let (start, end) = selection::word_at(&rows[point.0].0, point.1, &conf.text.word_separators);
+                                let selection = Selection::new((point.0, start), (point.0, end));
+                                let text = selection.text(|i| rows[i].0.clone());
+                                copy_selection(&text, conf.clipboard.copy_on_select);
+                                history.push(text);
+                                Some(selection)
+                            } else {
+                                None
+                            };
+                        rerender = true;
+                        continue;
+                    }
+                    if pane.mouse.tracking() == mouse::Tracking::Off && mouse::is_left_release(evt.bstate) {
+                        let start = match drag_start.take() {
+                            Some(start) => start,
+                            None => continue,
+                        };
+                        // Releases outside the screen, such as on the border,
+                        // end the selection at the edge of the screen.
+                        let grid = pane.term.grid();
+                        let line = cmp::min(cmp::max(evt.y - y, 0) as usize, grid.num_lines().0 - 1);
+                        let col = cmp::min(cmp::max(evt.x - x, 0) as usize, grid.num_cols().0 - 1);
+                        if (line, col) == start {
+                            continue;
+                        }
+
+                        // Holding Ctrl while dragging selects a block.
+                        let selection =
+                            if evt.bstate & pancurses::BUTTON_CTRL != 0 {
+                                Selection::block(start, (line, col))
+                            } else {
+                                Selection::new(start, (line, col))
+                            };
+                        let rows = render::screen_rows(&pane.term, visible_scrollback(&pane.alt_screen, &pane.scrollback));
+                        let text = selection.text(|i| rows[i].0.clone());
+                        copy_selection(&text, conf.clipboard.copy_on_select);
+                        history.push(text);
+                        chrome.selection = Some(selection);
+                        rerender = true;
+                        continue;
+                    }
+                    if pane.mouse.tracking() == mouse::Tracking::Off {
+                        let delta = match mouse::wheel_delta(evt.bstate) {
+                            Some(delta) => delta * conf.mouse.wheel_lines as isize,
+                            None => continue,
+                        };
+                        if pane.alt_screen.is_active() {
+                            if !conf.mouse.alternate_scroll {
+                                continue;
+                            }
+                            // Programs that use the alternate screen, such as
+                            // `less`, are scrolled using the arrow keys.
+                            let key = if delta > 0 { Input::KeyUp } else { Input::KeyDown };
+                            for _ in 0..delta.abs() {
+                                to_pty.extend(input::encode(&key, *pane.term.mode()).unwrap_or_default());
+                            }
+                        } else {
+                            pane.scrollback.scroll(delta);
+                            rerender = true;
+                        }
+                        continue;
+                    }
+                    // Events on the border aren't reported.
+                    let grid = pane.term.grid();
+                    let (line, col) = (evt.y - y, evt.x - x);
+                    if line < 0 || col < 0 || line as usize >= grid.num_lines().0 || col as usize >= grid.num_cols().0 {
+                        continue;
+                    }
+
+                    to_pty.extend(pane.mouse.report(&evt, line as usize, col as usize));
+                },
+                Input::KeyResize => {
+                    if host_resized(&win, &mut host_size) {
+                        relayout_due = Some(Instant::now() + RESIZE_DELAY);
+                    }
+                },
+                _ => {
+                    // Input that can't be sent to the child, such as keys that
+                    // have no encoding, is ignored.
+                    let bytes = match encoder.encode(&input, *pane.term.mode(), &pane.keyboard_modes) {
+                        Some(bytes) => bytes,
+                        None => continue,
+                    };
+                    let bytes = focus.filter(&bytes);
+                    let bytes = paste.filter(&bytes, pane.term.mode().contains(TermMode::BRACKETED_PASTE));
+
+                    // Typing returns the viewport to the bottom of the
+                    // scrollback, as in most terminals, and clears the
+                    // selection.
+                    if !bytes.is_empty() && (pane.scrollback.offset() > 0 || chrome.selection.is_some()) {
+                        pane.scrollback.scroll_to_bottom();
+                        chrome.selection = None;
+                        rerender = true;
+                    }
+
+                    let paused = pane.paused;
+                    let bytes = pane.flow(&bytes);
+                    if pane.paused != paused {
+                        rerender = true;
+                    }
+
+                    macros.record(&bytes);
+                    to_pty.extend_from_slice(&bytes);
+                },
+            }
+        }
+
+        // Bytes that were held back by `paste` and `focus` are written once
+        // there's no more input. Bytes held back by `paste` were received
+        // before those held back by `focus`.
+        to_pty.extend(paste.flush());
+        to_pty.extend(focus.flush());
+        // Input is sent to every pane of the current tab while its input is
+        // synchronized.
+        let targets = if tabs[current].sync { tabs[current].layout.panes() } else { vec![active] };
+        for id in targets {
+            panes.get_mut(&id).unwrap().outbox.push(&to_pty);
+        }
+        // Queries whose panes have been removed are dropped once they're
+        // answered.
+        clipboard_reads.retain(|&(id, target, ref replies)| {
+            match replies.try_recv() {
+                Ok(content) => {
+                    if let Some(pane) = panes.get_mut(&id) {
+                        let content = content.unwrap_or_default();
+                        pane.outbox.push_reply(clipboard::osc52(target, &content).as_bytes());
+                    }
+                    false
+                },
+                Err(TryRecvError::Empty) => true,
+                Err(TryRecvError::Disconnected) => false,
+            }
+        });
+        clipboard_writes.retain(|&(target, ref content, ref written)| {
+            match written.try_recv() {
+                Ok(false) => {
+                    write_host(&clipboard::osc52(target, content));
+                    false
+                },
+                Ok(true) | Err(TryRecvError::Disconnected) => false,
+                Err(TryRecvError::Empty) => true,
+            }
+        });
+        for (&id, pane) in panes.iter_mut() {
+            if pane.exited.is_some() || closed.iter().any(|&(c, _)| c == id) {
+                continue;
+            }
+            if let Err(msg) = pane.outbox.flush(&mut pane.ptyf) {
+                closed.push((id, Some(msg)));
+            }
+        }
+
+        // Panes whose children have exited are removed, and their space is
+        // given to their neighbours, unless they're kept so that they can be
+        // respawned, in which case they're removed when they're closed. A tab
+        // is closed along with its last pane, and the emulator exits once the
+        // last pane has been removed.
+        tabs[current].active = active;
+        for (id, reason) in closed {
+            // A pane can be closed twice if `Action::ClosePane` is repeated.
+            let t = match tabs.iter().position(|tab| tab.contains(id)) {
+                Some(t) if panes.contains_key(&id) => t,
+                _ => continue,
+            };
+            {
+                let pane = panes.get_mut(&id).unwrap();
+                let status = pane.status;
+                if pane.exited.is_none() {
+                    if let Some(restart) = pane.restart.as_mut().filter(|restart| restart.applies(status)) {
+                        let delay = restart.schedule();
+                        let reason = reason.unwrap_or_else(|| "exited".to_string());
+                        let secs = delay.as_millis() as f64 / 1000.0;
+                        pane.exited = Some(format!("{}, restarting in {:.1}s", reason, secs));
+                        rerender = true;
+                        continue;
+                    }
+                }
+            }
+            if conf.panes.remain_on_exit && panes[&id].exited.is_none() {
+                let pane = panes.get_mut(&id).unwrap();
+                pane.exited = Some(reason.unwrap_or_else(|| "exited".to_string()));
+                rerender = true;
+                continue;
+            }
+            if tabs[t].layout.remove(id) {
+                if tabs[t].active == id {
+                    tabs[t].active = tabs[t].layout.panes()[0];
+                }
+            } else if tabs.len() > 1 {
+                tabs.remove(t);
+                if t < current || current == tabs.len() {
+                    current -= 1;
+                }
+            } else if args.hold {
+                let pane = panes.get_mut(&id).unwrap();
+                chrome.message = Some(hold_banner(pane.status, reason.as_ref()));
+                pane.exited = Some(reason.clone().unwrap_or_else(|| "exited".to_string()));
+                exit_reason = reason;
+                holding = true;
+                rerender = true;
+                continue;
+            } else {
+                exit_reason = reason;
+                break 'evt_loop;
+            }
+            if id == active {
+                let to = tabs[current].active;
+                switch_pane(&panes, to, &mut focus, &mut chrome);
+                active = to;
+            }
+            panes.remove(&id);
+            if let Some(msg) = reason {
+                chrome.message = Some(msg);
+            }
+            for tab in &tabs {
+                relayout(&mut panes, &tab.layout, &win, args.size);
+            }
+            rerender = true;
+        }
+
+        if rerender {
+            render(&panes, &tabs, current, active, &win, &mut render_ctx, backend, &mut pairs, &chrome);
+        }
+    }
+
+    // The active pane is the only one whose modes are enabled on the host.
+    let pane = &panes[&active];
+    write_host(paste::DISABLE);
+    write_host(focus::DISABLE);
+    pancurses::mousemask(0, ptr::null_mut());
+    if pane.mouse.tracking() != mouse::Tracking::Off {
+        write_host("\x1b[?1003l\x1b[?1002l");
+    }
+    pancurses::endwin();
+
+    if quit {
+        for pane in panes.values() {
+            signal_child(pane, conf.quit.signal);
+        }
+    }
+
+    if let Some(s) = exit_reason {
+        println!("[{}]", s);
+    }
+
+    server.finish();
+}
+
+// `request_reload` handles `SIGUSR1`.
+extern "C" fn request_reload(_: libc::c_int) {
+    RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+// `respawn` starts a new child in `pane`, keeping its scrollback if `keep` is
+// `true`. If `pane` is the active pane then the modes that the old child
+// enabled on the host are disabled once the new child has started, as it
+// starts with none. `pane` is left unchanged if a new child can't be started.
+fn respawn(
+    pane: &mut Pane,
+    is_active: bool,
+    keep: bool,
+    term_conf: &TermConfig,
+    conf: &config::Config,
+    focus: &mut Focus,
+    chrome: &mut Chrome,
+) -> Result<(), String> {
+    pane.respawn(term_conf, conf, keep)?;
+    if is_active {
+        pancurses::mousemask(pane.mouse.mask(), ptr::null_mut());
+        write_host(pane.mouse.host_motion());
+        focus.set_reporting(false);
+        chrome.selection = None;
+        chrome.copy_mode = None;
+        chrome.search = None;
+    }
+
+    Ok(())
+}
+
+// `hold_banner` returns the message that's shown while the screen is held,
+// which describes how the last child exited, given its `status` and the
+// `reason` that its pane was closed.
+fn hold_banner(status: Option<Exit>, reason: Option<&String>) -> String {
+    match (status, reason) {
+        (Some(Exit::Status(status)), _) => format!("[process exited: status {} - press any key]", status),
+        (_, Some(reason)) => format!("[process exited: {} - press any key]", reason),
+        (_, None) => "[process exited - press any key]".to_string(),
+    }
+}
+
+// `on_winch` handles `SIGWINCH` by recording that the host terminal was
+// resized, and then passes the signal on to the handler of curses.
+extern "C" fn on_winch(signal: libc::c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+    let curses = CURSES_WINCH.load(Ordering::SeqCst);
+    if curses != libc::SIG_DFL && curses != libc::SIG_IGN {
+        let handler: extern "C" fn(libc::c_int) = unsafe { mem::transmute(curses) };
+        handler(signal);
+    }
+}
+
+// `terminal_size` returns the number of lines and columns of the host
+// terminal, or `None` if it can't be read.
+fn terminal_size() -> Option<(i32, i32)> {
+    let mut size: libc::winsize = unsafe { mem::zeroed() };
+    let read = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ as _, &mut size as *mut libc::winsize) };
+    if read == 0 && size.ws_row > 0 && size.ws_col > 0 {
+        Some((i32::from(size.ws_row), i32::from(size.ws_col)))
+    } else {
+        None
+    }
+}
+
+// `host_resized` returns `true` if the size of `win` has changed from `size`,
+// which is then updated.
+fn host_resized(win: &Window, size: &mut (i32, i32)) -> bool {
+    let new_size = win.get_max_yx();
+    if new_size == *size {
+        return false;
+    }
+    *size = new_size;
+
+    true
+}
+
+// `request_shutdown` handles the signals that end the server.
+extern "C" fn request_shutdown(signal: libc::c_int) {
+    SHUTDOWN_SIGNAL.store(signal as usize, Ordering::SeqCst);
+}
+
+// `hang_up` sends `SIGHUP` to each child, and to the foreground process group
+// of its terminal, as happens when a terminal is closed, and waits up to
+// `SHUTDOWN_WAIT` for the children to exit.
+fn hang_up(panes: &BTreeMap<PaneId, Pane>) {
+    let mut running = vec![];
+    for pane in panes.values().filter(|pane| pane.exited.is_none()) {
+        signal_child(pane, config::Signal::Hup);
+        unsafe {
+            let pgrp = libc::tcgetpgrp(pane.ptyf.as_raw_fd());
+            if pgrp > 0 {
+                libc::killpg(pgrp, libc::SIGHUP);
+            }
+        }
+        running.extend(pane.pid);
+    }
+
+    let start = Instant::now();
+    while !running.is_empty() && start.elapsed() < SHUTDOWN_WAIT {
+        for (pid, _) in child::reap() {
+            running.retain(|&p| p != pid);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+// `reload_config` loads the configuration from `path` again, and applies the
+// settings that can be changed without restarting the children, which are the
+// colours, keybindings, keyboard settings and border. Other settings only
+// apply to the panes that are opened afterwards, and nothing is changed if the
+// configuration is invalid. `dash` is `true` if the session is a dashboard,
+// whose panes are always kept after their commands exit.
+fn reload_config(
+    path: Option<&PathBuf>,
+    dash: bool,
+    conf: &mut config::Config,
+    keybindings: &mut Keybindings,
+    encoder: &mut Encoder,
+    ctx: &mut render::Context,
+    chrome: &mut Chrome,
+    panes: &mut BTreeMap<PaneId, Pane>,
+) -> Result<(), String> {
+    let path =
+        match path {
+            Some(path) => path,
+            None => return Err("no configuration file was loaded".to_string()),
+        };
+    let mut new_conf = config::load(path)?;
+    let palette = Palette::from_config(&new_conf.colours)?;
+    let selection_colours = new_conf.selection.colours("selection")?;
+    let match_colours = new_conf.search_match.colours("search_match")?;
+    let active_border_colours = new_conf.active_border.colours("active_border")?;
+    let meta_prefix = new_conf.keyboard.meta_prefix_key()?;
+    let new_keybindings = Keybindings::new(&new_conf.keybindings, &new_conf.prefix)?;
+    if new_conf.scrollback.lines > scrollback::MAX_LINES {
+        return Err(format!(
+            "'scrollback.lines' can't be more than {}: {}",
+            scrollback::MAX_LINES,
+            new_conf.scrollback.lines,
+        ));
+    }
+    if dash {
+        new_conf.panes.remain_on_exit = true;
+    }
+    new_conf.panes.env = conf.panes.env.clone();
+
+    // The colours that the children have changed are kept.
+    for pane in panes.values_mut() {
+        pane.palette.configure(&palette);
+    }
+    ctx.selection_colours = selection_colours;
+    ctx.match_colours = match_colours;
+    ctx.active_border_colours = active_border_colours;
+    ctx.fallbacks = new_conf.fallbacks;
+    ctx.line_drawing = new_conf.text.line_drawing;
+    *encoder = Encoder::new(
+        new_conf.keyboard.meta_8bit,
+        meta_prefix,
+        new_conf.keyboard.backspace,
+        new_conf.keyboard.delete,
+    );
+    chrome.peek_label = peek_label(&new_keybindings);
+    chrome.border_char = new_conf.panes.border;
+    *keybindings = new_keybindings;
+    *conf = new_conf;
+
+    Ok(())
+}
+
+// `peek_label` returns the label that's shown while the primary screen is
+// being peeked at.
+fn peek_label(keybindings: &Keybindings) -> String {
+    match keybindings.key_name(Action::PeekPrimary) {
+        Some(key) => format!("primary screen ({} to return)", key),
+        None => "primary screen".to_string(),
+    }
+}
+
+// `socket_path` returns the path of the socket of the session called `name`, or
+// of the default session, and exits if `name` isn't a valid name.
+fn socket_path(name: Option<&String>) -> PathBuf {
+    match session::socket_path(name.map_or(session::DEFAULT_NAME, String::as_str)) {
+        Ok(path) => path,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(1);
+        },
+    }
+}
+
+// `list_sessions` prints the names of the sessions that are running, and exits.
+fn list_sessions() -> ! {
+    match session::list() {
+        Ok(sessions) => {
+            for (name, attached) in sessions {
+                if attached {
+                    println!("{} (attached)", name);
+                } else {
+                    println!("{}", name);
+                }
+            }
+            process::exit(0);
+        },
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(1);
+        },
+    }
+}
+
+// `kill_session` ends the session whose socket is at `path`, and exits.
+fn kill_session(path: &Path) -> ! {
+    match session::kill(path) {
+        Ok(()) => process::exit(0),
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(1);
+        },
+    }
+}
+
+// `play` plays the output that was recorded to `path`, and exits.
+fn play(path: &Path, speed: f64, max_wait: Option<Duration>) -> ! {
+    match record::play(path, &mut io::stdout(), speed, max_wait) {
+        Ok(()) => process::exit(0),
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(1);
+        },
+    }
+}
+
+// `run_command` runs the command `words` in the session whose socket is at
+// `path`, prints its output, and exits.
+fn run_command(path: &Path, words: &[String]) -> ! {
+    let line: Vec<String> = words.iter().map(|word| prompt::quote(word)).collect();
+    match session::command(path, &line.join(" ")) {
+        Ok(output) => {
+            print!("{}", output);
+            process::exit(0);
+        },
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(1);
+        },
+    }
+}
+
+// `attach` relays the host terminal to the session whose socket is at `path`,
+// and exits once the client is detached or the session ends. Input isn't
+// relayed if `read_only` is `true`.
+fn attach(path: &Path, read_only: bool) -> ! {
+    match session::attach(path, read_only) {
+        Ok(End::Detached) => {
+            println!("[detached]");
+            process::exit(0);
+        },
+        Ok(End::Exited) => process::exit(0),
+        Ok(End::Lost) => {
+            eprintln!("lost the connection to the session");
+            process::exit(1);
+        },
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(1);
+        },
+    }
+}
+
+// `signal_child` sends `signal` to the child of `pane`, unless the child has
+// been reaped, in which case its ID may have been reused.
+fn signal_child(pane: &Pane, signal: config::Signal) {
+    let signal =
+        match signal {
+            config::Signal::Hup => libc::SIGHUP,
+            config::Signal::Int => libc::SIGINT,
+            config::Signal::Term => libc::SIGTERM,
+            config::Signal::Kill => libc::SIGKILL,
+            config::Signal::None => return,
+        };
+    let pid =
+        match pane.pid {
+            Some(pid) if pane.status.is_none() => pid,
+            _ => return,
+        };
+    // Failures are ignored because the child may have already exited.
+    unsafe {
+        libc::kill(pid, signal);
+    }
+}
+
+// `write_host` writes `seq`, which shouldn't move the cursor or change the
+// contents of the screen, directly to the host terminal.
+fn write_host(seq: &str) {
+    let mut stdout = io::stdout();
+    // Failures are ignored because the host is still usable if the sequence
+    // isn't written.
+    let _ = stdout.write_all(seq.as_bytes());
+    let _ = stdout.flush();
+}
+
+// `render` draws the status line, and each pane of the current tab to its part
+// of `win`. The active pane is drawn last, so that its border is drawn over
+// the borders that it shares with other panes, and so that the host's cursor
+// is left in it.
+fn render(
+    panes: &BTreeMap<PaneId, Pane>,
+    tabs: &[Tab],
+    current: usize,
+    active: PaneId,
+    win: &Window,
+    ctx: &mut render::Context,
+    backend: Backend,
+    pairs: &mut Pairs,
+    chrome: &Chrome,
+) {
+    let (y, x) = win.get_max_yx();
+    win.attrset(pancurses::A_NORMAL);
+    if y < MIN_LINES || x < MIN_COLS {
+        // curses doesn't know about the panes that other backends drew, so
+        // it's made to clear the whole screen rather than only the cells that
+        // it drew.
+        if backend != Backend::Curses {
+            win.clearok(true);
+        }
+        render_too_small(win);
+        return;
+    }
+    win.mv(y - 1, 0);
+    win.clrtoeol();
+    // The prompt and messages are drawn over the status line. The last
+    // column isn't drawn to, because curses scrolls the window if its last
+    // cell is drawn to.
+    if chrome.prompt.is_none() && chrome.message.is_none() {
+        let status: String = tab::status_line(tabs, current).chars().take(cmp::max(x - 1, 0) as usize).collect();
+        win.mvaddstr(y - 1, 0, &status);
+    }
+    win.refresh();
+
+    let synced = tabs[current].sync;
+    let ids = tabs[current].layout.panes();
+    // Panes are only told apart by their titles and borders when there's more
+    // than one of them.
+    let split = ids.len() > 1;
+    for id in ids {
+        if id != active {
+            render_pane(&panes[&id], false, synced, split, ctx, backend, pairs, chrome);
+        }
+    }
+    if let Some(pane) = panes.get(&active) {
+        render_pane(pane, true, synced, split, ctx, backend, pairs, chrome);
+    }
+
+    // The picker is drawn over the panes, so it's drawn using the same
+    // backend as them.
+    if let Some((_, ref picker)) = chrome.picker {
+        render::render_picker(&mut PaneRenderer::new(backend, win, pairs), picker);
+    }
+
+    if let Some(ref prompt) = chrome.prompt {
+        win.attrset(pancurses::A_NORMAL);
+        win.mvaddstr(y - 1, 1, &format!("{}{}", prompt.leader(), prompt.text()));
+        pancurses::curs_set(1);
+        win.refresh();
+    } else if let Some(ref msg) = chrome.message {
+        win.attrset(pancurses::A_NORMAL);
+        win.mvaddstr(y - 1, 1, &format!(" {} ", msg));
+        win.refresh();
+    }
+}
+
+// `render_too_small` draws `TOO_SMALL` in place of the panes, wrapped to the
+// width of `win`, and cut short if `win` isn't tall enough to fit it.
+fn render_too_small(win: &Window) {
+    let (y, x) = win.get_max_yx();
+    win.erase();
+    pancurses::curs_set(0);
+    let chars: Vec<char> = TOO_SMALL.chars().collect();
+    for (line, chunk) in chars.chunks(cmp::max(x, 1) as usize).take(cmp::max(y, 0) as usize).enumerate() {
+        // The last cell of the window isn't drawn to (see `render`).
+        let n = if line as i32 == y - 1 { cmp::min(chunk.len(), cmp::max(x - 1, 0) as usize) } else { chunk.len() };
+        win.mvaddstr(line as i32, 0, &chunk[..n].iter().collect::<String>());
+    }
+    win.refresh();
+}
+
+// `render_pane` draws the terminal of `pane`, or the copy of the primary
+// screen if it's being peeked at. The state of `chrome`, such as the selection,
+// belongs to the active pane, and so is only drawn if `is_active` is `true`.
+// `synced` is `true` if the input of the pane's tab is synchronized. `split` is
+// `true` if the pane's tab has more than one pane, in which case the title of
+// the pane is shown before its label, and the border of the active pane is
+// highlighted.
+fn render_pane(
+    pane: &Pane,
+    is_active: bool,
+    synced: bool,
+    split: bool,
+    ctx: &mut render::Context,
+    backend: Backend,
+    pairs: &mut Pairs,
+    chrome: &Chrome,
+) {
+    // The panes of `tep` are always drawn in a window.
+    let win =
+        match pane.win {
+            Some(ref win) => win,
+            None => return,
+        };
+    let mut r = PaneRenderer::new(backend, win, pairs);
+    let border_char = chrome.border_char;
+    let highlight = split && is_active;
+    let title = if split { pane.display_title() } else { None };
+    let restarts = pane.restart.as_ref()
+        .filter(|restart| restart.restarts() > 0)
+        .map(|restart| format!("restarts: {}", restart.restarts()));
+    ctx.cursor_shape = pane.cursor_shape;
+    match pane.alt_screen.peeked() {
+        Some((primary, primary_extras)) => {
+            let label = title_label(title, Some(chrome.peek_label.as_str()));
+            render::render_term(primary, &mut r, ctx, &pane.palette, primary_extras, None, None, &[], border_char, label.as_ref().map(String::as_str), highlight);
+        },
+        None if !is_active => {
+            let scrollback = visible_scrollback(&pane.alt_screen, &pane.scrollback);
+            let label =
+                match pane.exited {
+                    Some(ref exited) => Some(exited.as_str()),
+                    None if pane.paused => Some("paused"),
+                    None if synced => Some("sync"),
+                    None => restarts.as_ref().map(String::as_str),
+                };
+            let label = title_label(title, label);
+            render::render_term(&pane.term, &mut r, ctx, &pane.palette, &pane.extras, scrollback, None, &[], border_char, label.as_ref().map(String::as_str), highlight);
+        },
+        None => {
+            let term = &pane.term;
+            let scrollback = visible_scrollback(&pane.alt_screen, &pane.scrollback);
+            let mut selection = chrome.selection;
+            let mut matches = vec![];
+            let mut search_label = String::new();
+            let num_lines = term.grid().num_lines().0;
+            let num_cols = term.grid().num_cols().0;
+            if let (Some(search), Some(scrollback)) = (chrome.search.as_ref(), scrollback) {
+                // The current match is drawn as if it was selected, so that
+                // it stands out from the other matches.
+                if let Some(m) = search.current(scrollback, num_lines) {
+                    selection = Some(Selection::new((m.line, m.start), (m.line, m.end)));
+                }
+                matches = search.matches(&render::screen_rows(term, Some(scrollback)));
+                search_label = format!("/{} (n/N, q to exit)", search.pattern());
+            }
+            if let (Some(copy_mode), Some(scrollback)) = (chrome.copy_mode.as_ref(), scrollback) {
+                if let Some(s) = copy_mode.selection(scrollback, num_lines, num_cols) {
+                    selection = Some(s);
+                }
+            }
+            let label =
+                if !search_label.is_empty() {
+                    Some(search_label.as_str())
+                } else if chrome.copy_mode.is_some() {
+                    Some("copy mode (q to exit)")
+                } else if chrome.passthrough {
+                    Some("passthrough")
+                } else if chrome.recording {
+                    Some("recording macro")
+                } else if let Some(ref exited) = pane.exited {
+                    Some(exited.as_str())
+                } else if pane.paused {
+                    Some("paused")
+                } else if synced {
+                    Some("sync")
+                } else {
+                    restarts.as_ref().map(String::as_str)
+                };
+            let label = title_label(title, label);
+            render::render_term(term, &mut r, ctx, &pane.palette, &pane.extras, scrollback, selection.as_ref(), &matches, border_char, label.as_ref().map(String::as_str), highlight);
+            // The host's cursor is used to show the cursor of copy mode.
+            if let (Some(copy_mode), Some(scrollback)) = (chrome.copy_mode.as_ref(), scrollback) {
+                if let Some((line, col)) = copy_mode.cursor(scrollback, num_lines) {
+                    r.move_cursor(line as i32 + 1, col as i32 + 1, true);
+                    r.present();
+                }
+            }
+        },
+    }
+}
+
+// `title_label` returns the label that's drawn over the top border of a pane
+// with `title`, and whose state is described by `label`.
+fn title_label(title: Option<&str>, label: Option<&str>) -> Option<String> {
+    match (title, label) {
+        (Some(title), Some(label)) => Some(format!("{} | {}", title, label)),
+        (Some(s), None) | (None, Some(s)) => Some(s.to_string()),
+        (None, None) => None,
+    }
+}
+
+// `render_cursor` redraws the cursor of `pane`, which is the active pane.
+fn render_cursor(pane: &Pane, ctx: &mut render::Context, backend: Backend, pairs: &mut Pairs, chrome: &Chrome) {
+    // The cursor is left at the end of the prompt while a command is being
+    // entered, and at the cursor of copy mode while it's active. The cursor
+    // is hidden while a picker is open.
+    if chrome.prompt.is_some() || chrome.copy_mode.is_some() || chrome.picker.is_some() {
+        return;
+    }
+
+    let win =
+        match pane.win {
+            Some(ref win) => win,
+            None => return,
+        };
+    ctx.cursor_shape = pane.cursor_shape;
+    let mut r = PaneRenderer::new(backend, win, pairs);
+    match pane.alt_screen.peeked() {
+        Some((primary, primary_extras)) => {
+            render::render_cursor(primary, &mut r, ctx, &pane.palette, primary_extras, None, None, &[]);
+        },
+        None => {
+            let scrollback = visible_scrollback(&pane.alt_screen, &pane.scrollback);
+            render::render_cursor(&pane.term, &mut r, ctx, &pane.palette, &pane.extras, scrollback, None, &[]);
+        },
+    }
+}
+
+// `is_repeatable` returns `true` if `action` can be repeated without pressing
+// the prefix key again (see `REPEAT_INTERVAL`).
+fn is_repeatable(action: Action) -> bool {
+    match action {
+        Action::ResizePaneLeft | Action::ResizePaneDown | Action::ResizePaneUp | Action::ResizePaneRight => true,
+        _ => false,
+    }
+}
+
+// `copy_selection` copies `text`, which was selected using the mouse, to the
+// primary selection, as on X11, and also to the clipboard if `copy_on_select`
+// is `true`.
+fn copy_selection(text: &str, copy_on_select: bool) {
+    clipboard::write_async(Target::Primary, text.as_bytes().to_vec());
+    if copy_on_select {
+        clipboard::write_async(Target::Clipboard, text.as_bytes().to_vec());
+    }
+}
+
+// `window_area` returns the area of `win` that the panes are arranged in,
+// which is above the status line. If the size of the terminal is fixed then
+// the area is centred in the window, and is sized so that the terminal of a
+// single pane has the fixed size, but it's never larger than the window.
+fn window_area(win: &Window, fixed: FixedSize) -> Rect {
+    let (lines, cols) = win.get_max_yx();
+    let lines = cmp::max(lines - 1, 0);
+    // The border of a pane is drawn around its terminal.
+    let fixed_lines = fixed.lines.map_or(lines, |n| cmp::min(n.saturating_add(2), lines));
+    let fixed_cols = fixed.cols.map_or(cols, |n| cmp::min(n.saturating_add(2), cols));
+
+    Rect{
+        y: (lines - fixed_lines) / 2,
+        x: (cols - fixed_cols) / 2,
+        lines: fixed_lines,
+        cols: fixed_cols,
+    }
+}
+
+// `relayout` moves each pane to its area of `win` in `layout`, which is done
+// after the layout changes and after `win` is resized.
+fn relayout(panes: &mut BTreeMap<PaneId, Pane>, layout: &Layout, win: &Window, fixed: FixedSize) {
+    // The window is cleared so that the borders of panes that were moved
+    // aren't left behind.
+    win.clear();
+    for (id, rect) in layout.rects(window_area(win, fixed)) {
+        if let Some(pane) = panes.get_mut(&id) {
+            pane.place(Some(win), rect);
+        }
+    }
+}
+
+// `switch_tab` makes `to` the current tab, and makes the pane of it that was
+// last active the active pane.
+fn switch_tab(
+    panes: &BTreeMap<PaneId, Pane>,
+    tabs: &mut [Tab],
+    current: &mut usize,
+    active: &mut PaneId,
+    to: usize,
+    focus: &mut Focus,
+    chrome: &mut Chrome,
+) {
+    if to == *current {
+        return;
+    }
+    tabs[*current].active = *active;
+    *current = to;
+    let pane = tabs[to].active;
+    switch_pane(panes, pane, focus, chrome);
+    *active = pane;
+}
+
+// `switch_pane` makes `to` the active pane. The host's modes are changed to
+// those of `to`, and the state of `chrome` that belongs to the previous active
+// pane, such as the selection, is discarded.
+fn switch_pane(
+    panes: &BTreeMap<PaneId, Pane>,
+    to: PaneId,
+    focus: &mut Focus,
+    chrome: &mut Chrome,
+) {
+    if let Some(pane) = panes.get(&to) {
+        pancurses::mousemask(pane.mouse.mask(), ptr::null_mut());
+        write_host(pane.mouse.host_motion());
+        focus.set_reporting(pane.focus_reporting);
+    }
+    chrome.selection = None;
+    chrome.copy_mode = None;
+    chrome.search = None;
+}
+
+// `clear` clears `scrollback` and, if `screen` is `true`, the screen of `term`.
+// The line that the cursor is on is kept, and moved to the top of the screen,
+// so that the child's prompt stays visible without the child having to redraw
+// it.
+fn clear(
+    term: &mut Term,
+    parser: &mut Processor,
+    extras: &mut Extras,
+    scrollback: &mut Scrollback,
+    screen: bool,
+) {
+    scrollback.clear();
+    if !screen {
+        return;
+    }
+
+    let cursor = term.cursor().point;
+    let mut out = String::from("\x1b[H");
+    if cursor.line.0 > 0 {
+        out.push_str(&format!("\x1b[{}M", cursor.line.0));
+    }
+    if term.grid().num_lines().0 > 1 {
+        out.push_str("\x1b[2H\x1b[J");
+    }
+    out.push_str(&format!("\x1b[1;{}H", cursor.col.0 + 1));
+
+    let mut sink = io::sink();
+    for byte in out.as_bytes() {
+        parser.advance(term, *byte, &mut sink);
+    }
+    extras.clear();
+}
+
+// `screen_text` returns the text of the screen of `term`, without trailing
+// whitespace on each line.
+fn screen_text(term: &Term, extras: &Extras) -> String {
+    let mut text = String::new();
+    for i in 0..term.grid().num_lines().0 {
+        let line = Line(i);
+        let row = scrollback::copy_row(term, line);
+        let clusters = grapheme::clusters(&row, extras, line);
+        let s: String = clusters.iter().map(|c| c.text.as_str()).collect();
+        text.push_str(s.trim_end());
+        text.push('\n');
+    }
+
+    text
+}
+
+// `visible_scrollback` returns `None` if the alternate screen is active, as the
+// scrollback belongs to the primary screen.
+fn visible_scrollback<'a>(alt_screen: &AltScreen, scrollback: &'a Scrollback) -> Option<&'a Scrollback> {
+    if alt_screen.is_active() {
+        None
+    } else {
+        Some(scrollback)
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::collections::{HashMap, HashSet};
+
+use alacritty::index::{Column, Line, Point};
+
+use colour::Spec;
+use tap::LineSize;
+
+// `Style` holds the attributes of a cell that `alacritty::Term` doesn't track.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    pub underline_colour: Option<Spec>,
+    pub undercurl: bool,
+    pub strikethrough: bool,
+}
+
+// `Extras` records the `Style`s of cells. Styles are recorded against screen
+// positions rather than against cells, so the caller moves them when lines
+// scroll (see `scroll`) and discards them when cells are erased (see `erase`),
+// as `alacritty::Term` does with its cells.
+//
+// `Extras` also records the zero-width characters, such as combining
+// diacritics and zero-width joiners, that follow the character in each cell,
+// as `alacritty::Term` discards them, and the positions of ambiguous-width
+// characters that are drawn as wide characters, as `alacritty::Term` always
+// lays them out as narrow characters.
+//
+// Finally, `Extras` records the `LineSize` of each line that isn't
+// `LineSize::Single`, as `alacritty::Term` ignores DECDHL and DECDWL.
+pub struct Extras {
+    styles: HashMap<(usize, usize), Style>,
+    zero_width: HashMap<(usize, usize), String>,
+    wide: HashSet<(usize, usize)>,
+    line_sizes: HashMap<usize, LineSize>,
+}
+
+impl Extras {
+    pub fn new() -> Extras {
+        Extras{
+            styles: HashMap::new(),
+            zero_width: HashMap::new(),
+            wide: HashSet::new(),
+            line_sizes: HashMap::new(),
+        }
+    }
+
+    // `record` records that a character was printed at `point` while `style`
+    // was the current style. This also discards any zero-width characters
+    // that were recorded at `point`, and whether the character at `point` was
+    // wide, as these belonged to the character that was overwritten. Printing
+    // over the second half of a wide character also makes it narrow.
+    pub fn record(&mut self, point: Point, style: Style) {
+        let Point{line: Line(line), col: Column(col)} = point;
+        self.zero_width.remove(&(line, col));
+        self.wide.remove(&(line, col));
+        if col > 0 {
+            self.wide.remove(&(line, col - 1));
+        }
+        if style == Style::default() {
+            self.styles.remove(&(line, col));
+        } else {
+            self.styles.insert((line, col), style);
+        }
+    }
+
+    pub fn get(&self, line: Line, col: Column) -> Style {
+        self.styles.get(&(line.0, col.0)).cloned().unwrap_or_default()
+    }
+
+    // `push_zero_width` records that the zero-width character `c` follows the
+    // character at `point`.
+    pub fn push_zero_width(&mut self, point: Point, c: char) {
+        let Point{line: Line(line), col: Column(col)} = point;
+        self.zero_width.entry((line, col)).or_insert_with(String::new).push(c);
+    }
+
+    pub fn zero_width(&self, line: Line, col: Column) -> &str {
+        self.zero_width.get(&(line.0, col.0)).map_or("", |s| s.as_str())
+    }
+
+    // `set_wide` records that the ambiguous-width character at `point` is
+    // drawn as a wide character, and so also covers the following cell.
+    pub fn set_wide(&mut self, point: Point) {
+        let Point{line: Line(line), col: Column(col)} = point;
+        self.wide.insert((line, col));
+    }
+
+    pub fn is_wide(&self, line: Line, col: Column) -> bool {
+        self.wide.contains(&(line.0, col.0))
+    }
+
+    pub fn set_line_size(&mut self, line: Line, size: LineSize) {
+        if size == LineSize::Single {
+            self.line_sizes.remove(&line.0);
+        } else {
+            self.line_sizes.insert(line.0, size);
+        }
+    }
+
+    pub fn line_size(&self, line: Line) -> LineSize {
+        self.line_sizes.get(&line.0).cloned().unwrap_or(LineSize::Single)
+    }
+
+    // `erase` discards what's recorded for the cells from `start` to `end`,
+    // inclusive, in the order that they're read, where positions are `(line,
+    // column)` pairs, because their characters were erased.
+    pub fn erase(&mut self, start: (usize, usize), end: (usize, usize)) {
+        let kept = |pos: &(usize, usize)| *pos < start || *pos > end;
+        self.styles.retain(|pos, _| kept(pos));
+        self.zero_width.retain(|pos, _| kept(pos));
+        self.wide.retain(|pos| kept(pos));
+    }
+
+    // `scroll` moves what's recorded for the lines from `top` to `bottom`,
+    // inclusive, up by `n` lines, or down if `n` is negative, because the
+    // lines were scrolled. What's recorded for lines that are scrolled out of
+    // the region is discarded.
+    pub fn scroll(&mut self, top: usize, bottom: usize, n: isize) {
+        let moved = |line: usize| -> Option<usize> {
+            if line < top || line > bottom {
+                return Some(line);
+            }
+            let to = line as isize - n;
+            if to < top as isize || to > bottom as isize {
+                None
+            } else {
+                Some(to as usize)
+            }
+        };
+        self.styles = self.styles.drain()
+            .filter_map(|((line, col), style)| moved(line).map(|l| ((l, col), style)))
+            .collect();
+        self.zero_width = self.zero_width.drain()
+            .filter_map(|((line, col), s)| moved(line).map(|l| ((l, col), s)))
+            .collect();
+        self.wide = self.wide.drain()
+            .filter_map(|(line, col)| moved(line).map(|l| (l, col)))
+            .collect();
+        self.line_sizes = self.line_sizes.drain()
+            .filter_map(|(line, size)| moved(line).map(|l| (l, size)))
+            .collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.styles.clear();
+        self.zero_width.clear();
+        self.wide.clear();
+        self.line_sizes.clear();
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::mem;
+
+// `ENABLE` and `DISABLE` enable and disable focus reporting on the host.
+pub const ENABLE: &str = "\x1b[?1004h";
+pub const DISABLE: &str = "\x1b[?1004l";
+
+// `MODE` is the private mode that the child sets to enable focus reporting.
+pub const MODE: i64 = 1004;
+
+const FOCUS_IN: &[u8] = b"\x1b[I";
+const FOCUS_OUT: &[u8] = b"\x1b[O";
+
+// `Focus` detects the reports that the host sends when it gains or loses focus,
+// when focus reporting is enabled on the host. Reports are passed to the child
+// if the child has enabled focus reporting, so that, for example, editors can
+// save files when the terminal loses focus, and are discarded otherwise.
+pub struct Focus {
+    // `pending` holds the bytes that could be the start of a report.
+    pending: Vec<u8>,
+    focused: bool,
+    reporting: bool,
+}
+
+impl Focus {
+    pub fn new() -> Focus {
+        Focus{
+            pending: vec![],
+            focused: true,
+            reporting: false,
+        }
+    }
+
+    // `focused` returns `true` if the host most recently reported that it
+    // gained focus, or if it hasn't reported a change in focus.
+    pub fn focused(&self) -> bool {
+        self.focused
+    }
+
+    // `set_reporting` handles the child setting (if `set` is `true`) or
+    // resetting `MODE`.
+    pub fn set_reporting(&mut self, set: bool) {
+        self.reporting = set;
+    }
+
+    // `filter` returns the bytes that should be written to the child for
+    // `bytes`. Bytes that could be the start of a report are held back until
+    // they're known not to be, or until `flush` is called.
+    pub fn filter(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        for &b in bytes {
+            self.pending.push(b);
+            if self.pending == FOCUS_IN || self.pending == FOCUS_OUT {
+                self.focused = self.pending == FOCUS_IN;
+                if self.reporting {
+                    out.extend_from_slice(&self.pending);
+                }
+                self.pending.clear();
+            } else if !FOCUS_IN.starts_with(&self.pending) {
+                // Only the last byte can be the start of a new report, as
+                // all reports start with `ESC`.
+                let last = self.pending.pop();
+                out.extend(self.pending.drain(..));
+                match last {
+                    Some(0x1b) => self.pending.push(0x1b),
+                    Some(b) => out.push(b),
+                    None => {},
+                }
+            }
+        }
+
+        out
+    }
+
+    // `flush` returns the bytes that were held back by `filter`.
+    pub fn flush(&mut self) -> Vec<u8> {
+        mem::replace(&mut self.pending, vec![])
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+
+use alacritty::index::{Column, Line};
+use alacritty::term::cell::{Cell, Flags};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use extras::Extras;
+
+// `PLACEHOLDER` is drawn in place of each column of a grapheme cluster that the
+// host can't display.
+pub const PLACEHOLDER: char = '?';
+
+// `Cluster` is a grapheme cluster that starts at `col` and spans `span`
+// columns of the grid. `alacritty::Term` lays out each character of a cluster
+// separately, so clusters such as emoji ZWJ sequences can span more columns of
+// the grid than the host uses to display them.
+pub struct Cluster {
+    pub col: Column,
+    pub text: String,
+    pub span: usize,
+}
+
+// `clusters` returns the grapheme clusters of `cells`, in order, where `extras`
+// holds the `Extras` of `cells` as `line`.
+pub fn clusters(cells: &[Cell], extras: &Extras, line: Line) -> Vec<Cluster> {
+    let mut clusters: Vec<Cluster> = vec![];
+    let mut col = Column(0);
+    while col.0 < cells.len() {
+        let cell = cells[col.0];
+        // The spacer that follows a wide character is covered by the wide
+        // character when it's drawn, so it isn't part of any cluster.
+        if cell.flags.contains(Flags::WIDE_CHAR_SPACER) {
+            col += 1;
+            continue;
+        }
+
+        // The cell that follows a wide ambiguous-width character is a space
+        // that we inserted to make room for it (see `is_ambiguous`), so we
+        // skip it in the same way as `Flags::WIDE_CHAR_SPACER`.
+        let step = if extras.is_wide(line, col) { 2 } else { 1 };
+        let span = if cell.flags.contains(Flags::WIDE_CHAR) { 2 } else { step };
+        let mut text = cell.c.to_string();
+        text.push_str(extras.zero_width(line, col));
+
+        let mut joined = false;
+        if let Some(prev) = clusters.last_mut() {
+            if continues(&prev.text, &text) {
+                prev.text.push_str(&text);
+                prev.span += span;
+                joined = true;
+            }
+        }
+        if !joined {
+            clusters.push(Cluster{col: col, text: text, span: span});
+        }
+
+        col += step;
+    }
+
+    clusters
+}
+
+// `continues` returns `true` if `next` continues the grapheme cluster `prev`.
+fn continues(prev: &str, next: &str) -> bool {
+    if prev.is_ascii() && next.is_ascii() {
+        return false;
+    }
+    let mut s = String::with_capacity(prev.len() + next.len());
+    s.push_str(prev);
+    s.push_str(next);
+
+    s.graphemes(true).count() == 1
+}
+
+// `is_ambiguous` returns `true` if `c` is an East Asian ambiguous-width
+// character. `alacritty::Term` lays these out as narrow characters, so when
+// they're configured to be wide we insert a space after each one to make room
+// for it, and record its position using `Extras::set_wide`.
+pub fn is_ambiguous(c: char) -> bool {
+    c.width() != c.width_cjk()
+}
+
+// `host_width` estimates the number of columns that the host uses to display
+// `text`, which should be a single grapheme cluster. `UnicodeWidthStr` sums the
+// widths of the characters of a cluster, but hosts that support emoji
+// sequences display them using two columns. `ambiguous_wide` should be `true`
+// if the host displays ambiguous-width characters using two columns.
+pub fn host_width(text: &str, ambiguous_wide: bool) -> usize {
+    let is_emoji_sequence = text.chars().count() > 1 && text.chars().any(|c| {
+        c == '\u{200d}' ||
+            c == '\u{fe0f}' ||
+            ('\u{1f1e6}' <= c && c <= '\u{1f1ff}') ||
+            ('\u{1f3fb}' <= c && c <= '\u{1f3ff}')
+    });
+
+    if is_emoji_sequence {
+        2
+    } else if ambiguous_wide {
+        UnicodeWidthStr::width_cjk(text)
+    } else {
+        UnicodeWidthStr::width(text)
+    }
+}
+
+// `host_is_utf8` returns `true` if the locale of the host uses UTF-8, which
+// we take as a sign that the host can display non-ASCII characters.
+pub fn host_is_utf8() -> bool {
+    for var in &["LC_ALL", "LC_CTYPE", "LANG"] {
+        if let Ok(v) = env::var(var) {
+            if !v.is_empty() {
+                let v = v.to_lowercase();
+
+                return v.contains("utf-8") || v.contains("utf8");
+            }
+        }
+    }
+
+    false
+}
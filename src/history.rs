@@ -0,0 +1,42 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::collections::VecDeque;
+
+// `History` holds the text that was most recently copied or pasted, so that it
+// can be pasted again after the host's clipboard has been overwritten.
+pub struct History {
+    // `entries` holds the most recent entry first.
+    entries: VecDeque<String>,
+    max_entries: usize,
+}
+
+impl History {
+    pub fn new(max_entries: usize) -> History {
+        History{
+            entries: VecDeque::new(),
+            max_entries: max_entries,
+        }
+    }
+
+    // `push` makes `text` the most recent entry. `text` is moved to the front
+    // if it's already in the history, rather than being added twice.
+    pub fn push(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.entries.retain(|e| *e != text);
+        self.entries.push_front(text);
+        self.entries.truncate(self.max_entries);
+    }
+
+    pub fn latest(&self) -> Option<&str> {
+        self.entries.front().map(|e| e.as_str())
+    }
+
+    pub fn entries(&self) -> Vec<String> {
+        self.entries.iter().cloned().collect()
+    }
+}
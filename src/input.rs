@@ -0,0 +1,470 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+#[cfg(feature = "curses")]
+use std::mem;
+
+#[cfg(feature = "curses")]
+use alacritty::term::mode::TermMode;
+
+#[cfg(feature = "curses")]
+use pancurses;
+#[cfg(feature = "curses")]
+use pancurses::Input;
+
+#[cfg(feature = "curses")]
+use config::{Backspace, Delete};
+use tap::Event as TapEvent;
+
+// `MAX_KEYBOARD_FLAGS` limits the size of the stack of kitty keyboard protocol
+// flags, so that a misbehaving child can't grow it without bound.
+const MAX_KEYBOARD_FLAGS: usize = 16;
+
+// `KeyboardModes` tracks the extended keyboard modes that the child has
+// enabled, which are xterm's modifyOtherKeys and the kitty keyboard protocol
+// ("CSI u"). The modes aren't enabled on the host, because curses and the
+// keybindings can't decode the encodings that the host would then send, so the
+// keys that curses reports are encoded in the child's requested forms by
+// `encode` instead. Keys that curses can't tell apart, such as Ctrl+A and
+// Ctrl+Shift+A, are encoded in the same way, and `ESC` is sent unchanged,
+// because it can't be told apart from the start of an Alt-modified key.
+pub struct KeyboardModes {
+    modify_other_keys: i64,
+    flags: Vec<u32>,
+}
+
+impl KeyboardModes {
+    pub fn new() -> KeyboardModes {
+        KeyboardModes{
+            modify_other_keys: 0,
+            flags: vec![],
+        }
+    }
+
+    // `apply` updates the modes using `evt`.
+    pub fn apply(&mut self, evt: &TapEvent) {
+        match *evt {
+            TapEvent::SetModifyOtherKeys(level) => self.modify_other_keys = level,
+            TapEvent::PushKeyboardFlags(flags) => {
+                if self.flags.len() >= MAX_KEYBOARD_FLAGS {
+                    self.flags.remove(0);
+                }
+                self.flags.push(flags);
+            },
+            TapEvent::PopKeyboardFlags(n) => {
+                let n = cmp::min(n, self.flags.len());
+                let len = self.flags.len() - n;
+                self.flags.truncate(len);
+            },
+            TapEvent::SetKeyboardFlags(flags, mode) => {
+                let current = self.flags();
+                let new =
+                    match mode {
+                        1 => flags,
+                        2 => current | flags,
+                        3 => current & !flags,
+                        _ => return,
+                    };
+                match self.flags.last_mut() {
+                    Some(top) => *top = new,
+                    None => self.flags.push(new),
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // `flags` returns the current flags of the kitty keyboard protocol, which
+    // are reported to the child in response to `CSI ? u`.
+    pub fn flags(&self) -> u32 {
+        self.flags.last().cloned().unwrap_or(0)
+    }
+
+    // `encode` returns the encoding of the character `c`, which was typed with
+    // Alt if `alt` is `true`, in the form that the child requested, or `None`
+    // if it should be sent in the legacy form. Control characters are encoded
+    // as the Ctrl-modified keys that type them, other than Tab, Enter and
+    // Backspace, which are encoded as those keys.
+    pub fn encode(&self, c: char, alt: bool) -> Option<Vec<u8>> {
+        let (code, ctrl) =
+            match c {
+                '\x1b' => return None,
+                '\t' | '\r' | '\x7f' => (c as u32, false),
+                '\0' => (' ' as u32, true),
+                '\x01'..='\x1a' => (c as u32 + 0x60, true),
+                '\x1c'..='\x1f' => (c as u32 + 0x40, true),
+                _ => (c as u32, false),
+            };
+        // The modifier parameter is 1 plus the xterm modifier bits, where Alt
+        // is 2 and Ctrl is 4.
+        let modifier = 1 + if alt { 2 } else { 0 } + if ctrl { 4 } else { 0 };
+
+        // Flag 8 reports every key using `CSI u`, and flag 1 only reports the
+        // keys that are ambiguous in the legacy forms.
+        let flags = self.flags();
+        let legacy = modifier == 1;
+        let seq =
+            if flags & 8 != 0 || (flags & 1 != 0 && !legacy) {
+                if modifier == 1 {
+                    format!("\x1b[{}u", code)
+                } else {
+                    format!("\x1b[{};{}u", code, modifier)
+                }
+            } else if self.modify_other_keys >= 2 && !legacy {
+                format!("\x1b[27;{};{}~", modifier, code)
+            } else {
+                return None;
+            };
+
+        Some(seq.into_bytes())
+    }
+}
+
+// `Encoder` encodes input from the host for the child, and tracks whether the
+// next key should be sent as an Alt-modified key. Alt-modified keys are sent
+// prefixed with `ESC`, which is how most terminals send them by default.
+#[cfg(feature = "curses")]
+pub struct Encoder {
+    meta_8bit: bool,
+    meta_prefix: Option<char>,
+    meta_pending: bool,
+    backspace: Backspace,
+    delete: Delete,
+}
+
+#[cfg(feature = "curses")]
+impl Encoder {
+    // `meta_8bit` should be `true` if the host sends Alt-modified characters
+    // by setting their eighth bit. `meta_prefix` is a key that causes the next
+    // key to be sent as an Alt-modified key; pressing it twice sends it to the
+    // child. `backspace` and `delete` define the encodings of Backspace and
+    // Delete.
+    pub fn new(
+        meta_8bit: bool,
+        meta_prefix: Option<char>,
+        backspace: Backspace,
+        delete: Delete,
+    ) -> Encoder {
+        Encoder{
+            meta_8bit: meta_8bit,
+            meta_prefix: meta_prefix,
+            meta_pending: false,
+            backspace: backspace,
+            delete: delete,
+        }
+    }
+
+    // `encode` is like the `encode` function, but also handles Alt-modified
+    // keys, the configured encodings of Backspace and Delete, and the
+    // extended keyboard modes of the child, which are given by `keys`. It
+    // returns an empty `Vec` when `input` is `meta_prefix`.
+    pub fn encode(&mut self, input: &Input, mode: TermMode, keys: &KeyboardModes) -> Option<Vec<u8>> {
+        let c =
+            match *input {
+                Input::Character(c) => Some(c),
+                Input::KeyBackspace => Some('\x7f'),
+                _ => None,
+            };
+        if let Some(c) = c {
+            if Some(c) == self.meta_prefix && !self.meta_pending {
+                self.meta_pending = true;
+
+                return Some(vec![]);
+            }
+            // Input is decoded using the host's locale, so characters such
+            // as `é` can't be distinguished from Alt-modified characters
+            // when `meta_8bit` is `true`.
+            let meta_8bit = self.meta_8bit && '\u{80}' <= c && c <= '\u{ff}';
+            let (c, alt) =
+                if meta_8bit {
+                    (((c as u8) & 0x7f) as char, true)
+                } else {
+                    (c, self.meta_pending)
+                };
+            if let Some(bytes) = keys.encode(c, alt) {
+                self.meta_pending = false;
+
+                return Some(bytes);
+            }
+            if meta_8bit {
+                self.meta_pending = false;
+
+                return Some(vec![0x1b, c as u8]);
+            }
+        }
+
+        let mut bytes =
+            match *input {
+                // Hosts whose Backspace key sends `^?` may not have it decoded
+                // as `Input::KeyBackspace`.
+                Input::KeyBackspace | Input::Character('\x7f') => {
+                    match self.backspace {
+                        Backspace::Del => vec![0x7f],
+                        Backspace::Bs => vec![0x08],
+                    }
+                },
+                Input::KeyDC => {
+                    match self.delete {
+                        Delete::Csi => b"\x1b[3~".to_vec(),
+                        Delete::Del => vec![0x7f],
+                    }
+                },
+                _ => encode(input, mode)?,
+            };
+        if mem::replace(&mut self.meta_pending, false) {
+            bytes.insert(0, 0x1b);
+        }
+
+        Some(bytes)
+    }
+}
+
+// `encode` returns the bytes that should be written to the PTY when `input` is
+// received from the host, or `None` if `input` isn't a key that can be sent to
+// the child. Special keys are encoded as they are by xterm, where `mode` is
+// the mode of the child's terminal. In particular, the cursor keys are encoded
+// using SS3 rather than CSI in application cursor keys mode (DECCKM), and the
+// keypad's Enter and centre keys are encoded using SS3 in application keypad
+// mode (DECKPAM).
+#[cfg(feature = "curses")]
+pub fn encode(input: &Input, mode: TermMode) -> Option<Vec<u8>> {
+    let app_cursor = mode.contains(TermMode::APP_CURSOR);
+    let app_keypad = mode.contains(TermMode::APP_KEYPAD);
+
+    let seq: &[u8] =
+        match *input {
+            // Control characters, including NUL (which Ctrl+Space sends),
+            // are passed through as they were typed.
+            Input::Character(c) => {
+                let mut buf = [0; 4];
+
+                return Some(c.encode_utf8(&mut buf).as_bytes().to_vec());
+            },
+
+            Input::KeyUp if app_cursor => b"\x1bOA",
+            Input::KeyDown if app_cursor => b"\x1bOB",
+            Input::KeyRight if app_cursor => b"\x1bOC",
+            Input::KeyLeft if app_cursor => b"\x1bOD",
+            Input::KeyHome | Input::KeyA1 | Input::KeyFind if app_cursor => b"\x1bOH",
+            Input::KeyEnd | Input::KeyC1 | Input::KeySelect if app_cursor => b"\x1bOF",
+
+            Input::KeyUp => b"\x1b[A",
+            Input::KeyDown => b"\x1b[B",
+            Input::KeyRight => b"\x1b[C",
+            Input::KeyLeft => b"\x1b[D",
+            // Some hosts, such as `screen` and `rxvt`, send `CSI 1 ~` and
+            // `CSI 4 ~` for Home and End, which their terminfo entries
+            // describe as Find and Select.
+            Input::KeyHome | Input::KeyA1 | Input::KeyFind => b"\x1b[H",
+            Input::KeyEnd | Input::KeyC1 | Input::KeySelect => b"\x1b[F",
+
+            Input::KeyB2 if app_keypad => b"\x1bOE",
+            Input::KeyB2 => b"\x1b[E",
+            Input::KeyIC => b"\x1b[2~",
+            Input::KeyDC => b"\x1b[3~",
+            Input::KeyPPage | Input::KeyA3 => b"\x1b[5~",
+            Input::KeyNPage | Input::KeyC3 => b"\x1b[6~",
+
+            Input::KeySR => b"\x1b[1;2A",
+            Input::KeySF => b"\x1b[1;2B",
+            Input::KeySRight => b"\x1b[1;2C",
+            Input::KeySLeft => b"\x1b[1;2D",
+            Input::KeySHome => b"\x1b[1;2H",
+            Input::KeySEnd => b"\x1b[1;2F",
+            Input::KeySIC => b"\x1b[2;2~",
+            Input::KeySDC => b"\x1b[3;2~",
+            Input::KeySPrevious => b"\x1b[5;2~",
+            Input::KeySNext => b"\x1b[6;2~",
+            Input::KeyBTab => b"\x1b[Z",
+
+            Input::KeyF1 => b"\x1bOP",
+            Input::KeyF2 => b"\x1bOQ",
+            Input::KeyF3 => b"\x1bOR",
+            Input::KeyF4 => b"\x1bOS",
+            Input::KeyF5 => b"\x1b[15~",
+            Input::KeyF6 => b"\x1b[17~",
+            Input::KeyF7 => b"\x1b[18~",
+            Input::KeyF8 => b"\x1b[19~",
+            Input::KeyF9 => b"\x1b[20~",
+            Input::KeyF10 => b"\x1b[21~",
+            Input::KeyF11 => b"\x1b[23~",
+            Input::KeyF12 => b"\x1b[24~",
+
+            Input::KeyBackspace => b"\x7f",
+            Input::KeyEnter if app_keypad => b"\x1bOM",
+            Input::KeyEnter => b"\r",
+
+            Input::Unknown(code) => return encode_extended(code),
+
+            _ => return None,
+        };
+
+    Some(seq.to_vec())
+}
+
+// `encode_extended` encodes the key with the curses key code `code`, if it's
+// one of the modified special keys that are described by the extended
+// capabilities of the host's terminfo entry. curses assigns codes to these
+// keys when the terminfo entry is loaded, so they're identified using their
+// capability names, such as `kHOM5` for Ctrl+Home, where the digit is the
+// xterm modifier parameter of the key.
+#[cfg(feature = "curses")]
+fn encode_extended(code: i32) -> Option<Vec<u8>> {
+    let name = pancurses::keyname(code)?;
+    let split = name.find(|c: char| c.is_ascii_digit())?;
+    let (base, modifier) = name.split_at(split);
+    let modifier: u8 = modifier.parse().ok()?;
+    if modifier < 2 || modifier > 8 {
+        return None;
+    }
+
+    let seq =
+        match base {
+            "kUP" => format!("\x1b[1;{}A", modifier),
+            "kDN" => format!("\x1b[1;{}B", modifier),
+            "kRIT" => format!("\x1b[1;{}C", modifier),
+            "kLFT" => format!("\x1b[1;{}D", modifier),
+            "kHOM" => format!("\x1b[1;{}H", modifier),
+            "kEND" => format!("\x1b[1;{}F", modifier),
+            "kIC" => format!("\x1b[2;{}~", modifier),
+            "kDC" => format!("\x1b[3;{}~", modifier),
+            "kPRV" => format!("\x1b[5;{}~", modifier),
+            "kNXT" => format!("\x1b[6;{}~", modifier),
+            _ => return None,
+        };
+
+    Some(seq.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modes(evts: &[TapEvent]) -> KeyboardModes {
+        let mut modes = KeyboardModes::new();
+        for evt in evts {
+            modes.apply(evt);
+        }
+
+        modes
+    }
+
+    #[test]
+    fn keyboard_modes_encode() {
+        let tests: &[(&[TapEvent], char, bool, Option<&str>)] = &[
+            (&[], 'a', true, None),
+            (&[], '\x01', false, None),
+            // Flag 1 only reports keys that are ambiguous in the legacy forms.
+            (&[TapEvent::PushKeyboardFlags(1)], 'a', false, None),
+            (&[TapEvent::PushKeyboardFlags(1)], 'a', true, Some("\x1b[97;3u")),
+            (&[TapEvent::PushKeyboardFlags(1)], '\x01', false, Some("\x1b[97;5u")),
+            (&[TapEvent::PushKeyboardFlags(1)], '\x01', true, Some("\x1b[97;7u")),
+            (&[TapEvent::PushKeyboardFlags(1)], '\0', false, Some("\x1b[32;5u")),
+            (&[TapEvent::PushKeyboardFlags(1)], '\x1c', false, Some("\x1b[92;5u")),
+            (&[TapEvent::PushKeyboardFlags(1)], '\x1b', true, None),
+            // Flag 8 reports every key.
+            (&[TapEvent::PushKeyboardFlags(8)], 'a', false, Some("\x1b[97u")),
+            (&[TapEvent::PushKeyboardFlags(8)], '\t', false, Some("\x1b[9u")),
+            (&[TapEvent::PushKeyboardFlags(8)], '\r', true, Some("\x1b[13;3u")),
+            (&[TapEvent::PushKeyboardFlags(8)], '\x1b', false, None),
+            (&[TapEvent::SetModifyOtherKeys(1)], '\x01', false, None),
+            (&[TapEvent::SetModifyOtherKeys(2)], 'a', false, None),
+            (&[TapEvent::SetModifyOtherKeys(2)], 'a', true, Some("\x1b[27;3;97~")),
+            (&[TapEvent::SetModifyOtherKeys(2)], '\x01', false, Some("\x1b[27;5;97~")),
+            // The kitty keyboard protocol takes precedence over
+            // modifyOtherKeys.
+            (
+                &[TapEvent::SetModifyOtherKeys(2), TapEvent::PushKeyboardFlags(1)],
+                '\x01',
+                false,
+                Some("\x1b[97;5u"),
+            ),
+        ];
+
+        for &(evts, c, alt, expected) in tests {
+            let expected = expected.map(|s| s.as_bytes().to_vec());
+
+            assert_eq!(modes(evts).encode(c, alt), expected, "{:?} {:?} {}", evts, c, alt);
+        }
+    }
+
+    #[test]
+    fn keyboard_flags_stack() {
+        let tests: &[(&[TapEvent], u32)] = &[
+            (&[], 0),
+            (&[TapEvent::PushKeyboardFlags(1)], 1),
+            (&[TapEvent::PushKeyboardFlags(1), TapEvent::PushKeyboardFlags(8)], 8),
+            (
+                &[
+                    TapEvent::PushKeyboardFlags(1),
+                    TapEvent::PushKeyboardFlags(8),
+                    TapEvent::PopKeyboardFlags(1),
+                ],
+                1,
+            ),
+            (&[TapEvent::PushKeyboardFlags(1), TapEvent::PopKeyboardFlags(5)], 0),
+            (&[TapEvent::PopKeyboardFlags(1)], 0),
+            (&[TapEvent::SetKeyboardFlags(5, 1)], 5),
+            (&[TapEvent::SetKeyboardFlags(5, 1), TapEvent::SetKeyboardFlags(2, 2)], 7),
+            (&[TapEvent::SetKeyboardFlags(5, 1), TapEvent::SetKeyboardFlags(1, 3)], 4),
+            (&[TapEvent::SetKeyboardFlags(5, 1), TapEvent::SetKeyboardFlags(1, 4)], 5),
+            // `SetKeyboardFlags` replaces the flags at the top of the stack.
+            (
+                &[
+                    TapEvent::PushKeyboardFlags(1),
+                    TapEvent::PushKeyboardFlags(2),
+                    TapEvent::SetKeyboardFlags(8, 1),
+                    TapEvent::PopKeyboardFlags(1),
+                ],
+                1,
+            ),
+        ];
+
+        for &(evts, expected) in tests {
+            assert_eq!(modes(evts).flags(), expected, "{:?}", evts);
+        }
+    }
+
+    #[test]
+    fn keyboard_flags_stack_is_bounded() {
+        let mut modes = KeyboardModes::new();
+        for i in 0..MAX_KEYBOARD_FLAGS as u32 + 4 {
+            modes.apply(&TapEvent::PushKeyboardFlags(i));
+        }
+
+        assert_eq!(modes.flags.len(), MAX_KEYBOARD_FLAGS);
+        assert_eq!(modes.flags(), MAX_KEYBOARD_FLAGS as u32 + 3);
+    }
+
+    #[cfg(feature = "curses")]
+    #[test]
+    fn encoder_handles_meta_8bit() {
+        let tests: &[(bool, &[TapEvent], char, &[u8])] = &[
+            (true, &[], '\u{e1}', b"\x1ba"),
+            (false, &[], '\u{e1}', "\u{e1}".as_bytes()),
+            (true, &[], '\u{2603}', "\u{2603}".as_bytes()),
+            (true, &[TapEvent::PushKeyboardFlags(1)], '\u{e1}', b"\x1b[97;3u"),
+        ];
+
+        for &(meta_8bit, evts, c, expected) in tests {
+            let mut encoder = Encoder::new(meta_8bit, None, Backspace::Del, Delete::Csi);
+            let bytes = encoder.encode(&Input::Character(c), TermMode::empty(), &modes(evts));
+
+            assert_eq!(bytes, Some(expected.to_vec()), "{} {:?} {:?}", meta_8bit, evts, c);
+        }
+    }
+
+    #[cfg(feature = "curses")]
+    #[test]
+    fn encoder_prefixes_keys_after_meta_prefix() {
+        let mut encoder = Encoder::new(false, Some('\x1d'), Backspace::Del, Delete::Csi);
+        let keys = KeyboardModes::new();
+        let mut encode = |c| encoder.encode(&Input::Character(c), TermMode::empty(), &keys);
+
+        assert_eq!(encode('\x1d'), Some(vec![]));
+        assert_eq!(encode('a'), Some(b"\x1ba".to_vec()));
+        assert_eq!(encode('a'), Some(b"a".to_vec()));
+    }
+}
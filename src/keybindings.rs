@@ -0,0 +1,230 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::collections::HashMap;
+
+use pancurses::Input;
+
+use config;
+use config::{Action, Prefix};
+
+// `DEFAULTS` are the keybindings that are used unless they're overridden by
+// the configuration.
+const DEFAULTS: &[(&str, Action)] = &[
+    ("F12", Action::ToggleBorder),
+    ("^]", Action::PeekPrimary),
+    ("S-PageUp", Action::ScrollPageUp),
+    ("S-PageDown", Action::ScrollPageDown),
+];
+
+// `PREFIX_DEFAULTS` are the keybindings that follow the prefix key that are
+// used unless they're overridden by the configuration.
+const PREFIX_DEFAULTS: &[(&str, Action)] = &[
+    ("b", Action::ToggleBorder),
+    ("]", Action::PeekPrimary),
+    (":", Action::Prompt),
+    ("p", Action::PasteFile),
+    ("(", Action::StartMacro),
+    (")", Action::StopMacro),
+    ("@", Action::PlayMacro),
+    ("P", Action::TogglePassthrough),
+    ("[", Action::ScrollMode),
+    ("/", Action::Search),
+    ("=", Action::PasteClipboard),
+    ("#", Action::PasteHistory),
+    ("C", Action::ClearScrollback),
+    ("K", Action::ClearScreen),
+    ("d", Action::Detach),
+    ("%", Action::SplitRight),
+    ("\"", Action::SplitDown),
+    ("h", Action::SelectPaneLeft),
+    ("j", Action::SelectPaneDown),
+    ("k", Action::SelectPaneUp),
+    ("l", Action::SelectPaneRight),
+    ("o", Action::NextPane),
+    ("x", Action::ClosePane),
+    ("Left", Action::ResizePaneLeft),
+    ("Down", Action::ResizePaneDown),
+    ("Up", Action::ResizePaneUp),
+    ("Right", Action::ResizePaneRight),
+    ("}", Action::SwapPaneNext),
+    ("{", Action::SwapPanePrevious),
+    ("^O", Action::RotatePanes),
+    (" ", Action::NextLayout),
+    ("s", Action::TogglePause),
+    ("S", Action::ToggleSync),
+    ("c", Action::NewTab),
+    ("n", Action::NextTab),
+    ("N", Action::PreviousTab),
+    (",", Action::RenameTab),
+    ("w", Action::ChooseTab),
+    ("\\", Action::Quit),
+];
+
+// `Keybindings` maps keys, and keys that follow the prefix key, to the
+// emulator actions that they trigger. Keys that aren't bound are passed to the
+// child.
+pub struct Keybindings {
+    bindings: Bindings,
+    prefix: Option<Input>,
+    prefix_bindings: Bindings,
+}
+
+impl Keybindings {
+    // `new` returns the default keybindings, overridden by `conf` and
+    // `prefix`, which map key names (see `parse_key`) to actions.
+    pub fn new(conf: &HashMap<String, Action>, prefix: &Prefix) -> Result<Keybindings, String> {
+        let prefix_key =
+            if prefix.key.is_empty() {
+                None
+            } else {
+                match parse_key(&prefix.key) {
+                    Some(key) => Some(key),
+                    None => return Err(invalid_key("prefix.key", &prefix.key)),
+                }
+            };
+
+        Ok(Keybindings{
+            bindings: Bindings::new(DEFAULTS, conf, "keybindings")?,
+            prefix: prefix_key,
+            prefix_bindings: Bindings::new(PREFIX_DEFAULTS, &prefix.bindings, "prefix.bindings")?,
+        })
+    }
+
+    // `action` returns the action that `input` is bound to, if any.
+    pub fn action(&self, input: &Input) -> Option<Action> {
+        self.bindings.action(input)
+    }
+
+    pub fn prefix(&self) -> Option<Input> {
+        self.prefix
+    }
+
+    // `is_prefix` returns `true` if `input` is the prefix key.
+    pub fn is_prefix(&self, input: &Input) -> bool {
+        self.prefix == Some(*input)
+    }
+
+    // `prefix_action` returns the action that `input` is bound to when it
+    // follows the prefix key, if any.
+    pub fn prefix_action(&self, input: &Input) -> Option<Action> {
+        self.prefix_bindings.action(input)
+    }
+
+    // `key_name` returns the name of a key that's bound to `action`, if any.
+    pub fn key_name(&self, action: Action) -> Option<&str> {
+        self.bindings.key_name(action)
+    }
+}
+
+struct Bindings(Vec<(Input, String, Action)>);
+
+impl Bindings {
+    // `new` returns `defaults`, overridden by `conf`. `table` is the name of
+    // the configuration table that `conf` was read from.
+    fn new(defaults: &[(&str, Action)], conf: &HashMap<String, Action>, table: &str)
+        -> Result<Bindings, String>
+    {
+        let mut bindings = Bindings(vec![]);
+        for &(name, action) in defaults {
+            bindings.bind(name, action, table)?;
+        }
+        let mut names: Vec<&String> = conf.keys().collect();
+        // Keys are bound in a fixed order so that errors are reported
+        // consistently.
+        names.sort();
+        for name in names {
+            bindings.bind(name, conf[name], table)?;
+        }
+
+        Ok(bindings)
+    }
+
+    fn bind(&mut self, name: &str, action: Action, table: &str) -> Result<(), String> {
+        let key = match parse_key(name) {
+            Some(key) => key,
+            None => return Err(invalid_key(table, name)),
+        };
+        self.0.retain(|&(k, _, _)| k != key);
+        if action != Action::None {
+            self.0.push((key, name.to_string(), action));
+        }
+
+        Ok(())
+    }
+
+    fn action(&self, input: &Input) -> Option<Action> {
+        self.0.iter()
+            .find(|&&(k, _, _)| k == *input)
+            .map(|&(_, _, action)| action)
+    }
+
+    fn key_name(&self, action: Action) -> Option<&str> {
+        self.0.iter()
+            .find(|&&(_, _, a)| a == action)
+            .map(|&(_, ref name, _)| name.as_str())
+    }
+}
+
+fn invalid_key(field: &str, name: &str) -> String {
+    format!(
+        "invalid key in '{}' (expected caret notation, such as '^B', a function key, such as 'F12', or a named key, such as 'S-PageUp'): {}",
+        field,
+        name,
+    )
+}
+
+// `NAMED_KEYS` are the special keys, other than function keys, that can be
+// bound, where `S-` is the prefix for keys that are pressed with Shift.
+const NAMED_KEYS: &[(&str, Input)] = &[
+    ("Up", Input::KeyUp),
+    ("Down", Input::KeyDown),
+    ("Left", Input::KeyLeft),
+    ("Right", Input::KeyRight),
+    ("Home", Input::KeyHome),
+    ("End", Input::KeyEnd),
+    ("PageUp", Input::KeyPPage),
+    ("PageDown", Input::KeyNPage),
+    ("Insert", Input::KeyIC),
+    ("Delete", Input::KeyDC),
+    ("S-Up", Input::KeySR),
+    ("S-Down", Input::KeySF),
+    ("S-Left", Input::KeySLeft),
+    ("S-Right", Input::KeySRight),
+    ("S-Home", Input::KeySHome),
+    ("S-End", Input::KeySEnd),
+    ("S-PageUp", Input::KeySPrevious),
+    ("S-PageDown", Input::KeySNext),
+];
+
+// `parse_key` parses a key in caret notation (such as `^B`), a function key
+// (such as `F12`), a named key (see `NAMED_KEYS`) or a single character.
+pub fn parse_key(s: &str) -> Option<Input> {
+    if let Some(c) = config::parse_caret(s) {
+        return Some(Input::Character(c));
+    }
+
+    if let Some(&(_, key)) = NAMED_KEYS.iter().find(|&&(name, _)| name == s) {
+        return Some(key);
+    }
+
+    let fkeys = [
+        Input::KeyF1, Input::KeyF2, Input::KeyF3, Input::KeyF4,
+        Input::KeyF5, Input::KeyF6, Input::KeyF7, Input::KeyF8,
+        Input::KeyF9, Input::KeyF10, Input::KeyF11, Input::KeyF12,
+    ];
+    if s.starts_with('F') {
+        if let Ok(n) = s[1..].parse::<usize>() {
+            if n >= 1 && n <= fkeys.len() {
+                return Some(fkeys[n - 1]);
+            }
+        }
+    }
+
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(Input::Character(c)),
+        _ => None,
+    }
+}
@@ -0,0 +1,635 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+
+// `PaneId` identifies a pane within a `Layout`.
+pub type PaneId = usize;
+
+// `Direction` is the direction that the panes of a split are arranged in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    // `Horizontal` arranges panes side by side.
+    Horizontal,
+    // `Vertical` arranges panes one above the other.
+    Vertical,
+}
+
+// `Side` is a side of a pane, which is used to find the pane next to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+// `Preset` is a built-in arrangement of panes, in the same way as the layouts
+// of `tmux`'s `select-layout`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preset {
+    // `EvenHorizontal` arranges the panes side by side, with equal widths.
+    EvenHorizontal,
+    // `EvenVertical` arranges the panes one above the other, with equal
+    // heights.
+    EvenVertical,
+    // `MainVertical` puts the first pane on the left half of the window, and
+    // arranges the other panes one above the other on the right half.
+    MainVertical,
+    // `Tiled` arranges the panes in a grid of rows with equal heights, where
+    // each row has the same number of panes, other than the last.
+    Tiled,
+}
+
+// `PRESETS` are the presets in the order that they're cycled through, along
+// with their names.
+pub const PRESETS: &[(&str, Preset)] = &[
+    ("even-horizontal", Preset::EvenHorizontal),
+    ("even-vertical", Preset::EvenVertical),
+    ("main-vertical", Preset::MainVertical),
+    ("tiled", Preset::Tiled),
+];
+
+impl Preset {
+    pub fn from_name(name: &str) -> Option<Preset> {
+        PRESETS.iter().find(|&&(n, _)| n == name).map(|&(_, preset)| preset)
+    }
+}
+
+// `Rect` is the area of the window that a pane is drawn in, including its
+// border. The borders of neighbouring panes overlap, so that a single line
+// divides them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub y: i32,
+    pub x: i32,
+    pub lines: i32,
+    pub cols: i32,
+}
+
+impl Rect {
+    // `contains` returns `true` if `(y, x)` is inside the border of the rect.
+    pub fn contains(&self, y: i32, x: i32) -> bool {
+        y > self.y && y < self.y + self.lines - 1 && x > self.x && x < self.x + self.cols - 1
+    }
+}
+
+// `Node` is a node of the tree of splits. The children of a split are paired
+// with their weights, which are their sizes in the direction of the split,
+// relative to the sizes of the other children.
+enum Node {
+    Pane(PaneId),
+    Split(Direction, Vec<(Node, usize)>),
+}
+
+// `Layout` arranges panes in a window as a tree of splits, in the same way as
+// `tmux`. Splitting a pane in the direction of the split that it's already
+// part of adds a pane to that split, rather than nesting a new split in it.
+pub struct Layout {
+    root: Node,
+    // `preset` is the preset that was last applied, which is used to choose
+    // the next preset to cycle to.
+    preset: Option<Preset>,
+}
+
+impl Layout {
+    pub fn new(pane: PaneId) -> Layout {
+        Layout{root: Node::Pane(pane), preset: None}
+    }
+
+    // `with_preset` returns a layout that arranges `panes`, of which there must
+    // be at least one, using `preset`.
+    pub fn with_preset(panes: &[PaneId], preset: Preset) -> Layout {
+        Layout{root: arrange(panes, preset), preset: Some(preset)}
+    }
+
+    // `apply` arranges the panes of the layout using `preset`, keeping their
+    // order.
+    pub fn apply(&mut self, preset: Preset) {
+        self.root = arrange(&self.panes(), preset);
+        self.preset = Some(preset);
+    }
+
+    // `cycle` applies the preset after the one that was last applied, and
+    // returns it.
+    pub fn cycle(&mut self) -> Preset {
+        let i = self.preset.and_then(|p| PRESETS.iter().position(|&(_, preset)| preset == p));
+        let (_, preset) = PRESETS[i.map_or(0, |i| (i + 1) % PRESETS.len())];
+        self.apply(preset);
+
+        preset
+    }
+
+    // `panes` returns the panes of the layout, from left to right and top to
+    // bottom.
+    pub fn panes(&self) -> Vec<PaneId> {
+        let mut panes = vec![];
+        collect(&self.root, &mut panes);
+
+        panes
+    }
+
+    // `swap` exchanges the positions of `a` and `b`, and returns `false` if
+    // either isn't in the layout.
+    pub fn swap(&mut self, a: PaneId, b: PaneId) -> bool {
+        let mut panes = self.panes();
+        match (panes.iter().position(|&id| id == a), panes.iter().position(|&id| id == b)) {
+            (Some(i), Some(j)) => panes.swap(i, j),
+            _ => return false,
+        }
+        assign(&mut self.root, &mut panes.into_iter());
+
+        true
+    }
+
+    // `rotate` moves each pane to the position of the pane after it, and the
+    // last pane to the position of the first, or the other way around if
+    // `reverse` is `true`.
+    pub fn rotate(&mut self, reverse: bool) {
+        let mut panes = self.panes();
+        if reverse {
+            panes.rotate_left(1);
+        } else {
+            panes.rotate_right(1);
+        }
+        assign(&mut self.root, &mut panes.into_iter());
+    }
+
+    // `rects` returns the area of each pane when the layout fills `area`.
+    pub fn rects(&self, area: Rect) -> Vec<(PaneId, Rect)> {
+        let mut rects = vec![];
+        place(&self.root, area, &mut rects);
+
+        rects
+    }
+
+    // `split` splits `pane` in `direction`, adding `new_pane` after it, and
+    // returns `false` if `pane` is too small to be split when the layout fills
+    // `area`.
+    pub fn split(&mut self, pane: PaneId, new_pane: PaneId, direction: Direction, area: Rect) -> bool {
+        let rect =
+            match self.rects(area).into_iter().find(|&(id, _)| id == pane) {
+                Some((_, rect)) => rect,
+                None => return false,
+            };
+        // Each half needs at least one line or column inside its border, and
+        // the halves share a border.
+        let size = if direction == Direction::Horizontal { rect.cols } else { rect.lines };
+        if size < 5 {
+            return false;
+        }
+
+        split(&mut self.root, pane, new_pane, direction)
+    }
+
+    // `remove` removes `pane` from the layout, giving its space to its
+    // neighbours, and returns `false` if it's the last pane.
+    pub fn remove(&mut self, pane: PaneId) -> bool {
+        if let Node::Pane(_) = self.root {
+            return false;
+        }
+        remove(&mut self.root, pane);
+
+        true
+    }
+
+    // `resize` moves a border of `pane` by `cells` cells towards `side`, when
+    // the layout fills `area`, and returns `false` if no border could be
+    // moved. The border after `pane` in the direction of `side` is moved if
+    // there is one, and the border before it otherwise, so that the same keys
+    // move the same border.
+    pub fn resize(&mut self, pane: PaneId, side: Side, cells: i32, area: Rect) -> bool {
+        resize(&mut self.root, pane, side, cells, area)
+    }
+
+    // `drag` moves the border between panes that's at `from` to `to`, when
+    // the layout fills `area`, and returns `false` if there's no such border
+    // at `from`, or if it couldn't be moved.
+    pub fn drag(&mut self, from: (i32, i32), to: (i32, i32), area: Rect) -> bool {
+        drag(&mut self.root, from, to, area)
+    }
+
+    // `neighbour` returns the pane that's next to `side` of `pane` when the
+    // layout fills `area`. If several panes are next to it then the one that
+    // overlaps it the most is returned.
+    pub fn neighbour(&self, pane: PaneId, side: Side, area: Rect) -> Option<PaneId> {
+        let rects = self.rects(area);
+        let from = rects.iter().find(|&&(id, _)| id == pane)?.1;
+
+        let overlap = |a: i32, a_len: i32, b: i32, b_len: i32| cmp::min(a + a_len, b + b_len) - cmp::max(a, b);
+        rects.iter()
+            .filter(|&&(id, _)| id != pane)
+            .filter_map(|&(id, r)| {
+                let (adjacent, shared) =
+                    match side {
+                        Side::Left => (r.x + r.cols - 1 == from.x, overlap(r.y, r.lines, from.y, from.lines)),
+                        Side::Right => (from.x + from.cols - 1 == r.x, overlap(r.y, r.lines, from.y, from.lines)),
+                        Side::Up => (r.y + r.lines - 1 == from.y, overlap(r.x, r.cols, from.x, from.cols)),
+                        Side::Down => (from.y + from.lines - 1 == r.y, overlap(r.x, r.cols, from.x, from.cols)),
+                    };
+                // Panes that only share a corner overlap by a single cell.
+                if adjacent && shared > 1 {
+                    Some((id, shared))
+                } else {
+                    None
+                }
+            })
+            .max_by_key(|&(_, shared)| shared)
+            .map(|(id, _)| id)
+    }
+}
+
+// `arrange` returns the tree of splits that arranges `panes`, of which there
+// must be at least one, using `preset`.
+fn arrange(panes: &[PaneId], preset: Preset) -> Node {
+    match preset {
+        Preset::EvenHorizontal => even(Direction::Horizontal, panes),
+        Preset::EvenVertical => even(Direction::Vertical, panes),
+        Preset::MainVertical => {
+            match panes.split_first() {
+                Some((&main, rest)) if !rest.is_empty() => {
+                    Node::Split(Direction::Horizontal, vec![
+                        (Node::Pane(main), 1),
+                        (even(Direction::Vertical, rest), 1),
+                    ])
+                },
+                _ => even(Direction::Vertical, panes),
+            }
+        },
+        Preset::Tiled => {
+            // The grid has at least as many columns as rows, so that the panes
+            // are as close to square as possible.
+            let mut cols = 1;
+            while cols * cols < panes.len() {
+                cols += 1;
+            }
+            let mut rows: Vec<Node> = panes.chunks(cols).map(|row| even(Direction::Horizontal, row)).collect();
+            if rows.len() == 1 {
+                rows.remove(0)
+            } else {
+                Node::Split(Direction::Vertical, rows.into_iter().map(|row| (row, 1)).collect())
+            }
+        },
+    }
+}
+
+// `even` returns a split of `panes` in `direction` with equal weights, or the
+// pane if there's only one.
+fn even(direction: Direction, panes: &[PaneId]) -> Node {
+    if panes.len() == 1 {
+        return Node::Pane(panes[0]);
+    }
+
+    Node::Split(direction, panes.iter().map(|&id| (Node::Pane(id), 1)).collect())
+}
+
+fn collect(node: &Node, panes: &mut Vec<PaneId>) {
+    match *node {
+        Node::Pane(id) => panes.push(id),
+        Node::Split(_, ref children) => {
+            for &(ref child, _) in children {
+                collect(child, panes);
+            }
+        },
+    }
+}
+
+// `assign` replaces the panes of `node`, in the order that they're returned by
+// `collect`, with `ids`.
+fn assign<I: Iterator<Item = PaneId>>(node: &mut Node, ids: &mut I) {
+    match *node {
+        Node::Pane(ref mut id) => {
+            if let Some(new_id) = ids.next() {
+                *id = new_id;
+            }
+        },
+        Node::Split(_, ref mut children) => {
+            for &mut (ref mut child, _) in children.iter_mut() {
+                assign(child, ids);
+            }
+        },
+    }
+}
+
+fn place(node: &Node, area: Rect, rects: &mut Vec<(PaneId, Rect)>) {
+    match *node {
+        Node::Pane(id) => rects.push((id, area)),
+        Node::Split(direction, ref children) => {
+            for (&(ref child, _), child_area) in children.iter().zip(child_areas(direction, children, area)) {
+                place(child, child_area, rects);
+            }
+        },
+    }
+}
+
+// `child_areas` returns the areas of the children of a split in `direction`
+// that fills `area`.
+fn child_areas(direction: Direction, children: &[(Node, usize)], area: Rect) -> Vec<Rect> {
+    // The space inside the borders of the children is shared between them in
+    // proportion to their weights. Neighbouring children share a border, so
+    // `n` children have `n + 1` borders between them.
+    let n = children.len() as i32;
+    let size = if direction == Direction::Horizontal { area.cols } else { area.lines };
+    let inner = cmp::max(size - (n + 1), 0);
+    let total: usize = children.iter().map(|&(_, weight)| weight).sum();
+    let mut offset = 0;
+    let mut used = 0;
+    let mut areas = vec![];
+    for (i, &(_, weight)) in children.iter().enumerate() {
+        // The last child takes the space that's left over by rounding. Each
+        // other child gets at least one cell if there's one left, so that the
+        // children never take more space than there is.
+        let child_inner =
+            if i as i32 == n - 1 {
+                inner - used
+            } else {
+                let share = cmp::max((inner as usize * weight / cmp::max(total, 1)) as i32, 1);
+                cmp::min(share, inner - used)
+            };
+        used += child_inner;
+        let child_area =
+            if direction == Direction::Horizontal {
+                Rect{x: area.x + offset, cols: child_inner + 2, ..area}
+            } else {
+                Rect{y: area.y + offset, lines: child_inner + 2, ..area}
+            };
+        areas.push(child_area);
+        offset += child_inner + 1;
+    }
+
+    areas
+}
+
+fn contains(node: &Node, pane: PaneId) -> bool {
+    match *node {
+        Node::Pane(id) => id == pane,
+        Node::Split(_, ref children) => children.iter().any(|&(ref child, _)| contains(child, pane)),
+    }
+}
+
+// `min_size` returns the smallest size, inside its border, that `node` can
+// have in `direction`.
+fn min_size(node: &Node, direction: Direction) -> i32 {
+    match *node {
+        Node::Pane(_) => 1,
+        Node::Split(d, ref children) => {
+            let sizes = children.iter().map(|&(ref child, _)| min_size(child, direction));
+            if d == direction {
+                // Neighbouring children share a border.
+                sizes.sum::<i32>() + children.len() as i32 - 1
+            } else {
+                sizes.max().unwrap_or(1)
+            }
+        },
+    }
+}
+
+// `move_border` moves the border after the `j`th child of a split in
+// `direction` that fills `area` by `delta` cells, as far as the children on
+// either side of it can be resized, and returns `false` if it couldn't be
+// moved.
+fn move_border(direction: Direction, children: &mut [(Node, usize)], j: usize, delta: i32, area: Rect) -> bool {
+    let mut sizes: Vec<i32> = child_areas(direction, children, area)
+        .iter()
+        .map(|r| if direction == Direction::Horizontal { r.cols - 2 } else { r.lines - 2 })
+        .collect();
+    let delta =
+        if delta > 0 {
+            cmp::min(delta, cmp::max(sizes[j + 1] - min_size(&children[j + 1].0, direction), 0))
+        } else {
+            cmp::max(delta, cmp::min(min_size(&children[j].0, direction) - sizes[j], 0))
+        };
+    if delta == 0 {
+        return false;
+    }
+    sizes[j] += delta;
+    sizes[j + 1] -= delta;
+
+    // The weights are replaced by the sizes of the children, so that the
+    // children keep their sizes until the window is resized.
+    for (&mut (_, ref mut weight), size) in children.iter_mut().zip(sizes) {
+        *weight = cmp::max(size, 0) as usize;
+    }
+
+    true
+}
+
+fn resize(node: &mut Node, pane: PaneId, side: Side, cells: i32, area: Rect) -> bool {
+    let (direction, children) =
+        match *node {
+            Node::Split(d, ref mut children) => (d, children),
+            Node::Pane(_) => return false,
+        };
+    let i =
+        match children.iter().position(|&(ref child, _)| contains(child, pane)) {
+            Some(i) => i,
+            None => return false,
+        };
+
+    // The innermost split that can be resized is resized.
+    let areas = child_areas(direction, children, area);
+    if resize(&mut children[i].0, pane, side, cells, areas[i]) {
+        return true;
+    }
+
+    let horizontal = side == Side::Left || side == Side::Right;
+    if horizontal != (direction == Direction::Horizontal) || children.len() < 2 {
+        return false;
+    }
+    let j = if i + 1 < children.len() { i } else { i - 1 };
+    let delta = if side == Side::Left || side == Side::Up { -cells } else { cells };
+
+    move_border(direction, children, j, delta, area)
+}
+
+fn drag(node: &mut Node, from: (i32, i32), to: (i32, i32), area: Rect) -> bool {
+    let (direction, children) =
+        match *node {
+            Node::Split(d, ref mut children) => (d, children),
+            Node::Pane(_) => return false,
+        };
+    let (y, x) = from;
+
+    // The border after each child is the first line or column of the child
+    // after it. The borders of this split are checked before those of its
+    // children, as the ends of the borders of its children are on its
+    // borders.
+    let areas = child_areas(direction, children, area);
+    for j in 0..areas.len() - 1 {
+        let next = areas[j + 1];
+        let (on_border, delta) =
+            if direction == Direction::Horizontal {
+                (x == next.x && y >= area.y && y < area.y + area.lines, to.1 - x)
+            } else {
+                (y == next.y && x >= area.x && x < area.x + area.cols, to.0 - y)
+            };
+        if on_border {
+            return move_border(direction, children, j, delta, area);
+        }
+    }
+
+    let i = areas.iter().position(|r| y >= r.y && y < r.y + r.lines && x >= r.x && x < r.x + r.cols);
+    match i {
+        Some(i) => drag(&mut children[i].0, from, to, areas[i]),
+        None => false,
+    }
+}
+
+fn split(node: &mut Node, pane: PaneId, new_pane: PaneId, direction: Direction) -> bool {
+    if let Node::Pane(id) = *node {
+        if id != pane {
+            return false;
+        }
+        let old = Node::Pane(id);
+        *node = Node::Split(direction, vec![(old, 1), (Node::Pane(new_pane), 1)]);
+        return true;
+    }
+
+    let (split_direction, children) =
+        match *node {
+            Node::Split(d, ref mut children) => (d, children),
+            Node::Pane(_) => return false,
+        };
+    // A pane that's a direct child of a split in the same direction is split
+    // by halving its weight and adding the new pane after it.
+    if split_direction == direction {
+        let i = children.iter().position(|&(ref child, _)| {
+            if let Node::Pane(id) = *child { id == pane } else { false }
+        });
+        if let Some(i) = i {
+            // Weights are doubled first, so that odd weights can be halved.
+            for &mut (_, ref mut weight) in children.iter_mut() {
+                *weight *= 2;
+            }
+            let half = children[i].1 / 2;
+            children[i].1 = half;
+            children.insert(i + 1, (Node::Pane(new_pane), half));
+            return true;
+        }
+    }
+
+    children.iter_mut().any(|&mut (ref mut child, _)| split(child, pane, new_pane, direction))
+}
+
+fn remove(node: &mut Node, pane: PaneId) {
+    let children =
+        match *node {
+            Node::Split(_, ref mut children) => children,
+            Node::Pane(_) => return,
+        };
+
+    let i = children.iter().position(|&(ref child, _)| {
+        if let Node::Pane(id) = *child { id == pane } else { false }
+    });
+    match i {
+        // The pane's weight is given to the pane before it, or to the pane
+        // after it if it's the first pane.
+        Some(i) => {
+            let (_, weight) = children.remove(i);
+            let j = if i > 0 { i - 1 } else { 0 };
+            children[j].1 += weight;
+        },
+        None => {
+            for &mut (ref mut child, _) in children.iter_mut() {
+                remove(child, pane);
+            }
+        },
+    }
+
+    // Splits that are left with a single child are replaced by the child.
+    for &mut (ref mut child, _) in children.iter_mut() {
+        let lone =
+            match *child {
+                Node::Split(_, ref mut grandchildren) if grandchildren.len() == 1 => grandchildren.pop(),
+                _ => None,
+            };
+        if let Some((grandchild, _)) = lone {
+            *child = grandchild;
+        }
+    }
+    if children.len() == 1 {
+        if let Some((only, _)) = children.pop() {
+            *node = only;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(lines: i32, cols: i32) -> Rect {
+        Rect{y: 0, x: 0, lines: lines, cols: cols}
+    }
+
+    fn panes(n: usize) -> Vec<(Node, usize)> {
+        (0..n).map(|id| (Node::Pane(id), 1)).collect()
+    }
+
+    #[test]
+    fn rects_of_single_pane_fill_area() {
+        let layout = Layout::new(7);
+
+        assert_eq!(layout.rects(area(1, 1)), vec![(7, area(1, 1))]);
+    }
+
+    #[test]
+    fn child_areas_share_borders_and_fill_area() {
+        let areas = child_areas(Direction::Horizontal, &panes(2), area(3, 6));
+
+        assert_eq!(areas, vec![
+            Rect{y: 0, x: 0, lines: 3, cols: 3},
+            Rect{y: 0, x: 2, lines: 3, cols: 4},
+        ]);
+    }
+
+    #[test]
+    fn child_areas_with_room_for_only_borders() {
+        let areas = child_areas(Direction::Vertical, &panes(3), area(4, 3));
+
+        let ys: Vec<i32> = areas.iter().map(|a| a.y).collect();
+        assert_eq!(ys, vec![0, 1, 2]);
+        assert!(areas.iter().all(|a| a.lines == 2 && a.cols == 3));
+    }
+
+    #[test]
+    fn child_areas_of_tiny_area_are_never_negative() {
+        for size in 0..6 {
+            for n in 1..5 {
+                let areas = child_areas(Direction::Horizontal, &panes(n), area(size, size));
+
+                assert_eq!(areas.len(), n);
+                for a in &areas {
+                    assert!(a.cols >= 2, "{} panes in {} columns: {:?}", n, size, areas);
+                    assert_eq!(a.lines, size);
+                }
+                let end = areas[n - 1].x + areas[n - 1].cols;
+                assert_eq!(end, cmp::max(size, n as i32 + 1));
+            }
+        }
+    }
+
+    #[test]
+    fn split_needs_room_for_both_halves() {
+        let mut layout = Layout::new(0);
+
+        assert!(!layout.split(0, 1, Direction::Horizontal, area(3, 4)));
+        assert_eq!(layout.panes(), vec![0]);
+        assert!(layout.split(0, 1, Direction::Horizontal, area(3, 5)));
+        assert_eq!(layout.rects(area(3, 5)), vec![
+            (0, Rect{y: 0, x: 0, lines: 3, cols: 3}),
+            (1, Rect{y: 0, x: 2, lines: 3, cols: 3}),
+        ]);
+    }
+
+    #[test]
+    fn rects_of_tiled_panes_in_tiny_area() {
+        let layout = Layout::with_preset(&[0, 1, 2, 3], Preset::Tiled);
+
+        let rects = layout.rects(area(3, 3));
+
+        assert_eq!(rects.iter().map(|&(id, _)| id).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert!(rects.iter().all(|&(_, r)| r.lines >= 2 && r.cols >= 2));
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+// `tep` is a terminal emulator that runs inside another terminal. `event_loop`
+// runs the `tep` binary, and `TermEmu` lets other programs embed a terminal in
+// their own curses interface.
+//
+// Without the `curses` feature, only `TermEmu::detached` and the renderers that
+// don't use curses are provided, and the parts of the emulator that only the
+// event loop uses are still built, but aren't used.
+#![cfg_attr(not(feature = "curses"), allow(dead_code))]
+
+extern crate alacritty;
+extern crate base64;
+#[cfg(feature = "curses")]
+extern crate clap;
+#[cfg(feature = "crossterm")]
+extern crate crossterm;
+extern crate libc;
+#[cfg(feature = "curses")]
+extern crate pancurses;
+extern crate regex;
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "termion")]
+extern crate termion;
+extern crate toml;
+#[cfg(feature = "tui")]
+extern crate tui;
+extern crate unicode_segmentation;
+extern crate unicode_width;
+extern crate vte;
+
+mod acs;
+pub mod ansi_renderer;
+mod altscreen;
+#[cfg(feature = "curses")]
+mod args;
+#[cfg(feature = "curses")]
+mod backend;
+mod child;
+mod clipboard;
+mod colour;
+mod config;
+#[cfg(feature = "curses")]
+mod copymode;
+#[cfg(feature = "crossterm")]
+pub mod crossterm_renderer;
+#[cfg(feature = "curses")]
+mod curses_renderer;
+#[cfg(feature = "curses")]
+pub mod event_loop;
+mod extras;
+mod focus;
+mod grapheme;
+mod history;
+pub mod input;
+#[cfg(feature = "curses")]
+mod keybindings;
+mod layout;
+mod macros;
+mod mouse;
+mod outbox;
+#[cfg(feature = "curses")]
+mod pairs;
+mod palette;
+mod pane;
+mod paste;
+#[cfg(feature = "curses")]
+mod picker;
+#[cfg(feature = "curses")]
+mod prompt;
+pub mod pty;
+mod quantise;
+mod record;
+mod reflow;
+pub mod render;
+pub mod renderer;
+mod restart;
+mod scrollback;
+mod search;
+mod selection;
+mod serial;
+mod session;
+mod ssh;
+mod state;
+mod tab;
+mod tap;
+pub mod term;
+mod terminfo;
+#[cfg(feature = "termion")]
+pub mod termion_renderer;
+#[cfg(feature = "curses")]
+mod tmux;
+#[cfg(feature = "tui")]
+pub mod tui_widget;
+#[cfg(feature = "utmp")]
+mod utmp;
+
+pub use layout::Rect;
+pub use term::TermEmu;
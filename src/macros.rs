@@ -0,0 +1,99 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::fs;
+use std::path::PathBuf;
+
+use config;
+
+// `Macros` records the bytes that are written to the child for the keys that
+// the user types, so that they can be replayed later. Recorded macros can be
+// saved under a name, in `dir`, so that they can be replayed in later
+// sessions.
+pub struct Macros {
+    dir: Option<PathBuf>,
+    recording: Option<Vec<u8>>,
+    last: Vec<u8>,
+}
+
+impl Macros {
+    pub fn new() -> Macros {
+        Macros{
+            dir: default_dir(),
+            recording: None,
+            last: vec![],
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // `start` starts recording a new macro, discarding the current recording,
+    // if any.
+    pub fn start(&mut self) {
+        self.recording = Some(vec![]);
+    }
+
+    // `stop` stops recording, and makes the recorded macro the last macro.
+    pub fn stop(&mut self) {
+        if let Some(bytes) = self.recording.take() {
+            self.last = bytes;
+        }
+    }
+
+    // `record` adds `bytes` to the current recording, if any.
+    pub fn record(&mut self, bytes: &[u8]) {
+        if let Some(ref mut recording) = self.recording {
+            recording.extend_from_slice(bytes);
+        }
+    }
+
+    // `last` returns the last macro that was recorded or loaded.
+    pub fn last(&self) -> &[u8] {
+        &self.last
+    }
+
+    // `save` saves the last macro as `name`.
+    pub fn save(&self, name: &str) -> Result<(), String> {
+        let path = self.path(name)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("couldn't create '{}': {}", dir.display(), e))?;
+        }
+
+        fs::write(&path, &self.last)
+            .map_err(|e| format!("couldn't write '{}': {}", path.display(), e))
+    }
+
+    // `load` loads the macro saved as `name`, and makes it the last macro.
+    pub fn load(&mut self, name: &str) -> Result<&[u8], String> {
+        let path = self.path(name)?;
+        let bytes = fs::read(&path)
+            .map_err(|e| format!("couldn't read '{}': {}", path.display(), e))?;
+        self.last = bytes;
+
+        Ok(&self.last)
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf, String> {
+        // Names are restricted so that macros can't be saved outside of
+        // `dir`.
+        let valid = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(format!("invalid macro name (expected letters, digits, '-' or '_'): {}", name));
+        }
+
+        match self.dir {
+            Some(ref dir) => Ok(dir.join(name)),
+            None => Err("couldn't find the macro directory ($HOME isn't set)".to_string()),
+        }
+    }
+}
+
+// `default_dir` returns the directory that macros are saved in, which is
+// `macros` in `config::dir`.
+fn default_dir() -> Option<PathBuf> {
+    Some(config::dir()?.join("macros"))
+}
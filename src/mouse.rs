@@ -0,0 +1,424 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::char;
+
+#[cfg(feature = "curses")]
+use pancurses;
+#[cfg(feature = "curses")]
+use pancurses::{mmask_t, MEVENT};
+
+use config;
+
+// `Tracking` is the mouse tracking mode that the child has enabled, which
+// determines the mouse events that are reported to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tracking {
+    Off,
+    // `X10` (mode 9) only reports button presses.
+    X10,
+    // `Normal` (mode 1000) reports button presses and releases.
+    Normal,
+    // `ButtonEvent` (mode 1002) also reports motion while a button is held.
+    ButtonEvent,
+    // `AnyEvent` (mode 1003) also reports all motion.
+    AnyEvent,
+}
+
+// `Encoding` is the format that mouse events are reported in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    // `Default` encodes each value as a single byte, so coordinates above
+    // 223 can't be reported.
+    Default,
+    // `Utf8` (mode 1005) encodes each value as a UTF-8 character.
+    Utf8,
+    // `Sgr` (mode 1006) encodes values as decimal parameters.
+    Sgr,
+    // `Urxvt` (mode 1015) encodes values as decimal parameters, but encodes
+    // the button in the same way as `Default`.
+    Urxvt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    Press,
+    Release,
+    Motion,
+}
+
+const WHEEL_UP: u8 = 64;
+const WHEEL_DOWN: u8 = 65;
+const NO_BUTTON: u8 = 3;
+const MOTION: u8 = 32;
+
+// `Mouse` tracks the mouse modes that the child has enabled, and translates
+// mouse events from curses into reports for the child. Without curses, only
+// the modes are tracked.
+pub struct Mouse {
+    tracking: Tracking,
+    encoding: Encoding,
+    // `held` is the button that's currently held down, if any.
+    held: Option<u8>,
+    // `capture_mask` holds the events that are captured when the child
+    // hasn't enabled mouse tracking.
+    #[cfg(feature = "curses")]
+    capture_mask: mmask_t,
+}
+
+impl Mouse {
+    #[cfg_attr(not(feature = "curses"), allow(unused_variables))]
+    pub fn new(conf: &config::Mouse) -> Mouse {
+        Mouse{
+            tracking: Tracking::Off,
+            encoding: Encoding::Default,
+            held: None,
+            #[cfg(feature = "curses")]
+            capture_mask: capture_mask(conf),
+        }
+    }
+
+    pub fn tracking(&self) -> Tracking {
+        self.tracking
+    }
+
+    // `set_mode` handles the DECSET (if `set` is `true`) or DECRST of `mode`,
+    // and returns `true` if the tracking mode changed.
+    pub fn set_mode(&mut self, mode: i64, set: bool) -> bool {
+        let prev = self.tracking;
+        let tracking =
+            match mode {
+                9 => Tracking::X10,
+                1000 => Tracking::Normal,
+                1002 => Tracking::ButtonEvent,
+                1003 => Tracking::AnyEvent,
+                1005 | 1006 | 1015 => {
+                    let encoding =
+                        match mode {
+                            1005 => Encoding::Utf8,
+                            1006 => Encoding::Sgr,
+                            _ => Encoding::Urxvt,
+                        };
+                    if set {
+                        self.encoding = encoding;
+                    } else if self.encoding == encoding {
+                        self.encoding = Encoding::Default;
+                    }
+                    return false;
+                },
+                _ => return false,
+            };
+
+        if set {
+            self.tracking = tracking;
+        } else if self.tracking == tracking {
+            self.tracking = Tracking::Off;
+        }
+        self.held = None;
+
+        self.tracking != prev
+    }
+
+    // `mask` returns the curses mouse mask that should be used for the current
+    // tracking mode. Only the events in `capture_mask` are captured if the
+    // child hasn't enabled mouse tracking, so that the host can use the other
+    // events.
+    #[cfg(feature = "curses")]
+    pub fn mask(&self) -> mmask_t {
+        match self.tracking {
+            Tracking::Off => self.capture_mask,
+            Tracking::X10 | Tracking::Normal => pancurses::ALL_MOUSE_EVENTS,
+            Tracking::ButtonEvent | Tracking::AnyEvent => {
+                pancurses::ALL_MOUSE_EVENTS | pancurses::REPORT_MOUSE_POSITION
+            },
+        }
+    }
+
+    // `host_motion` returns the sequence that should be written to the host
+    // so that it reports motion events when the child wants them. curses only
+    // enables the reporting of button events on most hosts.
+    pub fn host_motion(&self) -> &'static str {
+        match self.tracking {
+            Tracking::ButtonEvent => "\x1b[?1002h",
+            Tracking::AnyEvent => "\x1b[?1003h",
+            _ => "\x1b[?1003l\x1b[?1002l",
+        }
+    }
+
+    // `report` returns the reports of `evt`, which happened at `(line, col)`
+    // of the terminal, for the child.
+    #[cfg(feature = "curses")]
+    pub fn report(&mut self, evt: &MEVENT, line: usize, col: usize) -> Vec<u8> {
+        let mut out = vec![];
+        if self.tracking == Tracking::Off {
+            return out;
+        }
+
+        let mut mods: u8 = 0;
+        if self.tracking != Tracking::X10 {
+            let mappings = [
+                (pancurses::BUTTON_SHIFT, 4),
+                (pancurses::BUTTON_ALT, 8),
+                (pancurses::BUTTON_CTRL, 16),
+            ];
+            for &(mask, m) in mappings.iter() {
+                if evt.bstate & mask != 0 {
+                    mods |= m;
+                }
+            }
+        }
+
+        for (button, action) in actions(evt.bstate) {
+            let code =
+                match action {
+                    Action::Press => {
+                        if button != WHEEL_UP && button != WHEEL_DOWN {
+                            self.held = Some(button);
+                        } else if self.tracking == Tracking::X10 {
+                            continue;
+                        }
+                        button
+                    },
+                    Action::Release => {
+                        if self.tracking == Tracking::X10 {
+                            continue;
+                        }
+                        self.held = None;
+                        button
+                    },
+                    Action::Motion => {
+                        match (self.tracking, self.held) {
+                            (Tracking::AnyEvent, None) => NO_BUTTON + MOTION,
+                            (Tracking::AnyEvent, Some(b)) |
+                            (Tracking::ButtonEvent, Some(b)) => b + MOTION,
+                            _ => continue,
+                        }
+                    },
+                };
+            out.extend(self.encode(code | mods, action == Action::Release, line, col));
+        }
+
+        out
+    }
+
+    fn encode(&self, code: u8, release: bool, line: usize, col: usize) -> Vec<u8> {
+        let (x, y) = (col + 1, line + 1);
+        // Releases are reported as button `3` by all encodings other than
+        // `Encoding::Sgr`, which reports which button was released.
+        let legacy_code = if release { NO_BUTTON | (code & !0b11) } else { code };
+
+        match self.encoding {
+            Encoding::Sgr => {
+                let kind = if release { 'm' } else { 'M' };
+
+                format!("\x1b[<{};{};{}{}", code, x, y, kind).into_bytes()
+            },
+            Encoding::Urxvt => {
+                format!("\x1b[{};{};{}M", legacy_code as usize + 32, x, y).into_bytes()
+            },
+            Encoding::Utf8 => {
+                let mut s = String::from("\x1b[M");
+                for &v in &[legacy_code as usize + 32, x + 32, y + 32] {
+                    match char::from_u32(v as u32) {
+                        Some(c) => s.push(c),
+                        None => return vec![],
+                    }
+                }
+
+                s.into_bytes()
+            },
+            Encoding::Default => {
+                if x + 32 > 0xff || y + 32 > 0xff {
+                    return vec![];
+                }
+
+                vec![0x1b, b'[', b'M', legacy_code + 32, (x + 32) as u8, (y + 32) as u8]
+            },
+        }
+    }
+}
+
+// `capture_mask` returns the mouse events that `conf` enables when the child
+// hasn't enabled mouse tracking.
+#[cfg(feature = "curses")]
+fn capture_mask(conf: &config::Mouse) -> mmask_t {
+    let mut capture_mask = 0;
+    if conf.wheel {
+        capture_mask |= pancurses::BUTTON4_PRESSED | pancurses::BUTTON5_PRESSED;
+    }
+    if conf.middle_click_paste {
+        capture_mask |= pancurses::BUTTON2_PRESSED;
+    }
+    if conf.double_click_select || conf.drag_select {
+        capture_mask |= pancurses::BUTTON1_PRESSED;
+    }
+    if conf.drag_select {
+        capture_mask |= pancurses::BUTTON1_RELEASED;
+    }
+
+    capture_mask
+}
+
+// `wheel_delta` returns `1` if `bstate` describes the wheel being scrolled up,
+// `-1` if it describes the wheel being scrolled down, and `None` otherwise.
+#[cfg(feature = "curses")]
+pub fn wheel_delta(bstate: mmask_t) -> Option<isize> {
+    if bstate & pancurses::BUTTON4_PRESSED != 0 {
+        Some(1)
+    } else if bstate & pancurses::BUTTON5_PRESSED != 0 {
+        Some(-1)
+    } else {
+        None
+    }
+}
+
+// `is_middle_press` returns `true` if `bstate` describes the middle button
+// being pressed.
+#[cfg(feature = "curses")]
+pub fn is_middle_press(bstate: mmask_t) -> bool {
+    bstate & (pancurses::BUTTON2_PRESSED | pancurses::BUTTON2_CLICKED) != 0
+}
+
+// `is_left_press` returns `true` if `bstate` describes the left button being
+// pressed.
+#[cfg(feature = "curses")]
+pub fn is_left_press(bstate: mmask_t) -> bool {
+    bstate & (pancurses::BUTTON1_PRESSED | pancurses::BUTTON1_CLICKED) != 0
+}
+
+// `is_left_release` returns `true` if `bstate` describes the left button being
+// released.
+#[cfg(feature = "curses")]
+pub fn is_left_release(bstate: mmask_t) -> bool {
+    bstate & pancurses::BUTTON1_RELEASED != 0
+}
+
+// `actions` returns the button actions described by `bstate`, in the order
+// that they should be reported.
+#[cfg(feature = "curses")]
+fn actions(bstate: mmask_t) -> Vec<(u8, Action)> {
+    let mut actions = vec![];
+    let buttons = [
+        (pancurses::BUTTON1_PRESSED, pancurses::BUTTON1_RELEASED, pancurses::BUTTON1_CLICKED),
+        (pancurses::BUTTON2_PRESSED, pancurses::BUTTON2_RELEASED, pancurses::BUTTON2_CLICKED),
+        (pancurses::BUTTON3_PRESSED, pancurses::BUTTON3_RELEASED, pancurses::BUTTON3_CLICKED),
+    ];
+    for (i, &(pressed, released, clicked)) in buttons.iter().enumerate() {
+        let button = i as u8;
+        if bstate & (pressed | clicked) != 0 {
+            actions.push((button, Action::Press));
+        }
+        if bstate & (released | clicked) != 0 {
+            actions.push((button, Action::Release));
+        }
+    }
+    if bstate & pancurses::BUTTON4_PRESSED != 0 {
+        actions.push((WHEEL_UP, Action::Press));
+    }
+    if bstate & pancurses::BUTTON5_PRESSED != 0 {
+        actions.push((WHEEL_DOWN, Action::Press));
+    }
+    if bstate & pancurses::REPORT_MOUSE_POSITION != 0 {
+        actions.push((NO_BUTTON, Action::Motion));
+    }
+
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mouse(modes: &[i64]) -> Mouse {
+        let mut mouse = Mouse::new(&config::Mouse::default());
+        for &mode in modes {
+            mouse.set_mode(mode, true);
+        }
+
+        mouse
+    }
+
+    #[test]
+    fn set_mode_tracks_tracking_and_encoding() {
+        let mut mouse = mouse(&[]);
+
+        assert!(mouse.set_mode(1000, true));
+        assert!(!mouse.set_mode(1006, true));
+        assert_eq!(mouse.encoding, Encoding::Sgr);
+        assert!(!mouse.set_mode(1002, false));
+        assert_eq!(mouse.tracking(), Tracking::Normal);
+        assert!(!mouse.set_mode(1005, false));
+        assert_eq!(mouse.encoding, Encoding::Sgr);
+        assert!(mouse.set_mode(1000, false));
+        assert_eq!(mouse.tracking(), Tracking::Off);
+        assert!(!mouse.set_mode(1006, false));
+        assert_eq!(mouse.encoding, Encoding::Default);
+    }
+
+    #[test]
+    fn encode_uses_encoding() {
+        let tests: &[(&[i64], u8, bool, usize, &[u8])] = &[
+            (&[], 0, false, 4, b"\x1b[M %#"),
+            (&[], 0, true, 4, b"\x1b[M#%#"),
+            (&[], 4, true, 4, b"\x1b[M'%#"),
+            // Coordinates above 223 can't be reported in the default encoding.
+            (&[], 0, false, 223, b""),
+            (&[1005], 0, false, 299, "\x1b[M \u{14c}#".as_bytes()),
+            (&[1006], 0, false, 4, b"\x1b[<0;5;3M"),
+            (&[1006], 0, true, 4, b"\x1b[<0;5;3m"),
+            (&[1006], 65, false, 4, b"\x1b[<65;5;3M"),
+            (&[1015], 0, false, 4, b"\x1b[32;5;3M"),
+            (&[1015], 0, true, 4, b"\x1b[35;5;3M"),
+        ];
+
+        for &(modes, code, release, col, expected) in tests {
+            assert_eq!(
+                mouse(modes).encode(code, release, 2, col),
+                expected.to_vec(),
+                "{:?} {} {} {}",
+                modes,
+                code,
+                release,
+                col,
+            );
+        }
+    }
+
+    #[cfg(feature = "curses")]
+    fn event(bstate: mmask_t) -> MEVENT {
+        MEVENT{id: 0, x: 0, y: 0, z: 0, bstate: bstate}
+    }
+
+    #[cfg(feature = "curses")]
+    #[test]
+    fn report_tracks_held_button() {
+        let mut mouse = mouse(&[1002, 1006]);
+
+        assert_eq!(mouse.report(&event(pancurses::BUTTON1_PRESSED), 0, 0), b"\x1b[<0;1;1M");
+        assert_eq!(
+            mouse.report(&event(pancurses::REPORT_MOUSE_POSITION), 0, 1),
+            b"\x1b[<32;2;1M",
+        );
+        assert_eq!(mouse.report(&event(pancurses::BUTTON1_RELEASED), 0, 1), b"\x1b[<0;2;1m");
+        assert_eq!(mouse.report(&event(pancurses::REPORT_MOUSE_POSITION), 0, 2), b"");
+    }
+
+    #[cfg(feature = "curses")]
+    #[test]
+    fn report_only_reports_presses_in_x10_mode() {
+        let mut mouse = mouse(&[9]);
+        let bstate = pancurses::BUTTON1_CLICKED | pancurses::BUTTON_SHIFT;
+
+        assert_eq!(mouse.report(&event(bstate), 0, 0), b"\x1b[M !!");
+        assert_eq!(mouse.report(&event(pancurses::BUTTON4_PRESSED), 0, 0), b"");
+    }
+
+    #[cfg(feature = "curses")]
+    #[test]
+    fn report_ignores_events_without_tracking() {
+        let mut mouse = mouse(&[]);
+
+        assert_eq!(mouse.report(&event(pancurses::BUTTON1_PRESSED), 0, 0), b"");
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::io::Write;
+
+// `CHUNK_SIZE` is the maximum number of bytes that are written to the PTY by
+// each call to `Outbox::flush`.
+const CHUNK_SIZE: usize = 0x1000;
+
+// `Enter` is what the carriage return that the Enter key sends is written to
+// the PTY as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Enter {
+    Cr,
+    Lf,
+    CrLf,
+}
+
+// `Outbox` queues the bytes that should be written to the PTY. The PTY is
+// non-blocking, and can only accept a limited number of bytes before the child
+// reads them, so large writes (such as pastes) are written in chunks, between
+// reads from the PTY. This means that the child's output continues to be read
+// and rendered while the bytes are written, which the child may depend on to
+// be able to read more input.
+pub struct Outbox {
+    pending: VecDeque<u8>,
+    // `enter` is what carriage returns are queued as, which are translated
+    // for children that aren't run in a terminal, in the same way as a
+    // terminal with `ICRNL` set would read them, and for serial ports.
+    enter: Enter,
+}
+
+impl Outbox {
+    pub fn new(enter: Enter) -> Outbox {
+        Outbox{pending: VecDeque::new(), enter: enter}
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            match (b, self.enter) {
+                (b'\r', Enter::Lf) => self.pending.push_back(b'\n'),
+                (b'\r', Enter::CrLf) => self.pending.extend(b"\r\n"),
+                _ => self.pending.push_back(b),
+            }
+        }
+    }
+
+    // `push_reply` queues `bytes` without translating carriage returns, for
+    // replies to queries from the child, which aren't typed by the user.
+    pub fn push_reply(&mut self, bytes: &[u8]) {
+        self.pending.extend(bytes);
+    }
+
+    // `flush` writes up to `CHUNK_SIZE` of the queued bytes to `ptyf`, stopping
+    // early if `ptyf` can't accept more bytes without blocking.
+    pub fn flush<W: Write>(&mut self, ptyf: &mut W) -> Result<(), String> {
+        let mut written = 0;
+        while written < CHUNK_SIZE && !self.pending.is_empty() {
+            let result = {
+                let (front, _) = self.pending.as_slices();
+                let n = cmp::min(front.len(), CHUNK_SIZE - written);
+                ptyf.write(&front[..n])
+            };
+            match result {
+                Ok(0) => {
+                    return Err(format!("PTY is unable to accept bytes"));
+                },
+                Ok(n) => {
+                    self.pending.drain(..n);
+                    written += n;
+                },
+                Err(e) => {
+                    let k = e.kind();
+                    if k == ErrorKind::WouldBlock {
+                        return Ok(());
+                    }
+                    if k != ErrorKind::Interrupted {
+                        return Err(format!(
+                            "couldn't write to PTY (error kind: {:?}, os error: {:?}): {}",
+                            e.kind(),
+                            e.raw_os_error(),
+                            e,
+                        ));
+                    };
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+use std::collections::HashMap;
+
+use pancurses;
+
+use colour;
+
+// `Pairs` allocates curses colour pairs on demand, because there are usually
+// too few colour pairs to allocate one for every foreground/background
+// combination up front. When all colour pairs are in use, the least recently
+// used colour pair is redefined.
+pub struct Pairs {
+    ids: HashMap<(i16, i16), i16>,
+    // `entries[i]` describes the colour pair with ID `i + 1`.
+    entries: Vec<Entry>,
+    default_pair: (i16, i16),
+    max_entries: usize,
+    frame: u64,
+    clock: u64,
+}
+
+struct Entry {
+    colours: (i16, i16),
+    last_used: u64,
+    last_frame: u64,
+}
+
+impl Pairs {
+    // `new` should only be called after `pancurses::start_color()`.
+    // `default_colours` should be `true` if `pancurses::use_default_colors()`
+    // was called successfully.
+    pub fn new(default_colours: bool) -> Pairs {
+        // Colour pair `0` is used to draw using the host's default colours if
+        // `pancurses::use_default_colors()` was called, and white on black
+        // otherwise.
+        let default_pair =
+            if default_colours {
+                (colour::DEFAULT, colour::DEFAULT)
+            } else {
+                (pancurses::COLOR_WHITE, pancurses::COLOR_BLACK)
+            };
+
+        // Colour pair `0` is reserved by curses, and colour pair IDs are
+        // `i16`s.
+        let max_entries = cmp::min(
+            cmp::max(pancurses::COLOR_PAIRS() - 1, 0),
+            i16::max_value() as i32,
+        );
+
+        Pairs{
+            ids: HashMap::new(),
+            entries: vec![],
+            default_pair: default_pair,
+            max_entries: max_entries as usize,
+            frame: 0,
+            clock: 0,
+        }
+    }
+
+    // `begin_frame` should be called before each time that the screen is
+    // redrawn, so that colour pairs that are in use on the screen aren't
+    // redefined.
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    // `get` returns the ID of a colour pair for `fg` on `bg`, allocating a new
+    // colour pair if necessary. The default colour pair is returned if all
+    // colour pairs are in use in the current frame.
+    pub fn get(&mut self, fg: i16, bg: i16) -> i16 {
+        if (fg, bg) == self.default_pair {
+            return 0;
+        }
+
+        self.clock += 1;
+
+        if let Some(&id) = self.ids.get(&(fg, bg)) {
+            let entry = &mut self.entries[(id - 1) as usize];
+            entry.last_used = self.clock;
+            entry.last_frame = self.frame;
+
+            return id;
+        }
+
+        let entry = Entry{
+            colours: (fg, bg),
+            last_used: self.clock,
+            last_frame: self.frame,
+        };
+
+        if self.entries.len() < self.max_entries {
+            self.entries.push(entry);
+            let id = self.entries.len() as i16;
+            pancurses::init_pair(id, fg, bg);
+            self.ids.insert((fg, bg), id);
+
+            return id;
+        }
+
+        // We search for the least recently used colour pair linearly because
+        // eviction is expected to be rare compared with lookups.
+        let lru = self.entries
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, e)| e.last_used)
+            .map(|(i, _)| i);
+
+        match lru {
+            Some(i) if self.entries[i].last_frame != self.frame => {
+                let id = (i + 1) as i16;
+                self.ids.remove(&self.entries[i].colours);
+                self.entries[i] = entry;
+                pancurses::init_pair(id, fg, bg);
+                self.ids.insert((fg, bg), id);
+
+                id
+            },
+            // Redefining a colour pair that has already been used in the
+            // current frame would change the colours of cells that have
+            // already been drawn, so we fall back to the default colour pair
+            // instead.
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::collections::HashMap;
+
+use config::Colours;
+
+pub type Rgb = (u8, u8, u8);
+
+// `Palette` holds the user's overrides for the 16 base colours, along with
+// overrides that the child makes at runtime using OSC 4, 10 and 11. The
+// child's overrides take precedence, and can be reset to restore the user's
+// overrides.
+#[derive(Clone, Debug, Default)]
+pub struct Palette {
+    colours: [Option<Rgb>; 16],
+    dynamic: HashMap<u8, Rgb>,
+    foreground: Option<Rgb>,
+    background: Option<Rgb>,
+}
+
+impl Palette {
+    pub fn from_config(conf: &Colours) -> Result<Palette, String> {
+        let mut palette = Palette::default();
+        for (i, (name, value)) in conf.entries().iter().enumerate() {
+            if let Some(s) = value {
+                match parse_rgb(s) {
+                    Some(rgb) => {
+                        palette.colours[i] = Some(rgb);
+                    },
+                    None => {
+                        return Err(format!(
+                            "invalid colour for '{}' (expected '#rrggbb'): {}",
+                            name,
+                            s,
+                        ));
+                    },
+                }
+            }
+        }
+
+        Ok(palette)
+    }
+
+    // `configure` replaces the user's overrides with those of `conf`, such as
+    // after the configuration is reloaded, keeping the child's overrides.
+    pub fn configure(&mut self, conf: &Palette) {
+        self.colours = conf.colours;
+    }
+
+    // `get` returns the override for the colour at `index` of the xterm
+    // 256-colour palette, if there is one.
+    pub fn get(&self, index: u8) -> Option<Rgb> {
+        if let Some(rgb) = self.dynamic.get(&index) {
+            return Some(*rgb);
+        }
+
+        if (index as usize) < self.colours.len() {
+            self.colours[index as usize]
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, index: u8, rgb: Rgb) {
+        self.dynamic.insert(index, rgb);
+    }
+
+    pub fn reset(&mut self, index: u8) {
+        self.dynamic.remove(&index);
+    }
+
+    pub fn reset_all(&mut self) {
+        self.dynamic.clear();
+    }
+
+    // `foreground` returns the override for the default foreground colour, if
+    // there is one.
+    pub fn foreground(&self) -> Option<Rgb> {
+        self.foreground
+    }
+
+    pub fn set_foreground(&mut self, rgb: Option<Rgb>) {
+        self.foreground = rgb;
+    }
+
+    // `background` returns the override for the default background colour, if
+    // there is one.
+    pub fn background(&self) -> Option<Rgb> {
+        self.background
+    }
+
+    pub fn set_background(&mut self, rgb: Option<Rgb>) {
+        self.background = rgb;
+    }
+}
+
+pub fn parse_rgb(s: &str) -> Option<Rgb> {
+    if s.len() != 7 || !s.starts_with('#') {
+        return None;
+    }
+
+    // `s` may contain multi-byte characters, so it's sliced using `get`,
+    // which can't panic.
+    let r = u8::from_str_radix(s.get(1..3)?, 16).ok()?;
+    let g = u8::from_str_radix(s.get(3..5)?, 16).ok()?;
+    let b = u8::from_str_radix(s.get(5..7)?, 16).ok()?;
+
+    Some((r, g, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rgb_parses_hex_colours() {
+        let tests = [
+            ("#ff8000", Some((0xff, 0x80, 0x00))),
+            ("#ABCDEF", Some((0xab, 0xcd, 0xef))),
+            ("ff8000", None),
+            ("#ff80", None),
+            ("#ff8000ff", None),
+            ("#ff80zz", None),
+            // `é` takes two bytes, so the string has 7 bytes, but its
+            // components don't lie on character boundaries.
+            ("#1é234", None),
+        ];
+
+        for &(s, expected) in tests.iter() {
+            assert_eq!(parse_rgb(s), expected, "{}", s);
+        }
+    }
+
+    #[test]
+    fn from_config_rejects_invalid_colours() {
+        let conf = Colours{red: Some("red".to_string()), ..Colours::default()};
+
+        assert!(Palette::from_config(&conf).is_err());
+    }
+
+    #[test]
+    fn get_prefers_child_overrides() {
+        let conf = Colours{red: Some("#ff0000".to_string()), ..Colours::default()};
+        let mut palette = Palette::from_config(&conf).unwrap();
+
+        assert_eq!(palette.get(1), Some((0xff, 0x00, 0x00)));
+        assert_eq!(palette.get(2), None);
+
+        palette.set(1, (0x80, 0x00, 0x00));
+        palette.set(100, (0x01, 0x02, 0x03));
+
+        assert_eq!(palette.get(1), Some((0x80, 0x00, 0x00)));
+        assert_eq!(palette.get(100), Some((0x01, 0x02, 0x03)));
+
+        palette.reset(1);
+
+        assert_eq!(palette.get(1), Some((0xff, 0x00, 0x00)));
+
+        palette.reset_all();
+
+        assert_eq!(palette.get(100), None);
+    }
+
+    #[test]
+    fn configure_keeps_child_overrides() {
+        let mut palette = Palette::default();
+        palette.set(2, (0x00, 0x80, 0x00));
+        let conf = Colours{blue: Some("#0000ff".to_string()), ..Colours::default()};
+
+        palette.configure(&Palette::from_config(&conf).unwrap());
+
+        assert_eq!(palette.get(2), Some((0x00, 0x80, 0x00)));
+        assert_eq!(palette.get(4), Some((0x00, 0x00, 0xff)));
+    }
+}
@@ -0,0 +1,745 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::borrow::Cow;
+use std::cmp;
+use std::env;
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::time::Duration;
+
+use alacritty::ansi::{Handler, Processor};
+use alacritty::config::Config as TermConfig;
+use alacritty::index::Point;
+use alacritty::Term;
+use alacritty::term::cell::Flags;
+use alacritty::term::SizeInfo;
+
+use libc;
+use libc::pid_t;
+#[cfg(feature = "curses")]
+use pancurses;
+#[cfg(feature = "curses")]
+pub use pancurses::Window;
+
+use altscreen::AltScreen;
+use child;
+use child::Exit;
+use clipboard::Target;
+use config::{self, Config};
+use extras::Extras;
+use focus;
+use grapheme;
+use input::KeyboardModes;
+use layout::{PaneId, Rect};
+use mouse::Mouse;
+use outbox::Outbox;
+use palette::Palette;
+use pty::{self, ChildIo, FlowControl, OpenPty, Pipe, Pty};
+use record::Recorder;
+use reflow;
+use restart::Restart;
+use scrollback::Scrollback;
+use serial::{self, Serial};
+use ssh;
+use tap::{CursorShape, Tap};
+use tap::Event as TapEvent;
+use terminfo;
+
+use unicode_width::UnicodeWidthChar;
+
+// `XOFF` and `XON` are the bytes that `^S` and `^Q` send, which stop and
+// restart the output of the child.
+const XOFF: u8 = 0x13;
+const XON: u8 = 0x11;
+
+// `Window` stands in for the windows of curses when `tep` is built without
+// curses, in which case panes are never drawn in windows (see
+// `TermEmu::detached`).
+#[cfg(not(feature = "curses"))]
+pub enum Window {}
+
+// `PANE_VAR` is the environment variable that holds the ID of the pane that a
+// child runs in, which is unique within the session, in the same way as
+// `TMUX_PANE`.
+pub const PANE_VAR: &str = "TEP_PANE";
+
+// `child_env` returns the environment variables that the child of the pane `id`
+// is started with, on top of the emulator's environment. They tell it which
+// terminal it's running in, rather than it inheriting the host's `TERM`, whose
+// capabilities the emulator may not support. `TERM_PROGRAM` lets it detect the
+// emulator itself, as other emulators set it. The variables of `conf.env`
+// override the others, including `TERM`.
+fn child_env(id: PaneId, conf: &config::Panes) -> Vec<(String, String)> {
+    let mut vars = vec![
+        ("TERM".to_string(), terminfo::term_name().to_string()),
+        ("COLORTERM".to_string(), "truecolor".to_string()),
+        ("TERM_PROGRAM".to_string(), "tep".to_string()),
+        ("TERM_PROGRAM_VERSION".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+        (PANE_VAR.to_string(), id.to_string()),
+    ];
+    for &(ref name, ref value) in &conf.env {
+        vars.retain(|&(ref n, _)| n != name);
+        vars.push((name.clone(), value.clone()));
+    }
+
+    vars
+}
+
+// `Pane` is a child running in its own PTY, along with the state of the
+// terminal that it's drawn on, which is drawn in an area of the window.
+pub struct Pane {
+    // `id` is the ID of the pane, which its children are given in `PANE_VAR`.
+    pub id: PaneId,
+    // `pty` provides methods for manipulating the PTY.
+    pub pty: ChildIo,
+    // `ptyf` is a `File` interface to the server end of the PTY client/server
+    // pair.
+    pub ptyf: File,
+    // `parser` reads and parses the data read from `pty`, and updates the
+    // state of the terminal "display" that is maintained in `term`.
+    pub parser: Processor,
+    pub term: Term,
+    pub size: SizeInfo,
+    // `tap` observes the data read from `pty` for sequences that `term`
+    // doesn't expose, such as changes to the palette.
+    pub tap: Tap,
+    pub extras: Extras,
+    pub alt_screen: AltScreen,
+    pub keyboard_modes: KeyboardModes,
+    pub mouse: Mouse,
+    pub scrollback: Scrollback,
+    // `outbox` holds the bytes that are waiting to be written to the child.
+    pub outbox: Outbox,
+    // `focus_reporting` is `true` if the child has enabled focus reporting.
+    pub focus_reporting: bool,
+    // `cursor_shape` is the cursor shape most recently requested by the
+    // child.
+    pub cursor_shape: Option<CursorShape>,
+    // `palette` holds the colours that the pane is drawn with, including the
+    // overrides that the child made, which don't affect other panes.
+    pub palette: Palette,
+    // `rect` is the area of the window that the pane is drawn in, and `win`
+    // is the part of the window that covers it, or `None` if the pane isn't
+    // drawn using curses (see `TermEmu::detached`).
+    pub rect: Rect,
+    pub win: Option<Window>,
+    // `exited` describes how the child exited, if it has exited and the pane
+    // was kept (see `config::Panes::remain_on_exit`).
+    pub exited: Option<String>,
+    // `title` is the title most recently set by the child, and `name` is the
+    // title set using the `title` command, which is shown instead of `title`
+    // if it's set.
+    pub title: Option<String>,
+    pub name: Option<String>,
+    // `program` is the program that the pane runs, which is run again when
+    // the pane is respawned, or `None` if the pane runs the user's shell.
+    pub program: Option<Program>,
+    // `pid` is the ID of the child, if there is one, and `status` is how the
+    // child exited, once it has been reaped.
+    pub pid: Option<pid_t>,
+    pub status: Option<Exit>,
+    // `restart` is used to restart the child when it exits, if it's set.
+    pub restart: Option<Restart>,
+    // `paused` is `true` while the output of the child isn't read, which stops
+    // the child once the buffer of its PTY is full.
+    pub paused: bool,
+    // `recorder` records the output of the child, if it's set (see
+    // `tep record`), including the output of the children that replace it.
+    pub recorder: Option<Recorder>,
+}
+
+// `Program` is a program that a pane runs instead of the user's shell.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Program {
+    // `Shell` is a command line that's run using `sh -c`.
+    Shell(String),
+    // `Exec` is a program, followed by its arguments, that's run directly.
+    Exec(Vec<String>),
+    // `Ssh` is a destination, optionally followed by a command, that's
+    // connected to using `ssh`.
+    Ssh(Vec<String>),
+    // `Serial` is a serial port that the pane is connected to, instead of a
+    // child.
+    Serial(serial::Options),
+}
+
+// `Output` is the result of `Pane::process_output`.
+pub enum Output {
+    // `Pending` means that there was no output to process.
+    Pending,
+    // `Read` means that output was processed, and holds the events in it
+    // that concern the host terminal.
+    Read(Vec<HostEvent>),
+    // `Closed` means that the pane should be closed, and holds the reason, if
+    // the child didn't exit normally.
+    Closed(Option<String>),
+}
+
+// `HostEvent` is an event in the output of a child that concerns the host
+// terminal or the embedding program, rather than only the pane, which
+// `Pane::process_bytes` handles itself.
+#[derive(Debug, PartialEq)]
+pub enum HostEvent {
+    EnterAltScreen,
+    SetFocusReporting(bool),
+    // `MouseChanged` means that the mouse tracking mode of the pane changed
+    // (see `Pane::mouse`).
+    MouseChanged,
+    SetClipboard(Target, Vec<u8>),
+    QueryClipboard(Target),
+    ClearScrollback,
+    // `RecordingFailed` is returned when the output of the child can't be
+    // recorded, after which the pane stops recording.
+    RecordingFailed(String),
+}
+
+impl Program {
+    // `title` is the title of the pane that runs the program until the child
+    // sets a title.
+    pub fn title(&self) -> String {
+        match *self {
+            Program::Shell(ref line) => line.clone(),
+            Program::Exec(ref argv) => argv.join(" "),
+            Program::Ssh(ref words) => format!("ssh {}", words.join(" ")),
+            Program::Serial(ref opts) => opts.device.display().to_string(),
+        }
+    }
+
+    fn argv(&self) -> Vec<String> {
+        match *self {
+            Program::Shell(ref line) => vec!["/bin/sh".to_string(), "-c".to_string(), line.clone()],
+            Program::Exec(ref argv) => argv.clone(),
+            Program::Ssh(ref words) => ssh::argv(words),
+            // A serial port isn't run (see `open_pty`).
+            Program::Serial(_) => vec![],
+        }
+    }
+}
+
+impl Pane {
+    // `spawn` starts `program` in a new pane that's drawn in `rect` of
+    // `parent`, if it's given. If `program` is `None` then the pane runs the
+    // command from `conf.panes`, if it's set, or the user's shell. The child
+    // is connected using a `Pipe`, rather than a PTY, if `pipe` is `true`.
+    pub fn spawn(
+        id: PaneId,
+        term_conf: &TermConfig,
+        conf: &Config,
+        parent: Option<&Window>,
+        rect: Rect,
+        program: Option<Program>,
+        pipe: bool,
+    ) -> Result<Pane, String> {
+        let program = program.or_else(|| conf.panes.command.clone().map(Program::Shell));
+        let palette = Palette::from_config(&conf.colours)?;
+        let size = new_size_info(rect.cols - 2, rect.lines - 2);
+        let (pty, ptyf) = open_pty(id, program.as_ref(), &conf.panes, &size, pipe)?;
+        let restart = new_restart(conf, program.as_ref());
+        let enter = pty.enter();
+
+        Ok(Pane{
+            id: id,
+            pid: pty.pid(),
+            pty: pty,
+            ptyf: ptyf,
+            parser: Processor::new(),
+            term: Term::new(term_conf, size),
+            size: size,
+            tap: Tap::new(conf.clipboard.osc52_limit),
+            extras: Extras::new(),
+            alt_screen: AltScreen::new(),
+            keyboard_modes: KeyboardModes::new(),
+            mouse: Mouse::new(&conf.mouse),
+            scrollback: Scrollback::new(conf.scrollback.lines),
+            outbox: Outbox::new(enter),
+            focus_reporting: false,
+            cursor_shape: None,
+            palette: palette,
+            rect: rect,
+            win: parent.map(|parent| subwindow(parent, rect)),
+            exited: None,
+            title: program.as_ref().map(Program::title),
+            name: None,
+            program: program,
+            status: None,
+            restart: restart,
+            paused: false,
+            recorder: None,
+        })
+    }
+
+    // `respawn` starts a new child in the pane after its child exited, with a
+    // new terminal of the same size. The scrollback is kept if `keep` is
+    // `true`, in which case the screen of the old child is added to it. The
+    // pane is left unchanged if a new PTY can't be opened.
+    pub fn respawn(&mut self, term_conf: &TermConfig, conf: &Config, keep: bool) -> Result<(), String> {
+        let pipe =
+            match self.pty {
+                ChildIo::Pipe(_) => true,
+                _ => false,
+            };
+        let palette = Palette::from_config(&conf.colours)?;
+        let (pty, ptyf) = open_pty(self.id, self.program.as_ref(), &conf.panes, &self.size, pipe)?;
+        self.pid = pty.pid();
+        self.pty = pty;
+        self.ptyf = ptyf;
+
+        if keep {
+            let primary = self.alt_screen.primary().unwrap_or(&self.term);
+            self.scrollback.push_screen(primary);
+        } else {
+            self.scrollback = Scrollback::new(conf.scrollback.lines);
+        }
+        self.scrollback.set_region(0, None);
+        self.scrollback.scroll_to_bottom();
+
+        self.parser = Processor::new();
+        self.term = Term::new(term_conf, self.size);
+        self.tap = Tap::new(conf.clipboard.osc52_limit);
+        self.extras = Extras::new();
+        self.alt_screen = AltScreen::new();
+        self.keyboard_modes = KeyboardModes::new();
+        self.mouse = Mouse::new(&conf.mouse);
+        self.outbox = Outbox::new(self.pty.enter());
+        self.focus_reporting = false;
+        self.cursor_shape = None;
+        self.palette = palette;
+        self.exited = None;
+        self.title = self.program.as_ref().map(Program::title);
+        self.status = None;
+        self.paused = false;
+
+        Ok(())
+    }
+
+    // `advance` passes `byte`, which was output by the child, to the terminal,
+    // and returns the events that `tap` observed, which are handled by the
+    // caller, other than entering and leaving the alternate screen. If
+    // `ambiguous_wide` is `true` then East Asian ambiguous-width characters
+    // are printed using two columns.
+    pub fn advance(&mut self, byte: u8, term_conf: &TermConfig, ambiguous_wide: bool) -> Vec<TapEvent> {
+        let point = self.term.cursor().point;
+        // `tap` is advanced before `parser` so that the primary screen can be
+        // copied before `term` switches to the alternate screen.
+        let printed = self.tap.advance(byte);
+        let evts = self.tap.take_events();
+        if evts.contains(&TapEvent::EnterAltScreen) {
+            self.alt_screen.enter(&self.term, &mut self.extras, term_conf, self.size);
+        }
+        // Lines that could be scrolled off the screen are copied before
+        // `parser` scrolls them, so that they can be added to `scrollback`.
+        let pending =
+            if self.alt_screen.is_active() {
+                None
+            } else {
+                self.scrollback.prepare(&self.term, &evts, printed.is_some())
+            };
+        self.parser.advance(&mut self.term, byte, &mut self.ptyf);
+        if let Some(pending) = pending {
+            self.scrollback.commit(pending, &self.term, point);
+        }
+        self.erase_and_scroll_extras(&evts, point);
+        if let Some(c) = printed {
+            if c.width() == Some(0) {
+                self.extras.push_zero_width(zero_width_base(&self.term, &self.extras, point), c);
+            } else {
+                self.extras.record(point, self.tap.style());
+                // Ambiguous-width characters in the last column are left
+                // narrow, as there's no room to widen them.
+                let room = point.col.0 + 1 < self.term.grid().num_cols().0;
+                if ambiguous_wide && grapheme::is_ambiguous(c) && room {
+                    self.term.input(' ');
+                    self.extras.set_wide(point);
+                }
+            }
+        }
+        if evts.contains(&TapEvent::ExitAltScreen) {
+            self.alt_screen.exit(&mut self.extras);
+        }
+
+        evts
+    }
+
+    // `process_output` reads the output that the child has written to the
+    // PTY, using `buf`, without blocking, and passes it to the terminal using
+    // `process_bytes`. The output of a paused child is left in its PTY, which
+    // stops the child from writing more once the PTY's buffer is full, but the
+    // pane is still closed once every process has closed the other end of the
+    // PTY, as it would be if its output were read.
+    pub fn process_output(&mut self, buf: &mut [u8], term_conf: &TermConfig, ambiguous_wide: bool) -> Output {
+        if self.paused {
+            if pty::hung_up(&self.ptyf) {
+                return Output::Closed(self.exit_reason());
+            }
+            return Output::Pending;
+        }
+
+        match self.ptyf.read(buf) {
+            // Reading from the PTY ends once every process has closed the
+            // other end of it, which usually happens when the child exits.
+            // The BSDs and macOS report this as end-of-file, and Linux reports
+            // it as `EIO`.
+            Ok(0) => Output::Closed(self.exit_reason()),
+            Ok(n) => {
+                let bytes: Cow<[u8]> =
+                    if self.pty.onlcr() {
+                        Cow::Owned(pty::onlcr(&buf[..n]))
+                    } else {
+                        Cow::Borrowed(&buf[..n])
+                    };
+                let mut evts = self.process_bytes(&bytes, term_conf, ambiguous_wide);
+                // The output is recorded as it was drawn, so the output of
+                // children that aren't run in PTYs is recorded after
+                // `pty::onlcr` is applied, so that it can be played back.
+                let recorded = self.recorder.as_mut().map(|recorder| recorder.write(&bytes));
+                if let Some(Err(e)) = recorded {
+                    self.recorder = None;
+                    evts.push(HostEvent::RecordingFailed(format!("couldn't record output: {}", e)));
+                }
+
+                Output::Read(evts)
+            },
+            Err(ref e) if e.raw_os_error() == Some(libc::EIO) => Output::Closed(self.exit_reason()),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                // If the child has exited, but another process, such as a
+                // background job, still has the PTY open, then the pane is
+                // closed once the child's output has been read. Only the
+                // child is reaped, rather than every child of the emulator.
+                let reason = self.exit_reason();
+                if self.status.is_some() {
+                    Output::Closed(reason)
+                } else {
+                    Output::Pending
+                }
+            },
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => Output::Pending,
+            Err(e) => {
+                Output::Closed(Some(format!(
+                    "couldn't read from PTY (error kind: {:?}, os error: {:?}): {}",
+                    e.kind(),
+                    e.raw_os_error(),
+                    e,
+                )))
+            },
+        }
+    }
+
+    // `process_bytes` passes `bytes`, which were output by the child, to the
+    // terminal, and handles the events in them that only concern the pane. It
+    // returns the events that concern the host, in the order that they were
+    // output.
+    pub fn process_bytes(&mut self, bytes: &[u8], term_conf: &TermConfig, ambiguous_wide: bool) -> Vec<HostEvent> {
+        let mut host_evts = vec![];
+        for &byte in bytes {
+            // Events are handled after each byte, rather than after all of
+            // `bytes`, because some events, such as `TapEvent::SetLineSize`,
+            // depend on the position of the cursor.
+            for evt in self.advance(byte, term_conf, ambiguous_wide) {
+                if let Some(host_evt) = self.handle(evt) {
+                    host_evts.push(host_evt);
+                }
+            }
+        }
+
+        host_evts
+    }
+
+    // `handle` handles `evt`, which was observed in the output of the child,
+    // and returns the event that the host should handle, if any.
+    fn handle(&mut self, evt: TapEvent) -> Option<HostEvent> {
+        let palette = &mut self.palette;
+        match evt {
+            TapEvent::SetColour(i, rgb) => palette.set(i, rgb),
+            TapEvent::ResetColour(i) => palette.reset(i),
+            TapEvent::ResetColours => palette.reset_all(),
+            TapEvent::SetForeground(rgb) => palette.set_foreground(Some(rgb)),
+            TapEvent::ResetForeground => palette.set_foreground(None),
+            TapEvent::SetBackground(rgb) => palette.set_background(Some(rgb)),
+            TapEvent::ResetBackground => palette.set_background(None),
+            TapEvent::SetCursorShape(shape) => self.cursor_shape = shape,
+            TapEvent::SetLineSize(line_size) => {
+                self.extras.set_line_size(self.term.cursor().point.line, line_size);
+            },
+            // The alternate screen is switched by `advance`, but the
+            // scrollback isn't shown while the alternate screen is active.
+            TapEvent::EnterAltScreen => return Some(HostEvent::EnterAltScreen),
+            TapEvent::ExitAltScreen => {},
+            TapEvent::SetPrivateMode(focus::MODE, set) => {
+                self.focus_reporting = set;
+                return Some(HostEvent::SetFocusReporting(set));
+            },
+            TapEvent::SetPrivateMode(mode, set) => {
+                if self.mouse.set_mode(mode, set) {
+                    return Some(HostEvent::MouseChanged);
+                }
+            },
+            TapEvent::QueryKeyboardFlags => {
+                // Replies are queued rather than written directly, as the PTY
+                // is non-blocking and may not be able to accept them yet.
+                let reply = format!("\x1b[?{}u", self.keyboard_modes.flags());
+                self.outbox.push_reply(reply.as_bytes());
+            },
+            TapEvent::SetScrollRegion(top, bottom) => self.scrollback.set_region(top, bottom),
+            TapEvent::SetClipboard(target, content) => return Some(HostEvent::SetClipboard(target, content)),
+            TapEvent::QueryClipboard(target) => return Some(HostEvent::QueryClipboard(target)),
+            TapEvent::SetTitle(title) => self.title = Some(title),
+            TapEvent::ClearScrollback => {
+                self.scrollback.clear();
+                return Some(HostEvent::ClearScrollback);
+            },
+            // Events that erase and scroll lines are handled by `advance`.
+            TapEvent::LineFeed |
+            TapEvent::ScrollUp(_) |
+            TapEvent::ScrollDown(_) |
+            TapEvent::ReverseIndex |
+            TapEvent::InsertLines(_) |
+            TapEvent::DeleteLines(_) |
+            TapEvent::EraseInDisplay(_) |
+            TapEvent::EraseInLine(_) => {},
+            TapEvent::SetModifyOtherKeys(_) |
+            TapEvent::PushKeyboardFlags(_) |
+            TapEvent::PopKeyboardFlags(_) |
+            TapEvent::SetKeyboardFlags(_, _) => {
+                self.keyboard_modes.apply(&evt);
+            },
+        }
+
+        None
+    }
+
+    // `erase_and_scroll_extras` discards or moves what `extras` recorded for
+    // the cells that were erased or scrolled by `evts`, where `point` is the
+    // position of the cursor before the byte that caused them.
+    fn erase_and_scroll_extras(&mut self, evts: &[TapEvent], point: Point) {
+        let last = self.term.grid().num_lines().0 - 1;
+        let (top, bottom) = self.scrollback.region(last);
+        let (line, col) = (point.line.0, point.col.0);
+        let in_region = top <= line && line <= bottom;
+        for evt in evts {
+            match *evt {
+                TapEvent::EraseInDisplay(0) => self.extras.erase((line, col), (last, usize::MAX)),
+                TapEvent::EraseInDisplay(1) => self.extras.erase((0, 0), (line, col)),
+                TapEvent::EraseInDisplay(2) => self.extras.erase((0, 0), (last, usize::MAX)),
+                TapEvent::EraseInLine(0) => self.extras.erase((line, col), (line, usize::MAX)),
+                TapEvent::EraseInLine(1) => self.extras.erase((line, 0), (line, col)),
+                TapEvent::EraseInLine(2) => self.extras.erase((line, 0), (line, usize::MAX)),
+                TapEvent::LineFeed if line == bottom => self.extras.scroll(top, bottom, 1),
+                TapEvent::ReverseIndex if line == top => self.extras.scroll(top, bottom, -1),
+                TapEvent::ScrollUp(n) => self.extras.scroll(top, bottom, n as isize),
+                TapEvent::ScrollDown(n) => self.extras.scroll(top, bottom, -(n as isize)),
+                TapEvent::InsertLines(n) if in_region => self.extras.scroll(line, bottom, -(n as isize)),
+                TapEvent::DeleteLines(n) if in_region => self.extras.scroll(line, bottom, n as isize),
+                _ => {},
+            }
+        }
+    }
+
+    // `flow` pauses or resumes the output of the child according to the XOFF
+    // and XON in `input`, which was typed in the pane, as its terminal does,
+    // and returns the bytes of `input` that should be sent to the child.
+    pub fn flow(&mut self, input: &[u8]) -> Vec<u8> {
+        match self.pty.flow_control() {
+            FlowControl::None => {},
+            FlowControl::Terminal{any} => {
+                for &byte in input {
+                    if byte == XOFF {
+                        self.paused = true;
+                    } else if byte == XON || any {
+                        self.paused = false;
+                    }
+                }
+            },
+            FlowControl::Emulated => {
+                let mut sent = Vec::with_capacity(input.len());
+                for &byte in input {
+                    match byte {
+                        XOFF => self.paused = true,
+                        XON => self.paused = false,
+                        _ => sent.push(byte),
+                    }
+                }
+
+                return sent;
+            },
+        }
+
+        input.to_vec()
+    }
+
+    // `exit_reason` returns the description of how the child exited, which is
+    // `None` if it exited successfully, or if it hasn't been reaped.
+    pub fn exit_reason(&mut self) -> Option<String> {
+        if let (None, Some(pid)) = (self.status, self.pid) {
+            self.status = child::wait(pid);
+        }
+
+        self.status.and_then(Exit::describe)
+    }
+
+    // `display_title` returns the title that's drawn over the top border of
+    // the pane.
+    pub fn display_title(&self) -> Option<&str> {
+        self.name.as_ref().or_else(|| self.title.as_ref()).map(String::as_str)
+    }
+
+    // `place` moves the pane to `rect` of `parent`, if it's given, which is
+    // also used after `parent` is resized, and resizes its terminal to fit
+    // inside its border if the size of the pane changed.
+    pub fn place(&mut self, parent: Option<&Window>, rect: Rect) {
+        self.win = parent.map(|parent| subwindow(parent, rect));
+        let resized = rect.lines != self.rect.lines || rect.cols != self.rect.cols;
+        self.rect = rect;
+        if resized {
+            self.resize(new_size_info(rect.cols - 2, rect.lines - 2));
+        }
+    }
+
+    // `resize` resizes the terminal to `size`, reflowing the primary screen.
+    pub fn resize(&mut self, size: SizeInfo) {
+        self.size = size;
+        let snapshot = reflow::capture(&self.term, &self.extras);
+        self.term.resize(&size);
+        self.extras.clear();
+        // Only one of the screens is reflowed, as `capture` skips the
+        // alternate screen. The rows that are scrolled off the top of the
+        // primary screen by being rewrapped are added to the scrollback.
+        let mut scrolled = self.alt_screen.resize(&size);
+        if let Some(ref snapshot) = snapshot {
+            scrolled.extend(reflow::replay(snapshot, &mut self.term, &mut self.parser, &mut self.extras));
+        }
+        for row in scrolled {
+            self.scrollback.push(row);
+        }
+        self.pty.resize(size.width as u16, size.height as u16);
+        // `alacritty::Term` resets the scrolling region when it's resized.
+        self.scrollback.set_region(0, None);
+        self.scrollback.scroll_to_bottom();
+    }
+}
+
+// `subwindow` returns the part of `parent` that covers `rect`. The subwindow
+// shares its contents with `parent`, so drawing to either is visible in both.
+#[cfg(feature = "curses")]
+fn subwindow(parent: &Window, rect: Rect) -> Window {
+    match parent.derwin(rect.lines, rect.cols, rect.y, rect.x) {
+        Ok(win) => win,
+        // `rect` doesn't fit inside `parent` if the window is too small for
+        // the layout, in which case the pane covers the whole window until
+        // the window is made larger.
+        Err(_) => {
+            let (lines, cols) = parent.get_max_yx();
+            parent.derwin(lines, cols, 0, 0).unwrap_or_else(|_| pancurses::newwin(0, 0, 0, 0))
+        },
+    }
+}
+
+#[cfg(not(feature = "curses"))]
+fn subwindow(parent: &Window, _: Rect) -> Window {
+    match *parent {}
+}
+
+// `new_restart` returns the `Restart` that new panes that run `program` are
+// given, according to `conf.panes`. Programs that connect using `ssh` are
+// restarted when they lose their connection, even if `restart` isn't set.
+pub fn new_restart(conf: &Config, program: Option<&Program>) -> Option<Restart> {
+    let delay = Duration::from_millis(conf.panes.restart_delay);
+    match program {
+        _ if conf.panes.restart => Some(Restart::new(delay)),
+        Some(&Program::Ssh(_)) => Some(Restart::new(delay).only_on(ssh::DISCONNECTED)),
+        _ => None,
+    }
+}
+
+// `new_size_info` returns the size of a terminal that's `w` by `h`, which is at
+// least 1x1, because a pane's area can be smaller than its border while the
+// window is too small to draw it.
+pub fn new_size_info(w: i32, h: i32) -> SizeInfo {
+    SizeInfo {
+        width: cmp::max(w, 1) as f32,
+        height: cmp::max(h, 1) as f32,
+        cell_width: 1.0,
+        cell_height: 1.0,
+        padding_x: 0.0,
+        padding_y: 0.0,
+    }
+}
+
+// `open_pty` starts `program`, or the shell that `conf` selects if `program` is
+// `None`, in a new PTY of `size`, or in a `Pipe` if `pipe` is `true`, and
+// returns the PTY along with the file that's used to read from and write to
+// it. The serial port of a `Program::Serial` is opened instead of starting a
+// child. The child is started in the pane `id`.
+fn open_pty(
+    id: PaneId,
+    program: Option<&Program>,
+    conf: &config::Panes,
+    size: &SizeInfo,
+    pipe: bool,
+) -> Result<(ChildIo, File), String> {
+    // Only the shell is started as a login shell, because other programs
+    // don't expect a `-` before their names.
+    let argv = program.map_or_else(|| vec![shell(conf)], Program::argv);
+    let login = program.is_none() && conf.login;
+    let env = child_env(id, conf);
+    let pty =
+        if let Some(&Program::Serial(ref opts)) = program {
+            ChildIo::Serial(Serial::open(opts)?)
+        } else if pipe {
+            ChildIo::Pipe(Pipe::spawn(&argv, &env, login)?)
+        } else {
+            let cols = cmp::max(size.width as u16, 1);
+            let lines = cmp::max(size.height as u16, 1);
+            ChildIo::OpenPty(OpenPty::spawn(&argv, &env, login, cols, lines)?)
+        };
+    let ptyf = pty.file().map_err(|e| format!("couldn't open the PTY: {}", e))?;
+
+    Ok((pty, ptyf))
+}
+
+// `shell` returns the shell that's set in `conf`, or the user's shell, which is
+// given by `SHELL`, or by the user's entry in the password database if `SHELL`
+// isn't set.
+fn shell(conf: &config::Panes) -> String {
+    if let Some(ref shell) = conf.shell {
+        return shell.clone();
+    }
+    if let Some(shell) = env::var("SHELL").ok().filter(|shell| !shell.is_empty()) {
+        return shell;
+    }
+    unsafe {
+        let entry = libc::getpwuid(libc::getuid());
+        if !entry.is_null() && !(*entry).pw_shell.is_null() {
+            let shell = CStr::from_ptr((*entry).pw_shell).to_string_lossy();
+            if !shell.is_empty() {
+                return shell.into_owned();
+            }
+        }
+    }
+
+    "/bin/sh".to_string()
+}
+
+// `zero_width_base` returns the position of the character that a zero-width
+// character that was printed while the cursor was at `point` belongs to. This
+// is usually the character before the cursor, but is the character before
+// that if the character before the cursor is the spacer of a wide character.
+fn zero_width_base(term: &Term, extras: &Extras, point: Point) -> Point {
+    if point.col.0 == 0 {
+        return point;
+    }
+
+    let mut col = point.col - 1;
+    if col.0 > 0 {
+        let is_spacer =
+            term.grid()[point.line][col].flags.contains(Flags::WIDE_CHAR_SPACER) ||
+                extras.is_wide(point.line, col - 1);
+        if is_spacer {
+            col = col - 1;
+        }
+    }
+
+    Point{line: point.line, col: col}
+}
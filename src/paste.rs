@@ -0,0 +1,101 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::mem;
+
+// `ENABLE` and `DISABLE` enable and disable bracketed paste mode on the host.
+pub const ENABLE: &str = "\x1b[?2004h";
+pub const DISABLE: &str = "\x1b[?2004l";
+
+const START: &[u8] = b"\x1b[200~";
+const END: &[u8] = b"\x1b[201~";
+
+// `Paste` detects the markers that the host wraps pastes in when bracketed
+// paste mode is enabled on the host. Pastes are passed to the child with these
+// markers if the child has enabled bracketed paste mode, so that, for example,
+// shells don't run each line of a paste as it arrives, and are passed without
+// them otherwise.
+pub struct Paste {
+    // `pending` holds the bytes that could be the start of a marker.
+    pending: Vec<u8>,
+    active: bool,
+}
+
+impl Paste {
+    pub fn new() -> Paste {
+        Paste{
+            pending: vec![],
+            active: false,
+        }
+    }
+
+    // `active` returns `true` while a paste is being received.
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    // `filter` returns the bytes that should be written to the child for
+    // `bytes`. `bracketed` should be `true` if the child has enabled bracketed
+    // paste mode. Bytes that could be the start of a marker are held back
+    // until they're known not to be, or until `flush` is called.
+    pub fn filter(&mut self, bytes: &[u8], bracketed: bool) -> Vec<u8> {
+        let mut out = vec![];
+        for &b in bytes {
+            self.pending.push(b);
+            let marker = if self.active { END } else { START };
+            if marker.starts_with(&self.pending) {
+                if self.pending.len() == marker.len() {
+                    if bracketed {
+                        out.extend_from_slice(&self.pending);
+                    }
+                    self.pending.clear();
+                    self.active = !self.active;
+                }
+            } else {
+                // Only the last byte can be the start of a new marker, as
+                // all markers start with `ESC`.
+                let last = self.pending.pop();
+                out.extend(self.pending.drain(..));
+                match last {
+                    Some(0x1b) => self.pending.push(0x1b),
+                    Some(b) => out.push(b),
+                    None => {},
+                }
+            }
+        }
+
+        out
+    }
+
+    // `flush` returns the bytes that were held back by `filter`. It should be
+    // called when there's no more input from the host, so that keys such as
+    // `ESC` aren't held back indefinitely; markers are written by the host
+    // all at once, and so aren't split by this.
+    pub fn flush(&mut self) -> Vec<u8> {
+        mem::replace(&mut self.pending, vec![])
+    }
+}
+
+// `bracket` returns `content`, wrapped in paste markers if `bracketed` is
+// `true`, for writing to the child as a single paste. End markers are removed
+// from `content` so that it can't end the paste early.
+pub fn bracket(content: &[u8], bracketed: bool) -> Vec<u8> {
+    if !bracketed {
+        return content.to_vec();
+    }
+
+    let mut out = START.to_vec();
+    let mut i = 0;
+    while i < content.len() {
+        if content[i..].starts_with(END) {
+            i += END.len();
+        } else {
+            out.push(content[i]);
+            i += 1;
+        }
+    }
+    out.extend_from_slice(END);
+
+    out
+}
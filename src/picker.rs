@@ -0,0 +1,79 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use pancurses::Input;
+
+use prompt::Outcome;
+
+// `Picker` is a list of items, which is drawn over the screen, that one item
+// can be chosen from.
+pub struct Picker {
+    title: String,
+    items: Vec<String>,
+    selected: usize,
+}
+
+impl Picker {
+    pub fn new(title: &str, items: Vec<String>) -> Picker {
+        Picker{
+            title: title.to_string(),
+            items: items,
+            selected: 0,
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    // `selected` is the item that's selected, which is also the item that was
+    // chosen once `handle` returns `Outcome::Submit`.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, i: usize) {
+        if i < self.items.len() {
+            self.selected = i;
+        }
+    }
+
+    // `handle` updates the picker with `input`. The arrow keys, `j` and `k`
+    // move through the items, and `Enter` chooses the selected item. The
+    // digits `1` to `9` choose the item with that number directly.
+    pub fn handle(&mut self, input: &Input) -> Outcome {
+        match *input {
+            Input::KeyUp | Input::Character('k') => {
+                self.selected = self.selected.saturating_sub(1);
+            },
+            Input::KeyDown | Input::Character('j') => {
+                if self.selected + 1 < self.items.len() {
+                    self.selected += 1;
+                }
+            },
+            Input::Character('\n') | Input::Character('\r') | Input::KeyEnter => {
+                if let Some(item) = self.items.get(self.selected) {
+                    return Outcome::Submit(item.clone());
+                }
+            },
+            Input::Character(c @ '1'..='9') => {
+                let i = c as usize - '1' as usize;
+                if let Some(item) = self.items.get(i) {
+                    self.selected = i;
+                    return Outcome::Submit(item.clone());
+                }
+            },
+            Input::Character('q') | Input::Character('\x1b') | Input::Character('\x03') | Input::Character('\x07') => {
+                return Outcome::Cancel;
+            },
+            _ => {},
+        }
+
+        Outcome::Pending
+    }
+}
@@ -0,0 +1,405 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use pancurses::Input;
+
+use config::{Action, Signal};
+use layout::{self, Preset};
+use tmux;
+
+// `COMMANDS` are the names of the commands that can be run from the prompt,
+// including the `tmux` commands of `tmux::COMMANDS`.
+const COMMANDS: &[&str] = &[
+    "capture-pane", "clear", "dump", "kill", "kill-pane", "layout", "macro", "paste", "reload", "rename",
+    "resize", "respawn", "save", "select-pane", "send-keys", "set", "split-window", "title",
+];
+
+// `SUBCOMMANDS` are the names of the arguments that can follow each command,
+// for completion.
+const SUBCOMMANDS: &[(&str, &[&str])] = &[
+    ("clear", &["screen"]),
+    ("kill", &["hup", "int", "term", "kill"]),
+    ("layout", &["even-horizontal", "even-vertical", "main-vertical", "tiled"]),
+    ("macro", &["play", "save"]),
+    ("respawn", &["keep"]),
+    ("set", &["border", "restart"]),
+];
+
+// `Command` is a command that was entered at the prompt.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    // `Resize` resizes the child's terminal to `(cols, lines)`.
+    Resize(usize, usize),
+    // `Dump` writes the text of the screen to a file.
+    Dump(String),
+    // `Save` writes the state of the screen and the scrollback to a file, so
+    // that it can be restored by a later emulator.
+    Save(String),
+    // `Paste` writes the contents of a file to the child as a paste.
+    Paste(String),
+    // `SetBorder` sets the border character, where `None` hides the border.
+    SetBorder(Option<char>),
+    // `SetRestart` sets whether the child of the active pane is restarted
+    // when it exits.
+    SetRestart(bool),
+    // `SaveMacro` saves the last recorded macro under a name.
+    SaveMacro(String),
+    // `PlayMacro` replays the macro that was saved under a name.
+    PlayMacro(String),
+    // `Clear` clears the scrollback, and also the screen if its argument is
+    // `true`.
+    Clear(bool),
+    // `Rename` renames the current tab.
+    Rename(String),
+    // `Layout` arranges the panes of the current tab using a preset.
+    Layout(Preset),
+    // `Kill` sends a signal to the child of the active pane.
+    Kill(Signal),
+    // `Respawn` starts a new child in the active pane after its child exited,
+    // keeping its scrollback if its argument is `true`.
+    Respawn(bool),
+    // `Reload` loads the configuration file again.
+    Reload,
+    // `Title` sets the title of the active pane, where `None` restores the
+    // title set by the child.
+    Title(Option<String>),
+    // `Action` triggers an action, in the same way as the key that's bound to
+    // it.
+    Action(Action),
+    // `SelectPane` makes the pane at an index of the current tab active.
+    SelectPane(usize),
+    // `SendKeys` sends keys to the child of the active pane, as if they were
+    // typed.
+    SendKeys(Vec<Input>),
+    // `CapturePane` copies the text of the screen of the active pane to the
+    // paste history, and also outputs it if its argument is `true`.
+    CapturePane(bool),
+}
+
+// `Outcome` is the result of passing a key to `Prompt`.
+pub enum Outcome {
+    Pending,
+    Cancel,
+    Submit(String),
+}
+
+// `Prompt` is a line editor for entering emulator commands, or search
+// patterns, which is drawn over the status line.
+pub struct Prompt {
+    text: String,
+    search: bool,
+}
+
+impl Prompt {
+    pub fn new() -> Prompt {
+        Prompt::with_text("")
+    }
+
+    // `with_text` returns a prompt that starts with `text` already entered.
+    pub fn with_text(text: &str) -> Prompt {
+        Prompt{text: text.to_string(), search: false}
+    }
+
+    // `search` returns a prompt for entering a search pattern, which doesn't
+    // complete command names.
+    pub fn search() -> Prompt {
+        Prompt{text: String::new(), search: true}
+    }
+
+    pub fn is_search(&self) -> bool {
+        self.search
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // `leader` is the character that's drawn before the text of the prompt.
+    pub fn leader(&self) -> char {
+        if self.search {
+            '/'
+        } else {
+            ':'
+        }
+    }
+
+    // `handle` updates the prompt with `input`. `Tab` completes the current
+    // word.
+    pub fn handle(&mut self, input: &Input) -> Outcome {
+        match *input {
+            Input::Character('\n') | Input::Character('\r') | Input::KeyEnter => {
+                return Outcome::Submit(self.text.clone());
+            },
+            Input::Character('\x1b') | Input::Character('\x03') | Input::Character('\x07') => {
+                return Outcome::Cancel;
+            },
+            Input::Character('\x7f') | Input::Character('\x08') | Input::KeyBackspace => {
+                // Deleting from an empty prompt closes it, as in `vim`.
+                if self.text.pop().is_none() {
+                    return Outcome::Cancel;
+                }
+            },
+            Input::Character('\x15') => self.text.clear(),
+            Input::Character('\t') if !self.search => self.text = complete(&self.text),
+            Input::Character(c) if !c.is_control() => self.text.push(c),
+            _ => {},
+        }
+
+        Outcome::Pending
+    }
+}
+
+// `complete` returns `text` with its last word completed, if the word is the
+// prefix of exactly one command or subcommand.
+fn complete(text: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let ends_word = !text.is_empty() && !text.ends_with(' ');
+    let subcommands = words.first().and_then(|cmd| {
+        SUBCOMMANDS.iter()
+            .find(|&&(name, _)| name == *cmd)
+            .map(|&(_, subcommands)| subcommands)
+    });
+    let (candidates, prefix) =
+        match (words.len(), ends_word, subcommands) {
+            (0, _, _) => (COMMANDS, ""),
+            (1, true, _) => (COMMANDS, words[0]),
+            (1, false, Some(subcommands)) => (subcommands, ""),
+            (2, true, Some(subcommands)) => (subcommands, words[1]),
+            _ => return text.to_string(),
+        };
+
+    let matches: Vec<&str> = candidates.iter()
+        .cloned()
+        .filter(|c| c.starts_with(prefix))
+        .collect();
+    if matches.len() != 1 {
+        return text.to_string();
+    }
+
+    let mut completed = text[..text.len() - prefix.len()].to_string();
+    completed.push_str(matches[0]);
+    completed.push(' ');
+
+    completed
+}
+
+// `parse` parses `line` as a command. Words of `line` can be quoted, as in the
+// shell, so that they can contain spaces.
+pub fn parse(line: &str) -> Result<Command, String> {
+    let words = split_words(line)?;
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let args = if words.is_empty() { &words[..] } else { &words[1..] };
+    if let Some(result) = words.first().and_then(|name| tmux::parse(name, args)) {
+        return result;
+    }
+    match words.first() {
+        Some(&"resize") => {
+            let usage = "usage: resize <cols> <lines>".to_string();
+            if args.len() != 2 {
+                return Err(usage);
+            }
+            match (args[0].parse(), args[1].parse()) {
+                (Ok(cols), Ok(lines)) if cols > 0 && lines > 0 => Ok(Command::Resize(cols, lines)),
+                _ => Err(usage),
+            }
+        },
+        Some(&"dump") => {
+            if args.len() != 1 {
+                return Err("usage: dump <path>".to_string());
+            }
+            Ok(Command::Dump(args[0].to_string()))
+        },
+        Some(&"macro") => {
+            match (args.len(), args.first()) {
+                (2, Some(&"save")) => Ok(Command::SaveMacro(args[1].to_string())),
+                (2, Some(&"play")) => Ok(Command::PlayMacro(args[1].to_string())),
+                _ => Err("usage: macro save|play <name>".to_string()),
+            }
+        },
+        Some(&"save") => {
+            if args.len() != 1 {
+                return Err("usage: save <path>".to_string());
+            }
+            Ok(Command::Save(args[0].to_string()))
+        },
+        Some(&"paste") => {
+            if args.len() != 1 {
+                return Err("usage: paste <path>".to_string());
+            }
+            Ok(Command::Paste(args[0].to_string()))
+        },
+        Some(&"clear") => {
+            match (args.len(), args.first()) {
+                (0, _) => Ok(Command::Clear(false)),
+                (1, Some(&"screen")) => Ok(Command::Clear(true)),
+                _ => Err("usage: clear [screen]".to_string()),
+            }
+        },
+        Some(&"rename") => {
+            if args.is_empty() {
+                return Err("usage: rename <name>".to_string());
+            }
+            Ok(Command::Rename(args.join(" ")))
+        },
+        Some(&"layout") => {
+            match (args.len(), args.first().and_then(|name| Preset::from_name(name))) {
+                (1, Some(preset)) => Ok(Command::Layout(preset)),
+                _ => {
+                    let names: Vec<&str> = layout::PRESETS.iter().map(|&(name, _)| name).collect();
+                    Err(format!("usage: layout {}", names.join("|")))
+                },
+            }
+        },
+        Some(&"kill") => {
+            match (args.len(), args.first()) {
+                (0, _) | (1, Some(&"hup")) => Ok(Command::Kill(Signal::Hup)),
+                (1, Some(&"int")) => Ok(Command::Kill(Signal::Int)),
+                (1, Some(&"term")) => Ok(Command::Kill(Signal::Term)),
+                (1, Some(&"kill")) => Ok(Command::Kill(Signal::Kill)),
+                _ => Err("usage: kill [hup|int|term|kill]".to_string()),
+            }
+        },
+        Some(&"respawn") => {
+            match (args.len(), args.first()) {
+                (0, _) => Ok(Command::Respawn(false)),
+                (1, Some(&"keep")) => Ok(Command::Respawn(true)),
+                _ => Err("usage: respawn [keep]".to_string()),
+            }
+        },
+        Some(&"reload") => {
+            if args.is_empty() {
+                Ok(Command::Reload)
+            } else {
+                Err("usage: reload".to_string())
+            }
+        },
+        Some(&"title") => {
+            if args.is_empty() {
+                Ok(Command::Title(None))
+            } else {
+                Ok(Command::Title(Some(args.join(" "))))
+            }
+        },
+        Some(&"set") => {
+            match args.first() {
+                Some(&"border") => {
+                    let mut chars = args.get(1).map_or("", |s| *s).chars();
+                    match (args.len(), args.get(1), chars.next(), chars.next()) {
+                        (2, Some(&"none"), _, _) => Ok(Command::SetBorder(None)),
+                        (2, _, Some(c), None) => Ok(Command::SetBorder(Some(c))),
+                        _ => Err("usage: set border <char>|none".to_string()),
+                    }
+                },
+                Some(&"restart") => {
+                    match (args.len(), args.get(1)) {
+                        (2, Some(&"on")) => Ok(Command::SetRestart(true)),
+                        (2, Some(&"off")) => Ok(Command::SetRestart(false)),
+                        _ => Err("usage: set restart on|off".to_string()),
+                    }
+                },
+                Some(name) => Err(format!("unknown setting: {}", name)),
+                None => Err("usage: set <setting> <value>".to_string()),
+            }
+        },
+        Some(name) => Err(format!("unknown command: {}", name)),
+        None => Err("no command given".to_string()),
+    }
+}
+
+// `split_words` splits `line` into words, which are separated by whitespace.
+// Characters inside single quotes are taken literally, and backslashes escape
+// the next character outside of single quotes.
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => {
+                match chars.next() {
+                    Some(escaped) => word.get_or_insert_with(String::new).push(escaped),
+                    None => return Err("unexpected '\\' at the end of the command".to_string()),
+                }
+            },
+            (Some(_), _) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'') | (None, '"') => {
+                word.get_or_insert_with(String::new);
+                quote = Some(c);
+            },
+            (None, _) if c.is_whitespace() => words.extend(word.take()),
+            (None, _) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("missing closing {}", q));
+    }
+    words.extend(word);
+
+    Ok(words)
+}
+
+// `quote` returns `word` quoted so that `split_words` returns it as a single
+// word.
+pub fn quote(word: &str) -> String {
+    if !word.is_empty() && !word.chars().any(|c| c.is_whitespace() || "'\"\\".contains(c)) {
+        return word.to_string();
+    }
+
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(ws: &[&str]) -> Vec<String> {
+        ws.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn quote_leaves_plain_words() {
+        assert_eq!(quote("vim"), "vim");
+        assert_eq!(quote("--restart=100"), "--restart=100");
+    }
+
+    #[test]
+    fn quote_round_trips_through_split_words() {
+        let cases = [
+            "plain",
+            "",
+            "with space",
+            "it's",
+            "\"double\"",
+            "back\\slash",
+            "tab\there",
+            "'",
+            "''\\'",
+        ];
+        for &case in cases.iter() {
+            assert_eq!(split_words(&quote(case)), Ok(words(&[case])), "{:?}", case);
+        }
+
+        let line: Vec<String> = cases.iter().map(|w| quote(w)).collect();
+        assert_eq!(split_words(&line.join(" ")), Ok(words(&cases)));
+    }
+
+    #[test]
+    fn split_words_handles_quotes_and_escapes() {
+        assert_eq!(split_words("  a  b "), Ok(words(&["a", "b"])));
+        assert_eq!(split_words("a'b c'd"), Ok(words(&["ab cd"])));
+        assert_eq!(split_words("\"a\\\"b\""), Ok(words(&["a\"b"])));
+        assert_eq!(split_words("a\\ b"), Ok(words(&["a b"])));
+        assert_eq!(split_words(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn split_words_rejects_unterminated_input() {
+        assert!(split_words("'a").is_err());
+        assert!(split_words("\"a").is_err());
+        assert!(split_words("a\\").is_err());
+    }
+}
@@ -0,0 +1,500 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+use std::ffi::{CStr, CString, NulError, OsStr, OsString};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::Path;
+use std::ptr;
+
+use libc;
+use libc::pid_t;
+
+use outbox::Enter;
+use serial::Serial;
+#[cfg(feature = "utmp")]
+use utmp;
+
+// `DEFAULT_PATH` is searched for programs if `PATH` isn't set, as it is by
+// `execvp`.
+const DEFAULT_PATH: &str = "/bin:/usr/bin";
+
+// `EXEC_FAILED` is the status that a child exits with if its program couldn't
+// be run, which is the status that shells use when a command isn't found.
+const EXEC_FAILED: i32 = 127;
+
+// `Pty` is a PTY that a child runs in. The output of the child is read from,
+// and its input is written to, the file that `file` returns.
+pub trait Pty {
+    // `file` returns the end of the PTY that the emulator uses, which doesn't
+    // block when it's read from.
+    fn file(&self) -> io::Result<File>;
+
+    // `pid` returns the ID of the child, which leads its own session, or
+    // `None` if there's no child, such as for a serial port.
+    fn pid(&self) -> Option<pid_t>;
+
+    // `resize` sets the size of the PTY, which sends `SIGWINCH` to the child.
+    fn resize(&self, cols: u16, lines: u16);
+
+    // `onlcr` returns `true` if each `\n` that's read should be preceded by
+    // `\r`, which a terminal with `ONLCR` set would do, and `enter` returns
+    // what the carriage return that the Enter key sends should be written as.
+    fn onlcr(&self) -> bool;
+
+    fn enter(&self) -> Enter;
+
+    // `flow_control` returns how the `^S` and `^Q` that are typed affect the
+    // output of the child.
+    fn flow_control(&self) -> FlowControl;
+}
+
+// `FlowControl` is how the XOFF (`^S`) and XON (`^Q`) that are typed are
+// handled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlowControl {
+    // `None` passes them to the child unchanged, and they don't affect its
+    // output.
+    None,
+    // `Terminal` passes them to the child's terminal, which stops the output
+    // of the child at XOFF and restarts it at XON, or at any key if `any` is
+    // `true`, as `IXON` and `IXANY` do.
+    Terminal{any: bool},
+    // `Emulated` is used for children that have no terminal to stop their
+    // output, so the emulator stops reading it instead, and XOFF and XON
+    // aren't passed to the child.
+    Emulated,
+}
+
+// `OpenPty` is a `Pty` that's opened using `openpty`, and whose child is
+// started using `fork`, both of which are provided by Linux, macOS and the
+// BSDs.
+pub struct OpenPty {
+    master: File,
+    pid: pid_t,
+    // `_login` is the record of the child in utmp, which is removed when the
+    // PTY is dropped.
+    #[cfg(feature = "utmp")]
+    _login: Option<utmp::Entry>,
+}
+
+impl OpenPty {
+    // `spawn` runs `argv` in a new PTY that's `cols` by `lines`, as a login
+    // shell if `login` is `true`. The child's environment is the emulator's,
+    // with the variables of `env` added or replaced. If the program can't be
+    // run then the child writes the reason to the PTY and exits with
+    // `EXEC_FAILED`, as a shell would, instead of `spawn` returning an error.
+    pub fn spawn(
+        argv: &[String],
+        env: &[(String, String)],
+        login: bool,
+        cols: u16,
+        lines: u16,
+    ) -> Result<OpenPty, String> {
+        let argv = Argv::new(argv, env, login)?;
+
+        let mut size = winsize(cols, lines);
+        let (mut master, mut slave) = (0, 0);
+        let opened = unsafe {
+            libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null_mut(), &mut size)
+        };
+        if opened != 0 {
+            return Err(format!("couldn't open a PTY: {}", io::Error::last_os_error()));
+        }
+        let master = unsafe { File::from_raw_fd(master) };
+        let slave = unsafe { File::from_raw_fd(slave) };
+        // The master is kept from the children of other panes.
+        set_flag(master.as_raw_fd(), libc::F_GETFD, libc::F_SETFD, libc::FD_CLOEXEC);
+        set_flag(master.as_raw_fd(), libc::F_GETFL, libc::F_SETFL, libc::O_NONBLOCK);
+
+        let pid = start(&argv, &slave, &master, true)?;
+
+        Ok(OpenPty{
+            master: master,
+            pid: pid,
+            #[cfg(feature = "utmp")]
+            _login: utmp::Entry::add(slave.as_raw_fd(), pid),
+        })
+    }
+}
+
+impl Pty for OpenPty {
+    fn file(&self) -> io::Result<File> {
+        self.master.try_clone()
+    }
+
+    fn pid(&self) -> Option<pid_t> {
+        Some(self.pid)
+    }
+
+    fn resize(&self, cols: u16, lines: u16) {
+        let size = winsize(cols, lines);
+        // Failures are ignored because the child may have already exited.
+        unsafe {
+            libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ as _, &size as *const libc::winsize);
+        }
+    }
+
+    fn onlcr(&self) -> bool {
+        false
+    }
+
+    fn enter(&self) -> Enter {
+        Enter::Cr
+    }
+
+    // The attributes of the terminal are read each time, because the child
+    // can change them, such as by running `stty -ixon`.
+    fn flow_control(&self) -> FlowControl {
+        let mut attrs: libc::termios = unsafe { mem::zeroed() };
+        if unsafe { libc::tcgetattr(self.master.as_raw_fd(), &mut attrs) } != 0 {
+            return FlowControl::None;
+        }
+        if attrs.c_iflag & libc::IXON == 0 {
+            FlowControl::None
+        } else {
+            FlowControl::Terminal{any: attrs.c_iflag & libc::IXANY != 0}
+        }
+    }
+}
+
+// `Pipe` is a `Pty` whose child isn't run in a terminal, and is instead
+// connected to the emulator by a pair of sockets, which behave like a pair of
+// pipes in each direction, but which can be read from and written to using one
+// file. The child's input isn't echoed, and it has no size.
+pub struct Pipe {
+    socket: File,
+    pid: pid_t,
+}
+
+impl Pipe {
+    // `spawn` runs `argv` with its standard input, output and error connected
+    // to the pipe, and is otherwise started in the same way as
+    // `OpenPty::spawn`.
+    pub fn spawn(argv: &[String], env: &[(String, String)], login: bool) -> Result<Pipe, String> {
+        let argv = Argv::new(argv, env, login)?;
+
+        let mut fds = [0; 2];
+        let created = unsafe {
+            libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr())
+        };
+        if created != 0 {
+            return Err(format!("couldn't open a pipe: {}", io::Error::last_os_error()));
+        }
+        let socket = unsafe { File::from_raw_fd(fds[0]) };
+        let child_end = unsafe { File::from_raw_fd(fds[1]) };
+        set_flag(socket.as_raw_fd(), libc::F_GETFD, libc::F_SETFD, libc::FD_CLOEXEC);
+        set_flag(socket.as_raw_fd(), libc::F_GETFL, libc::F_SETFL, libc::O_NONBLOCK);
+
+        let pid = start(&argv, &child_end, &socket, false)?;
+
+        Ok(Pipe{socket: socket, pid: pid})
+    }
+}
+
+impl Pty for Pipe {
+    fn file(&self) -> io::Result<File> {
+        self.socket.try_clone()
+    }
+
+    fn pid(&self) -> Option<pid_t> {
+        Some(self.pid)
+    }
+
+    fn resize(&self, _cols: u16, _lines: u16) {}
+
+    fn onlcr(&self) -> bool {
+        true
+    }
+
+    fn enter(&self) -> Enter {
+        Enter::Lf
+    }
+
+    fn flow_control(&self) -> FlowControl {
+        FlowControl::Emulated
+    }
+}
+
+// `ChildIo` is the `Pty` that the child of a pane is connected to.
+pub enum ChildIo {
+    OpenPty(OpenPty),
+    Pipe(Pipe),
+    Serial(Serial),
+}
+
+impl Pty for ChildIo {
+    fn file(&self) -> io::Result<File> {
+        match *self {
+            ChildIo::OpenPty(ref pty) => pty.file(),
+            ChildIo::Pipe(ref pipe) => pipe.file(),
+            ChildIo::Serial(ref port) => port.file(),
+        }
+    }
+
+    fn pid(&self) -> Option<pid_t> {
+        match *self {
+            ChildIo::OpenPty(ref pty) => pty.pid(),
+            ChildIo::Pipe(ref pipe) => pipe.pid(),
+            ChildIo::Serial(ref port) => port.pid(),
+        }
+    }
+
+    fn resize(&self, cols: u16, lines: u16) {
+        match *self {
+            ChildIo::OpenPty(ref pty) => pty.resize(cols, lines),
+            ChildIo::Pipe(ref pipe) => pipe.resize(cols, lines),
+            ChildIo::Serial(ref port) => port.resize(cols, lines),
+        }
+    }
+
+    fn onlcr(&self) -> bool {
+        match *self {
+            ChildIo::OpenPty(ref pty) => pty.onlcr(),
+            ChildIo::Pipe(ref pipe) => pipe.onlcr(),
+            ChildIo::Serial(ref port) => port.onlcr(),
+        }
+    }
+
+    fn enter(&self) -> Enter {
+        match *self {
+            ChildIo::OpenPty(ref pty) => pty.enter(),
+            ChildIo::Pipe(ref pipe) => pipe.enter(),
+            ChildIo::Serial(ref port) => port.enter(),
+        }
+    }
+
+    fn flow_control(&self) -> FlowControl {
+        match *self {
+            ChildIo::OpenPty(ref pty) => pty.flow_control(),
+            ChildIo::Pipe(ref pipe) => pipe.flow_control(),
+            ChildIo::Serial(ref port) => port.flow_control(),
+        }
+    }
+}
+
+// `onlcr` returns `bytes`, which were output by a child that isn't run in a
+// terminal, with each `\n` preceded by `\r`, as a terminal with `ONLCR` set
+// would output them, so that lines start at the first column.
+pub fn onlcr(bytes: &[u8]) -> Vec<u8> {
+    let mut translated = Vec::with_capacity(bytes.len());
+    for &byte in bytes {
+        if byte == b'\n' {
+            translated.push(b'\r');
+        }
+        translated.push(byte);
+    }
+
+    translated
+}
+
+// `hung_up` returns `true` if every process has closed the other end of `f`,
+// without reading from `f`, so that the output that's left in `f` isn't
+// consumed.
+pub fn hung_up(f: &File) -> bool {
+    let mut fds = [libc::pollfd{fd: f.as_raw_fd(), events: 0, revents: 0}];
+    let n = unsafe { libc::poll(fds.as_mut_ptr(), 1, 0) };
+
+    n > 0 && fds[0].revents & libc::POLLHUP != 0
+}
+
+// `Argv` is a program, along with its arguments and its environment, which is
+// prepared before forking, because only async-signal-safe functions can be
+// called in the child of a process that has other threads.
+//
+// The environment is passed to `execve`, rather than set in the emulator
+// before forking, so that the environment of a program that embeds the
+// emulator (see `TermEmu`) isn't changed.
+struct Argv {
+    // `file` is the path of the program, and `_args` owns the strings that
+    // `ptrs` points to.
+    file: CString,
+    _args: Vec<CString>,
+    ptrs: Vec<*const libc::c_char>,
+    // `_env` owns the strings that `env_ptrs` points to, which are of the form
+    // `name=value`.
+    _env: Vec<CString>,
+    env_ptrs: Vec<*const libc::c_char>,
+    // `prefix` starts the message that's written if the program can't be run.
+    prefix: String,
+}
+
+impl Argv {
+    // If `login` is `true` then the program is told that it's a login shell,
+    // by starting its `argv[0]` with `-`, as `login` does.
+    fn new(argv: &[String], env: &[(String, String)], login: bool) -> Result<Argv, String> {
+        let mut args: Vec<CString> =
+            match argv.iter().map(|arg| CString::new(arg.as_bytes())).collect() {
+                Ok(args) => args,
+                Err(_) => return Err("the command contains a null byte".to_string()),
+            };
+        if args.is_empty() {
+            return Err("no program was given".to_string());
+        }
+        let vars = child_env(env);
+        let path = vars.iter()
+            .find(|&&(ref name, _)| name == OsStr::new("PATH"))
+            .map_or_else(|| OsString::from(DEFAULT_PATH), |&(_, ref value)| value.clone());
+        let file = find_program(&argv[0], &path);
+        if login {
+            let name = argv[0].rsplit('/').next().unwrap_or(&argv[0]);
+            args[0] = CString::new(format!("-{}", name)).expect("`argv[0]` contains a null byte");
+        }
+        let mut ptrs: Vec<*const libc::c_char> = args.iter().map(|arg| arg.as_ptr()).collect();
+        ptrs.push(ptr::null());
+
+        let env: Vec<CString> =
+            match vars.into_iter().map(|(name, value)| env_entry(name, value)).collect() {
+                Ok(env) => env,
+                Err(_) => return Err("the environment contains a null byte".to_string()),
+            };
+        let mut env_ptrs: Vec<*const libc::c_char> = env.iter().map(|var| var.as_ptr()).collect();
+        env_ptrs.push(ptr::null());
+
+        Ok(Argv{
+            file: file,
+            _args: args,
+            ptrs: ptrs,
+            _env: env,
+            env_ptrs: env_ptrs,
+            prefix: format!("couldn't run '{}': ", argv[0]),
+        })
+    }
+}
+
+// `child_env` returns the emulator's environment, with the variables of `env`
+// added or replaced.
+fn child_env(env: &[(String, String)]) -> Vec<(OsString, OsString)> {
+    let mut vars: Vec<(OsString, OsString)> = env::vars_os()
+        .filter(|&(ref name, _)| !env.iter().any(|&(ref n, _)| OsStr::new(n) == name))
+        .collect();
+    vars.extend(env.iter().map(|&(ref name, ref value)| (OsString::from(name), OsString::from(value))));
+
+    vars
+}
+
+fn env_entry(name: OsString, value: OsString) -> Result<CString, NulError> {
+    let mut entry = name.into_vec();
+    entry.push(b'=');
+    entry.extend(value.into_vec());
+
+    CString::new(entry)
+}
+
+// `find_program` returns the path of the program called `name`, searching the
+// directories of `path` if `name` doesn't contain a `/`, as `execvp` does. An
+// empty directory means the current directory. `name` is returned if the
+// program isn't found, so that `execve` fails, and the child reports why.
+fn find_program(name: &str, path: &OsStr) -> CString {
+    let fallback = || CString::new(name.as_bytes()).unwrap_or_default();
+    if name.contains('/') {
+        return fallback();
+    }
+
+    for dir in path.as_bytes().split(|&b| b == b':') {
+        let dir = if dir.is_empty() { Path::new(".") } else { Path::new(OsStr::from_bytes(dir)) };
+        let candidate = dir.join(name);
+        let executable = fs::metadata(&candidate)
+            .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false);
+        if executable {
+            if let Ok(file) = CString::new(candidate.into_os_string().into_vec()) {
+                return file;
+            }
+        }
+    }
+
+    fallback()
+}
+
+// `start` forks a child that runs `argv` with `fd` as its standard input,
+// output and error, and closes `other`, which is the emulator's end of `fd`. If
+// `controlling` is `true` then `fd` becomes the controlling terminal of the
+// child, so that it receives the signals that the terminal sends, such as
+// `SIGINT` when `^C` is typed.
+fn start(argv: &Argv, fd: &File, other: &File, controlling: bool) -> Result<pid_t, String> {
+    match unsafe { libc::fork() } {
+        -1 => Err(format!("couldn't start a child: {}", io::Error::last_os_error())),
+        0 => unsafe {
+            libc::setsid();
+            if controlling {
+                libc::ioctl(fd.as_raw_fd(), libc::TIOCSCTTY as _, 0);
+            }
+            for target in 0..3 {
+                libc::dup2(fd.as_raw_fd(), target);
+            }
+            libc::close(fd.as_raw_fd());
+            libc::close(other.as_raw_fd());
+            // Rust ignores `SIGPIPE`, which would otherwise be inherited by the
+            // program.
+            libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+
+            libc::execve(argv.file.as_ptr(), argv.ptrs.as_ptr(), argv.env_ptrs.as_ptr());
+
+            let code = io::Error::last_os_error().raw_os_error().unwrap_or(0);
+            let reason = CStr::from_ptr(libc::strerror(code));
+            write_all(libc::STDERR_FILENO, argv.prefix.as_bytes());
+            write_all(libc::STDERR_FILENO, reason.to_bytes());
+            write_all(libc::STDERR_FILENO, b"\n");
+            libc::_exit(EXEC_FAILED);
+        },
+        pid => Ok(pid),
+    }
+}
+
+fn winsize(cols: u16, lines: u16) -> libc::winsize {
+    libc::winsize{ws_row: lines, ws_col: cols, ws_xpixel: 0, ws_ypixel: 0}
+}
+
+// `set_flag` sets `flag` in the flags of `fd` that are read using `get` and
+// written using `set`.
+fn set_flag(fd: RawFd, get: libc::c_int, set: libc::c_int, flag: libc::c_int) {
+    unsafe {
+        let flags = libc::fcntl(fd, get);
+        if flags != -1 {
+            libc::fcntl(fd, set, flags | flag);
+        }
+    }
+}
+
+// `write_all` writes `bytes` to `fd`, and is safe to call after forking.
+fn write_all(fd: RawFd, mut bytes: &[u8]) {
+    while !bytes.is_empty() {
+        let n = unsafe { libc::write(fd, bytes.as_ptr() as *const libc::c_void, bytes.len()) };
+        if n <= 0 {
+            return;
+        }
+        bytes = &bytes[n as usize..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn onlcr_precedes_newlines_with_carriage_returns() {
+        assert_eq!(onlcr(b"a\nb\n"), b"a\r\nb\r\n".to_vec());
+        assert_eq!(onlcr(b"\n\n"), b"\r\n\r\n".to_vec());
+    }
+
+    #[test]
+    fn onlcr_leaves_other_bytes() {
+        assert_eq!(onlcr(b""), b"".to_vec());
+        assert_eq!(onlcr(b"abc\r"), b"abc\r".to_vec());
+    }
+
+    // A `\r` that's already before a `\n` is kept, as a terminal with `ONLCR`
+    // set would keep it.
+    #[test]
+    fn onlcr_keeps_existing_carriage_returns() {
+        assert_eq!(onlcr(b"a\r\n"), b"a\r\r\n".to_vec());
+    }
+}
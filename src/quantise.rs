@@ -0,0 +1,205 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+#[cfg(feature = "curses")]
+use pancurses;
+
+// `Depth` is the number of colours that the host curses session can display.
+// Curses numbers its first 16 colours in the same order as the SGR colour
+// numbering, and terminals that support 256 colours number the rest according
+// to the xterm palette, so a colour in a given `Depth` can be passed to curses
+// directly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Depth {
+    Colours8,
+    Colours16,
+    Colours256,
+}
+
+impl Depth {
+    // `detect` should only be called after `pancurses::start_color()`.
+    #[cfg(feature = "curses")]
+    pub fn detect() -> Depth {
+        let n = pancurses::COLORS();
+        if n >= 256 {
+            Depth::Colours256
+        } else if n >= 16 {
+            Depth::Colours16
+        } else {
+            Depth::Colours8
+        }
+    }
+
+    pub fn len(self) -> usize {
+        match self {
+            Depth::Colours8 => 8,
+            Depth::Colours16 => 16,
+            Depth::Colours256 => 256,
+        }
+    }
+}
+
+// `quantise` returns the colour in `depth` that is nearest to the 24-bit colour
+// `(r, g, b)`.
+pub fn quantise(depth: Depth, r: u8, g: u8, b: u8) -> u8 {
+    match depth {
+        Depth::Colours8 | Depth::Colours16 => {
+            nearest_of(0..depth.len() as u8, r, g, b)
+        },
+        Depth::Colours256 => {
+            // We only consider the colour cube and the greyscale ramp because
+            // the first 16 colours are commonly redefined by the user's theme,
+            // whereas the rest of the palette is fixed.
+            let cube = 16
+                + 36 * nearest_cube_level(r)
+                + 6 * nearest_cube_level(g)
+                + nearest_cube_level(b);
+            let grey = nearest_of(232..=255, r, g, b);
+
+            if dist(xterm_rgb(cube), (r, g, b)) <= dist(xterm_rgb(grey), (r, g, b)) {
+                cube
+            } else {
+                grey
+            }
+        },
+    }
+}
+
+// `reduce` returns the colour in `depth` that is nearest to `index`, which is a
+// colour in the xterm 256-colour palette.
+pub fn reduce(depth: Depth, index: u8) -> u8 {
+    if (index as usize) < depth.len() {
+        return index;
+    }
+    let (r, g, b) = xterm_rgb(index);
+
+    quantise(depth, r, g, b)
+}
+
+// `xterm_rgb` returns the 24-bit colour that xterm uses for `index` by default.
+pub fn xterm_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_RGB[index as usize],
+        16..=231 => {
+            let i = index - 16;
+
+            (
+                CUBE_LEVELS[(i / 36) as usize],
+                CUBE_LEVELS[((i / 6) % 6) as usize],
+                CUBE_LEVELS[(i % 6) as usize],
+            )
+        },
+        232..=255 => {
+            let v = 8 + 10 * (index - 232);
+
+            (v, v, v)
+        },
+    }
+}
+
+const ANSI_RGB: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xcd, 0x00, 0x00),
+    (0x00, 0xcd, 0x00),
+    (0xcd, 0xcd, 0x00),
+    (0x00, 0x00, 0xee),
+    (0xcd, 0x00, 0xcd),
+    (0x00, 0xcd, 0xcd),
+    (0xe5, 0xe5, 0xe5),
+    (0x7f, 0x7f, 0x7f),
+    (0xff, 0x00, 0x00),
+    (0x00, 0xff, 0x00),
+    (0xff, 0xff, 0x00),
+    (0x5c, 0x5c, 0xff),
+    (0xff, 0x00, 0xff),
+    (0x00, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+const CUBE_LEVELS: [u8; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+fn nearest_cube_level(v: u8) -> u8 {
+    let mut nearest = 0;
+    for (i, &level) in CUBE_LEVELS.iter().enumerate() {
+        if sq_diff(v, level) < sq_diff(v, CUBE_LEVELS[nearest]) {
+            nearest = i;
+        }
+    }
+
+    nearest as u8
+}
+
+fn nearest_of<I: Iterator<Item = u8>>(indices: I, r: u8, g: u8, b: u8) -> u8 {
+    let mut nearest = 0;
+    let mut nearest_dist = u32::max_value();
+    for i in indices {
+        let d = dist(xterm_rgb(i), (r, g, b));
+        if d < nearest_dist {
+            nearest = i;
+            nearest_dist = d;
+        }
+    }
+
+    nearest
+}
+
+fn dist(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    sq_diff(a.0, b.0) + sq_diff(a.1, b.1) + sq_diff(a.2, b.2)
+}
+
+fn sq_diff(a: u8, b: u8) -> u32 {
+    let d = (a as i32) - (b as i32);
+
+    (d * d) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantise_finds_nearest_colour_in_each_depth() {
+        let tests = [
+            (Depth::Colours8, (0xff, 0x00, 0x00), 1),
+            (Depth::Colours16, (0xff, 0x00, 0x00), 9),
+            (Depth::Colours256, (0xff, 0x00, 0x00), 196),
+            (Depth::Colours8, (0xff, 0xff, 0xff), 7),
+            (Depth::Colours16, (0xff, 0xff, 0xff), 15),
+            (Depth::Colours256, (0xff, 0xff, 0xff), 231),
+            (Depth::Colours256, (0x00, 0x00, 0x00), 16),
+        ];
+
+        for &(depth, (r, g, b), expected) in tests.iter() {
+            assert_eq!(quantise(depth, r, g, b), expected, "{:?} {:?}", depth, (r, g, b));
+        }
+    }
+
+    #[test]
+    fn quantise_prefers_greyscale_ramp_for_greys() {
+        assert_eq!(quantise(Depth::Colours256, 0x80, 0x80, 0x80), 244);
+    }
+
+    #[test]
+    fn reduce_keeps_colours_in_depth() {
+        assert_eq!(reduce(Depth::Colours8, 5), 5);
+        assert_eq!(reduce(Depth::Colours16, 13), 13);
+        assert_eq!(reduce(Depth::Colours256, 200), 200);
+    }
+
+    #[test]
+    fn reduce_approximates_colours_outside_depth() {
+        assert_eq!(reduce(Depth::Colours8, 196), 1);
+        assert_eq!(reduce(Depth::Colours16, 196), 9);
+        assert_eq!(reduce(Depth::Colours8, 9), 1);
+    }
+
+    #[test]
+    fn xterm_rgb_covers_cube_and_greyscale_ramp() {
+        assert_eq!(xterm_rgb(16), (0x00, 0x00, 0x00));
+        assert_eq!(xterm_rgb(67), (0x5f, 0x87, 0xaf));
+        assert_eq!(xterm_rgb(231), (0xff, 0xff, 0xff));
+        assert_eq!(xterm_rgb(232), (0x08, 0x08, 0x08));
+        assert_eq!(xterm_rgb(255), (0xee, 0xee, 0xee));
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// `HEADER_LEN` is the length of the header of each frame of a recording, which
+// holds the seconds and microseconds of the time that the frame was recorded
+// at, followed by the length of its data, each as a little-endian `u32`. This
+// is the format that `ttyrec` uses, so recordings can be played by `ttyplay`
+// and other players, and `tep play` can play recordings made by `ttyrec`.
+const HEADER_LEN: usize = 12;
+
+// `MAX_FRAME_LEN` limits the length of the frames that are read, so that a
+// corrupt recording isn't read into memory in its entirety.
+const MAX_FRAME_LEN: usize = 1 << 20;
+
+// `Recorder` records the output of a child, with the time that each part of it
+// was output, so that it can be played back using `play`.
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    // `create` truncates the file at `path` if it exists.
+    pub fn create(path: &Path) -> Result<Recorder, String> {
+        match File::create(path) {
+            Ok(f) => Ok(Recorder{out: BufWriter::new(f)}),
+            Err(e) => Err(format!("couldn't create '{}': {}", path.display(), e)),
+        }
+    }
+
+    // `write` records `bytes` as output at the current time. The frame is
+    // flushed so that the recording can be played while it's being made.
+    pub fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut header = [0; HEADER_LEN];
+        put_u32(&mut header[0..4], now.as_secs() as u32);
+        put_u32(&mut header[4..8], now.subsec_micros());
+        put_u32(&mut header[8..12], bytes.len() as u32);
+        self.out.write_all(&header)?;
+        self.out.write_all(bytes)?;
+
+        self.out.flush()
+    }
+}
+
+// `play` writes the frames of the recording at `path` to `out`, waiting between
+// frames for the time that passed between them when they were recorded,
+// divided by `speed`. Waits are limited to `max_wait`, if it's given, so that
+// idle periods can be skipped.
+pub fn play<W: Write>(path: &Path, out: &mut W, speed: f64, max_wait: Option<Duration>) -> Result<(), String> {
+    let f =
+        match File::open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(format!("couldn't open '{}': {}", path.display(), e)),
+        };
+    let mut r = BufReader::new(f);
+
+    let mut last: Option<Duration> = None;
+    loop {
+        let (time, data) =
+            match read_frame(&mut r) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return Ok(()),
+                Err(e) => return Err(format!("couldn't read '{}': {}", path.display(), e)),
+            };
+        if let Some(last) = last {
+            // Frames that were recorded out of order, such as after the
+            // clock was changed, are played without waiting.
+            if let Some(gap) = time.checked_sub(last) {
+                let mut wait = gap.div_f64(speed);
+                if let Some(max_wait) = max_wait {
+                    wait = wait.min(max_wait);
+                }
+                thread::sleep(wait);
+            }
+        }
+        last = Some(time);
+
+        if let Err(e) = out.write_all(&data).and_then(|_| out.flush()) {
+            return Err(format!("couldn't write output: {}", e));
+        }
+    }
+}
+
+// `read_frame` returns the time and data of the next frame of `r`, or `None`
+// if `r` ends before the frame.
+fn read_frame<R: Read>(r: &mut R) -> io::Result<Option<(Duration, Vec<u8>)>> {
+    let mut header = [0; HEADER_LEN];
+    match r.read_exact(&mut header) {
+        Ok(()) => {},
+        Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let secs = get_u32(&header[0..4]);
+    let micros = get_u32(&header[4..8]);
+    let len = get_u32(&header[8..12]) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(ErrorKind::InvalidData, format!("frame is too long ({} bytes)", len)));
+    }
+    let mut data = vec![0; len];
+    r.read_exact(&mut data)?;
+
+    Ok(Some((Duration::from_secs(secs as u64) + Duration::from_micros(micros as u64), data)))
+}
+
+fn put_u32(buf: &mut [u8], v: u32) {
+    buf[0] = v as u8;
+    buf[1] = (v >> 8) as u8;
+    buf[2] = (v >> 16) as u8;
+    buf[3] = (v >> 24) as u8;
+}
+
+fn get_u32(buf: &[u8]) -> u32 {
+    buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn read_frame_reads_ttyrec_frames() {
+        let mut r = Cursor::new(b"\x02\x00\x00\x00\x40\x42\x0f\x00\x02\x00\x00\x00hi".to_vec());
+
+        let frame = read_frame(&mut r).unwrap();
+
+        assert_eq!(frame, Some((Duration::new(3, 0), b"hi".to_vec())));
+        assert_eq!(read_frame(&mut r).unwrap(), None);
+    }
+
+    #[test]
+    fn read_frame_rejects_truncated_frames() {
+        let mut r = Cursor::new(b"\x00\x00\x00\x00\x00\x00\x00\x00\x05\x00\x00\x00hi".to_vec());
+
+        assert_eq!(read_frame(&mut r).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn put_u32_is_little_endian() {
+        let mut buf = [0; 4];
+        put_u32(&mut buf, 0x1234_5678);
+
+        assert_eq!(buf, [0x78, 0x56, 0x34, 0x12]);
+        assert_eq!(get_u32(&buf), 0x1234_5678);
+    }
+}
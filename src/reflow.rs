@@ -0,0 +1,368 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+use std::io;
+
+use alacritty::ansi::{Color, NamedColor, Processor};
+use alacritty::index::{Column, Line, Point};
+use alacritty::Term;
+use alacritty::term::cell::{Cell, Flags};
+use alacritty::term::mode::TermMode;
+
+use colour;
+use extras::{Extras, Style};
+use tap::LineSize;
+
+// `Snapshot` holds the contents of the screen of a terminal as logical lines,
+// which are the lines of the screen with the lines that were wrapped by the
+// terminal joined together. `alacritty::Term` truncates lines when it's made
+// narrower, so we take a `Snapshot` before resizing and replay it afterwards
+// to rewrap the lines to the new width.
+pub struct Snapshot {
+    lines: Vec<Vec<Cell>>,
+    // `extras` holds what was recorded in the `Extras` of the terminal for each
+    // cell of `lines`, and `line_sizes` holds the size of each line, so that
+    // they can be restored along with the cells.
+    extras: Vec<Vec<CellExtras>>,
+    line_sizes: Vec<LineSize>,
+    // `cursor` is the position of the cursor, as the index of its logical line
+    // and its offset within that line.
+    cursor: (usize, usize),
+}
+
+// `CellExtras` is what `Extras` records for a single cell.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct CellExtras {
+    style: Style,
+    zero_width: String,
+    wide: bool,
+}
+
+impl CellExtras {
+    fn get(extras: &Extras, line: Line, col: Column) -> CellExtras {
+        CellExtras{
+            style: extras.get(line, col),
+            zero_width: extras.zero_width(line, col).to_string(),
+            wide: extras.is_wide(line, col),
+        }
+    }
+
+    // `restore` records `self` at `point` of `extras`. Cells without a style
+    // aren't recorded, as recording a cell makes the character before it
+    // narrow (see `Extras::record`).
+    fn restore(&self, extras: &mut Extras, point: Point) {
+        if self.style != Style::default() {
+            extras.record(point, self.style);
+        }
+        for c in self.zero_width.chars() {
+            extras.push_zero_width(point, c);
+        }
+        if self.wide {
+            extras.set_wide(point);
+        }
+    }
+}
+
+// `capture` returns `None` if the alternate screen is active, as the programs
+// that use the alternate screen redraw it when the terminal is resized.
+// `extras` are the `Extras` of `term`.
+pub fn capture(term: &Term, extras: &Extras) -> Option<Snapshot> {
+    if term.mode().contains(TermMode::ALT_SCREEN) {
+        return None;
+    }
+
+    let grid = term.grid();
+    let num_cols = grid.num_cols();
+    let cursor = term.cursor().point;
+
+    let mut lines: Vec<Vec<Cell>> = vec![];
+    let mut lines_extras: Vec<Vec<CellExtras>> = vec![];
+    let mut line_sizes: Vec<LineSize> = vec![];
+    let mut logical: Vec<Cell> = vec![];
+    let mut logical_extras: Vec<CellExtras> = vec![];
+    let mut cursor_pos = (0, 0);
+    let mut line = Line(0);
+    while line < grid.num_lines() {
+        if line == cursor.line {
+            cursor_pos = (lines.len(), logical.len() + cursor.col.0);
+        }
+        // A logical line has the size of the first line that it starts on.
+        if logical.is_empty() {
+            line_sizes.push(extras.line_size(line));
+        }
+
+        let row = &grid[line];
+        let wrapped = row[num_cols - 1].flags.contains(Flags::WRAPLINE);
+        let mut col = Column(0);
+        while col < num_cols {
+            logical.push(row[col]);
+            logical_extras.push(CellExtras::get(extras, line, col));
+            col += 1;
+        }
+
+        if !wrapped {
+            let len = logical.iter()
+                .zip(&logical_extras)
+                .rposition(|(c, e)| !is_blank(c) || *e != CellExtras::default())
+                .map_or(0, |i| i + 1);
+            logical.truncate(len);
+            logical_extras.truncate(len);
+            lines.push(logical);
+            lines_extras.push(logical_extras);
+            logical = vec![];
+            logical_extras = vec![];
+        }
+        line += 1;
+    }
+    if !logical.is_empty() {
+        lines.push(logical);
+        lines_extras.push(logical_extras);
+    }
+
+    // Blank lines below the cursor are dropped so that they don't push the
+    // rest of the screen up when the terminal is made narrower.
+    let last = lines.iter().rposition(|l| !l.is_empty()).unwrap_or(0);
+    let n = cmp::max(last, cursor_pos.0) + 1;
+    lines.truncate(n);
+    lines_extras.truncate(n);
+    line_sizes.truncate(n);
+
+    Some(Snapshot{
+        lines: lines,
+        extras: lines_extras,
+        line_sizes: line_sizes,
+        cursor: cursor_pos,
+    })
+}
+
+// `replay` redraws `snapshot` on `term`, which should have been resized since
+// `snapshot` was captured, by feeding `parser` the output that a program would
+// write to draw it, and restores what `snapshot` holds of the `Extras` of the
+// terminal in `extras`, which should be empty.
+//
+// Lines that no longer fit on the screen once they're rewrapped would scroll
+// off the top, so their rows are returned instead, oldest first, so that they
+// can be added to the scrollback.
+pub fn replay(snapshot: &Snapshot, term: &mut Term, parser: &mut Processor, extras: &mut Extras) -> Vec<Vec<Cell>> {
+    let num_cols = term.grid().num_cols().0;
+    let num_lines = term.grid().num_lines().0;
+
+    let heights: Vec<usize> = snapshot.lines
+        .iter()
+        .map(|line| cmp::max((line.len() + num_cols - 1) / num_cols, 1))
+        .collect();
+    let scrolled = heights.iter().sum::<usize>().saturating_sub(num_lines);
+
+    let mut out = String::from("\x1b[0m\x1b[H\x1b[2J");
+    let mut scrolled_rows = vec![];
+    // `top` is the row that the current line starts on, counting the rows
+    // that are scrolled off.
+    let mut top = 0;
+    let mut cursor = (0, 0);
+    for (i, line) in snapshot.lines.iter().enumerate() {
+        if i == snapshot.cursor.0 {
+            let offset = snapshot.cursor.1;
+            cursor = (top + offset / num_cols, offset % num_cols);
+        }
+
+        let skipped = cmp::min(scrolled.saturating_sub(top), heights[i]);
+        for k in 0..skipped {
+            scrolled_rows.push(row(line, k, num_cols, k + 1 < heights[i]));
+        }
+        if skipped < heights[i] {
+            if top + skipped > scrolled {
+                out.push_str("\x1b[0m\r\n");
+            }
+            let start = cmp::min(skipped * num_cols, line.len());
+            encode(&line[start..], &mut out);
+
+            for (offset, e) in snapshot.extras[i].iter().enumerate().skip(start) {
+                let point = Point{line: Line(top + offset / num_cols - scrolled), col: Column(offset % num_cols)};
+                e.restore(extras, point);
+            }
+            for r in top + skipped..top + heights[i] {
+                extras.set_line_size(Line(r - scrolled), snapshot.line_sizes[i]);
+            }
+        }
+        top += heights[i];
+    }
+
+    // The cursor moves up with the lines that scrolled off the top.
+    let row = cmp::min(cursor.0.saturating_sub(scrolled), num_lines - 1);
+    out.push_str(&format!("\x1b[0m\x1b[{};{}H", row + 1, cursor.1 + 1));
+
+    let mut sink = io::sink();
+    for byte in out.as_bytes() {
+        parser.advance(term, *byte, &mut sink);
+    }
+
+    scrolled_rows
+}
+
+// `row` returns the `k`th row of `line` when it's wrapped at `num_cols`
+// columns, which is marked as wrapping onto the next row if `wrapped` is
+// `true`, as the terminal would mark it.
+fn row(line: &[Cell], k: usize, num_cols: usize, wrapped: bool) -> Vec<Cell> {
+    let start = cmp::min(k * num_cols, line.len());
+    let end = cmp::min(start + num_cols, line.len());
+    let mut cells = line[start..end].to_vec();
+    for cell in &mut cells {
+        cell.flags.remove(Flags::WRAPLINE);
+    }
+    if wrapped {
+        if let Some(last) = cells.last_mut() {
+            last.flags.insert(Flags::WRAPLINE);
+        }
+    }
+
+    cells
+}
+
+// `encode` appends the output that draws `cells`, with their attributes, to
+// `out`.
+pub fn encode(cells: &[Cell], out: &mut String) {
+    let mut sgr = String::new();
+    for cell in cells {
+        if cell.flags.contains(Flags::WIDE_CHAR_SPACER) {
+            continue;
+        }
+        let cell_sgr = cell_sgr(cell);
+        if cell_sgr != sgr {
+            out.push_str(&cell_sgr);
+            sgr = cell_sgr;
+        }
+        out.push(cell.c);
+    }
+}
+
+pub fn is_blank(cell: &Cell) -> bool {
+    cell.c == ' ' &&
+        cell.bg == Color::Named(NamedColor::Background) &&
+        !cell.flags.intersects(Flags::INVERSE | Flags::UNDERLINE)
+}
+
+// `cell_sgr` returns the SGR sequence that sets the attributes of `cell`.
+fn cell_sgr(cell: &Cell) -> String {
+    let mut params = vec!["0".to_string()];
+    let mappings = [
+        (Flags::BOLD, "1"),
+        (Flags::DIM, "2"),
+        (Flags::ITALIC, "3"),
+        (Flags::UNDERLINE, "4"),
+        (Flags::INVERSE, "7"),
+        (Flags::HIDDEN, "8"),
+    ];
+    for (flag, param) in mappings.iter() {
+        if cell.flags.contains(*flag) {
+            params.push(param.to_string());
+        }
+    }
+    params.push(colour_sgr(cell.fg, 30));
+    params.push(colour_sgr(cell.bg, 40));
+
+    format!("\x1b[{}m", params.join(";"))
+}
+
+// `colour_sgr` returns the SGR parameters that set a colour to `c`, where
+// `base` is `30` for the foreground colour and `40` for the background colour.
+fn colour_sgr(c: Color, base: u8) -> String {
+    match c {
+        Color::Named(NamedColor::Foreground) |
+        Color::Named(NamedColor::Background) => format!("{}", base + 9),
+        Color::Named(name) => {
+            let index = colour::named_to_index(name);
+            if index < 8 {
+                format!("{}", base + index)
+            } else {
+                format!("{}", base + 60 + index - 8)
+            }
+        },
+        Color::Indexed(i) => format!("{};5;{}", base + 8, i),
+        Color::Spec(rgb) => format!("{};2;{};{};{}", base + 8, rgb.r, rgb.g, rgb.b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alacritty::config::Config as TermConfig;
+
+    use pane::new_size_info;
+    use scrollback;
+
+    fn term(lines: i32, cols: i32, output: &str) -> Term {
+        let mut term = Term::new(&TermConfig::default(), new_size_info(cols, lines));
+        let mut parser = Processor::new();
+        for byte in output.as_bytes() {
+            parser.advance(&mut term, *byte, &mut io::sink());
+        }
+
+        term
+    }
+
+    fn text(cells: &[Cell]) -> String {
+        cells.iter().map(|c| c.c).collect()
+    }
+
+    fn resize(term: &mut Term, lines: i32, cols: i32, extras: &mut Extras) -> Vec<Vec<Cell>> {
+        let snapshot = capture(term, extras).unwrap();
+        term.resize(&new_size_info(cols, lines));
+        extras.clear();
+
+        replay(&snapshot, term, &mut Processor::new(), extras)
+    }
+
+    #[test]
+    fn replay_rewraps_lines() {
+        let mut t = term(3, 4, "abc\r\nd");
+
+        let scrolled = resize(&mut t, 3, 2, &mut Extras::new());
+
+        assert!(scrolled.is_empty());
+        let rows: Vec<String> = (0..3).map(|l| text(&scrollback::copy_row(&t, Line(l)))).collect();
+        assert_eq!(rows, vec!["ab", "c ", "d "]);
+    }
+
+    #[test]
+    fn replay_returns_rows_that_scroll_off() {
+        let mut t = term(2, 4, "abcd\r\nef");
+
+        let scrolled = resize(&mut t, 2, 2, &mut Extras::new());
+
+        assert_eq!(scrolled.len(), 1);
+        assert_eq!(text(&scrolled[0]), "ab");
+        assert!(scrolled[0][1].flags.contains(Flags::WRAPLINE));
+        assert_eq!(t.grid()[Line(0)][Column(0)].c, 'c');
+        assert_eq!(t.grid()[Line(1)][Column(0)].c, 'e');
+    }
+
+    #[test]
+    fn replay_moves_extras_with_their_cells() {
+        let mut t = term(3, 4, "abc");
+        let mut extras = Extras::new();
+        let point = Point{line: Line(0), col: Column(2)};
+        let style = Style{undercurl: true, ..Style::default()};
+        extras.record(point, style);
+        extras.push_zero_width(point, '\u{301}');
+        extras.set_line_size(Line(0), LineSize::DoubleWidth);
+
+        resize(&mut t, 3, 2, &mut extras);
+
+        assert_eq!(extras.get(Line(1), Column(0)), style);
+        assert_eq!(extras.zero_width(Line(1), Column(0)), "\u{301}");
+        assert_eq!(extras.get(Line(0), Column(2)), Style::default());
+        assert_eq!(extras.line_size(Line(0)), LineSize::DoubleWidth);
+        assert_eq!(extras.line_size(Line(1)), LineSize::DoubleWidth);
+        assert_eq!(extras.line_size(Line(2)), LineSize::Single);
+    }
+
+    #[test]
+    fn capture_skips_alternate_screen() {
+        let t = term(2, 2, "\x1b[?1049h");
+
+        assert!(capture(&t, &Extras::new()).is_none());
+    }
+}
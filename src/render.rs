@@ -0,0 +1,513 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+use std::convert::TryInto;
+
+use alacritty::index::{Column, Line, Point};
+use alacritty::Term;
+use alacritty::term::cell::{Cell, Flags};
+use alacritty::term::mode::TermMode;
+
+use acs;
+use colour;
+use colour::Spec;
+use config::{Fallback, Fallbacks, LineDrawing};
+use extras::{Extras, Style};
+use grapheme;
+use grapheme::Cluster;
+use palette::{Palette, Rgb};
+#[cfg(feature = "curses")]
+use picker::Picker;
+use renderer::{Attrs, Glyph, Renderer};
+use scrollback;
+use scrollback::Scrollback;
+use search;
+use search::Match;
+use selection::Selection;
+use tap::{CursorShape, LineSize};
+
+// `Context` holds the state that's used to render terminals, and that persists
+// between renders.
+pub struct Context {
+    // `colour_support` is `None` if we're rendering without colour, in which
+    // case only cell attributes are rendered.
+    pub colour_support: Option<colour::Support>,
+    pub fallbacks: Fallbacks,
+    // `selection_colours` holds the foreground and background colours used to
+    // draw selected cells. Selected cells are drawn in reverse video if
+    // `selection_colours` is `None`.
+    pub selection_colours: Option<(Rgb, Rgb)>,
+    // `match_colours` holds the foreground and background colours used to
+    // draw cells that are part of search matches. Such cells are drawn in
+    // bold and underlined if `match_colours` is `None`.
+    pub match_colours: Option<(Rgb, Rgb)>,
+    // `active_border_colours` holds the foreground and background colours
+    // used to draw the border of the active pane when it's highlighted. The
+    // border is drawn in bold if `active_border_colours` is `None`.
+    pub active_border_colours: Option<(Rgb, Rgb)>,
+    // `cursor_shape` is the cursor shape most recently requested by the child,
+    // or `None` if the default cursor shape should be used.
+    pub cursor_shape: Option<CursorShape>,
+    // `cursor_blink_on` is `false` while a blinking cursor is in the "off"
+    // phase of its blink.
+    pub cursor_blink_on: bool,
+    // `utf8` is `true` if the host can display non-ASCII characters.
+    pub utf8: bool,
+    // `ambiguous_wide` is `true` if the host displays East Asian
+    // ambiguous-width characters using two columns.
+    pub ambiguous_wide: bool,
+    pub line_drawing: LineDrawing,
+}
+
+// `render_term` draws `term` using `r`, with the colours of `palette`, which
+// belongs to the pane of `term`. If `scrollback` is given then the viewport is
+// drawn at its offset. The border, and `label`, are drawn using
+// `Context::active_border_colours` if `highlight_border` is `true`.
+pub fn render_term<R: Renderer>(
+    term: &Term,
+    r: &mut R,
+    ctx: &Context,
+    palette: &Palette,
+    extras: &Extras,
+    scrollback: Option<&Scrollback>,
+    selection: Option<&Selection>,
+    matches: &[Match],
+    border_char: char,
+    label: Option<&str>,
+    highlight_border: bool,
+) {
+    r.clear();
+    let border_attrs = if highlight_border { active_border_attrs(ctx) } else { Attrs::default() };
+    let border = Glyph::Char(border_char);
+
+    let (y, x) = r.size();
+    for i in 0..y {
+        r.draw_cell(i, 0, border, border_attrs);
+        r.draw_cell(i, x-1, border, border_attrs);
+    }
+    for i in 0..x {
+        r.draw_cell(0, i, border, border_attrs);
+        r.draw_cell(y-1, i, border, border_attrs);
+    }
+    // `label` is drawn over the top border, and is used to show the title of
+    // the pane and the state of the emulator (such as whether the primary
+    // screen is being viewed). Long labels are cut off before the corner.
+    if let Some(label) = label {
+        let label: String = format!(" {} ", label).chars().take(cmp::max(x - 4, 0) as usize).collect();
+        r.draw_cell(0, 2, Glyph::Text(&label), border_attrs);
+    }
+
+    let offset = scrollback.map_or(0, |s| s.offset());
+    // The position of the viewport is drawn over the right of the top border
+    // while it's scrolled back, as the number of lines that it's scrolled back
+    // by out of the number of lines in the scrollback.
+    if let Some(scrollback) = scrollback {
+        if offset > 0 {
+            let indicator = format!(" [{}/{}] ", offset, scrollback.len());
+            let col = x - 2 - indicator.len() as i32;
+            if col > 0 {
+                r.draw_cell(0, col, Glyph::Text(&indicator), Attrs::default());
+            }
+        }
+    }
+
+    let cursor = term.cursor().point;
+    let cursor_attr = if offset == 0 { cursor_attr(term, ctx) } else { None };
+
+    let grid = term.grid();
+    let num_cols = grid.num_cols().0;
+    let matched = search::mark_matches(matches, grid.num_lines().0, num_cols);
+    // Lines of the scrollback are drawn without `Extras`.
+    let no_extras = Extras::new();
+    for (i, (cells, live)) in screen_rows(term, scrollback).into_iter().enumerate() {
+        let line = Line(i);
+        let (row_extras, row_line) =
+            match live {
+                Some(l) => (extras, l),
+                None => (&no_extras, line),
+            };
+        let scale = line_scale(row_extras, row_line);
+        for cluster in grapheme::clusters(&cells, row_extras, row_line) {
+            let col = cluster.col;
+            // Characters that don't fit on double-width lines are dropped, as
+            // they are by physical terminals.
+            if (col.0 + cluster.span) * scale > num_cols {
+                break;
+            }
+            let cell = cells[col.0];
+            let selected = selection.map_or(false, |s| s.contains(line.0, col.0));
+            let mut attrs = cell_attrs(ctx, palette, &cell, row_extras.get(row_line, col), selected, matched[line.0][col.0], r.passthrough());
+            if live == Some(cursor.line) && col == cursor.col {
+                if let Some(attr) = cursor_attr {
+                    attrs.toggle(attr);
+                }
+            }
+
+            let x = (col.0 * scale) as i32 + 1;
+            draw_cluster(r, (line.0 as i32) + 1, x, &cluster, scale, attrs, ctx);
+        }
+    }
+
+    place_cursor(term, r, ctx, extras, offset);
+
+    r.present();
+}
+
+// `render_cursor` only redraws the cell under the cursor, and is used when the
+// cursor blinks so that the rest of the screen doesn't have to be redrawn. The
+// arguments should be the same as those last passed to `render_term`.
+pub fn render_cursor<R: Renderer>(
+    term: &Term,
+    r: &mut R,
+    ctx: &Context,
+    palette: &Palette,
+    extras: &Extras,
+    scrollback: Option<&Scrollback>,
+    selection: Option<&Selection>,
+    matches: &[Match],
+) {
+    // The cursor isn't drawn while the viewport is scrolled back.
+    let offset = scrollback.map_or(0, |s| s.offset());
+    if offset > 0 {
+        return;
+    }
+
+    let cursor = term.cursor().point;
+    let cursor_attr = cursor_attr(term, ctx);
+
+    // The cursor is only drawn using attributes if it's at the start of a
+    // grapheme cluster.
+    let scale = line_scale(extras, cursor.line);
+    let cells = scrollback::copy_row(term, cursor.line);
+    let clusters = grapheme::clusters(&cells, extras, cursor.line);
+    let cluster = clusters.iter().find(|c| {
+        c.col == cursor.col && (c.col.0 + c.span) * scale <= cells.len()
+    });
+    if let Some(cluster) = cluster {
+        let cell = cells[cursor.col.0];
+        let selected = selection.map_or(false, |s| s.contains(cursor.line.0, cursor.col.0));
+        let is_match = matches.iter().any(|m| {
+            m.line == cursor.line.0 && m.start <= cursor.col.0 && cursor.col.0 <= m.end
+        });
+        let mut attrs = cell_attrs(ctx, palette, &cell, extras.get(cursor.line, cursor.col), selected, is_match, r.passthrough());
+        if let Some(attr) = cursor_attr {
+            attrs.toggle(attr);
+        }
+
+        draw_cluster(
+            r,
+            (cursor.line.0 as i32) + 1,
+            (cursor.col.0 * scale) as i32 + 1,
+            cluster,
+            scale,
+            attrs,
+            ctx,
+        );
+    }
+
+    place_cursor(term, r, ctx, extras, offset);
+
+    r.present();
+}
+
+// `render_picker` draws `picker` in a box in the middle of the area of `r`.
+// Each item is drawn on a single line, so only the first line of each item is
+// shown, and long lines are cut short.
+#[cfg(feature = "curses")]
+pub fn render_picker<R: Renderer>(r: &mut R, picker: &Picker) {
+    let (y, x) = r.size();
+    let items = picker.items();
+    let width = cmp::min(x - 4, 64);
+    let height = cmp::min(y - 2, items.len() as i32 + 2);
+    if width < 8 || height < 3 {
+        return;
+    }
+    let top = (y - height) / 2;
+    let left = (x - width) / 2;
+
+    // Items that don't fit in the box are scrolled so that the selected item
+    // is visible.
+    let rows = (height - 2) as usize;
+    let first = (picker.selected() + 1).saturating_sub(rows);
+
+    for i in 0..height {
+        let line =
+            if i == 0 || i == height - 1 {
+                format!("+{}+", "-".repeat((width - 2) as usize))
+            } else {
+                format!("|{}|", " ".repeat((width - 2) as usize))
+            };
+        r.draw_cell(top + i, left, Glyph::Text(&line), Attrs::default());
+    }
+    r.draw_cell(top, left + 2, Glyph::Text(&format!(" {} ", picker.title())), Attrs::default());
+
+    for (row, (i, item)) in items.iter().enumerate().skip(first).take(rows).enumerate() {
+        let number = if i < 9 { format!("{}", i + 1) } else { " ".to_string() };
+        let first_line = item.lines().next().unwrap_or("");
+        // Control characters, such as tabs, are replaced so that they don't
+        // move the cursor.
+        let text: String = format!("{} {}", number, first_line)
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .take((width - 4) as usize)
+            .collect();
+        let attrs = Attrs{reverse: i == picker.selected(), ..Attrs::default()};
+        r.draw_cell(top + 1 + row as i32, left + 2, Glyph::Text(&text), attrs);
+    }
+    r.move_cursor(top, left, false);
+    r.present();
+}
+
+// `screen_rows` returns the rows that are visible in the viewport, along with
+// the line of `term` that each row shows, or `None` if the row shows a line of
+// `scrollback`.
+pub fn screen_rows(term: &Term, scrollback: Option<&Scrollback>) -> Vec<(Vec<Cell>, Option<Line>)> {
+    let grid = term.grid();
+    let num_lines = grid.num_lines().0;
+    let num_cols = grid.num_cols().0;
+
+    let mut rows = Vec::with_capacity(num_lines);
+    if let Some(scrollback) = scrollback {
+        let offset = scrollback.offset();
+        for i in 0..cmp::min(offset, num_lines) {
+            // Lines of the scrollback keep the width of the screen at the
+            // time that they were scrolled off it.
+            let mut cells = scrollback.line(scrollback.len() - offset + i).to_vec();
+            cells.resize(num_cols, Cell::default());
+            rows.push((cells, None));
+        }
+    }
+    let mut line = Line(0);
+    while rows.len() < num_lines {
+        rows.push((scrollback::copy_row(term, line), Some(line)));
+        line += 1;
+    }
+
+    rows
+}
+
+// `cursor_attr` returns the attribute that should be used to draw the cursor,
+// if the cursor is drawn using attributes. Curses can't change the shape of the
+// host's cursor, so we hide it and draw block and underline cursors using cell
+// attributes instead. The host's cursor is used for the default and bar cursor
+// shapes, as it's the closest approximation that we have to a bar.
+fn cursor_attr(term: &Term, ctx: &Context) -> Option<Attrs> {
+    if !term.mode().contains(TermMode::SHOW_CURSOR) || !ctx.cursor_blink_on {
+        return None;
+    }
+
+    match ctx.cursor_shape {
+        Some(CursorShape::Block) => Some(Attrs{reverse: true, ..Attrs::default()}),
+        Some(CursorShape::Underline) => Some(Attrs{underline: true, ..Attrs::default()}),
+        _ => None,
+    }
+}
+
+// `place_cursor` moves the host's cursor to the position of `term`'s cursor,
+// and only shows it if it's being used to draw the cursor (see `cursor_attr`).
+// The cursor is hidden if the viewport is scrolled back by `offset` lines.
+fn place_cursor<R: Renderer>(term: &Term, r: &mut R, ctx: &Context, extras: &Extras, offset: usize) {
+    let visible = term.mode().contains(TermMode::SHOW_CURSOR) && ctx.cursor_blink_on && offset == 0;
+    let drawn_with_attrs = match ctx.cursor_shape {
+        Some(CursorShape::Block) | Some(CursorShape::Underline) => true,
+        _ => false,
+    };
+
+    let Point{line, col: Column(col)} = term.cursor().point;
+    let Line(row) = line;
+    // The cursor is kept within the window on double-width lines, where its
+    // position can be beyond the last character that's drawn.
+    let max_col = term.grid().num_cols().0 - 1;
+    let col = cmp::min(col * line_scale(extras, line), max_col);
+    r.move_cursor(
+        ((row + 1) as usize).try_into().unwrap(),
+        ((col + 1) as usize).try_into().unwrap(),
+        visible && !drawn_with_attrs,
+    );
+}
+
+// `line_scale` returns the number of columns that each column of `line`
+// occupies when drawn. Double-height lines can't be drawn using curses, so
+// we approximate them, and double-width lines, by spacing out their
+// characters.
+fn line_scale(extras: &Extras, line: Line) -> usize {
+    if extras.line_size(line) == LineSize::Single {
+        1
+    } else {
+        2
+    }
+}
+
+// `cell_attrs` returns the attributes that `cell` is drawn with. `passthrough`
+// is `true` if the renderer draws the attributes of `style` itself (see
+// `Renderer::passthrough`).
+fn cell_attrs(ctx: &Context, palette: &Palette, cell: &Cell, style: Style, selected: bool, is_match: bool, passthrough: bool) -> Attrs {
+    let mut attrs = flags_to_attrs(cell.flags);
+    if passthrough {
+        attrs.undercurl = style.undercurl;
+        attrs.strikethrough = style.strikethrough;
+    } else {
+        if style.undercurl {
+            apply_fallback(&mut attrs, ctx.fallbacks.undercurl);
+        }
+        if style.strikethrough {
+            apply_fallback(&mut attrs, ctx.fallbacks.strikethrough);
+        }
+    }
+
+    // Selection is drawn above search matches, which are drawn above the
+    // cell's own colours.
+    let highlight =
+        if selected {
+            Some((ctx.selection_colours, Attrs{reverse: true, ..Attrs::default()}))
+        } else if is_match {
+            Some((ctx.match_colours, Attrs{bold: true, underline: true, ..Attrs::default()}))
+        } else {
+            None
+        };
+
+    if let Some(support) = ctx.colour_support {
+        let (mut fg, mut bright) = colour::to_curses(support, palette, cell.fg);
+        let mut rgb = (colour::to_rgb(palette, cell.fg), colour::to_rgb(palette, cell.bg));
+        // Curses can't draw underlines in a different colour to the text that
+        // they're under, so unless the renderer can, we only draw coloured
+        // underlines under blank cells, where there's no text to be
+        // miscoloured.
+        let underlined = cell.flags.contains(Flags::UNDERLINE) || style.undercurl;
+        if passthrough {
+            attrs.underline_colour = style.underline_colour.map(|spec| colour::resolve_spec(palette, spec));
+        } else if underlined && cell.c == ' ' {
+            if let Some(spec) = style.underline_colour {
+                let (c, b) = colour::spec_to_curses(support, palette, spec);
+                fg = c;
+                bright = b;
+                rgb.0 =
+                    match colour::resolve_spec(palette, spec) {
+                        Spec::Rgb(c) => Some(c),
+                        Spec::Indexed(_) => None,
+                    };
+            }
+        }
+        // Bright backgrounds can't be distinguished using bold, so we ignore
+        // the second value that's returned here.
+        let (mut bg, _) = colour::to_curses(support, palette, cell.bg);
+
+        if let Some((Some((hl_fg, hl_bg)), _)) = highlight {
+            fg = colour::rgb_to_curses(support.depth, hl_fg.0, hl_fg.1, hl_fg.2);
+            bg = colour::rgb_to_curses(support.depth, hl_bg.0, hl_bg.1, hl_bg.2);
+            rgb = (Some(hl_fg), Some(hl_bg));
+            bright = false;
+            attrs.reverse = false;
+        }
+
+        attrs.colours = Some((fg, bg));
+        attrs.rgb = rgb;
+        if bright {
+            attrs.bold = true;
+        }
+    }
+
+    // Highlights that don't have colours, or that are drawn without colour
+    // support, are drawn using attributes instead. We toggle reverse video,
+    // rather than setting it, so that highlighted cells that are already drawn
+    // in reverse video can be distinguished from other cells.
+    if let Some((hl_colours, hl_attrs)) = highlight {
+        if hl_colours.is_none() || ctx.colour_support.is_none() {
+            attrs.reverse ^= hl_attrs.reverse;
+            attrs.bold |= hl_attrs.bold;
+            attrs.underline |= hl_attrs.underline;
+        }
+    }
+
+    attrs
+}
+
+fn active_border_attrs(ctx: &Context) -> Attrs {
+    match (ctx.colour_support, ctx.active_border_colours) {
+        (Some(support), Some((fg, bg))) => {
+            let colours = (
+                colour::rgb_to_curses(support.depth, fg.0, fg.1, fg.2),
+                colour::rgb_to_curses(support.depth, bg.0, bg.1, bg.2),
+            );
+
+            Attrs{colours: Some(colours), rgb: (Some(fg), Some(bg)), ..Attrs::default()}
+        },
+        _ => Attrs{bold: true, ..Attrs::default()},
+    }
+}
+
+// `draw_cluster` draws `cluster` at `(y, x)` using `attrs`, and pads it with
+// spaces if the host displays it using fewer columns than it spans in the
+// grid, so that the rest of the line stays aligned. Line-drawing characters
+// are translated using `acs::translate`. Other clusters that the host can't
+// display, or that the host would display using more columns than they span,
+// are replaced by `grapheme::PLACEHOLDER`s. Each column of the cluster is drawn
+// using `scale` columns of the host.
+fn draw_cluster<R: Renderer>(
+    r: &mut R,
+    y: i32,
+    x: i32,
+    cluster: &Cluster,
+    scale: usize,
+    attrs: Attrs,
+    ctx: &Context,
+) {
+    let text = &cluster.text;
+    let cols = cluster.span * scale;
+
+    let mut chars = text.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if let Some(glyph) = acs::translate(ctx.line_drawing, c) {
+            r.draw_cell(y, x, glyph, attrs);
+            for i in 1..cols {
+                r.draw_cell(y, x + i as i32, Glyph::Char(' '), attrs);
+            }
+            return;
+        }
+    }
+
+    let width = grapheme::host_width(text, ctx.ambiguous_wide);
+
+    if (!ctx.utf8 && !text.is_ascii()) || width > cluster.span {
+        for i in 0..cols {
+            r.draw_cell(y, x + i as i32, Glyph::Char(grapheme::PLACEHOLDER), attrs);
+        }
+        return;
+    }
+
+    if text.len() == 1 {
+        // Single ASCII characters are drawn directly, which is the common
+        // case.
+        r.draw_cell(y, x, Glyph::Char(text.as_bytes()[0] as char), attrs);
+    } else {
+        r.draw_cell(y, x, Glyph::Text(text), attrs);
+    }
+    for i in width..cols {
+        r.draw_cell(y, x + i as i32, Glyph::Char(' '), attrs);
+    }
+}
+
+fn flags_to_attrs(flags: Flags) -> Attrs {
+    Attrs{
+        bold: flags.contains(Flags::BOLD),
+        dim: flags.contains(Flags::DIM),
+        italic: flags.contains(Flags::ITALIC),
+        underline: flags.contains(Flags::UNDERLINE),
+        reverse: flags.contains(Flags::INVERSE),
+        hidden: flags.contains(Flags::HIDDEN),
+        ..Attrs::default()
+    }
+}
+
+fn apply_fallback(attrs: &mut Attrs, fallback: Fallback) {
+    match fallback {
+        Fallback::Underline => attrs.underline = true,
+        Fallback::Bold => attrs.bold = true,
+        Fallback::Dim => attrs.dim = true,
+        Fallback::Italic => attrs.italic = true,
+        Fallback::Reverse => attrs.reverse = true,
+        Fallback::None => {},
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use colour::Spec;
+use palette::Rgb;
+
+// `Renderer` draws terminals on a host, such as a curses window, so that
+// `render` doesn't depend on how the host is drawn to. Positions are `(y, x)`
+// cells from the top-left of the area that the renderer draws in, which
+// includes the border of the terminal.
+pub trait Renderer {
+    // `size` returns the number of lines and columns of the area.
+    fn size(&self) -> (i32, i32);
+
+    // `clear` clears the area, and is called before each terminal is drawn.
+    fn clear(&mut self);
+
+    // `draw_cell` draws `glyph` at `(y, x)` using `attrs`. A `Glyph::Text`
+    // can span several cells.
+    fn draw_cell(&mut self, y: i32, x: i32, glyph: Glyph, attrs: Attrs);
+
+    // `move_cursor` moves the host's cursor to `(y, x)`, and shows it if
+    // `visible` is `true`, and hides it otherwise.
+    fn move_cursor(&mut self, y: i32, x: i32, visible: bool);
+
+    // `present` shows what was drawn since it was last called.
+    fn present(&mut self);
+
+    // `passthrough` returns `true` if the renderer draws `Attrs::undercurl`,
+    // `Attrs::strikethrough` and `Attrs::underline_colour` itself, in which
+    // case they aren't approximated using other attributes (see
+    // `config::Fallbacks`).
+    fn passthrough(&self) -> bool {
+        false
+    }
+}
+
+// `Glyph` is what's drawn in a cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Glyph<'a> {
+    // `Text` is one or more grapheme clusters, which are drawn starting at
+    // the cell.
+    Text(&'a str),
+    // `Char` is a single character that occupies one column, which is the
+    // common case.
+    Char(char),
+    // `LineDrawing` is a character of the DEC special graphics character set,
+    // such as `─`, which is drawn using the host's own line-drawing
+    // characters if it has them (see `acs::translate`).
+    LineDrawing(char),
+}
+
+// `Attrs` is how a cell is drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Attrs {
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+    pub hidden: bool,
+    pub undercurl: bool,
+    pub strikethrough: bool,
+    // `underline_colour` is the colour of underlines and undercurls, if it
+    // differs from the colour of the text.
+    pub underline_colour: Option<Spec>,
+    // `colours` are the foreground and background colours, which are entries
+    // of the xterm 256-colour palette that have been approximated for the
+    // host (see `colour::to_curses`), or `colour::DEFAULT`. Cells are drawn
+    // in the host's default colours if `colours` is `None`.
+    pub colours: Option<(i16, i16)>,
+    // `rgb` holds the 24-bit foreground and background colours that
+    // `colours` approximates, for renderers that can draw them. A colour is
+    // `None` if it's an entry of the host's palette, or its default colour,
+    // which `colours` holds exactly.
+    pub rgb: (Option<Rgb>, Option<Rgb>),
+}
+
+impl Attrs {
+    // `toggle` toggles each attribute of `self` that's set in `other`.
+    pub fn toggle(&mut self, other: Attrs) {
+        self.bold ^= other.bold;
+        self.dim ^= other.dim;
+        self.italic ^= other.italic;
+        self.underline ^= other.underline;
+        self.reverse ^= other.reverse;
+        self.hidden ^= other.hidden;
+        self.undercurl ^= other.undercurl;
+        self.strikethrough ^= other.strikethrough;
+    }
+}
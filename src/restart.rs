@@ -0,0 +1,145 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+use std::time::{Duration, Instant};
+
+use child::Exit;
+
+// `MAX_DELAY` is the longest that `Restart` waits before restarting a child.
+// A child that runs for at least this long is treated as having started
+// successfully, and the delay goes back to the initial delay after it exits.
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+// `MIN_DELAY` is the shortest that `Restart` waits before first restarting a
+// child, as a delay of zero would never grow, and so would restart a child that
+// fails straight away continuously.
+const MIN_DELAY: Duration = Duration::from_millis(100);
+
+// `Restart` restarts the child of a pane when it exits, after a delay that
+// doubles each time that the child exits soon after being restarted, so that a
+// child that fails straight away isn't restarted continuously.
+pub struct Restart {
+    delay: Duration,
+    // `failures` is the number of times in a row that the child exited soon
+    // after being started, and `restarts` is the number of times that it has
+    // been restarted.
+    failures: u32,
+    restarts: usize,
+    started: Instant,
+    // `due` is when the child is restarted, once it has exited.
+    due: Option<Instant>,
+    // `only_on` is the only way of exiting that the child is restarted after,
+    // if it's set.
+    only_on: Option<Exit>,
+}
+
+impl Restart {
+    // `delay` is the time that's waited before the child is first restarted,
+    // which is raised to `MIN_DELAY` if it's shorter.
+    pub fn new(delay: Duration) -> Restart {
+        Restart{
+            delay: cmp::max(delay, MIN_DELAY),
+            failures: 0,
+            restarts: 0,
+            started: Instant::now(),
+            due: None,
+            only_on: None,
+        }
+    }
+
+    // `only_on` returns the `Restart`, which only restarts the child if it
+    // exits in the way that `exit` describes.
+    pub fn only_on(self, exit: Exit) -> Restart {
+        Restart{only_on: Some(exit), ..self}
+    }
+
+    // `applies` returns `true` if the child should be restarted after exiting
+    // in the way that `exit` describes, which is `None` if that isn't known.
+    pub fn applies(&self, exit: Option<Exit>) -> bool {
+        self.only_on.map_or(true, |only_on| exit == Some(only_on))
+    }
+
+    // `schedule` is called when the child exits, and returns how long it will
+    // be until the child is restarted.
+    pub fn schedule(&mut self) -> Duration {
+        if self.started.elapsed() >= MAX_DELAY {
+            self.failures = 0;
+        }
+        let factor = 1u32.checked_shl(self.failures).unwrap_or(u32::max_value());
+        let delay = self.delay.checked_mul(factor).map_or(MAX_DELAY, |d| cmp::min(d, MAX_DELAY));
+        self.failures = self.failures.saturating_add(1);
+        self.due = Some(Instant::now() + delay);
+
+        delay
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.due.map_or(false, |due| Instant::now() >= due)
+    }
+
+    // `restarted` is called once the child has been restarted.
+    pub fn restarted(&mut self) {
+        self.restarts += 1;
+        self.started = Instant::now();
+        self.due = None;
+    }
+
+    pub fn restarts(&self) -> usize {
+        self.restarts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_doubles_delay_up_to_max() {
+        let mut restart = Restart::new(Duration::from_secs(1));
+
+        let delays: Vec<u64> = (0..8).map(|_| restart.schedule().as_secs()).collect();
+
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+    }
+
+    #[test]
+    fn schedule_raises_delay_to_min() {
+        let mut restart = Restart::new(Duration::from_millis(0));
+
+        assert_eq!(restart.schedule(), MIN_DELAY);
+        assert_eq!(restart.schedule(), MIN_DELAY * 2);
+    }
+
+    #[test]
+    fn schedule_never_overflows() {
+        let mut restart = Restart::new(Duration::from_secs(1));
+
+        for _ in 0..100 {
+            assert!(restart.schedule() <= MAX_DELAY);
+        }
+    }
+
+    #[test]
+    fn schedule_resets_after_long_run() {
+        let mut restart = Restart::new(Duration::from_secs(1));
+        restart.schedule();
+        restart.schedule();
+        restart.restarted();
+        restart.started = Instant::now() - MAX_DELAY;
+
+        assert_eq!(restart.schedule(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn restarted_counts_restarts() {
+        let mut restart = Restart::new(Duration::from_secs(1));
+        restart.schedule();
+
+        assert!(!restart.is_due());
+        restart.restarted();
+        assert_eq!(restart.restarts(), 1);
+        assert!(!restart.is_due());
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+use std::collections::VecDeque;
+
+use alacritty::index::{Column, Line, Point};
+use alacritty::Term;
+use alacritty::term::cell::Cell;
+
+use tap::Event as TapEvent;
+
+// `MAX_LINES` limits the number of lines that can be kept in the scrollback,
+// so that its memory use stays bounded.
+pub const MAX_LINES: usize = 1_000_000;
+
+// `Scrollback` holds the lines that have been scrolled off the top of the
+// primary screen, and the position of the viewport within them. We keep our
+// own scrollback, rather than using that of `alacritty::Term`, so that it can
+// be rendered in the same way as the screen.
+//
+// Lines are added to the scrollback by copying the lines that are about to be
+// scrolled off the screen before each byte of output that could scroll them
+// is processed (see `prepare`).
+//
+// The number of lines in the scrollback is limited to `max_lines`, and the
+// scrollback is disabled if `max_lines` is `0`.
+pub struct Scrollback {
+    lines: VecDeque<Vec<Cell>>,
+    max_lines: usize,
+    // `offset` is the number of lines that the viewport is scrolled back by.
+    offset: usize,
+    // `region_top` and `region_bottom` track the scrolling region of the
+    // screen, as only lines that are scrolled off the top of the screen are
+    // added to the scrollback.
+    region_top: usize,
+    region_bottom: Option<usize>,
+    // `evicted` is the number of lines that have been removed from the start
+    // of the scrollback, either because it was full or because it was
+    // cleared. Positions that count from the oldest line are kept on the same
+    // lines by moving them up when `evicted` increases (see
+    // `CopyMode::sync`).
+    evicted: usize,
+}
+
+// `Pending` holds the lines that could be scrolled off the screen by the next
+// byte of output.
+pub struct Pending {
+    rows: Vec<Vec<Cell>>,
+    // `wrap` is `true` if the lines are only scrolled if the next character
+    // wraps onto a new line.
+    wrap: bool,
+}
+
+impl Scrollback {
+    pub fn new(max_lines: usize) -> Scrollback {
+        Scrollback{
+            lines: VecDeque::new(),
+            max_lines: max_lines,
+            offset: 0,
+            region_top: 0,
+            region_bottom: None,
+            evicted: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    // `line` returns the `i`th line of the scrollback, where the oldest line
+    // is `0`.
+    pub fn line(&self, i: usize) -> &[Cell] {
+        &self.lines[i]
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn evicted(&self) -> usize {
+        self.evicted
+    }
+
+    // `scroll` scrolls the viewport back by `delta` lines, or forward if
+    // `delta` is negative.
+    pub fn scroll(&mut self, delta: isize) {
+        let offset = self.offset as isize + delta;
+        self.offset = cmp::min(cmp::max(offset, 0) as usize, self.lines.len());
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        self.offset = 0;
+    }
+
+    // `reveal` scrolls the viewport the least amount needed to show `line`,
+    // which counts from the oldest line of the scrollback, followed by the
+    // `num_lines` lines of the screen.
+    pub fn reveal(&mut self, line: usize, num_lines: usize) {
+        let top = self.lines.len() - self.offset;
+        if line < top {
+            self.scroll((top - line) as isize);
+        } else if line >= top + num_lines {
+            self.scroll(-((line + 1 - top - num_lines) as isize));
+        }
+    }
+
+    // `clear` discards the lines of the scrollback.
+    pub fn clear(&mut self) {
+        self.evicted += self.lines.len();
+        self.lines.clear();
+        self.lines.shrink_to_fit();
+        self.offset = 0;
+    }
+
+    // `push_screen` adds the lines of the screen of `term`, up to the line that
+    // the cursor is on, to the scrollback.
+    pub fn push_screen(&mut self, term: &Term) {
+        if self.max_lines == 0 {
+            return;
+        }
+        for i in 0..=term.cursor().point.line.0 {
+            self.push(copy_row(term, Line(i)));
+        }
+    }
+
+    pub fn set_region(&mut self, top: usize, bottom: Option<usize>) {
+        self.region_top = top;
+        self.region_bottom = bottom;
+    }
+
+    // `region` returns the top and bottom lines of the scrolling region of a
+    // screen whose last line is `last`.
+    pub fn region(&self, last: usize) -> (usize, usize) {
+        (cmp::min(self.region_top, last), cmp::min(self.region_bottom.unwrap_or(last), last))
+    }
+
+    // `prepare` should be called before each byte of output is processed by
+    // `term`, where `evts` and `printed` are the results of passing the byte
+    // to `Tap`. It returns the lines that could be scrolled off the screen by
+    // the byte, which should be passed to `commit` after the byte has been
+    // processed.
+    pub fn prepare(&self, term: &Term, evts: &[TapEvent], printed: bool) -> Option<Pending> {
+        if self.max_lines == 0 || self.region_top != 0 {
+            return None;
+        }
+
+        let grid = term.grid();
+        let cursor = term.cursor().point;
+        let (_, bottom) = self.region(grid.num_lines().0 - 1);
+
+        let mut n = 0;
+        for evt in evts {
+            match *evt {
+                TapEvent::LineFeed if cursor.line.0 == bottom => n += 1,
+                TapEvent::ScrollUp(k) => n += k,
+                _ => {},
+            }
+        }
+        let wrap = n == 0 && printed && cursor.line.0 == bottom && cursor.col.0 == grid.num_cols().0 - 1;
+        if wrap {
+            n = 1;
+        }
+        if n == 0 {
+            return None;
+        }
+
+        let n = cmp::min(n, bottom + 1);
+        let rows = (0..n).map(|i| copy_row(term, Line(i))).collect();
+
+        Some(Pending{rows: rows, wrap: wrap})
+    }
+
+    // `commit` adds the lines of `pending` to the scrollback if they were
+    // scrolled off the screen, where `before` is the position of the cursor
+    // before the byte was processed.
+    pub fn commit(&mut self, pending: Pending, term: &Term, before: Point) {
+        // A character that's printed in the last column only wraps if the
+        // cursor was waiting to wrap, in which case the cursor moves back to
+        // the start of the line.
+        if pending.wrap && term.cursor().point.col >= before.col {
+            return;
+        }
+
+        for row in pending.rows {
+            self.push(row);
+        }
+    }
+
+    // `push` adds `row` to the end of the scrollback, such as a row that was
+    // scrolled off the screen when the terminal was resized.
+    pub fn push(&mut self, mut row: Vec<Cell>) {
+        if self.max_lines == 0 {
+            return;
+        }
+        // Blank cells at the end of each line aren't stored, as they're
+        // restored when the line is drawn.
+        let blank = Cell::default();
+        while row.last() == Some(&blank) {
+            row.pop();
+        }
+        row.shrink_to_fit();
+
+        if self.lines.len() == self.max_lines {
+            self.lines.pop_front();
+            self.evicted += 1;
+        }
+        self.lines.push_back(row);
+
+        // The viewport stays on the same lines if it's scrolled back.
+        if self.offset > 0 {
+            self.offset = cmp::min(self.offset + 1, self.lines.len());
+        }
+    }
+}
+
+// `copy_row` returns a copy of the cells of `line` of `term`.
+pub fn copy_row(term: &Term, line: Line) -> Vec<Cell> {
+    let grid = term.grid();
+    let mut cells = Vec::with_capacity(grid.num_cols().0);
+    let mut col = Column(0);
+    while col < grid.num_cols() {
+        cells.push(grid[line][col]);
+        col += 1;
+    }
+
+    cells
+}
+
+// `line_cells` returns the cells of the `i`th line of `scrollback`, followed by
+// the lines of the screen of `term`.
+pub fn line_cells(term: &Term, scrollback: &Scrollback, i: usize) -> Vec<Cell> {
+    if i < scrollback.len() {
+        scrollback.line(i).to_vec()
+    } else {
+        copy_row(term, Line(i - scrollback.len()))
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+
+use alacritty::index::Line;
+use alacritty::Term;
+use alacritty::term::cell::{Cell, Flags};
+
+use regex::Regex;
+
+use scrollback;
+use scrollback::Scrollback;
+
+// `Match` is a span of cells on a single line that matched a search. `line` is
+// a line of the screen, unless stated otherwise, and `start` and `end` are
+// inclusive column indices.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Match {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+// `mark_matches` returns a table that records whether each cell of a screen
+// with the given dimensions is part of a match, so that the renderer doesn't
+// have to search `matches` for every cell. Matches that are outside the screen
+// are ignored.
+pub fn mark_matches(matches: &[Match], num_lines: usize, num_cols: usize) -> Vec<Vec<bool>> {
+    let mut marks = vec![vec![false; num_cols]; num_lines];
+    for m in matches {
+        if m.line >= num_lines {
+            continue;
+        }
+        let row = &mut marks[m.line];
+        let mut col = m.start;
+        while col <= m.end && col < num_cols {
+            row[col] = true;
+            col += 1;
+        }
+    }
+
+    marks
+}
+
+// `Search` finds the matches of a regular expression in the scrollback and on
+// the screen. Matches are found on each line separately, so they can't span
+// lines.
+pub struct Search {
+    regex: Regex,
+    pattern: String,
+    // `current` is the match that was last moved to, where `line` counts from
+    // the oldest line of the scrollback, followed by the lines of the screen.
+    current: Option<Match>,
+    // `evicted` is the number of lines that had been evicted from the
+    // scrollback when `current` was last synced (see `sync`).
+    evicted: usize,
+}
+
+impl Search {
+    pub fn new(pattern: &str) -> Result<Search, String> {
+        let regex = Regex::new(pattern)
+            .map_err(|_| format!("invalid pattern: {}", pattern))?;
+
+        Ok(Search{regex: regex, pattern: pattern.to_string(), current: None, evicted: 0})
+    }
+
+    // `sync` moves the current match up by the number of lines that have been
+    // evicted from `scrollback` since it was last synced, in the same way as
+    // `CopyMode::sync`. The current match is forgotten if its line was
+    // evicted, so the next step backwards starts from the bottom of the
+    // screen.
+    pub fn sync(&mut self, scrollback: &Scrollback) {
+        let n = scrollback.evicted().saturating_sub(self.evicted);
+        self.evicted = scrollback.evicted();
+        self.current =
+            match self.current {
+                Some(m) if m.line >= n => Some(Match{line: m.line - n, ..m}),
+                _ => None,
+            };
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    // `step` moves to the match that comes before the current match if
+    // `older` is `true`, or after it otherwise. The first step backwards
+    // starts from the bottom of the screen. `step` returns `false` if there's
+    // no match to move to.
+    pub fn step(&mut self, term: &Term, scrollback: &Scrollback, older: bool) -> bool {
+        self.sync(scrollback);
+        let num_lines = scrollback.len() + term.grid().num_lines().0;
+        let (line, col) =
+            match self.current {
+                Some(m) if m.line < num_lines => (m.line, m.start),
+                _ if older => (num_lines - 1, usize::MAX),
+                _ => return false,
+            };
+
+        let mut i = line;
+        loop {
+            let spans = find(&self.regex, &scrollback::line_cells(term, scrollback, i));
+            let found =
+                if older {
+                    spans.into_iter().rev().find(|&(start, _)| i < line || start < col)
+                } else {
+                    spans.into_iter().find(|&(start, _)| i > line || start > col)
+                };
+            if let Some((start, end)) = found {
+                self.current = Some(Match{line: i, start: start, end: end});
+                return true;
+            }
+
+            if older && i > 0 {
+                i -= 1;
+            } else if !older && i + 1 < num_lines {
+                i += 1;
+            } else {
+                return false;
+            }
+        }
+    }
+
+    // `position` returns the line and the first column of the current match,
+    // where the line counts from the oldest line of the scrollback.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        self.current.map(|m| (m.line, m.start))
+    }
+
+    // `current` returns the current match, relative to the top of the
+    // viewport, if it's visible on a screen of `num_lines` lines.
+    pub fn current(&self, scrollback: &Scrollback, num_lines: usize) -> Option<Match> {
+        let m = self.current?;
+        let top = scrollback.len() - scrollback.offset();
+        if m.line < top || m.line >= top + num_lines {
+            return None;
+        }
+
+        Some(Match{line: m.line - top, start: m.start, end: m.end})
+    }
+
+    // `matches` returns the matches in `rows`, which are the rows that are
+    // visible in the viewport.
+    pub fn matches(&self, rows: &[(Vec<Cell>, Option<Line>)]) -> Vec<Match> {
+        let mut matches = vec![];
+        for (i, &(ref cells, _)) in rows.iter().enumerate() {
+            for (start, end) in find(&self.regex, cells) {
+                matches.push(Match{line: i, start: start, end: end});
+            }
+        }
+
+        matches
+    }
+}
+
+// `find` returns the first and last columns of each non-empty match of `regex`
+// in `cells`.
+fn find(regex: &Regex, cells: &[Cell]) -> Vec<(usize, usize)> {
+    // `cols` maps each byte of `text` to the column of the cell that it came
+    // from. The spacers that follow wide characters are skipped, so that
+    // wide characters can be matched.
+    let mut text = String::with_capacity(cells.len());
+    let mut cols = Vec::with_capacity(cells.len());
+    for (col, cell) in cells.iter().enumerate() {
+        if cell.flags.contains(Flags::WIDE_CHAR_SPACER) {
+            continue;
+        }
+        text.push(cell.c);
+        cols.resize(text.len(), col);
+    }
+
+    let mut spans = vec![];
+    for m in regex.find_iter(&text) {
+        if m.start() == m.end() {
+            continue;
+        }
+        let start = cols[m.start()];
+        let mut end = cols[m.end() - 1];
+        if cells[end].flags.contains(Flags::WIDE_CHAR) {
+            end = cmp::min(end + 1, cells.len() - 1);
+        }
+        spans.push((start, end));
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io;
+
+    use alacritty::ansi::Processor;
+    use alacritty::config::Config as TermConfig;
+
+    use pane::new_size_info;
+
+    fn term(lines: i32, cols: i32, output: &str) -> Term {
+        let mut term = Term::new(&TermConfig::default(), new_size_info(cols, lines));
+        let mut parser = Processor::new();
+        for byte in output.as_bytes() {
+            parser.advance(&mut term, *byte, &mut io::sink());
+        }
+
+        term
+    }
+
+    fn find_in(pattern: &str, output: &str) -> Vec<(usize, usize)> {
+        let t = term(1, 10, output);
+
+        find(&Regex::new(pattern).unwrap(), &scrollback::copy_row(&t, Line(0)))
+    }
+
+    #[test]
+    fn find_returns_columns_of_matches() {
+        assert_eq!(find_in("o+", "foo boo"), vec![(1, 2), (5, 6)]);
+        assert_eq!(find_in("x", "foo boo"), vec![]);
+    }
+
+    #[test]
+    fn find_skips_empty_matches() {
+        assert_eq!(find_in("o*", "foo"), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn find_matches_wide_characters() {
+        assert_eq!(find_in("世", "a世b"), vec![(1, 2)]);
+        assert_eq!(find_in("世b", "a世b"), vec![(1, 3)]);
+        assert_eq!(find_in("b", "a世b"), vec![(3, 3)]);
+    }
+
+    fn search_fixture() -> (Term, Scrollback) {
+        let mut scrollback = Scrollback::new(10);
+        scrollback.push(scrollback::copy_row(&term(1, 10, "foo x"), Line(0)));
+
+        (term(2, 10, "foo bar\r\nbaz foo"), scrollback)
+    }
+
+    #[test]
+    fn step_moves_through_matches() {
+        let (t, scrollback) = search_fixture();
+        let mut search = Search::new("foo").unwrap();
+
+        assert!(!search.step(&t, &scrollback, false));
+
+        let mut positions = vec![];
+        while search.step(&t, &scrollback, true) {
+            positions.push(search.position().unwrap());
+        }
+
+        assert_eq!(positions, vec![(2, 4), (1, 0), (0, 0)]);
+        assert_eq!(search.position(), Some((0, 0)));
+
+        let mut positions = vec![];
+        while search.step(&t, &scrollback, false) {
+            positions.push(search.position().unwrap());
+        }
+
+        assert_eq!(positions, vec![(1, 0), (2, 4)]);
+    }
+
+    #[test]
+    fn step_follows_evicted_lines() {
+        let (t, mut scrollback) = search_fixture();
+        let mut search = Search::new("bar").unwrap();
+        assert!(search.step(&t, &scrollback, true));
+        assert_eq!(search.position(), Some((1, 4)));
+
+        // `scrollback` holds 10 lines, so this evicts its first line.
+        for _ in 0..10 {
+            scrollback.push(vec![]);
+        }
+        search.sync(&scrollback);
+
+        assert_eq!(search.position(), Some((0, 4)));
+    }
+
+    #[test]
+    fn current_is_relative_to_viewport() {
+        let (t, scrollback) = search_fixture();
+        let mut search = Search::new("baz").unwrap();
+        assert!(search.step(&t, &scrollback, true));
+
+        assert_eq!(search.current(&scrollback, 2), Some(Match{line: 1, start: 0, end: 2}));
+        assert_eq!(search.current(&scrollback, 1), None);
+    }
+
+    #[test]
+    fn new_rejects_invalid_patterns() {
+        assert!(Search::new("(").is_err());
+    }
+}
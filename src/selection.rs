@@ -0,0 +1,199 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+
+use alacritty::term::cell::{Cell, Flags};
+
+// `Selection` is a region of the grid that runs from `start` to `end`, which
+// are `(line, column)` pairs. Each end of the selection is inclusive, and
+// `start` doesn't have to come before `end`; this allows `end` to follow a
+// cursor that moves back past the point where the selection started.
+//
+// If `block` is `true` then the selection is the rectangle that has `start`
+// and `end` as its corners, instead of running from `start` to `end` in
+// reading order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Selection {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub block: bool,
+}
+
+impl Selection {
+    pub fn new(start: (usize, usize), end: (usize, usize)) -> Selection {
+        Selection{start: start, end: end, block: false}
+    }
+
+    pub fn block(start: (usize, usize), end: (usize, usize)) -> Selection {
+        Selection{start: start, end: end, block: true}
+    }
+
+    // `bounds` returns the ends of the selection in the order that they appear
+    // on the screen.
+    pub fn bounds(&self) -> ((usize, usize), (usize, usize)) {
+        (cmp::min(self.start, self.end), cmp::max(self.start, self.end))
+    }
+
+    pub fn contains(&self, line: usize, col: usize) -> bool {
+        let (first, last) = self.bounds();
+        if self.block {
+            let (left, right) = self.columns();
+            return first.0 <= line && line <= last.0 && left <= col && col <= right;
+        }
+
+        first <= (line, col) && (line, col) <= last
+    }
+
+    // `columns` returns the first and last columns of a block selection.
+    fn columns(&self) -> (usize, usize) {
+        (cmp::min(self.start.1, self.end.1), cmp::max(self.start.1, self.end.1))
+    }
+
+    // `text` returns the selected text, without trailing whitespace on each
+    // line, where `row` returns the cells of each line of the selection.
+    pub fn text<F: Fn(usize) -> Vec<Cell>>(&self, row: F) -> String {
+        let (first, last) = self.bounds();
+        let mut lines = vec![];
+        for line in first.0..last.0 + 1 {
+            let cells = row(line);
+            let (start, end) =
+                if self.block {
+                    let (left, right) = self.columns();
+                    (left, right + 1)
+                } else {
+                    (
+                        if line == first.0 { first.1 } else { 0 },
+                        if line == last.0 { last.1 + 1 } else { cells.len() },
+                    )
+                };
+            let s: String = cells.iter()
+                .take(end)
+                .skip(start)
+                .filter(|c| !c.flags.contains(Flags::WIDE_CHAR_SPACER))
+                .map(|c| c.c)
+                .collect();
+            lines.push(s.trim_end().to_string());
+        }
+
+        lines.join("\n")
+    }
+}
+
+// `word_at` returns the first and last columns of the word in `cells` that
+// contains `col`, where words are separated by the characters in `separators`.
+// A separator is treated as a word on its own.
+pub fn word_at(cells: &[Cell], col: usize, separators: &str) -> (usize, usize) {
+    // The spacers that follow wide characters belong to the same word as
+    // the wide characters.
+    let is_word = |cell: &Cell| {
+        cell.flags.contains(Flags::WIDE_CHAR_SPACER) || !separators.contains(cell.c)
+    };
+    if col >= cells.len() || !is_word(&cells[col]) {
+        return (col, col);
+    }
+
+    let mut start = col;
+    while start > 0 && is_word(&cells[start - 1]) {
+        start -= 1;
+    }
+    let mut end = col;
+    while end + 1 < cells.len() && is_word(&cells[end + 1]) {
+        end += 1;
+    }
+
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `cells` returns the cells of `s`, where `_` marks the spacer that follows
+    // a wide character.
+    fn cells(s: &str) -> Vec<Cell> {
+        s.chars()
+            .map(|c| {
+                let mut cell = Cell::default();
+                if c == '_' {
+                    cell.flags.insert(Flags::WIDE_CHAR_SPACER);
+                } else {
+                    cell.c = c;
+                }
+
+                cell
+            })
+            .collect()
+    }
+
+    #[test]
+    fn contains_runs_in_reading_order() {
+        let sel = Selection::new((2, 3), (1, 5));
+
+        assert_eq!(sel.bounds(), ((1, 5), (2, 3)));
+        assert!(!sel.contains(1, 4));
+        assert!(sel.contains(1, 5));
+        assert!(sel.contains(1, 79));
+        assert!(sel.contains(2, 0));
+        assert!(sel.contains(2, 3));
+        assert!(!sel.contains(2, 4));
+        assert!(!sel.contains(0, 5));
+    }
+
+    #[test]
+    fn text_trims_trailing_whitespace_of_each_line() {
+        let rows = vec![cells("abc  "), cells("de   "), cells("fghij")];
+        let sel = Selection::new((0, 1), (2, 2));
+
+        assert_eq!(sel.text(|line| rows[line].clone()), "bc\nde\nfgh");
+    }
+
+    #[test]
+    fn text_skips_wide_character_spacers() {
+        let rows = vec![cells("a世_b")];
+
+        assert_eq!(Selection::new((0, 0), (0, 3)).text(|line| rows[line].clone()), "a世b");
+    }
+
+    #[test]
+    fn word_at_stops_at_separators() {
+        let row = cells("ab cd,e");
+
+        assert_eq!(word_at(&row, 0, " ,"), (0, 1));
+        assert_eq!(word_at(&row, 4, " ,"), (3, 4));
+        assert_eq!(word_at(&row, 6, " ,"), (6, 6));
+        assert_eq!(word_at(&row, 2, " ,"), (2, 2));
+        assert_eq!(word_at(&row, 5, " ,"), (5, 5));
+        assert_eq!(word_at(&row, 9, " ,"), (9, 9));
+    }
+
+    #[test]
+    fn word_at_includes_wide_character_spacers() {
+        let row = cells("a 世_b c");
+
+        assert_eq!(word_at(&row, 3, " "), (2, 4));
+        assert_eq!(word_at(&row, 2, " "), (2, 4));
+    }
+
+    #[test]
+    fn block_contains_rectangle() {
+        let sel = Selection::block((3, 1), (1, 4));
+
+        assert!(sel.contains(1, 1));
+        assert!(sel.contains(2, 4));
+        assert!(sel.contains(3, 2));
+        assert!(!sel.contains(2, 0));
+        assert!(!sel.contains(2, 5));
+        assert!(!sel.contains(0, 2));
+        assert!(!sel.contains(4, 2));
+    }
+
+    #[test]
+    fn block_text_takes_same_columns_of_each_line() {
+        let rows = vec![cells("abcdef"), cells("gh    "), cells("ijklmn")];
+        let sel = Selection::block((0, 4), (2, 1));
+
+        assert_eq!(sel.text(|line| rows[line].clone()), "bcde\nh\njklm");
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::PathBuf;
+
+use libc;
+use libc::pid_t;
+
+use outbox::Enter;
+use pty::{FlowControl, Pty};
+
+// `BAUD_RATES` are the baud rates that can be used on all of the supported
+// systems, along with their `termios` speeds.
+const BAUD_RATES: &[(u32, libc::speed_t)] = &[
+    (1200, libc::B1200),
+    (2400, libc::B2400),
+    (4800, libc::B4800),
+    (9600, libc::B9600),
+    (19200, libc::B19200),
+    (38400, libc::B38400),
+    (57600, libc::B57600),
+    (115200, libc::B115200),
+    (230400, libc::B230400),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+    None,
+    // `Hardware` uses the RTS and CTS lines.
+    Hardware,
+    // `Software` uses `^S` (XOFF) and `^Q` (XON).
+    Software,
+}
+
+// `Options` are the settings of a serial port. `data_bits`, `parity` and
+// `stop_bits` describe the frames that are sent, and `lf_crlf` and `enter` are
+// the line discipline, which translates the newlines that are received and
+// the Enter key, because devices differ in which newlines they use.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub device: PathBuf,
+    pub baud: u32,
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: u8,
+    pub flow: Flow,
+    pub lf_crlf: bool,
+    pub enter: Enter,
+}
+
+impl Options {
+    // `new` returns the options that are used most commonly, which are 115200
+    // baud and 8N1, without flow control.
+    pub fn new(device: PathBuf) -> Options {
+        Options{
+            device: device,
+            baud: 115200,
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: 1,
+            flow: Flow::None,
+            lf_crlf: false,
+            enter: Enter::Cr,
+        }
+    }
+}
+
+// `Serial` is a `Pty` that's a serial port, such as a USB serial adapter, which
+// has no child, and which is read from and written to unchanged, other than by
+// the line discipline of `Options`.
+pub struct Serial {
+    port: File,
+    lf_crlf: bool,
+    enter: Enter,
+}
+
+impl Serial {
+    // `open` opens the serial port that `opts` describes, for the exclusive
+    // use of the emulator, and configures it.
+    pub fn open(opts: &Options) -> Result<Serial, String> {
+        let speed =
+            match BAUD_RATES.iter().find(|&&(baud, _)| baud == opts.baud) {
+                Some(&(_, speed)) => speed,
+                None => return Err(format!("unsupported baud rate: {}", opts.baud)),
+            };
+        let data_bits =
+            match opts.data_bits {
+                5 => libc::CS5,
+                6 => libc::CS6,
+                7 => libc::CS7,
+                8 => libc::CS8,
+                n => return Err(format!("unsupported number of data bits: {}", n)),
+            };
+
+        let device = opts.device.display();
+        let path =
+            match CString::new(opts.device.as_os_str().as_bytes()) {
+                Ok(path) => path,
+                Err(_) => return Err(format!("invalid device: '{}'", device)),
+            };
+        let flags = libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK | libc::O_CLOEXEC;
+        let fd = unsafe { libc::open(path.as_ptr(), flags) };
+        if fd == -1 {
+            return Err(format!("couldn't open '{}': {}", device, io::Error::last_os_error()));
+        }
+        let port = unsafe { File::from_raw_fd(fd) };
+        let fd = port.as_raw_fd();
+
+        unsafe {
+            if libc::isatty(fd) != 1 {
+                return Err(format!("'{}' isn't a serial port", device));
+            }
+            libc::ioctl(fd, libc::TIOCEXCL as _);
+
+            let mut attrs: libc::termios = mem::zeroed();
+            if libc::tcgetattr(fd, &mut attrs) != 0 {
+                return Err(format!("couldn't configure '{}': {}", device, io::Error::last_os_error()));
+            }
+            // The port is raw, so that the bytes that are received are passed
+            // to the emulator, and the bytes that are sent are passed to the
+            // device, unchanged.
+            libc::cfmakeraw(&mut attrs);
+            libc::cfsetispeed(&mut attrs, speed);
+            libc::cfsetospeed(&mut attrs, speed);
+            attrs.c_cflag |= libc::CLOCAL | libc::CREAD;
+            attrs.c_cflag &= !(libc::CSIZE | libc::PARENB | libc::PARODD | libc::CSTOPB | libc::CRTSCTS);
+            attrs.c_cflag |= data_bits;
+            match opts.parity {
+                Parity::None => {},
+                Parity::Odd => attrs.c_cflag |= libc::PARENB | libc::PARODD,
+                Parity::Even => attrs.c_cflag |= libc::PARENB,
+            }
+            if opts.stop_bits == 2 {
+                attrs.c_cflag |= libc::CSTOPB;
+            }
+            attrs.c_iflag &= !(libc::IXON | libc::IXOFF | libc::IXANY);
+            match opts.flow {
+                Flow::None => {},
+                Flow::Hardware => attrs.c_cflag |= libc::CRTSCTS,
+                Flow::Software => attrs.c_iflag |= libc::IXON | libc::IXOFF,
+            }
+            if libc::tcsetattr(fd, libc::TCSANOW, &attrs) != 0 {
+                return Err(format!("couldn't configure '{}': {}", device, io::Error::last_os_error()));
+            }
+        }
+
+        Ok(Serial{port: port, lf_crlf: opts.lf_crlf, enter: opts.enter})
+    }
+}
+
+impl Pty for Serial {
+    fn file(&self) -> io::Result<File> {
+        self.port.try_clone()
+    }
+
+    fn pid(&self) -> Option<pid_t> {
+        None
+    }
+
+    // The size of the terminal can't be sent to the device.
+    fn resize(&self, _cols: u16, _lines: u16) {}
+
+    fn onlcr(&self) -> bool {
+        self.lf_crlf
+    }
+
+    fn enter(&self) -> Enter {
+        self.enter
+    }
+
+    // `^S` and `^Q` are sent to the device, which stops and restarts its
+    // output if it uses software flow control.
+    fn flow_control(&self) -> FlowControl {
+        FlowControl::None
+    }
+}
@@ -0,0 +1,853 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::mem;
+use std::net::Shutdown;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use libc;
+
+// `DEFAULT_NAME` is the name of the session that's started, or attached to, if
+// no name is given.
+pub const DEFAULT_NAME: &str = "default";
+
+// Messages between clients and servers are framed as a byte that gives the
+// kind of the message, followed by the length of the payload as a big-endian
+// `u32`, followed by the payload.
+//
+// The first message of each connection is `ATTACH`, `INFO`, `KILL` or
+// `COMMAND`. `ATTACH` attaches the client to the session, and its payload is
+// `1` if the client is read-only, and `0` otherwise. `INFO` asks for the state
+// of the session, and is answered with an `INFO` message whose payload is `1`
+// if a client is attached, and `0` otherwise. `KILL` ends the session.
+// `COMMAND` runs the command in its payload, and is answered with a `COMMAND`
+// message whose payload is `0` followed by the output of the command if it
+// succeeded, and `1` followed by an error message otherwise.
+//
+// `DATA` carries input from the client's host terminal to the server, or output
+// from the server to the client's host terminal. `RESIZE` carries the size of
+// the client's host terminal, as big-endian `u16` columns and lines. `DETACH`
+// is sent by a client that asks to be detached. `DETACHED` and `EXITED` are
+// sent by the server before it disconnects a client, because the client was
+// detached or because the session ended.
+const ATTACH: u8 = b'a';
+const INFO: u8 = b'i';
+const KILL: u8 = b'k';
+const COMMAND: u8 = b'c';
+const DATA: u8 = b'd';
+const RESIZE: u8 = b'r';
+const DETACH: u8 = b'q';
+const DETACHED: u8 = b'D';
+const EXITED: u8 = b'x';
+
+// `MAX_FRAME_LEN` limits the length of the payloads of the frames that are
+// read, so that a peer can't make the reader allocate an arbitrary amount of
+// memory. Payloads are much smaller in practice: the largest are the outputs
+// of commands, such as the screen that `capture-pane` prints.
+const MAX_FRAME_LEN: usize = 1 << 20;
+
+// `WRITE_TIMEOUT` is how long the server waits for a client to accept output
+// before it disconnects the client, so that a client that stops reading, such
+// as a read-only viewer that's suspended, doesn't stall the other clients.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// `RESIZE_POLL_INTERVAL` is how often a client checks the size of its host
+// terminal. The size is polled, rather than waiting for `SIGWINCH`, so that the
+// client doesn't need to install a signal handler.
+const RESIZE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// `READ_ONLY_DETACH` is the key that detaches a read-only client, whose other
+// input is ignored.
+const READ_ONLY_DETACH: u8 = b'q';
+
+// `HOST_RESET` disables the modes that the server may have enabled on the host
+// terminal, which are the mouse, focus and bracketed paste modes, the extended
+// keyboard modes, the keypad mode and the alternate screen. A client writes it
+// when it's detached, because the server's output is no longer relayed to the
+// host at that point, so the server can't disable the modes itself.
+const HOST_RESET: &str = concat!(
+    "\x1b[?1000l\x1b[?1002l\x1b[?1003l\x1b[?1006l",
+    "\x1b[?1004l\x1b[?2004l",
+    "\x1b[>4m\x1b[<99u",
+    "\x1b[?1l\x1b>",
+    "\x1b[m\x1b[?25h\x1b[?1049l",
+);
+
+// `Role` is the part that a process plays in a session after `start`.
+pub enum Role {
+    // `Client` relays the host terminal to the session, using `attach`.
+    Client,
+    // `Server` runs the emulator, using the `Server` to communicate with
+    // clients.
+    Server(Server),
+}
+
+// `End` is the reason that `attach` returned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum End {
+    Detached,
+    Exited,
+    // `Lost` means that the connection to the server was lost unexpectedly.
+    Lost,
+}
+
+// `socket_dir` returns the directory that holds the sockets of the user's
+// sessions, creating it if it doesn't exist. The directory is only accessible
+// to the user, because anyone who can connect to a session can control it.
+pub fn socket_dir() -> Result<PathBuf, String> {
+    let dir =
+        match env::var_os("XDG_RUNTIME_DIR") {
+            Some(ref runtime_dir) if !runtime_dir.is_empty() => PathBuf::from(runtime_dir).join("tep"),
+            _ => env::temp_dir().join(format!("tep-{}", unsafe { libc::getuid() })),
+        };
+
+    if let Err(e) = fs::DirBuilder::new().mode(0o700).create(&dir) {
+        if e.kind() != ErrorKind::AlreadyExists {
+            return Err(format!("couldn't create '{}': {}", dir.display(), e));
+        }
+    }
+    // A directory that was created by another user, such as in a shared
+    // temporary directory, isn't used.
+    match fs::metadata(&dir) {
+        Ok(ref metadata) if metadata.uid() == unsafe { libc::getuid() } => Ok(dir),
+        Ok(_) => Err(format!("'{}' is owned by another user", dir.display())),
+        Err(e) => Err(format!("couldn't read '{}': {}", dir.display(), e)),
+    }
+}
+
+// `socket_path` returns the path of the socket of the session called `name`.
+pub fn socket_path(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.starts_with('.') || name.contains('/') {
+        return Err(format!("invalid session name: '{}'", name));
+    }
+
+    Ok(socket_dir()?.join(name))
+}
+
+// `unused_name` returns `DEFAULT_NAME` if no session of that name is running,
+// or otherwise the first of `default-2`, `default-3` and so on that isn't
+// running, so that sessions can be started without naming them.
+pub fn unused_name() -> Result<String, String> {
+    let mut n = 1;
+    loop {
+        let name = if n == 1 { DEFAULT_NAME.to_string() } else { format!("{}-{}", DEFAULT_NAME, n) };
+        if UnixStream::connect(socket_path(&name)?).is_err() {
+            return Ok(name);
+        }
+        n += 1;
+    }
+}
+
+// `list` returns the names of the sessions that are running, in order, along
+// with whether a client is attached to each.
+pub fn list() -> Result<Vec<(String, bool)>, String> {
+    let dir = socket_dir()?;
+    let entries =
+        match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => return Err(format!("couldn't read '{}': {}", dir.display(), e)),
+        };
+
+    let mut sessions = vec![];
+    for entry in entries {
+        let entry =
+            match entry {
+                Ok(entry) => entry,
+                Err(e) => return Err(format!("couldn't read '{}': {}", dir.display(), e)),
+            };
+        let name =
+            match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+        // Sockets whose names start with `.` aren't sessions, such as the
+        // control sockets of `ssh`.
+        if name.starts_with('.') {
+            continue;
+        }
+        let mut stream =
+            match UnixStream::connect(entry.path()) {
+                Ok(stream) => stream,
+                // Sockets that can't be connected to were left behind by
+                // servers that didn't exit cleanly.
+                Err(_) => {
+                    remove_stale(&entry.path());
+                    continue;
+                },
+            };
+        let reply = write_frame(&mut stream, INFO, &[]).and_then(|_| read_frame(&mut stream));
+        if let Ok((INFO, payload)) = reply {
+            sessions.push((name, payload.first() == Some(&1)));
+        }
+    }
+    sessions.sort();
+
+    Ok(sessions)
+}
+
+// `kill` ends the session whose socket is at `path`, in the same way as
+// quitting the emulator.
+pub fn kill(path: &Path) -> Result<(), String> {
+    let result = UnixStream::connect(path).and_then(|mut stream| write_frame(&mut stream, KILL, &[]));
+    if let Err(e) = result {
+        return Err(format!("couldn't kill '{}': {}", path.display(), e));
+    }
+
+    Ok(())
+}
+
+// `command` runs the command `line` in the session whose socket is at `path`,
+// in the same way as entering it at the prompt, and returns its output.
+pub fn command(path: &Path, line: &str) -> Result<String, String> {
+    let mut stream =
+        match UnixStream::connect(path) {
+            Ok(stream) => stream,
+            Err(e) => return Err(format!("couldn't connect to '{}': {}", path.display(), e)),
+        };
+    let reply = write_frame(&mut stream, COMMAND, line.as_bytes()).and_then(|_| read_frame(&mut stream));
+    match reply {
+        Ok((COMMAND, ref payload)) if !payload.is_empty() => {
+            let text = String::from_utf8_lossy(&payload[1..]).into_owned();
+            if payload[0] == 0 {
+                Ok(text)
+            } else {
+                Err(text)
+            }
+        },
+        Ok(_) => Err("unexpected reply from the session".to_string()),
+        Err(e) => Err(format!("lost the connection to the session: {}", e)),
+    }
+}
+
+// `start` starts a session whose socket is at `path`. The process forks into a
+// server, which runs in the background on a new PTY, and a client, which should
+// attach to the server using `attach`. The server's standard streams are
+// connected to the PTY, so that curses renders to the PTY, and its output is
+// relayed to whichever client is attached.
+pub fn start(path: &Path) -> Result<Role, String> {
+    // The socket is checked and created while holding a lock, so that a
+    // session that's started with the same name at the same time can't
+    // remove the socket of this one, after mistaking it for one that was left
+    // behind.
+    let lock = lock(&lock_path(path))?;
+    if UnixStream::connect(path).is_ok() {
+        return Err(format!("a session is already running at '{}'", path.display()));
+    }
+    // A socket that can't be connected to was left behind by a server that
+    // didn't exit cleanly.
+    let _ = fs::remove_file(path);
+    let listener =
+        match UnixListener::bind(path) {
+            Ok(listener) => listener,
+            Err(e) => return Err(format!("couldn't create '{}': {}", path.display(), e)),
+        };
+    // The lock is released before forking, as the server would otherwise hold
+    // it.
+    drop(lock);
+
+    let mut size = winsize(window_size(libc::STDIN_FILENO).unwrap_or((80, 24)));
+    let (mut master, mut slave) = (0, 0);
+    let opened = unsafe {
+        libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null_mut(), &mut size)
+    };
+    if opened != 0 {
+        let _ = fs::remove_file(path);
+        return Err(format!("couldn't open a PTY: {}", io::Error::last_os_error()));
+    }
+
+    match unsafe { libc::fork() } {
+        -1 => {
+            let _ = fs::remove_file(path);
+            Err(format!("couldn't start the server: {}", io::Error::last_os_error()))
+        },
+        0 => {
+            // The server starts a new session, so that it isn't affected by
+            // the host terminal closing, with the PTY as its controlling
+            // terminal, so that it receives `SIGWINCH` when a client resizes
+            // the PTY.
+            unsafe {
+                libc::setsid();
+                libc::ioctl(slave, libc::TIOCSCTTY as _, 0);
+                for fd in 0..3 {
+                    libc::dup2(slave, fd);
+                }
+                if slave > 2 {
+                    libc::close(slave);
+                }
+                // The child isn't given the server's end of the PTY.
+                libc::fcntl(master, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+
+            Ok(Role::Server(Server::new(path, listener, master)))
+        },
+        _ => {
+            unsafe {
+                libc::close(master);
+                libc::close(slave);
+            }
+
+            Ok(Role::Client)
+        },
+    }
+}
+
+// `attach` relays the host terminal to the session whose socket is at `path`,
+// until the client is detached or the session ends. The host terminal is put
+// into raw mode while it's attached, as the server's curses handles input. If
+// `read_only` is `true` then input isn't relayed, other than
+// `READ_ONLY_DETACH`.
+pub fn attach(path: &Path, read_only: bool) -> Result<End, String> {
+    let stream =
+        match UnixStream::connect(path) {
+            Ok(stream) => stream,
+            Err(e) => return Err(format!("couldn't attach to '{}': {}", path.display(), e)),
+        };
+    let writer =
+        match stream.try_clone() {
+            Ok(writer) => Arc::new(Mutex::new(writer)),
+            Err(e) => return Err(format!("couldn't attach to '{}': {}", path.display(), e)),
+        };
+    if let Err(e) = write_frame(&mut *writer.lock().unwrap(), ATTACH, &[read_only as u8]) {
+        return Err(format!("couldn't attach to '{}': {}", path.display(), e));
+    }
+    let raw_mode = RawMode::enter(libc::STDIN_FILENO)?;
+
+    // The size of the host terminal is sent first, because the size of the
+    // PTY may have been set by a different host terminal.
+    let size = window_size(libc::STDIN_FILENO);
+    if let Some(size) = size {
+        let _ = write_frame(&mut *writer.lock().unwrap(), RESIZE, &resize_payload(size));
+    }
+
+    // The threads that relay input and resizes are left running when `attach`
+    // returns, as reads from standard input can't be interrupted. They stop
+    // when the process exits.
+    let input = writer.clone();
+    thread::spawn(move || {
+        let mut buf = [0u8; 0x1000];
+        let stdin = io::stdin();
+        let mut stdin = stdin.lock();
+        loop {
+            match stdin.read(&mut buf) {
+                Ok(0) => return,
+                Ok(n) if read_only => {
+                    if buf[..n].contains(&READ_ONLY_DETACH) {
+                        let _ = write_frame(&mut *input.lock().unwrap(), DETACH, &[]);
+                        return;
+                    }
+                },
+                Ok(n) => {
+                    if write_frame(&mut *input.lock().unwrap(), DATA, &buf[..n]).is_err() {
+                        return;
+                    }
+                },
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {},
+                Err(_) => return,
+            }
+        }
+    });
+    thread::spawn(move || {
+        let mut size = size;
+        loop {
+            thread::sleep(RESIZE_POLL_INTERVAL);
+            let new_size = window_size(libc::STDIN_FILENO);
+            if new_size == size {
+                continue;
+            }
+            size = new_size;
+            if let Some(size) = size {
+                if write_frame(&mut *writer.lock().unwrap(), RESIZE, &resize_payload(size)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut reader = stream;
+    let end =
+        loop {
+            match read_frame(&mut reader) {
+                Ok((DATA, payload)) => {
+                    // Failures are ignored because the host terminal may be
+                    // closing, in which case the server keeps running.
+                    let _ = stdout.write_all(&payload);
+                    let _ = stdout.flush();
+                },
+                Ok((DETACHED, _)) => break End::Detached,
+                Ok((EXITED, _)) => break End::Exited,
+                Ok(_) => {},
+                Err(_) => break End::Lost,
+            }
+        };
+    if end != End::Exited {
+        let _ = stdout.write_all(HOST_RESET.as_bytes());
+        let _ = stdout.flush();
+    }
+    drop(raw_mode);
+
+    Ok(end)
+}
+
+// `Client` is a client that's attached to a server.
+struct Client {
+    // `id` is the number of the client's connection, which distinguishes it
+    // from the other clients.
+    id: usize,
+    stream: UnixStream,
+    // `size` is the size of the client's host terminal, once the client has
+    // sent it.
+    size: Option<(u16, u16)>,
+}
+
+// `Remote` is a command that was sent to the server using `command`, which
+// the emulator should run and reply to.
+pub struct Remote {
+    pub line: String,
+    reply: Sender<Result<String, String>>,
+}
+
+impl Remote {
+    // `reply` sends the result of the command back to the process that sent
+    // it.
+    pub fn reply(self, result: Result<String, String>) {
+        // Failures are ignored because the sender may have disconnected.
+        let _ = self.reply.send(result);
+    }
+}
+
+// `Shared` is the state of a server that's shared between the emulator and the
+// threads that serve clients.
+struct Shared {
+    // `master` is the server's end of the PTY that the emulator renders to.
+    master: File,
+    clients: Mutex<Vec<Client>>,
+    // `last_input` is the number of the connection of the client whose input
+    // was last relayed to the emulator.
+    last_input: AtomicUsize,
+    // `commands` holds the commands that have been sent to the server and
+    // haven't been taken by the emulator.
+    commands: Mutex<Vec<Remote>>,
+    attached: AtomicBool,
+    killed: AtomicBool,
+}
+
+// `Server` relays between the server's end of the PTY that the emulator renders
+// to and the clients that are attached. Every client is sent the same output,
+// and the PTY is kept at the size of the smallest of their host terminals, in
+// the same way as `tmux`, so that the whole window fits on each of them.
+pub struct Server {
+    path: PathBuf,
+    shared: Arc<Shared>,
+    output: JoinHandle<()>,
+}
+
+impl Server {
+    fn new(path: &Path, listener: UnixListener, master: RawFd) -> Server {
+        let shared = Arc::new(Shared{
+            master: unsafe { File::from_raw_fd(master) },
+            clients: Mutex::new(vec![]),
+            last_input: AtomicUsize::new(0),
+            commands: Mutex::new(vec![]),
+            attached: AtomicBool::new(false),
+            killed: AtomicBool::new(false),
+        });
+
+        {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                for (id, stream) in listener.incoming().enumerate() {
+                    if let Ok(stream) = stream {
+                        let shared = shared.clone();
+                        thread::spawn(move || serve(id, stream, &shared));
+                    }
+                }
+            });
+        }
+
+        let output = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let mut buf = [0u8; 0x1000];
+                loop {
+                    let n =
+                        match (&shared.master).read(&mut buf) {
+                            Ok(0) => break,
+                            Ok(n) => n,
+                            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                            // Reads fail once the emulator has closed the
+                            // client end of the PTY.
+                            Err(_) => break,
+                        };
+                    // Output is dropped while no client is attached, as the
+                    // window is redrawn when a client attaches.
+                    // A client that misses `WRITE_TIMEOUT` may have been sent
+                    // part of a frame, so its connection is closed.
+                    let mut clients = shared.clients.lock().unwrap();
+                    let n_clients = clients.len();
+                    clients.retain(|client| {
+                        let written = write_frame(&mut &client.stream, DATA, &buf[..n]).is_ok();
+                        if !written {
+                            let _ = client.stream.shutdown(Shutdown::Both);
+                        }
+                        written
+                    });
+                    if clients.len() != n_clients {
+                        resize_to_smallest(&shared.master, &clients);
+                    }
+                }
+                for client in shared.clients.lock().unwrap().drain(..) {
+                    disconnect(client.stream, EXITED);
+                }
+            })
+        };
+
+        Server{
+            path: path.to_path_buf(),
+            shared: shared,
+            output: output,
+        }
+    }
+
+    // `attached` returns `true` if a client has attached since `attached` was
+    // last called, in which case the window should be redrawn in full, as the
+    // client's host terminal doesn't have its contents.
+    pub fn attached(&self) -> bool {
+        self.shared.attached.swap(false, Ordering::SeqCst)
+    }
+
+    // `killed` returns `true` if the session has been killed using `kill`, in
+    // which case the emulator should quit.
+    pub fn killed(&self) -> bool {
+        self.shared.killed.load(Ordering::SeqCst)
+    }
+
+    // `take_commands` returns the commands that have been sent to the server
+    // since `take_commands` was last called, in the order that they were sent.
+    pub fn take_commands(&self) -> Vec<Remote> {
+        self.shared.commands.lock().unwrap().drain(..).collect()
+    }
+
+    // `detach` disconnects the client whose input was last relayed to the
+    // emulator, which is the client that asked to be detached.
+    pub fn detach(&self) {
+        let id = self.shared.last_input.load(Ordering::SeqCst);
+        let mut clients = self.shared.clients.lock().unwrap();
+        if let Some(i) = clients.iter().position(|client| client.id == id) {
+            disconnect(clients.remove(i).stream, DETACHED);
+            resize_to_smallest(&self.shared.master, &clients);
+        }
+    }
+
+    // `finish` ends the session after the emulator has finished writing to the
+    // PTY, disconnecting the clients that are attached once the remaining
+    // output has been relayed to them.
+    pub fn finish(self) {
+        // Closing the standard streams closes the emulator's end of the PTY,
+        // which ends the thread that relays output once the PTY is drained.
+        unsafe {
+            for fd in 0..3 {
+                libc::close(fd);
+            }
+        }
+        let _ = self.output.join();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// `serve` handles the connection `stream`, whose number is `id`, according to
+// the first message that's received from it.
+fn serve(id: usize, mut stream: UnixStream, shared: &Shared) {
+    let read_only =
+        match read_frame(&mut stream) {
+            Ok((ATTACH, payload)) => payload.first() == Some(&1),
+            Ok((INFO, _)) => {
+                let attached = !shared.clients.lock().unwrap().is_empty();
+                let _ = write_frame(&mut stream, INFO, &[attached as u8]);
+                return;
+            },
+            Ok((KILL, _)) => {
+                shared.killed.store(true, Ordering::SeqCst);
+                return;
+            },
+            Ok((COMMAND, payload)) => {
+                let (reply, replies) = mpsc::channel();
+                let line = String::from_utf8_lossy(&payload).into_owned();
+                shared.commands.lock().unwrap().push(Remote{line: line, reply: reply});
+                let (status, text) =
+                    match replies.recv() {
+                        Ok(Ok(output)) => (0, output),
+                        Ok(Err(msg)) => (1, msg),
+                        Err(_) => (1, "the session ended".to_string()),
+                    };
+                let mut payload = vec![status];
+                payload.extend_from_slice(text.as_bytes());
+                let _ = write_frame(&mut stream, COMMAND, &payload);
+                return;
+            },
+            _ => return,
+        };
+
+    let writer =
+        match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(_) => return,
+        };
+    if writer.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+        return;
+    }
+    shared.clients.lock().unwrap().push(Client{id: id, stream: writer, size: None});
+    shared.attached.store(true, Ordering::SeqCst);
+
+    relay_input(id, stream, shared, read_only);
+}
+
+// `relay_input` writes the input from the client `stream`, whose connection
+// number is `id`, to the PTY, until the client disconnects or asks to be
+// detached. Input from read-only clients is dropped, but their sizes are still
+// used to size the PTY.
+fn relay_input(id: usize, mut stream: UnixStream, shared: &Shared, read_only: bool) {
+    let mut master = &shared.master;
+    let mut detached = false;
+    loop {
+        match read_frame(&mut stream) {
+            Ok((DATA, _)) if read_only => {},
+            Ok((DATA, payload)) => {
+                shared.last_input.store(id, Ordering::SeqCst);
+                if master.write_all(&payload).is_err() {
+                    break;
+                }
+            },
+            Ok((RESIZE, ref payload)) if payload.len() == 4 => {
+                let cols = (payload[0] as u16) << 8 | payload[1] as u16;
+                let lines = (payload[2] as u16) << 8 | payload[3] as u16;
+                let mut clients = shared.clients.lock().unwrap();
+                if let Some(client) = clients.iter_mut().find(|client| client.id == id) {
+                    client.size = Some((cols, lines));
+                }
+                resize_to_smallest(&shared.master, &clients);
+            },
+            Ok((DETACH, _)) => {
+                detached = true;
+                break;
+            },
+            Ok(_) => {},
+            Err(_) => break,
+        }
+    }
+
+    // The client may have already been removed, such as by `Server::detach`.
+    let mut clients = shared.clients.lock().unwrap();
+    if let Some(i) = clients.iter().position(|client| client.id == id) {
+        let client = clients.remove(i);
+        if detached {
+            disconnect(client.stream, DETACHED);
+        }
+        resize_to_smallest(&shared.master, &clients);
+    }
+}
+
+// `resize_to_smallest` resizes the PTY `master` to the smallest of the sizes of
+// the host terminals of `clients`, so that the window fits on each of them.
+// The columns and lines are minimised separately, so the size may be smaller
+// than that of every client.
+fn resize_to_smallest(master: &File, clients: &[Client]) {
+    let sizes: Vec<(u16, u16)> = clients.iter().filter_map(|client| client.size).collect();
+    let cols = sizes.iter().map(|&(cols, _)| cols).min();
+    let lines = sizes.iter().map(|&(_, lines)| lines).min();
+    if let (Some(cols), Some(lines)) = (cols, lines) {
+        let size = winsize((cols, lines));
+        // Resizing the PTY sends `SIGWINCH` to the server, which curses turns
+        // into `Input::KeyResize`. The kernel doesn't send `SIGWINCH` if the
+        // size hasn't changed.
+        unsafe {
+            libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size as *const libc::winsize);
+        }
+    }
+}
+
+// `remove_stale` removes the socket at `path` if it can't be connected to. The
+// socket is checked again while holding the session's lock (see `start`), so
+// that a socket that a new server has just created isn't removed.
+fn remove_stale(path: &Path) {
+    if let Ok(_lock) = lock(&lock_path(path)) {
+        if UnixStream::connect(path).is_err() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// `lock_path` returns the path of the lock file of the session whose socket is
+// at `path`. Its name starts with `.` so that it isn't listed as a session.
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".lock");
+
+    path.with_file_name(name)
+}
+
+// `lock` takes an exclusive lock on the file at `path`, creating it if it
+// doesn't exist, and waits for the lock if another process holds it. The lock
+// is released when the returned file is closed. Lock files are left in place,
+// as removing them would let two processes lock different files.
+fn lock(path: &Path) -> Result<File, String> {
+    let file =
+        match OpenOptions::new().write(true).create(true).open(path) {
+            Ok(file) => file,
+            Err(e) => return Err(format!("couldn't create '{}': {}", path.display(), e)),
+        };
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(format!("couldn't lock '{}': {}", path.display(), io::Error::last_os_error()));
+    }
+
+    Ok(file)
+}
+
+// `disconnect` tells the client `stream` why it's being disconnected, using the
+// message `kind`, and closes the connection.
+fn disconnect(mut stream: UnixStream, kind: u8) {
+    // Failures are ignored because the client may have already disconnected.
+    let _ = write_frame(&mut stream, kind, &[]);
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+fn write_frame<W: Write>(w: &mut W, kind: u8, payload: &[u8]) -> io::Result<()> {
+    let len = payload.len() as u32;
+    let mut frame = vec![kind, (len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+    frame.extend_from_slice(payload);
+
+    w.write_all(&frame)
+}
+
+fn read_frame<R: Read>(r: &mut R) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 5];
+    r.read_exact(&mut header)?;
+    let len =
+        (header[1] as usize) << 24 |
+        (header[2] as usize) << 16 |
+        (header[3] as usize) << 8 |
+        header[4] as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(ErrorKind::InvalidData, format!("frame is too long ({} bytes)", len)));
+    }
+    let mut payload = vec![0; len];
+    r.read_exact(&mut payload)?;
+
+    Ok((header[0], payload))
+}
+
+fn resize_payload((cols, lines): (u16, u16)) -> [u8; 4] {
+    [(cols >> 8) as u8, cols as u8, (lines >> 8) as u8, lines as u8]
+}
+
+// `window_size` returns the size of the terminal `fd`, in columns and lines,
+// if it's a terminal.
+fn window_size(fd: RawFd) -> Option<(u16, u16)> {
+    let mut size: libc::winsize = unsafe { mem::zeroed() };
+    if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size as *mut libc::winsize) } != 0 || size.ws_col == 0 {
+        return None;
+    }
+
+    Some((size.ws_col, size.ws_row))
+}
+
+fn winsize((cols, lines): (u16, u16)) -> libc::winsize {
+    libc::winsize{ws_row: lines, ws_col: cols, ws_xpixel: 0, ws_ypixel: 0}
+}
+
+// `RawMode` puts a terminal into raw mode, so that input is relayed to the
+// server unchanged, and restores the terminal's previous mode when it's
+// dropped.
+struct RawMode {
+    fd: RawFd,
+    saved: libc::termios,
+}
+
+impl RawMode {
+    fn enter(fd: RawFd) -> Result<RawMode, String> {
+        let mut saved: libc::termios = unsafe { mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return Err(format!("couldn't read the mode of the terminal: {}", io::Error::last_os_error()));
+        }
+        let mut raw = saved;
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+            libc::tcsetattr(fd, libc::TCSANOW, &raw);
+        }
+
+        Ok(RawMode{fd: fd, saved: saved})
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn frames_round_trip() {
+        let mut buf = vec![];
+        write_frame(&mut buf, DATA, b"hello").unwrap();
+        write_frame(&mut buf, DETACH, &[]).unwrap();
+
+        assert_eq!(&buf[..10], b"d\x00\x00\x00\x05hello");
+
+        let mut r = Cursor::new(buf);
+
+        assert_eq!(read_frame(&mut r).unwrap(), (DATA, b"hello".to_vec()));
+        assert_eq!(read_frame(&mut r).unwrap(), (DETACH, vec![]));
+        assert_eq!(read_frame(&mut r).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_frame_rejects_truncated_payloads() {
+        let mut r = Cursor::new(b"d\x00\x00\x00\x05hel".to_vec());
+
+        assert_eq!(read_frame(&mut r).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn resize_payload_is_big_endian() {
+        assert_eq!(resize_payload((300, 40)), [0x01, 0x2c, 0x00, 0x28]);
+    }
+
+    #[test]
+    fn read_frame_rejects_long_frames() {
+        let len = MAX_FRAME_LEN as u32 + 1;
+        let header = [DATA, (len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+
+        let err = read_frame(&mut Cursor::new(header.to_vec())).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
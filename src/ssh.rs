@@ -0,0 +1,58 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+
+use child::Exit;
+use session;
+use terminfo;
+
+// `DISCONNECTED` is how `ssh` exits if it can't connect, or if it loses the
+// connection.
+pub const DISCONNECTED: Exit = Exit::Status(255);
+
+// `OPTIONS` are the options that `ssh` is always run with. A connection that
+// stops responding is dropped after `ServerAliveCountMax` unanswered checks,
+// so that it can be reconnected, rather than the pane hanging until the
+// connection times out.
+const OPTIONS: &[&str] = &[
+    "ServerAliveInterval=15",
+    "ServerAliveCountMax=3",
+];
+
+// `CONTROL_PERSIST` is how long the shared connection to a host is kept open
+// after the last pane that uses it is closed.
+const CONTROL_PERSIST: &str = "60";
+
+// `argv` returns the command that connects to the destination that starts
+// `words`, and runs the rest of `words` on it as a command, or the remote
+// user's shell if there are no other words. The panes that connect to the same
+// host share one connection, using `ControlMaster`, so that opening a pane
+// doesn't authenticate again.
+pub fn argv(words: &[String]) -> Vec<String> {
+    let mut argv = vec![];
+    // The emulator's terminfo entry is unlikely to be installed on the remote
+    // host, so the fallback is used instead.
+    if env::var("TERM").ok().as_ref().map(String::as_str) == Some(terminfo::NAME) {
+        argv.push("env".to_string());
+        argv.push(format!("TERM={}", terminfo::FALLBACK_NAME));
+    }
+    argv.push("ssh".to_string());
+    argv.push("-t".to_string());
+    let mut options: Vec<String> = OPTIONS.iter().map(|opt| opt.to_string()).collect();
+    // The control sockets are kept with the sockets of the sessions, and the
+    // connection isn't shared if that directory can't be used.
+    if let Ok(dir) = session::socket_dir() {
+        options.push("ControlMaster=auto".to_string());
+        options.push(format!("ControlPath={}", dir.join(".ssh-%C").display()));
+        options.push(format!("ControlPersist={}", CONTROL_PERSIST));
+    }
+    for opt in options {
+        argv.push("-o".to_string());
+        argv.push(opt);
+    }
+    argv.extend(words.iter().cloned());
+
+    argv
+}
@@ -0,0 +1,68 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::fs;
+
+use alacritty::Term;
+use alacritty::term::cell::Flags;
+
+use reflow;
+use scrollback;
+use scrollback::Scrollback;
+
+// `save` writes the visual state of `term`, which should be the primary screen,
+// and of `scrollback` to `path`, so that it can be restored after the emulator
+// has been upgraded or the machine has restarted (see `--restore`). The state
+// is saved as the output that draws the lines of the scrollback and the screen,
+// with their attributes, and moves the cursor to its position, so restoring it
+// is a matter of processing it as if it was output from the child, which also
+// adds the lines that scroll off the screen to the scrollback.
+//
+// The child isn't saved, and neither are the modes that it set, such as mouse
+// reporting, as they belong to the child, which doesn't exist after the state
+// is restored.
+pub fn save(path: &str, term: &Term, scrollback: &Scrollback) -> Result<(), String> {
+    let num_lines = term.grid().num_lines().0;
+    let cursor = term.cursor().point;
+    let cursor_line = scrollback.len() + cursor.line.0;
+
+    // Blank lines below the cursor aren't saved, so that they don't push the
+    // restored lines up if the terminal is restored at a smaller size.
+    let mut last = cursor_line;
+    for i in cursor_line + 1..scrollback.len() + num_lines {
+        let cells = scrollback::line_cells(term, scrollback, i);
+        if cells.iter().any(|c| !reflow::is_blank(c)) {
+            last = i;
+        }
+    }
+
+    let mut out = String::from("\x1b[0m");
+    for i in 0..=last {
+        let cells = scrollback::line_cells(term, scrollback, i);
+        // Lines that were wrapped by the terminal are drawn in full, so that
+        // they're wrapped again when they're restored, rather than being
+        // split by a newline.
+        if cells.last().map_or(false, |c| c.flags.contains(Flags::WRAPLINE)) {
+            reflow::encode(&cells, &mut out);
+            continue;
+        }
+        let len = cells.iter().rposition(|c| !reflow::is_blank(c)).map_or(0, |i| i + 1);
+        reflow::encode(&cells[..len], &mut out);
+        if i < last {
+            out.push_str("\x1b[0m\r\n");
+        }
+    }
+
+    // The cursor is moved relative to the last line, as the lines above it may
+    // have scrolled off the screen when the state is restored.
+    out.push_str("\x1b[0m\r");
+    if last > cursor_line {
+        out.push_str(&format!("\x1b[{}A", last - cursor_line));
+    }
+    if cursor.col.0 > 0 {
+        out.push_str(&format!("\x1b[{}C", cursor.col.0));
+    }
+
+    fs::write(path, out).map_err(|e| format!("couldn't write '{}': {}", path, e))
+}
@@ -0,0 +1,52 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use layout::{Layout, PaneId};
+
+// `DEFAULT_NAME` is the name of new tabs, until they're renamed.
+const DEFAULT_NAME: &str = "shell";
+
+// `Tab` is a window of the emulator, which arranges some of the panes using its
+// own layout. Only the panes of the current tab are drawn, but the children of
+// the other tabs keep running, and their output is still read.
+pub struct Tab {
+    pub name: String,
+    pub layout: Layout,
+    // `active` is the pane of the tab that was last active, which becomes
+    // active again when the tab is switched back to.
+    pub active: PaneId,
+    // `sync` is `true` if input is sent to every pane of the tab, instead of
+    // only to the active pane.
+    pub sync: bool,
+}
+
+impl Tab {
+    pub fn new(pane: PaneId) -> Tab {
+        Tab{
+            name: DEFAULT_NAME.to_string(),
+            layout: Layout::new(pane),
+            active: pane,
+            sync: false,
+        }
+    }
+
+    pub fn contains(&self, pane: PaneId) -> bool {
+        self.layout.panes().contains(&pane)
+    }
+}
+
+// `status_line` returns the list of tabs that's drawn on the status line, where
+// each tab is numbered from 1, in the same way as the items of a `Picker`,
+// `current` is marked with `*`, and tabs whose input is synchronized are marked
+// with `(sync)`.
+pub fn status_line(tabs: &[Tab], current: usize) -> String {
+    tabs.iter()
+        .enumerate()
+        .map(|(i, tab)| {
+            let marker = if i == current { "*" } else { "" };
+            let sync = if tab.sync { " (sync)" } else { "" };
+            format!(" {}:{}{}{} ", i + 1, tab.name, marker, sync)
+        })
+        .collect()
+}
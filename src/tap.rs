@@ -0,0 +1,822 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+use std::convert::TryFrom;
+use std::mem;
+use std::str;
+
+use base64;
+use vte;
+
+use clipboard::Target;
+use colour::Spec;
+use extras::Style;
+use palette::Rgb;
+
+// `Tap` observes the output of the child for sequences that `alacritty::Term`
+// handles internally but doesn't expose, such as changes to the palette. It's
+// fed the same bytes as `alacritty::ansi::Processor`, and the sequences that
+// it recognises are made available through `take_events`.
+//
+// `Tap` also tracks the current `Style`, which is set using SGR attributes
+// that `alacritty::Term` doesn't track, such as underline colours (SGR 58) and
+// undercurl (SGR 4:3).
+pub struct Tap {
+    parser: vte::Parser,
+    performer: Performer,
+    sgr: SgrScanner,
+    osc52: Osc52Scanner,
+    style: Style,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    SetColour(u8, Rgb),
+    ResetColour(u8),
+    ResetColours,
+    SetForeground(Rgb),
+    ResetForeground,
+    SetBackground(Rgb),
+    ResetBackground,
+    // `SetCursorShape` is `None` if the child requested the default cursor
+    // shape.
+    SetCursorShape(Option<CursorShape>),
+    // `SetLineSize` applies to the line that the cursor is on.
+    SetLineSize(LineSize),
+    // `EnterAltScreen` and `ExitAltScreen` are returned before
+    // `alacritty::Term` switches screens, if `Tap::advance` is called before
+    // `alacritty::ansi::Processor::advance`.
+    EnterAltScreen,
+    ExitAltScreen,
+    // `SetPrivateMode(mode, set)` is returned for each mode that's set (if
+    // `set` is `true`) or reset using DECSET or DECRST.
+    SetPrivateMode(i64, bool),
+    // `SetModifyOtherKeys` sets the level of xterm's modifyOtherKeys mode.
+    SetModifyOtherKeys(i64),
+    // `PushKeyboardFlags`, `PopKeyboardFlags`, `SetKeyboardFlags` and
+    // `QueryKeyboardFlags` manipulate the stack of flags of the kitty keyboard
+    // protocol, which is also known as "CSI u".
+    PushKeyboardFlags(u32),
+    PopKeyboardFlags(usize),
+    // `SetKeyboardFlags(flags, mode)` replaces the current flags with `flags`
+    // if `mode` is `1`, sets `flags` if `mode` is `2`, and clears `flags` if
+    // `mode` is `3`.
+    SetKeyboardFlags(u32, u32),
+    QueryKeyboardFlags,
+    // `LineFeed` is returned for each control that moves the cursor down a
+    // line, scrolling the screen if the cursor is at the bottom margin.
+    LineFeed,
+    // `ScrollUp` and `ScrollDown` scroll the scrolling region up or down by
+    // the given number of lines.
+    ScrollUp(usize),
+    ScrollDown(usize),
+    // `ReverseIndex` moves the cursor up a line, scrolling the screen down if
+    // the cursor is at the top margin.
+    ReverseIndex,
+    // `InsertLines` and `DeleteLines` scroll the lines from the cursor to the
+    // bottom margin down or up by the given number of lines.
+    InsertLines(usize),
+    DeleteLines(usize),
+    // `EraseInDisplay` and `EraseInLine` erase the screen or the line of the
+    // cursor: from the cursor to the end if the parameter is `0`, from the
+    // start to the cursor if it's `1`, and all of it if it's `2`.
+    EraseInDisplay(i64),
+    EraseInLine(i64),
+    // `SetScrollRegion(top, bottom)` sets the scrolling region to the lines
+    // from `top` to `bottom`, inclusive and counting from `0`, where `bottom`
+    // is `None` if the scrolling region extends to the bottom of the screen.
+    SetScrollRegion(usize, Option<usize>),
+    // `ClearScrollback` is returned when the child requests that the lines
+    // that have scrolled off the top of the screen be discarded.
+    ClearScrollback,
+    // `SetClipboard` and `QueryClipboard` are returned when the child sets or
+    // queries one of the host's selections using OSC 52.
+    SetClipboard(Target, Vec<u8>),
+    QueryClipboard(Target),
+    // `SetTitle` is returned when the child sets its window title using OSC 0
+    // or OSC 2.
+    SetTitle(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CursorShape {
+    Block,
+    Underline,
+    Bar,
+}
+
+// `LineSize` is the size of the characters on a line, as set by DECDHL, DECSWL
+// and DECDWL.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineSize {
+    Single,
+    DoubleWidth,
+    DoubleHeightTop,
+    DoubleHeightBottom,
+}
+
+impl Tap {
+    // `new` returns a `Tap` that ignores OSC 52 sequences that set selections
+    // to more than `clipboard_limit` bytes.
+    pub fn new(clipboard_limit: usize) -> Tap {
+        Tap{
+            parser: vte::Parser::new(),
+            performer: Performer{
+                events: vec![],
+                printed: None,
+            },
+            sgr: SgrScanner::Ground,
+            osc52: Osc52Scanner{
+                state: Osc52State::Ground,
+                limit: clipboard_limit,
+            },
+            style: Style::default(),
+        }
+    }
+
+    // `advance` returns the printable character that `byte` completed, if
+    // any.
+    pub fn advance(&mut self, byte: u8) -> Option<char> {
+        self.performer.printed = None;
+        self.parser.advance(&mut self.performer, byte);
+
+        if let Some(params) = self.sgr.advance(byte) {
+            apply_sgr(&mut self.style, &params);
+        }
+        if let Some(payload) = self.osc52.advance(byte) {
+            self.performer.events.extend(parse_osc52(&payload, self.osc52.limit));
+        }
+
+        self.performer.printed
+    }
+
+    pub fn style(&self) -> Style {
+        self.style
+    }
+
+    pub fn take_events(&mut self) -> Vec<Event> {
+        mem::replace(&mut self.performer.events, vec![])
+    }
+}
+
+struct Performer {
+    events: Vec<Event>,
+    printed: Option<char>,
+}
+
+impl vte::Perform for Performer {
+    fn print(&mut self, c: char) {
+        self.printed = Some(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        // LF, VT and FF are all treated as line feeds.
+        if byte == 0x0a || byte == 0x0b || byte == 0x0c {
+            self.events.push(Event::LineFeed);
+        }
+    }
+
+    fn hook(&mut self, _params: &[i64], _intermediates: &[u8], _ignore: bool) {}
+
+    fn put(&mut self, _byte: u8) {}
+
+    fn unhook(&mut self) {}
+
+    fn osc_dispatch(&mut self, params: &[&[u8]]) {
+        if params.is_empty() {
+            return;
+        }
+
+        match params[0] {
+            // `OSC 0 ; title ST` and `OSC 2 ; title ST`. The title may itself
+            // contain semicolons, which split it into more than one parameter.
+            b"0" | b"2" => {
+                let title = params[1..].join(&b';');
+                self.events.push(Event::SetTitle(String::from_utf8_lossy(&title).into_owned()));
+            },
+            // `OSC 4 ; index ; spec [; index ; spec ...] ST`
+            b"4" => {
+                for pair in params[1..].chunks(2) {
+                    if pair.len() != 2 {
+                        break;
+                    }
+                    let index = parse_number(pair[0]);
+                    let rgb = parse_colour_spec(pair[1]);
+                    if let (Some(index), Some(rgb)) = (index, rgb) {
+                        self.events.push(Event::SetColour(index, rgb));
+                    }
+                }
+            },
+            // `OSC 104 [; index ...] ST`
+            b"104" => {
+                if params.len() == 1 {
+                    self.events.push(Event::ResetColours);
+                }
+                for param in &params[1..] {
+                    if let Some(index) = parse_number(param) {
+                        self.events.push(Event::ResetColour(index));
+                    }
+                }
+            },
+            // `OSC 10 ; spec [; spec] ST`, where the second specification, if
+            // present, sets the default background colour.
+            b"10" => {
+                if let Some(rgb) = params.get(1).and_then(|p| parse_colour_spec(p)) {
+                    self.events.push(Event::SetForeground(rgb));
+                }
+                if let Some(rgb) = params.get(2).and_then(|p| parse_colour_spec(p)) {
+                    self.events.push(Event::SetBackground(rgb));
+                }
+            },
+            b"11" => {
+                if let Some(rgb) = params.get(1).and_then(|p| parse_colour_spec(p)) {
+                    self.events.push(Event::SetBackground(rgb));
+                }
+            },
+            b"110" => {
+                self.events.push(Event::ResetForeground);
+            },
+            b"111" => {
+                self.events.push(Event::ResetBackground);
+            },
+            _ => {},
+        }
+    }
+
+    fn csi_dispatch(
+        &mut self,
+        params: &[i64],
+        intermediates: &[u8],
+        ignore: bool,
+        c: char,
+    ) {
+        if ignore {
+            return;
+        }
+
+        match (intermediates, c) {
+            // `CSI Ps SP q` (DECSCUSR). Odd values of `Ps` request blinking
+            // cursors, and even values request steady ones.
+            (b" ", 'q') => {
+                let shape =
+                    match params.get(0).cloned().unwrap_or(0) {
+                        0 => None,
+                        1 | 2 => Some(CursorShape::Block),
+                        3 | 4 => Some(CursorShape::Underline),
+                        5 | 6 => Some(CursorShape::Bar),
+                        _ => return,
+                    };
+                self.events.push(Event::SetCursorShape(shape));
+            },
+            // `CSI ? Pm h` (DECSET) and `CSI ? Pm l` (DECRST), where modes
+            // `47`, `1047` and `1049` switch between the primary and
+            // alternate screens.
+            (b"?", 'h') | (b"?", 'l') => {
+                let set = c == 'h';
+                if params.iter().any(|&p| p == 47 || p == 1047 || p == 1049) {
+                    if set {
+                        self.events.push(Event::EnterAltScreen);
+                    } else {
+                        self.events.push(Event::ExitAltScreen);
+                    }
+                }
+                for &p in params {
+                    self.events.push(Event::SetPrivateMode(p, set));
+                }
+            },
+            // `CSI > 4 ; Pv m` (XTMODKEYS), where `CSI > 4 m` resets the level
+            // of modifyOtherKeys.
+            (b">", 'm') => {
+                if params.get(0) == Some(&4) {
+                    let level = params.get(1).cloned().unwrap_or(0);
+                    self.events.push(Event::SetModifyOtherKeys(level));
+                }
+            },
+            // `CSI > flags u`, `CSI < n u`, `CSI = flags ; mode u` and
+            // `CSI ? u`.
+            (b">", 'u') => {
+                let flags = params.get(0).cloned().unwrap_or(0);
+                self.events.push(Event::PushKeyboardFlags(flags as u32));
+            },
+            (b"<", 'u') => {
+                let n = params.get(0).cloned().unwrap_or(1);
+                self.events.push(Event::PopKeyboardFlags(cmp::max(n, 1) as usize));
+            },
+            (b"=", 'u') => {
+                let flags = params.get(0).cloned().unwrap_or(0);
+                let mode = params.get(1).cloned().unwrap_or(1);
+                self.events.push(Event::SetKeyboardFlags(flags as u32, mode as u32));
+            },
+            (b"?", 'u') => {
+                self.events.push(Event::QueryKeyboardFlags);
+            },
+            // `CSI Ps J` (ED), where `CSI 3 J` is an xterm extension that
+            // clears the scrollback, as used by `clear`.
+            (b"", 'J') => {
+                match params.get(0).cloned().unwrap_or(0) {
+                    3 => self.events.push(Event::ClearScrollback),
+                    mode => self.events.push(Event::EraseInDisplay(mode)),
+                }
+            },
+            // `CSI Ps K` (EL).
+            (b"", 'K') => {
+                let mode = params.get(0).cloned().unwrap_or(0);
+                self.events.push(Event::EraseInLine(mode));
+            },
+            // `CSI Ps S` (SU), `CSI Ps T` (SD), `CSI Ps L` (IL) and `CSI Ps M`
+            // (DL).
+            (b"", 'S') | (b"", 'T') | (b"", 'L') | (b"", 'M') => {
+                let n = cmp::max(params.get(0).cloned().unwrap_or(1), 1) as usize;
+                let evt =
+                    match c {
+                        'S' => Event::ScrollUp(n),
+                        'T' => Event::ScrollDown(n),
+                        'L' => Event::InsertLines(n),
+                        _ => Event::DeleteLines(n),
+                    };
+                self.events.push(evt);
+            },
+            // `CSI Ps ; Ps r` (DECSTBM), where the margins count from `1`.
+            (b"", 'r') => {
+                let top = params.get(0).cloned().unwrap_or(1);
+                let bottom =
+                    match params.get(1).cloned().unwrap_or(0) {
+                        0 => None,
+                        n => Some((n - 1) as usize),
+                    };
+                self.events.push(Event::SetScrollRegion((cmp::max(top, 1) - 1) as usize, bottom));
+            },
+            _ => {},
+        }
+    }
+
+    fn esc_dispatch(
+        &mut self,
+        _params: &[i64],
+        intermediates: &[u8],
+        ignore: bool,
+        byte: u8,
+    ) {
+        if ignore {
+            return;
+        }
+
+        let evt =
+            match (intermediates, byte) {
+                (b"#", b'3') => Event::SetLineSize(LineSize::DoubleHeightTop),
+                (b"#", b'4') => Event::SetLineSize(LineSize::DoubleHeightBottom),
+                (b"#", b'5') => Event::SetLineSize(LineSize::Single),
+                (b"#", b'6') => Event::SetLineSize(LineSize::DoubleWidth),
+                // `ESC D` (IND) and `ESC E` (NEL).
+                (b"", b'D') | (b"", b'E') => Event::LineFeed,
+                // `ESC M` (RI).
+                (b"", b'M') => Event::ReverseIndex,
+                // `ESC c` (RIS) resets the scrolling region, among other
+                // things.
+                (b"", b'c') => Event::SetScrollRegion(0, None),
+                _ => return,
+            };
+        self.events.push(evt);
+    }
+}
+
+// `SgrScanner` extracts the parameters of SGR sequences from a byte stream. We
+// scan SGR sequences ourselves, instead of using `vte::Perform::csi_dispatch`,
+// because `vte` doesn't support the colon-separated subparameters used by
+// sequences such as `CSI 4:3 m` (undercurl).
+enum SgrScanner {
+    Ground,
+    Escape,
+    Csi(Vec<u8>),
+    // `Ignore` is used for the remainder of CSI sequences that can't be SGR
+    // sequences.
+    Ignore,
+}
+
+impl SgrScanner {
+    // `advance` returns the parameters of an SGR sequence if `byte` completes
+    // one. Each parameter is returned along with its subparameters, so that
+    // `CSI 1;4:3 m` is returned as `[[1], [4, 3]]`. Empty parameters are
+    // returned as `0`.
+    fn advance(&mut self, byte: u8) -> Option<Vec<Vec<i64>>> {
+        let mut sgr = None;
+        *self =
+            match (mem::replace(self, SgrScanner::Ground), byte) {
+                (_, 0x1b) => SgrScanner::Escape,
+                (SgrScanner::Escape, b'[') => SgrScanner::Csi(vec![]),
+                (SgrScanner::Csi(mut buf), 0x30..=0x3b) => {
+                    buf.push(byte);
+                    SgrScanner::Csi(buf)
+                },
+                (SgrScanner::Csi(buf), b'm') => {
+                    sgr = Some(parse_sgr_params(&buf));
+                    SgrScanner::Ground
+                },
+                (SgrScanner::Csi(_), 0x40..=0x7e) |
+                (SgrScanner::Ignore, 0x40..=0x7e) => SgrScanner::Ground,
+                // Private parameter prefixes and intermediate bytes don't
+                // occur in SGR sequences.
+                (SgrScanner::Csi(_), _) |
+                (SgrScanner::Ignore, _) => SgrScanner::Ignore,
+                _ => SgrScanner::Ground,
+            };
+
+        sgr
+    }
+}
+
+// `Osc52Scanner` extracts the payloads of OSC 52 sequences from a byte stream.
+// We scan OSC 52 sequences ourselves, instead of using
+// `vte::Perform::osc_dispatch`, because `vte` truncates long OSC sequences,
+// and OSC 52 sequences can be as long as the text that's being copied.
+struct Osc52Scanner {
+    state: Osc52State,
+    // `limit` is the maximum length of the decoded content of a sequence.
+    limit: usize,
+}
+
+enum Osc52State {
+    Ground,
+    Escape,
+    Osc(Vec<u8>),
+    // `OscEscape` is used after an `ESC` that may start the `ST` that
+    // terminates the sequence.
+    OscEscape(Vec<u8>),
+    // `Ignore` is used for the remainder of OSC sequences that aren't OSC 52
+    // sequences, or that are too long.
+    Ignore,
+    IgnoreEscape,
+}
+
+impl Osc52Scanner {
+    // `advance` returns the parameters of an OSC 52 sequence, such as `c;aGk=`,
+    // if `byte` completes one.
+    fn advance(&mut self, byte: u8) -> Option<Vec<u8>> {
+        // The length of the base64 encoding of `limit` bytes, including the
+        // parameters that precede it.
+        let max_len = self.limit / 3 * 4 + 16;
+        let mut payload = None;
+        self.state =
+            match (mem::replace(&mut self.state, Osc52State::Ground), byte) {
+                (Osc52State::Osc(buf), 0x1b) => Osc52State::OscEscape(buf),
+                (Osc52State::Ignore, 0x1b) => Osc52State::IgnoreEscape,
+                (Osc52State::OscEscape(buf), b'\\') => {
+                    payload = Some(buf);
+                    Osc52State::Ground
+                },
+                (Osc52State::IgnoreEscape, b'\\') => Osc52State::Ground,
+                (Osc52State::Osc(buf), 0x07) => {
+                    payload = Some(buf);
+                    Osc52State::Ground
+                },
+                (Osc52State::Ignore, 0x07) => Osc52State::Ground,
+                (_, 0x1b) => Osc52State::Escape,
+                (Osc52State::Escape, b']') |
+                (Osc52State::OscEscape(_), b']') |
+                (Osc52State::IgnoreEscape, b']') => Osc52State::Osc(vec![]),
+                // CAN and SUB cancel the sequence.
+                (Osc52State::Osc(_), 0x18) | (Osc52State::Osc(_), 0x1a) |
+                (Osc52State::Ignore, 0x18) | (Osc52State::Ignore, 0x1a) => Osc52State::Ground,
+                (Osc52State::Osc(mut buf), _) => {
+                    buf.push(byte);
+                    let is_osc52 = b"52;".starts_with(&buf) || buf.starts_with(b"52;");
+                    if is_osc52 && buf.len() <= max_len {
+                        Osc52State::Osc(buf)
+                    } else {
+                        Osc52State::Ignore
+                    }
+                },
+                (Osc52State::Ignore, _) => Osc52State::Ignore,
+                _ => Osc52State::Ground,
+            };
+
+        payload
+    }
+}
+
+// `parse_osc52` returns the events for the parameters of an OSC 52 sequence,
+// which are the selections to set or query, and the base64-encoded content to
+// set them to, or `?` to query them.
+fn parse_osc52(payload: &[u8], limit: usize) -> Vec<Event> {
+    // Sequences that were terminated before their parameters could be
+    // checked, such as `OSC 5 ST`, are ignored.
+    if !payload.starts_with(b"52;") {
+        return vec![];
+    }
+    let mut params = payload[3..].splitn(2, |&b| b == b';');
+    let (selections, data) =
+        match (params.next(), params.next()) {
+            (Some(s), Some(d)) => (s, d),
+            _ => return vec![],
+        };
+
+    // `s` is the "selection", which we treat as the clipboard, and is the
+    // default if no selections are given. The cut buffers (`0` to `7`) aren't
+    // supported.
+    let selections: &[u8] = if selections.is_empty() { b"s" } else { selections };
+    let mut targets = vec![];
+    for &c in selections {
+        let target =
+            match c {
+                b'c' | b's' => Target::Clipboard,
+                b'p' => Target::Primary,
+                _ => continue,
+            };
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+
+    if data == b"?" {
+        return targets.into_iter().take(1).map(Event::QueryClipboard).collect();
+    }
+    let content =
+        match base64::decode(data) {
+            Ok(content) if content.len() <= limit => content,
+            _ => return vec![],
+        };
+
+    targets.into_iter()
+        .map(|t| Event::SetClipboard(t, content.clone()))
+        .collect()
+}
+
+fn parse_sgr_params(buf: &[u8]) -> Vec<Vec<i64>> {
+    let s = String::from_utf8_lossy(buf);
+
+    s.split(';')
+        .map(|param| {
+            param.split(':')
+                .map(|sub| sub.parse().unwrap_or(0))
+                .collect()
+        })
+        .collect()
+}
+
+fn apply_sgr(style: &mut Style, params: &[Vec<i64>]) {
+    let mut i = 0;
+    while i < params.len() {
+        let param = &params[i];
+        match param[0] {
+            0 => {
+                *style = Style::default();
+            },
+            4 => {
+                // `CSI 4:n m` selects an underline style, where `3` is
+                // undercurl and `0` disables underlining.
+                style.undercurl = param.get(1) == Some(&3);
+            },
+            24 => {
+                style.undercurl = false;
+            },
+            9 => {
+                style.strikethrough = true;
+            },
+            29 => {
+                style.strikethrough = false;
+            },
+            59 => {
+                style.underline_colour = None;
+            },
+            38 | 48 | 58 => {
+                // Extended colours can be given either as subparameters
+                // (`58:5:n`, `58:2::r:g:b`) or as parameters (`58;5;n`,
+                // `58;2;r;g;b`). In the latter case we skip the parameters
+                // that make up the colour so that they aren't interpreted as
+                // separate attributes.
+                let colour =
+                    if param.len() > 1 {
+                        parse_extended_colour(&param[1..])
+                    } else {
+                        let rest: Vec<i64> = params[i + 1..]
+                            .iter()
+                            .map(|p| p[0])
+                            .take(4)
+                            .collect();
+                        match rest.get(0) {
+                            Some(5) => i += 2,
+                            Some(2) => i += 4,
+                            _ => {},
+                        }
+
+                        parse_extended_colour(&rest)
+                    };
+                if param[0] == 58 {
+                    style.underline_colour = colour;
+                }
+            },
+            _ => {},
+        }
+        i += 1;
+    }
+}
+
+// `parse_extended_colour` parses the arguments of an extended colour, in the
+// form `[5, n]` or `[2, r, g, b]`. The colour space ID that can precede
+// `r, g, b` when subparameters are used (`58:2::r:g:b`) is also supported.
+fn parse_extended_colour(args: &[i64]) -> Option<Spec> {
+    match args.get(0) {
+        // Components that are out of range make the colour invalid, rather
+        // than being wrapped.
+        Some(5) => Some(Spec::Indexed(u8::try_from(*args.get(1)?).ok()?)),
+        Some(2) => {
+            let rgb = if args.len() >= 5 { &args[2..5] } else { &args[1..] };
+            if rgb.len() < 3 {
+                return None;
+            }
+
+            Some(Spec::Rgb((
+                u8::try_from(rgb[0]).ok()?,
+                u8::try_from(rgb[1]).ok()?,
+                u8::try_from(rgb[2]).ok()?,
+            )))
+        },
+        _ => None,
+    }
+}
+
+fn parse_number(bytes: &[u8]) -> Option<u8> {
+    str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+// `parse_colour_spec` parses colour specifications of the forms
+// `rgb:<r>/<g>/<b>`, where each component has between 1 and 4 hex digits, and
+// `#<rrggbb>`. Queries (`?`) aren't colour specifications and so aren't
+// handled.
+fn parse_colour_spec(bytes: &[u8]) -> Option<Rgb> {
+    let spec = str::from_utf8(bytes).ok()?;
+
+    if spec.starts_with("rgb:") {
+        let parts: Vec<&str> = spec[4..].split('/').collect();
+        if parts.len() != 3 {
+            return None;
+        }
+
+        return Some((
+            parse_scaled_hex(parts[0])?,
+            parse_scaled_hex(parts[1])?,
+            parse_scaled_hex(parts[2])?,
+        ));
+    }
+
+    // `spec` comes from the child, and may contain multi-byte characters, so
+    // it's sliced using `get`, which can't panic.
+    if spec.starts_with('#') && spec.len() == 7 {
+        return Some((
+            u8::from_str_radix(spec.get(1..3)?, 16).ok()?,
+            u8::from_str_radix(spec.get(3..5)?, 16).ok()?,
+            u8::from_str_radix(spec.get(5..7)?, 16).ok()?,
+        ));
+    }
+
+    None
+}
+
+// `parse_scaled_hex` parses a hex number of between 1 and 4 digits and scales
+// it to 8 bits, so that `f`, `ff` and `ffff` are all parsed as `0xff`.
+fn parse_scaled_hex(s: &str) -> Option<u8> {
+    if s.is_empty() || s.len() > 4 {
+        return None;
+    }
+    let v = u32::from_str_radix(s, 16).ok()?;
+    let max = (1 << (4 * s.len())) - 1;
+
+    Some((v * 0xff / max) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(tap: &mut Tap, output: &str) -> Vec<Event> {
+        for &byte in output.as_bytes() {
+            tap.advance(byte);
+        }
+
+        tap.take_events()
+    }
+
+    #[test]
+    fn parse_colour_spec_parses_specs() {
+        let tests = [
+            ("rgb:ff/80/00", Some((0xff, 0x80, 0x00))),
+            ("rgb:f/8/0", Some((0xff, 0x88, 0x00))),
+            ("rgb:ffff/8000/0000", Some((0xff, 0x7f, 0x00))),
+            ("#ff8000", Some((0xff, 0x80, 0x00))),
+            ("rgb:ff/80", None),
+            ("rgb:fffff/0/0", None),
+            ("rgb://", None),
+            ("#ff80", None),
+            ("#1é234", None),
+            ("?", None),
+        ];
+
+        for &(spec, expected) in tests.iter() {
+            assert_eq!(parse_colour_spec(spec.as_bytes()), expected, "{}", spec);
+        }
+    }
+
+    #[test]
+    fn tap_returns_palette_changes() {
+        let tests: &[(&str, &[Event])] = &[
+            (
+                "\x1b]4;1;rgb:ff/00/00;2;#00ff00\x07",
+                &[Event::SetColour(1, (0xff, 0x00, 0x00)), Event::SetColour(2, (0x00, 0xff, 0x00))],
+            ),
+            ("\x1b]4;1;?\x07", &[]),
+            ("\x1b]104\x07", &[Event::ResetColours]),
+            ("\x1b]104;3;4\x07", &[Event::ResetColour(3), Event::ResetColour(4)]),
+            (
+                "\x1b]10;#010203;#040506\x07",
+                &[Event::SetForeground((1, 2, 3)), Event::SetBackground((4, 5, 6))],
+            ),
+            ("\x1b]11;#040506\x07", &[Event::SetBackground((4, 5, 6))]),
+            ("\x1b]110\x07\x1b]111\x07", &[Event::ResetForeground, Event::ResetBackground]),
+        ];
+
+        for &(output, expected) in tests {
+            assert_eq!(feed(&mut Tap::new(0), output), expected, "{:?}", output);
+        }
+    }
+
+    #[test]
+    fn parse_sgr_params_splits_subparameters() {
+        assert_eq!(parse_sgr_params(b"1;4:3"), vec![vec![1], vec![4, 3]]);
+        assert_eq!(parse_sgr_params(b"58:2::1:2:3"), vec![vec![58, 2, 0, 1, 2, 3]]);
+        assert_eq!(parse_sgr_params(b""), vec![vec![0]]);
+    }
+
+    #[test]
+    fn tap_tracks_style() {
+        let underline = |colour| Style{underline_colour: colour, ..Style::default()};
+        let tests = [
+            ("\x1b[4:3m", Style{undercurl: true, ..Style::default()}),
+            ("\x1b[4:3m\x1b[4:0m", Style::default()),
+            ("\x1b[4:3m\x1b[24m", Style::default()),
+            ("\x1b[9m", Style{strikethrough: true, ..Style::default()}),
+            ("\x1b[9m\x1b[29m", Style::default()),
+            ("\x1b[58:5:196m", underline(Some(Spec::Indexed(196)))),
+            ("\x1b[58:2::1:2:3m", underline(Some(Spec::Rgb((1, 2, 3))))),
+            ("\x1b[58:2:1:2:3m", underline(Some(Spec::Rgb((1, 2, 3))))),
+            ("\x1b[58:5:256m", underline(None)),
+            ("\x1b[58:5:196m\x1b[59m", underline(None)),
+            // The parameters of colours that aren't given as subparameters
+            // aren't treated as attributes.
+            ("\x1b[58;5;9m", underline(Some(Spec::Indexed(9)))),
+            ("\x1b[38;2;9;9;9;58;5;4m", underline(Some(Spec::Indexed(4)))),
+            (
+                "\x1b[9;58;2;1;2;3m",
+                Style{strikethrough: true, ..underline(Some(Spec::Rgb((1, 2, 3))))},
+            ),
+            ("\x1b[9;4:3m\x1b[m", Style::default()),
+            // Sequences with private parameter prefixes or intermediate bytes
+            // aren't SGR sequences.
+            ("\x1b[?9m", Style::default()),
+            ("\x1b[9 m", Style::default()),
+            ("\x1b[9\x1bm", Style::default()),
+        ];
+
+        for &(output, expected) in tests.iter() {
+            let mut tap = Tap::new(0);
+            feed(&mut tap, output);
+
+            assert_eq!(tap.style(), expected, "{:?}", output);
+        }
+    }
+
+    #[test]
+    fn tap_returns_clipboard_events() {
+        let set = |t| Event::SetClipboard(t, b"hi".to_vec());
+        let tests: &[(&str, &[Event])] = &[
+            ("\x1b]52;c;aGk=\x07", &[set(Target::Clipboard)]),
+            ("\x1b]52;c;aGk=\x1b\\", &[set(Target::Clipboard)]),
+            ("\x1b]52;;aGk=\x07", &[set(Target::Clipboard)]),
+            ("\x1b]52;pcs;aGk=\x07", &[set(Target::Primary), set(Target::Clipboard)]),
+            ("\x1b]52;01;aGk=\x07", &[]),
+            ("\x1b]52;p;?\x07", &[Event::QueryClipboard(Target::Primary)]),
+            ("\x1b]52;c;!!!\x07", &[]),
+            ("\x1b]52;c\x07", &[]),
+            // CAN cancels the sequence.
+            ("\x1b]52;c;aGk=\x18\x07", &[]),
+            ("\x1b]5\x07", &[]),
+            ("\x1b]0;52;c;aGk=\x07", &[Event::SetTitle("52;c;aGk=".to_string())]),
+        ];
+
+        for &(output, expected) in tests {
+            assert_eq!(feed(&mut Tap::new(16), output), expected, "{:?}", output);
+        }
+    }
+
+    #[test]
+    fn tap_ignores_clipboard_content_over_limit() {
+        assert_eq!(feed(&mut Tap::new(2), "\x1b]52;c;aGk=\x07").len(), 1);
+        assert_eq!(feed(&mut Tap::new(1), "\x1b]52;c;aGk=\x07"), vec![]);
+
+        // Payloads that are much longer than the limit are dropped before
+        // they're decoded.
+        let long = format!("\x1b]52;c;{}\x07", "QUFB".repeat(100));
+        assert_eq!(feed(&mut Tap::new(3), &long), vec![]);
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+
+use alacritty::config::Config as TermConfig;
+use alacritty::term::mode::TermMode;
+
+#[cfg(feature = "curses")]
+use pancurses;
+#[cfg(feature = "curses")]
+use pancurses::Input;
+
+use colour;
+use config::{self, Config};
+#[cfg(feature = "curses")]
+use curses_renderer::CursesRenderer;
+use grapheme;
+#[cfg(feature = "curses")]
+use input;
+use layout::Rect;
+#[cfg(feature = "curses")]
+use pairs::Pairs;
+use pane::{Output, Pane, Program, Window};
+use paste;
+use quantise::Depth;
+use render;
+use renderer::Renderer;
+
+// `BORDER_CHAR` is the character that the border of a `TermEmu` is drawn with.
+const BORDER_CHAR: char = '*';
+
+// `TermEmu` is a terminal that runs a program in a PTY, for programs that embed
+// a terminal in their own curses interface, such as to show a shell in part of
+// their window. The embedding program owns the window and reads its input: it
+// passes keys to `input`, and calls `process` regularly to read the output of
+// the program, and `render` to draw the terminal when `process` reports that
+// it changed.
+//
+// A terminal can also be started using `detached`, for programs that don't use
+// curses, in which case the embedding program draws it using `draw`, such as
+// through `tui_widget::TermWidget`; only `detached` terminals can be
+// started if `tep` is built without the `curses` feature.
+//
+// The terminal is drawn inside a border, in the same way as the panes of `tep`.
+// Sequences that change the host terminal, such as those that enable mouse
+// tracking or set the clipboard, are ignored, because the host terminal
+// belongs to the embedding program.
+pub struct TermEmu {
+    pane: Pane,
+    term_conf: TermConfig,
+    ctx: render::Context,
+    #[cfg(feature = "curses")]
+    pairs: Pairs,
+    ambiguous_wide: bool,
+    // `closed` is `true` once the output of the program has been read after
+    // it exited.
+    closed: bool,
+}
+
+impl TermEmu {
+    // `spawn` runs `argv`, or the user's shell if `argv` is empty, in a
+    // terminal that's drawn in `rect` of `parent`, including its border.
+    // `parent` must belong to a curses session that has been started, and
+    // the terminal allocates the colour pairs that curses provides if the
+    // host supports colour. The program is told which terminal it's running
+    // in through its own environment, which the embedding program's
+    // environment isn't changed to provide.
+    #[cfg(feature = "curses")]
+    pub fn spawn(parent: &Window, rect: Rect, argv: &[String]) -> Result<TermEmu, String> {
+        let colour_support =
+            if no_colour() || !pancurses::has_colors() {
+                None
+            } else {
+                pancurses::start_color();
+                Some(colour::Support::detect())
+            };
+
+        TermEmu::start(Some(parent), rect, argv, colour_support)
+    }
+
+    // `detached` runs `argv` in the same way as `spawn`, in a terminal of
+    // `lines` by `cols` cells, including its border, that isn't drawn in a
+    // curses window, and so doesn't need a curses session. Colours are drawn
+    // using the xterm 256-colour palette, or using the 24-bit colours that the
+    // program gives (see `renderer::Attrs::rgb`).
+    pub fn detached(lines: i32, cols: i32, argv: &[String]) -> Result<TermEmu, String> {
+        let colour_support =
+            if no_colour() {
+                None
+            } else {
+                Some(colour::Support{depth: Depth::Colours256, default_colours: true})
+            };
+        let rect = Rect{y: 0, x: 0, lines: lines, cols: cols};
+
+        TermEmu::start(None, rect, argv, colour_support)
+    }
+
+    fn start(
+        parent: Option<&Window>,
+        rect: Rect,
+        argv: &[String],
+        colour_support: Option<colour::Support>,
+    ) -> Result<TermEmu, String> {
+        let conf = Config::default();
+        let ambiguous_wide = conf.text.ambiguous_width == config::Width::Wide;
+        let ctx = render::Context{
+            colour_support: colour_support,
+            fallbacks: conf.fallbacks,
+            selection_colours: None,
+            match_colours: None,
+            active_border_colours: None,
+            cursor_shape: None,
+            cursor_blink_on: true,
+            utf8: grapheme::host_is_utf8(),
+            ambiguous_wide: ambiguous_wide,
+            line_drawing: conf.text.line_drawing,
+        };
+
+        let program = if argv.is_empty() { None } else { Some(Program::Exec(argv.to_vec())) };
+        let term_conf = TermConfig::default();
+        let pane = Pane::spawn(0, &term_conf, &conf, parent, rect, program, false)?;
+
+        Ok(TermEmu{
+            pane: pane,
+            term_conf: term_conf,
+            ctx: ctx,
+            #[cfg(feature = "curses")]
+            pairs: Pairs::new(colour_support.map_or(false, |s| s.default_colours)),
+            ambiguous_wide: ambiguous_wide,
+            closed: false,
+        })
+    }
+
+    // `input` sends `input`, which was read from `Window::getch`, to the
+    // program, in the form that the program requested using its keyboard
+    // modes (see `input::KeyboardModes`). Keys that have no encoding, such as
+    // `Input::KeyResize`, are ignored.
+    #[cfg(feature = "curses")]
+    pub fn input(&mut self, input: &Input) {
+        let extended =
+            match *input {
+                Input::Character(c) => self.pane.keyboard_modes.encode(c, false),
+                _ => None,
+            };
+        if let Some(bytes) = extended.or_else(|| input::encode(input, *self.pane.term.mode())) {
+            self.send(&bytes);
+        }
+    }
+
+    // `send` sends `bytes` to the program as if they were typed, such as to
+    // paste text.
+    pub fn send(&mut self, bytes: &[u8]) {
+        let bytes = self.pane.flow(bytes);
+        self.pane.outbox.push(&bytes);
+    }
+
+    // `paste` sends `text` to the program as a single paste, which is
+    // bracketed if the program enabled bracketed paste mode.
+    pub fn paste(&mut self, text: &[u8]) {
+        let bracketed = self.pane.term.mode().contains(TermMode::BRACKETED_PASTE);
+        self.send(&paste::bracket(text, bracketed));
+    }
+
+    // `process` writes the input that's waiting to be sent to the program, and
+    // reads the output that the program has written, without blocking. It
+    // returns `true` if the terminal should be rendered again.
+    pub fn process(&mut self) -> bool {
+        if self.closed {
+            return false;
+        }
+        if let Err(msg) = self.pane.outbox.flush(&mut self.pane.ptyf) {
+            self.close(Some(msg));
+            return true;
+        }
+        // Sequences that would change the host terminal are ignored.
+        let mut buf = [0u8; 0x1000];
+        match self.pane.process_output(&mut buf[..], &self.term_conf, self.ambiguous_wide) {
+            Output::Pending => false,
+            Output::Read(_) => true,
+            Output::Closed(reason) => {
+                self.close(reason);
+                true
+            },
+        }
+    }
+
+    // `render` draws the terminal, and places the host's cursor at the cursor
+    // of the terminal. It does nothing if the terminal was started using
+    // `detached`.
+    #[cfg(feature = "curses")]
+    pub fn render(&mut self) {
+        if let Some(ref win) = self.pane.win {
+            draw(&mut self.ctx, &self.pane, &mut CursesRenderer::new(win, &mut self.pairs));
+        }
+    }
+
+    // `draw` draws the terminal using `r`, which draws in an area of the same
+    // size as the terminal.
+    pub fn draw<R: Renderer>(&mut self, r: &mut R) {
+        draw(&mut self.ctx, &self.pane, r);
+    }
+
+    // `place` moves the terminal to `rect` of `parent`, which is also used
+    // after `parent` is resized. The program is told about the new size if
+    // the size of the terminal changes.
+    #[cfg(feature = "curses")]
+    pub fn place(&mut self, parent: &Window, rect: Rect) {
+        self.pane.place(Some(parent), rect);
+    }
+
+    // `resize` resizes a terminal that was started using `detached` to
+    // `lines` by `cols` cells, including its border, in the same way as
+    // `place`.
+    pub fn resize(&mut self, lines: i32, cols: i32) {
+        self.pane.place(None, Rect{y: 0, x: 0, lines: lines, cols: cols});
+    }
+
+    // `size` returns the number of lines and columns of the terminal,
+    // including its border.
+    pub fn size(&self) -> (i32, i32) {
+        (self.pane.rect.lines, self.pane.rect.cols)
+    }
+
+    // `is_closed` returns `true` once the program has exited and its output
+    // has been read, after which `exit_reason` describes how it exited.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    // `exit_reason` returns `None` if the program is running or exited
+    // successfully.
+    pub fn exit_reason(&self) -> Option<&str> {
+        self.pane.exited.as_ref().map(String::as_str)
+    }
+
+    fn close(&mut self, reason: Option<String>) {
+        self.closed = true;
+        self.pane.exited = reason;
+    }
+}
+
+// `draw` draws the terminal of `pane` using `r`. The title that the program
+// set, or how it exited, is drawn over the top of the border.
+fn draw<R: Renderer>(ctx: &mut render::Context, pane: &Pane, r: &mut R) {
+    ctx.cursor_shape = pane.cursor_shape;
+    let label = pane.exited.as_ref().map(String::as_str).or_else(|| pane.display_title());
+    render::render_term(&pane.term, r, ctx, &pane.palette, &pane.extras, None, None, &[], BORDER_CHAR, label, false);
+}
+
+// `no_colour` returns `true` if `NO_COLOR` asks for terminals to be drawn
+// without colour.
+fn no_colour() -> bool {
+    env::var_os("NO_COLOR").map_or(false, |v| !v.is_empty())
+}
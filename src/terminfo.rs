@@ -0,0 +1,78 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+use std::path::PathBuf;
+
+// `NAME` is the name of the terminfo entry of the emulator, which is defined in
+// `tep.terminfo`.
+pub const NAME: &str = "tep";
+
+// `FALLBACK_NAME` is the name of the terminfo entry that children are given if
+// `NAME` isn't installed, which describes a subset of the capabilities of
+// `NAME`.
+pub const FALLBACK_NAME: &str = "xterm-256color";
+
+// `SYSTEM_DIRS` are the directories that ncurses searches for terminfo entries
+// by default.
+const SYSTEM_DIRS: &[&str] = &[
+    "/etc/terminfo",
+    "/lib/terminfo",
+    "/usr/share/terminfo",
+    "/usr/lib/terminfo",
+];
+
+// `term_name` returns the value of `TERM` that children are given, which is
+// `NAME` if its terminfo entry is installed, and `FALLBACK_NAME` otherwise.
+pub fn term_name() -> &'static str {
+    if is_installed(NAME) {
+        NAME
+    } else {
+        FALLBACK_NAME
+    }
+}
+
+// `is_installed` returns `true` if the terminfo entry called `name` is in one
+// of the directories that ncurses searches, in the order that it searches them.
+fn is_installed(name: &str) -> bool {
+    let first = match name.chars().next() {
+        Some(c) => c,
+        None => return false,
+    };
+    // Entries are kept in a directory that's named after the first character
+    // of their name, or after its hexadecimal code on some systems, such as
+    // macOS.
+    let subdirs = [first.to_string(), format!("{:x}", first as u32)];
+
+    search_dirs()
+        .iter()
+        .any(|dir| subdirs.iter().any(|subdir| dir.join(subdir).join(name).is_file()))
+}
+
+// `search_dirs` returns the directories that ncurses searches for terminfo
+// entries, which can be changed using `TERMINFO` and `TERMINFO_DIRS`, where an
+// empty entry of `TERMINFO_DIRS` stands for the system directories.
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    match env::var("TERMINFO_DIRS") {
+        Ok(list) => {
+            for dir in list.split(':') {
+                if dir.is_empty() {
+                    dirs.extend(SYSTEM_DIRS.iter().map(PathBuf::from));
+                } else {
+                    dirs.push(PathBuf::from(dir));
+                }
+            }
+        },
+        Err(_) => dirs.extend(SYSTEM_DIRS.iter().map(PathBuf::from)),
+    }
+
+    dirs
+}
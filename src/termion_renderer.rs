@@ -0,0 +1,130 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::cmp;
+use std::io::Write;
+
+use termion::color::{AnsiValue, Bg, Fg};
+use termion::cursor::{Goto, Hide, Show};
+use termion::style;
+
+use acs;
+use colour;
+use layout::Rect;
+use renderer::{Attrs, Glyph, Renderer};
+
+// `CONCEAL` hides text, which termion has no style for.
+const CONCEAL: &str = "\x1b[8m";
+
+// `TermionRenderer` is a `Renderer` that draws in `area` of the host terminal
+// using termion, which writes escape sequences to `out` (usually standard
+// output) in the same way as `CrosstermRenderer`, but which only supports
+// Unix-like systems and has fewer dependencies. Cells outside of `area` aren't
+// drawn. Write errors are ignored, as they are by curses.
+pub struct TermionRenderer<W: Write> {
+    out: W,
+    area: Rect,
+}
+
+impl<W: Write> TermionRenderer<W> {
+    pub fn new(out: W, area: Rect) -> TermionRenderer<W> {
+        TermionRenderer{out: out, area: area}
+    }
+
+    // `set_area` moves the area that's drawn in, such as after the host
+    // terminal is resized.
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+    }
+
+    fn set_attrs(&mut self, attrs: Attrs) {
+        let _ = write!(self.out, "{}", style::Reset);
+        if attrs.bold {
+            let _ = write!(self.out, "{}", style::Bold);
+        }
+        if attrs.dim {
+            let _ = write!(self.out, "{}", style::Faint);
+        }
+        if attrs.italic {
+            let _ = write!(self.out, "{}", style::Italic);
+        }
+        if attrs.underline {
+            let _ = write!(self.out, "{}", style::Underline);
+        }
+        if attrs.reverse {
+            let _ = write!(self.out, "{}", style::Invert);
+        }
+        if attrs.hidden {
+            let _ = write!(self.out, "{}", CONCEAL);
+        }
+        if let Some((fg, bg)) = attrs.colours {
+            // `colour::DEFAULT` is left as it is, because `style::Reset` has
+            // already restored the default colours.
+            if fg != colour::DEFAULT {
+                let _ = write!(self.out, "{}", Fg(AnsiValue(fg as u8)));
+            }
+            if bg != colour::DEFAULT {
+                let _ = write!(self.out, "{}", Bg(AnsiValue(bg as u8)));
+            }
+        }
+    }
+
+    // `move_to` moves the cursor to `(y, x)` of the area. Unlike the positions
+    // of `Renderer`, those of `Goto` start from 1.
+    fn move_to(&mut self, y: i32, x: i32) {
+        let goto = Goto((self.area.x + x + 1) as u16, (self.area.y + y + 1) as u16);
+        let _ = write!(self.out, "{}", goto);
+    }
+}
+
+impl<W: Write> Renderer for TermionRenderer<W> {
+    fn size(&self) -> (i32, i32) {
+        (self.area.lines, self.area.cols)
+    }
+
+    // Only the area is cleared, rather than the whole screen, so that the
+    // rest of the host terminal is left as it is.
+    fn clear(&mut self) {
+        self.set_attrs(Attrs::default());
+        let blank = " ".repeat(cmp::max(self.area.cols, 0) as usize);
+        for y in 0..self.area.lines {
+            self.move_to(y, 0);
+            let _ = write!(self.out, "{}", blank);
+        }
+    }
+
+    fn draw_cell(&mut self, y: i32, x: i32, glyph: Glyph, attrs: Attrs) {
+        if y < 0 || y >= self.area.lines || x < 0 || x >= self.area.cols {
+            return;
+        }
+
+        self.move_to(y, x);
+        self.set_attrs(attrs);
+        let _ =
+            match glyph {
+                Glyph::Text(text) => write!(self.out, "{}", text),
+                Glyph::Char(c) => write!(self.out, "{}", c),
+                Glyph::LineDrawing(c) => {
+                    match acs::to_dec(c) {
+                        Some(dec) => write!(self.out, "\x1b(0{}\x1b(B", dec),
+                        None => write!(self.out, "{}", c),
+                    }
+                },
+            };
+        let _ = write!(self.out, "{}", style::Reset);
+    }
+
+    fn move_cursor(&mut self, y: i32, x: i32, visible: bool) {
+        self.move_to(y, x);
+        if visible {
+            let _ = write!(self.out, "{}", Show);
+        } else {
+            let _ = write!(self.out, "{}", Hide);
+        }
+    }
+
+    fn present(&mut self) {
+        let _ = self.out.flush();
+    }
+}
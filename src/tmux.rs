@@ -0,0 +1,117 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use pancurses::Input;
+
+use config::{self, Action};
+use keybindings;
+use prompt::Command;
+
+// `COMMANDS` are the names of the `tmux` commands that are supported, along
+// with their aliases. They can be entered at the prompt, or given as
+// subcommands of `tep`, so that they can be used by scripts.
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("capture-pane", "capturep"),
+    ("kill-pane", "killp"),
+    ("select-pane", "selectp"),
+    ("send-keys", "send"),
+    ("split-window", "splitw"),
+];
+
+// `KEYS` are the names that `tmux` uses for keys in `send-keys`, other than
+// those that can be bound in `Keybindings`.
+const KEYS: &[(&str, Input)] = &[
+    ("Enter", Input::Character('\r')),
+    ("Tab", Input::Character('\t')),
+    ("BTab", Input::KeyBTab),
+    ("Space", Input::Character(' ')),
+    ("Escape", Input::Character('\x1b')),
+    ("BSpace", Input::KeyBackspace),
+    ("PPage", Input::KeyPPage),
+    ("NPage", Input::KeyNPage),
+    ("IC", Input::KeyIC),
+    ("DC", Input::KeyDC),
+];
+
+// `is_command` returns `true` if `name` is the name or alias of a supported
+// `tmux` command.
+pub fn is_command(name: &str) -> bool {
+    COMMANDS.iter().any(|&(long, short)| name == long || name == short)
+}
+
+// `parse` parses the `tmux` command `name`, with the arguments `args`, and
+// returns `None` if `name` isn't a supported command. Only the most common
+// flags of each command are supported, and the targets of commands (`-t`)
+// are panes of the current tab, numbered from `0`, as `tmux` numbers them.
+pub fn parse(name: &str, args: &[&str]) -> Option<Result<Command, String>> {
+    let name = COMMANDS.iter().find(|&&(long, short)| name == long || name == short)?.0;
+    let result =
+        match name {
+            "split-window" => {
+                match (args.len(), args.first()) {
+                    (0, _) | (1, Some(&"-v")) => Ok(Command::Action(Action::SplitDown)),
+                    (1, Some(&"-h")) => Ok(Command::Action(Action::SplitRight)),
+                    _ => Err("usage: split-window [-h|-v]".to_string()),
+                }
+            },
+            "select-pane" => {
+                match (args.len(), args.first()) {
+                    (1, Some(&"-L")) => Ok(Command::Action(Action::SelectPaneLeft)),
+                    (1, Some(&"-D")) => Ok(Command::Action(Action::SelectPaneDown)),
+                    (1, Some(&"-U")) => Ok(Command::Action(Action::SelectPaneUp)),
+                    (1, Some(&"-R")) => Ok(Command::Action(Action::SelectPaneRight)),
+                    (2, Some(&"-t")) => {
+                        match args[1].parse() {
+                            Ok(index) => Ok(Command::SelectPane(index)),
+                            Err(_) => Err(format!("invalid pane: {}", args[1])),
+                        }
+                    },
+                    _ => Err("usage: select-pane -L|-D|-U|-R|-t <index>".to_string()),
+                }
+            },
+            "kill-pane" => {
+                if args.is_empty() {
+                    Ok(Command::Action(Action::ClosePane))
+                } else {
+                    Err("usage: kill-pane".to_string())
+                }
+            },
+            "send-keys" => {
+                match args.split_first() {
+                    Some((&"-l", text)) => Ok(Command::SendKeys(text.join("").chars().map(Input::Character).collect())),
+                    Some(_) => Ok(Command::SendKeys(args.iter().cloned().flat_map(keys).collect())),
+                    None => Err("usage: send-keys [-l] <key>...".to_string()),
+                }
+            },
+            _ => {
+                match (args.len(), args.first()) {
+                    (0, _) => Ok(Command::CapturePane(false)),
+                    (1, Some(&"-p")) => Ok(Command::CapturePane(true)),
+                    _ => Err("usage: capture-pane [-p]".to_string()),
+                }
+            },
+        };
+
+    Some(result)
+}
+
+// `keys` returns the keys that `arg` names, as an argument of `send-keys`.
+// Arguments that aren't the names of keys are sent as text.
+fn keys(arg: &str) -> Vec<Input> {
+    if let Some(&(_, key)) = KEYS.iter().find(|&&(name, _)| name == arg) {
+        return vec![key];
+    }
+    if arg.starts_with("C-") {
+        if let Some(c) = config::parse_caret(&format!("^{}", &arg[2..])) {
+            return vec![Input::Character(c)];
+        }
+    }
+    if arg.chars().count() > 1 {
+        if let Some(key) = keybindings::parse_key(arg) {
+            return vec![key];
+        }
+    }
+
+    arg.chars().map(Input::Character).collect()
+}
@@ -0,0 +1,244 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+#[cfg(all(feature = "curses", feature = "crossterm"))]
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+#[cfg(all(feature = "curses", feature = "crossterm"))]
+use pancurses::Input;
+use tui::buffer::{Buffer, Cell};
+use tui::layout::Rect as TuiRect;
+use tui::style::{Color, Modifier, Style};
+use tui::widgets::Widget;
+
+use colour;
+use palette::Rgb;
+use renderer::{Attrs, Glyph, Renderer};
+use term::TermEmu;
+
+// `TermWidget` is a tui widget that draws a `TermEmu`, for programs built on
+// tui that embed a terminal. The `TermEmu` should be started using
+// `TermEmu::detached`, and is resized to fit the area that the widget is drawn
+// in, including its border, so the program that runs in it is told when the
+// area changes size. Keys are passed to the program using `route_event`, which
+// needs the `curses` and `crossterm` features, as keys are read using
+// crossterm and encoded from the keys of curses.
+//
+// The cursor of the terminal is drawn in reverse video, as widgets can't move
+// the host's cursor.
+pub struct TermWidget<'a> {
+    term: &'a mut TermEmu,
+}
+
+impl<'a> TermWidget<'a> {
+    pub fn new(term: &'a mut TermEmu) -> TermWidget<'a> {
+        TermWidget{term: term}
+    }
+}
+
+impl<'a> Widget for TermWidget<'a> {
+    fn draw(&mut self, area: TuiRect, buf: &mut Buffer) {
+        // A terminal needs at least one cell inside its border.
+        if area.height < 3 || area.width < 3 {
+            return;
+        }
+
+        let size = (area.height as i32, area.width as i32);
+        if self.term.size() != size {
+            self.term.resize(size.0, size.1);
+        }
+        self.term.draw(&mut BufferRenderer::new(buf, area));
+    }
+}
+
+// `BufferRenderer` is a `Renderer` that draws in `area` of a tui `Buffer`.
+pub struct BufferRenderer<'a> {
+    buf: &'a mut Buffer,
+    area: TuiRect,
+}
+
+impl<'a> BufferRenderer<'a> {
+    pub fn new(buf: &'a mut Buffer, area: TuiRect) -> BufferRenderer<'a> {
+        BufferRenderer{buf: buf, area: area}
+    }
+
+    // `cell_mut` returns the cell at `(y, x)` of the area, or `None` if it's
+    // outside of the area or the buffer.
+    fn cell_mut(&mut self, y: i32, x: i32) -> Option<&mut Cell> {
+        if y < 0 || y >= self.area.height as i32 || x < 0 || x >= self.area.width as i32 {
+            return None;
+        }
+        let (x, y) = (self.area.x + x as u16, self.area.y + y as u16);
+        let bounds = self.buf.area;
+        if x < bounds.left() || x >= bounds.right() || y < bounds.top() || y >= bounds.bottom() {
+            return None;
+        }
+
+        Some(self.buf.get_mut(x, y))
+    }
+}
+
+impl<'a> Renderer for BufferRenderer<'a> {
+    fn size(&self) -> (i32, i32) {
+        (self.area.height as i32, self.area.width as i32)
+    }
+
+    fn clear(&mut self) {
+        for y in 0..self.area.height as i32 {
+            for x in 0..self.area.width as i32 {
+                if let Some(cell) = self.cell_mut(y, x) {
+                    cell.reset();
+                }
+            }
+        }
+    }
+
+    fn draw_cell(&mut self, y: i32, x: i32, glyph: Glyph, attrs: Attrs) {
+        let cell =
+            match self.cell_mut(y, x) {
+                Some(cell) => cell,
+                None => return,
+            };
+        // The buffer is drawn using Unicode, so line-drawing characters are
+        // drawn as they are.
+        match glyph {
+            Glyph::Text(text) => cell.set_symbol(text),
+            Glyph::Char(c) | Glyph::LineDrawing(c) => cell.set_char(c),
+        };
+        cell.set_style(to_style(attrs));
+    }
+
+    fn move_cursor(&mut self, y: i32, x: i32, visible: bool) {
+        if !visible {
+            return;
+        }
+        if let Some(cell) = self.cell_mut(y, x) {
+            cell.style.modifier.toggle(Modifier::REVERSED);
+        }
+    }
+
+    // The buffer is shown by tui once every widget has been drawn.
+    fn present(&mut self) {
+    }
+}
+
+fn to_style(attrs: Attrs) -> Style {
+    let mut modifier = Modifier::empty();
+    let mappings = [
+        (attrs.bold, Modifier::BOLD),
+        (attrs.dim, Modifier::DIM),
+        (attrs.italic, Modifier::ITALIC),
+        (attrs.underline, Modifier::UNDERLINED),
+        (attrs.reverse, Modifier::REVERSED),
+        (attrs.hidden, Modifier::HIDDEN),
+        (attrs.strikethrough, Modifier::CROSSED_OUT),
+    ];
+    for &(set, m) in mappings.iter() {
+        if set {
+            modifier |= m;
+        }
+    }
+    let mut style = Style::default().modifier(modifier);
+    if let Some((fg, bg)) = attrs.colours {
+        style = style.fg(to_color(fg, attrs.rgb.0)).bg(to_color(bg, attrs.rgb.1));
+    }
+
+    style
+}
+
+// `to_color` returns the tui colour for `rgb`, if it's given, or for `c`,
+// which is an entry of the xterm 256-colour palette, or `colour::DEFAULT`.
+fn to_color(c: i16, rgb: Option<Rgb>) -> Color {
+    match rgb {
+        Some((r, g, b)) => Color::Rgb(r, g, b),
+        None if c == colour::DEFAULT => Color::Reset,
+        None => Color::Indexed(c as u8),
+    }
+}
+
+// `route_event` passes `event`, which was read using crossterm, to the program
+// that runs in `term`, if it's a key press. It returns `false` if `event`
+// wasn't passed, so that the embedding program can handle it instead.
+#[cfg(all(feature = "curses", feature = "crossterm"))]
+pub fn route_event(term: &mut TermEmu, event: &Event) -> bool {
+    match *event {
+        Event::Key(key) => route_key(term, key),
+        _ => false,
+    }
+}
+
+// `route_key` passes `key` to the program that runs in `term`, and returns
+// `false` if `key` has no encoding. Alt-modified keys are prefixed with `ESC`.
+#[cfg(all(feature = "curses", feature = "crossterm"))]
+pub fn route_key(term: &mut TermEmu, key: KeyEvent) -> bool {
+    let input =
+        match to_input(key) {
+            Some(input) => input,
+            None => return false,
+        };
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        term.send(b"\x1b");
+    }
+    term.input(&input);
+
+    true
+}
+
+// `to_input` returns the curses key that corresponds to `key`, which is
+// encoded by `TermEmu::input`. Ctrl-modified characters are returned as the
+// control characters that they type.
+#[cfg(all(feature = "curses", feature = "crossterm"))]
+pub fn to_input(key: KeyEvent) -> Option<Input> {
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+    let input =
+        match key.code {
+            KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                match c {
+                    ' ' | '@' => Input::Character('\0'),
+                    'a'..='z' | '[' | '\\' | ']' | '^' | '_' => Input::Character(((c as u8) & 0x1f) as char),
+                    '?' => Input::Character('\x7f'),
+                    _ => Input::Character(c),
+                }
+            },
+            KeyCode::Char(c) => Input::Character(c),
+            KeyCode::Enter => Input::Character('\r'),
+            KeyCode::Tab => Input::Character('\t'),
+            KeyCode::Esc => Input::Character('\x1b'),
+            KeyCode::BackTab => Input::KeyBTab,
+            KeyCode::Backspace => Input::KeyBackspace,
+            KeyCode::Delete if shift => Input::KeySDC,
+            KeyCode::Delete => Input::KeyDC,
+            KeyCode::Insert if shift => Input::KeySIC,
+            KeyCode::Insert => Input::KeyIC,
+            KeyCode::Up if shift => Input::KeySR,
+            KeyCode::Down if shift => Input::KeySF,
+            KeyCode::Right if shift => Input::KeySRight,
+            KeyCode::Left if shift => Input::KeySLeft,
+            KeyCode::Home if shift => Input::KeySHome,
+            KeyCode::End if shift => Input::KeySEnd,
+            KeyCode::PageUp if shift => Input::KeySPrevious,
+            KeyCode::PageDown if shift => Input::KeySNext,
+            KeyCode::Up => Input::KeyUp,
+            KeyCode::Down => Input::KeyDown,
+            KeyCode::Right => Input::KeyRight,
+            KeyCode::Left => Input::KeyLeft,
+            KeyCode::Home => Input::KeyHome,
+            KeyCode::End => Input::KeyEnd,
+            KeyCode::PageUp => Input::KeyPPage,
+            KeyCode::PageDown => Input::KeyNPage,
+            KeyCode::F(n) => {
+                let keys = [
+                    Input::KeyF1, Input::KeyF2, Input::KeyF3, Input::KeyF4,
+                    Input::KeyF5, Input::KeyF6, Input::KeyF7, Input::KeyF8,
+                    Input::KeyF9, Input::KeyF10, Input::KeyF11, Input::KeyF12,
+                ];
+                match keys.get((n as usize).wrapping_sub(1)) {
+                    Some(&input) => input,
+                    None => return None,
+                }
+            },
+            _ => return None,
+        };
+
+    Some(input)
+}
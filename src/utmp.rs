@@ -0,0 +1,108 @@
+// Copyright 2020 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by a MIT
+// licence that can be found in the LICENCE file.
+
+use std::ffi::CStr;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libc;
+use libc::pid_t;
+
+// `WTMP_PATH` is the file that logins and logouts are appended to on Linux,
+// which `last` reads. `pututxline` appends to it on macOS and the BSDs.
+#[cfg(target_os = "linux")]
+const WTMP_PATH: &[u8] = b"/var/log/wtmp\0";
+
+// `updwtmpx` isn't provided by `libc`.
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn updwtmpx(wtmpx_file: *const libc::c_char, utmpx: *const libc::utmpx);
+}
+
+// `Entry` is the record of a login to a PTY in utmp, which `who` and `w` list,
+// and which is replaced by a record of the logout when the `Entry` is dropped.
+pub struct Entry {
+    record: libc::utmpx,
+}
+
+impl Entry {
+    // `add` records that the user logged in to the PTY whose slave is `slave`,
+    // and that `pid` is the login process. `None` is returned if the record
+    // can't be written, which is usually because only programs that are
+    // setgid `utmp` can write utmp.
+    pub fn add(slave: RawFd, pid: pid_t) -> Option<Entry> {
+        let mut record: libc::utmpx = unsafe { mem::zeroed() };
+
+        let mut path = [0 as libc::c_char; 64];
+        if unsafe { libc::ttyname_r(slave, path.as_mut_ptr(), path.len()) } != 0 {
+            return None;
+        }
+        let path = unsafe { CStr::from_ptr(path.as_ptr()) }.to_bytes();
+        let line = if path.starts_with(b"/dev/") { &path[5..] } else { path };
+        copy(&mut record.ut_line, line);
+        // The ID is the end of the line, as `login` and `xterm` use.
+        let id_start = line.len().saturating_sub(record.ut_id.len());
+        copy(&mut record.ut_id, &line[id_start..]);
+
+        let user = unsafe { libc::getpwuid(libc::getuid()) };
+        if user.is_null() {
+            return None;
+        }
+        copy(&mut record.ut_user, unsafe { CStr::from_ptr((*user).pw_name) }.to_bytes());
+
+        record.ut_type = libc::USER_PROCESS;
+        record.ut_pid = pid;
+        set_time(&mut record);
+
+        if write(&record) {
+            Some(Entry{record: record})
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        self.record.ut_type = libc::DEAD_PROCESS;
+        for c in self.record.ut_user.iter_mut() {
+            *c = 0;
+        }
+        set_time(&mut self.record);
+        write(&self.record);
+    }
+}
+
+// `write` writes `record` to utmp, and to wtmp, and returns `false` if it
+// couldn't be written to utmp.
+fn write(record: &libc::utmpx) -> bool {
+    unsafe {
+        libc::setutxent();
+        let written = !libc::pututxline(record).is_null();
+        libc::endutxent();
+        #[cfg(target_os = "linux")]
+        {
+            if written {
+                updwtmpx(WTMP_PATH.as_ptr() as *const libc::c_char, record);
+            }
+        }
+
+        written
+    }
+}
+
+fn set_time(record: &mut libc::utmpx) {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    record.ut_tv.tv_sec = now.as_secs() as _;
+    record.ut_tv.tv_usec = now.subsec_micros() as _;
+}
+
+// `copy` copies as much of `src` into `dst` as fits, which doesn't need to be
+// null-terminated.
+fn copy(dst: &mut [libc::c_char], src: &[u8]) {
+    for (d, &s) in dst.iter_mut().zip(src) {
+        *d = s as libc::c_char;
+    }
+}